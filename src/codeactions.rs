@@ -1,45 +1,102 @@
 use std::path::{Path, PathBuf};
 
+use itertools::Itertools;
+use once_cell::sync::Lazy;
 use pathdiff::diff_paths;
+use regex::{Captures, Regex};
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionOrCommand, CodeActionParams, CreateFile, CreateFileOptions,
-    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
-    Position, Range, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CreateFile,
+    CreateFileOptions, Diagnostic, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
 };
 
 use crate::{
-    diagnostics::path_unresolved_references,
-    vault::{Reference, Vault},
+    commands::{title_to_filename, toggle_tasks},
+    config::{FilenameStyle, Settings},
+    diagnostics::{
+        ambiguous_link_diagnostic, ambiguous_links, missing_attachment_diagnostic,
+        missing_attachments, orphaned_footnotes, path_unresolved_references, unresolved_diagnostic,
+    },
+    util::{append_heading_edit, levenshtein},
+    vault::{get_obsidian_ref_path, MDHeading, Reference, Referenceable, ResolutionIndex, Vault},
 };
 
 pub fn code_actions(
     vault: &Vault,
+    settings: &Settings,
     params: &CodeActionParams,
     path: &Path,
 ) -> Option<Vec<CodeActionOrCommand>> {
     // Diagnostics
     // get all links for changed file
 
-    let unresolved = path_unresolved_references(vault, path)?;
+    let index = vault.resolution_index();
+    let unresolved = path_unresolved_references(vault, &index, path)?;
 
     let unresolved_file_links = unresolved;
 
-    let code_action_unresolved = unresolved_file_links.into_iter().filter(|(_, reference)| {
-        reference.data().range.start.line <= params.range.start.line
-            && reference.data().range.end.line >= params.range.end.line
-            && reference.data().range.start.character <= params.range.start.character
-            && reference.data().range.end.character >= params.range.end.character
-    });
+    let code_action_unresolved = unresolved_file_links
+        .into_iter()
+        .filter(|(_, reference)| {
+            reference.data().range.start.line <= params.range.start.line
+                && reference.data().range.end.line >= params.range.end.line
+                && reference.data().range.start.character <= params.range.start.character
+                && reference.data().range.end.character >= params.range.end.character
+        })
+        .collect_vec();
+
+    // quick fixes carry the diagnostic of the link they fix, so clients show them with it
+    let all_references = vault.select_references(None)?;
+    let diagnostics = |path: &Path, reference: &Reference| {
+        unresolved_diagnostic(&index, settings, path, reference, &all_references)
+            .map(|diagnostic| vec![diagnostic])
+    };
+
+    let closest_heading_actions = code_action_unresolved
+        .iter()
+        .flat_map(|(path, reference)| {
+            closest_heading_actions(&index, path, reference)
+                .into_iter()
+                .map(|action| fixing(action, diagnostics(path, reference)))
+        })
+        .collect_vec();
+
+    let create_heading_actions = code_action_unresolved
+        .iter()
+        .flat_map(|(path, reference)| {
+            create_heading_action(vault, &index, path, reference)
+                .map(|action| fixing(action, diagnostics(path, reference)))
+        })
+        .collect_vec();
+
+    let extract_section_actions = extract_section_actions(vault, settings, params, path);
+    let wrap_in_callout_actions = wrap_in_callout_actions(vault, params, path);
+    let toggle_task_action = toggle_task_action(vault, settings, params, path);
+
+    let orphaned_footnote_actions = match settings.orphaned_footnote_diagnostics {
+        true => orphaned_footnote_actions(vault, params, path),
+        false => vec![],
+    };
+
+    let missing_attachment_actions = match settings.unresolved_diagnostics {
+        true => missing_attachment_actions(vault, &index, settings, params, path),
+        false => vec![],
+    };
+
+    let ambiguous_link_actions = match settings.ambiguous_link_diagnostics {
+        true => ambiguous_link_actions(vault, &index, settings, params, path),
+        false => vec![],
+    };
 
     Some(
         code_action_unresolved
-            .flat_map(|(_path, reference)| {
+            .into_iter()
+            .flat_map(|(reference_path, reference)| {
                 match reference {
                     Reference::WikiFileLink(_data) => {
-                        let mut new_path_buf = PathBuf::new();
-                        new_path_buf.push(vault.root_dir());
-                        new_path_buf.push(&reference.data().reference_text);
-                        new_path_buf.set_extension("md");
+                        let new_path_buf =
+                            new_note_path(vault, settings, path, &reference.data().reference_text);
 
                         let new_path = Url::from_file_path(&new_path_buf).ok()?;
 
@@ -48,6 +105,8 @@ pub fn code_actions(
                                 "Create File: {:?}",
                                 diff_paths(new_path_buf, vault.root_dir())?
                             ),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            diagnostics: diagnostics(reference_path, reference),
                             edit: Some(WorkspaceEdit {
                                 document_changes: Some(DocumentChanges::Operations(vec![
                                     DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
@@ -61,12 +120,12 @@ pub fn code_actions(
                             ..Default::default()
                         }))
                     }
-                    Reference::WikiHeadingLink(_data, link_path, heading) => {
+                    // headings in existing files are added by `create_heading_action`
+                    Reference::WikiHeadingLink(_data, link_path, heading)
+                        if index.linked_file(reference, reference_path).is_none() =>
+                    {
 
-                        let mut new_path_buf = PathBuf::new();
-                        new_path_buf.push(vault.root_dir());
-                        new_path_buf.push(link_path);
-                        new_path_buf.set_extension("md");
+                        let new_path_buf = new_note_path(vault, settings, path, link_path);
 
                         let new_path = Url::from_file_path(&new_path_buf).ok()?;
 
@@ -90,6 +149,8 @@ pub fn code_actions(
                                 heading,
                                 link_path
                             ),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            diagnostics: diagnostics(reference_path, reference),
                             edit: Some(WorkspaceEdit{
                                 document_changes: Some(DocumentChanges::Operations(vec![
                                     DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
@@ -131,6 +192,1111 @@ pub fn code_actions(
                 }
 
             })
+            .chain(closest_heading_actions)
+            .chain(create_heading_actions)
+            .chain(orphaned_footnote_actions)
+            .chain(missing_attachment_actions)
+            .chain(extract_section_actions)
+            .chain(ambiguous_link_actions)
+            .chain(wrap_in_callout_actions)
+            .chain(toggle_task_action)
             .collect(),
     )
 }
+
+/// Where the note an unresolved link in the file at `path` points to is created: new notes for links
+/// with a path go at that path from the vault root, as in Obsidian, and the rest in the
+/// `new_file_folder`
+pub fn new_note_path(vault: &Vault, settings: &Settings, path: &Path, file_ref: &str) -> PathBuf {
+    let mut new_path = match file_ref.contains('/') {
+        true => vault.root_dir().to_path_buf(),
+        false => settings
+            .new_file_folder(vault.root_dir(), path)
+            .unwrap_or_else(|| vault.root_dir().to_path_buf()),
+    };
+    match file_ref.ends_with(".md") {
+        true => new_path.push(file_ref),
+        false => new_path.push(format!("{file_ref}.md")),
+    }
+
+    new_path
+}
+
+/// The quick fix, shown with the diagnostics it fixes
+fn fixing(
+    action: CodeActionOrCommand,
+    diagnostics: Option<Vec<Diagnostic>>,
+) -> CodeActionOrCommand {
+    match action {
+        CodeActionOrCommand::CodeAction(action) => CodeActionOrCommand::CodeAction(CodeAction {
+            diagnostics,
+            ..action
+        }),
+        command => command,
+    }
+}
+
+/// Quick fixes that point a link to a missing heading at one of the three closest headings in the
+/// linked file
+fn closest_heading_actions(
+    index: &ResolutionIndex,
+    path: &Path,
+    reference: &Reference,
+) -> Vec<CodeActionOrCommand> {
+    let (data, file_ref, heading) = match reference {
+        Reference::WikiHeadingLink(data, file_ref, heading)
+        | Reference::MDHeadingLink(data, file_ref, heading) => (data, file_ref, heading),
+        _ => return vec![],
+    };
+
+    let Some((_, linked_file)) = index.linked_file(reference, path) else {
+        return vec![];
+    };
+    let Ok(uri) = Url::from_file_path(path) else {
+        return vec![];
+    };
+
+    linked_file
+        .headings
+        .iter()
+        .map(|existing| existing.heading_text.as_str())
+        .unique()
+        .sorted_by_cached_key(|existing| {
+            levenshtein(&existing.to_lowercase(), &heading.to_lowercase())
+        })
+        .take(3)
+        .map(|existing| {
+            let new_text = match reference {
+                Reference::MDHeadingLink(..) => {
+                    let link = format!("{}#{}", file_ref, existing);
+                    format!(
+                        "[{}]({})",
+                        data.display_text.as_deref().unwrap_or(""),
+                        match link.contains(' ') {
+                            true => format!("<{}>", link),
+                            false => link,
+                        }
+                    )
+                }
+                _ => format!(
+                    "[[{}#{}{}]]",
+                    file_ref,
+                    existing,
+                    data.display_text
+                        .as_ref()
+                        .map(|text| format!("|{text}"))
+                        .unwrap_or_default()
+                ),
+            };
+
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Link to heading \"{}\"", existing),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    document_changes: Some(DocumentChanges::Operations(vec![
+                        DocumentChangeOperation::Edit(TextDocumentEdit {
+                            text_document: OptionalVersionedTextDocumentIdentifier {
+                                uri: uri.clone(),
+                                version: None,
+                            },
+                            edits: vec![OneOf::Left(TextEdit {
+                                range: *data.range,
+                                new_text,
+                            })],
+                        }),
+                    ])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Append the heading of a heading link to the end of the linked file, when the file exists but
+/// doesn't have the heading
+fn create_heading_action(
+    vault: &Vault,
+    index: &ResolutionIndex,
+    path: &Path,
+    reference: &Reference,
+) -> Option<CodeActionOrCommand> {
+    let heading = match reference {
+        Reference::WikiHeadingLink(.., heading) | Reference::MDHeadingLink(.., heading) => heading,
+        _ => return None,
+    };
+
+    let (linked_path, linked_file) = index.linked_file(reference, path)?;
+    if linked_file.headings.iter().any(|existing| {
+        Referenceable::Heading(&linked_file.path, existing)
+            .matches_reference(vault, reference, path)
+    }) {
+        return None;
+    }
+
+    let edit = append_heading_edit(vault.ropes.get(linked_path)?, linked_file, heading);
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!(
+            "Create heading '{}' in {}",
+            heading,
+            linked_path.file_name()?.to_string_lossy()
+        ),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: Url::from_file_path(linked_path).ok()?,
+                        version: None,
+                    },
+                    edits: vec![OneOf::Left(edit)],
+                }),
+            ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Quick fixes for a link in the requested range that matches several files: one per file, writing
+/// the file part of the link as the file's path from the vault root
+fn ambiguous_link_actions(
+    vault: &Vault,
+    index: &ResolutionIndex,
+    settings: &Settings,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Vec<CodeActionOrCommand> {
+    let (Some(ambiguous), Some(rope), Ok(uri)) = (
+        ambiguous_links(vault, index, path),
+        vault.ropes.get(path),
+        Url::from_file_path(path),
+    ) else {
+        return vec![];
+    };
+
+    ambiguous
+        .into_iter()
+        .filter(|(reference, _)| {
+            let range = reference.data().range;
+            range.start.line == range.end.line
+                && range.start.line <= params.range.end.line
+                && range.end.line >= params.range.start.line
+        })
+        .flat_map(|(reference, candidates)| {
+            let diagnostic = ambiguous_link_diagnostic(vault, settings, reference, &candidates);
+            let range = *reference.data().range;
+            let text = rope
+                .line(range.start.line as usize)
+                .chars()
+                .skip(range.start.character as usize)
+                .take((range.end.character - range.start.character) as usize)
+                .collect::<String>();
+            let file_ref = match reference {
+                Reference::WikiHeadingLink(_, file_ref, _)
+                | Reference::WikiIndexedBlockLink(_, file_ref, _)
+                | Reference::MDHeadingLink(_, file_ref, _)
+                | Reference::MDIndexedBlockLink(_, file_ref, _) => file_ref.clone(),
+                _ => reference.data().reference_text.clone(),
+            };
+            let is_md = matches!(
+                reference,
+                Reference::MDFileLink(..)
+                    | Reference::MDHeadingLink(..)
+                    | Reference::MDIndexedBlockLink(..)
+            );
+            // the target starts after the `[[` of a wikilink or the `](` of a markdown link
+            let opening = match is_md {
+                true => text.find("](").map(|i| i + 2),
+                false => text.find("[[").map(|i| i + 2),
+            };
+
+            candidates
+                .into_iter()
+                .filter_map(|candidate| {
+                    let from_root = diff_paths(&candidate, vault.root_dir())?;
+                    // keep the extension only where the link has one
+                    let qualified = match candidate.extension().is_some_and(|ext| {
+                        file_ref.ends_with(&format!(".{}", ext.to_string_lossy()))
+                    }) {
+                        true => from_root.to_str()?.to_string(),
+                        false => from_root.with_extension("").to_str()?.to_string(),
+                    };
+                    let written = match is_md && !text.contains('<') {
+                        true => qualified.replace(' ', "%20"),
+                        false => qualified.clone(),
+                    };
+
+                    let (before, target) = text.split_at(opening?);
+                    let new_text = format!("{}{}", before, target.replacen(&file_ref, &written, 1));
+                    if new_text == text {
+                        return None;
+                    }
+
+                    Some(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Link to {}", qualified),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            document_changes: Some(DocumentChanges::Operations(vec![
+                                DocumentChangeOperation::Edit(TextDocumentEdit {
+                                    text_document: OptionalVersionedTextDocumentIdentifier {
+                                        uri: uri.clone(),
+                                        version: None,
+                                    },
+                                    edits: vec![OneOf::Left(TextEdit { range, new_text })],
+                                }),
+                            ])),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }))
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
+/// Delete the lines of unused footnote definitions in the requested range
+fn orphaned_footnote_actions(
+    vault: &Vault,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Vec<CodeActionOrCommand> {
+    let (Some(orphaned), Ok(uri)) = (orphaned_footnotes(vault, path), Url::from_file_path(path))
+    else {
+        return vec![];
+    };
+
+    orphaned
+        .into_iter()
+        .filter(|footnote| {
+            footnote.range.start.line <= params.range.end.line
+                && footnote.range.end.line >= params.range.start.line
+        })
+        .map(|footnote| {
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Delete unused footnote definition [{}]", footnote.index),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    document_changes: Some(DocumentChanges::Operations(vec![
+                        DocumentChangeOperation::Edit(TextDocumentEdit {
+                            text_document: OptionalVersionedTextDocumentIdentifier {
+                                uri: uri.clone(),
+                                version: None,
+                            },
+                            edits: vec![OneOf::Left(TextEdit {
+                                range: Range {
+                                    start: Position {
+                                        line: footnote.range.start.line,
+                                        character: 0,
+                                    },
+                                    end: Position {
+                                        line: footnote.range.end.line + 1,
+                                        character: 0,
+                                    },
+                                },
+                                new_text: String::new(),
+                            })],
+                        }),
+                    ])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Quick fixes removing links to missing attachments, with the `!` of an embed; unlike a missing
+/// note, a missing attachment isn't offered to be created
+fn missing_attachment_actions(
+    vault: &Vault,
+    index: &ResolutionIndex,
+    settings: &Settings,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Vec<CodeActionOrCommand> {
+    let (Some(missing), Ok(uri)) = (
+        missing_attachments(vault, index, path),
+        Url::from_file_path(path),
+    ) else {
+        return vec![];
+    };
+
+    missing
+        .into_iter()
+        .filter(|reference| {
+            let range = reference.data().range;
+            range.start.line <= params.range.end.line && range.end.line >= params.range.start.line
+        })
+        .map(|reference| {
+            let range = *reference.data().range;
+            let is_embed = range.start.character > 0
+                && vault
+                    .select_line(path, range.start.line as isize)
+                    .and_then(|line| line.get(range.start.character as usize - 1).copied())
+                    == Some('!');
+            let start = match is_embed {
+                true => Position::new(range.start.line, range.start.character - 1),
+                false => range.start,
+            };
+
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: match is_embed {
+                    true => "Remove embed".to_string(),
+                    false => "Remove link".to_string(),
+                },
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![missing_attachment_diagnostic(settings, reference)]),
+                edit: Some(WorkspaceEdit {
+                    document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: uri.clone(),
+                            version: None,
+                        },
+                        edits: vec![OneOf::Left(TextEdit {
+                            range: Range::new(start, range.end),
+                            new_text: String::new(),
+                        })],
+                    }])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The callout types selections can be wrapped in, as separate actions
+const WRAP_CALLOUT_TYPES: [&str; 3] = ["note", "warning", "tip"];
+
+/// Wrap the selected lines in a callout of each of the `WRAP_CALLOUT_TYPES`: a `> [!note] Note`
+/// header, titled by the type, and each line quoted. Only for a non-empty selection; a selection
+/// ending at the start of a line leaves that line out.
+fn wrap_in_callout_actions(
+    vault: &Vault,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Vec<CodeActionOrCommand> {
+    let (Some(rope), Ok(uri)) = (vault.ropes.get(path), Url::from_file_path(path)) else {
+        return vec![];
+    };
+    let Range { start, end } = params.range;
+    if start == end {
+        return vec![];
+    }
+
+    let first = start.line as usize;
+    let last = match end.character == 0 && end.line > start.line {
+        true => end.line as usize - 1,
+        false => end.line as usize,
+    };
+    if last >= rope.len_lines() {
+        return vec![];
+    }
+
+    let lines = (first..=last)
+        .map(|line| {
+            rope.line(line)
+                .to_string()
+                .trim_end_matches(['\r', '\n'])
+                .to_string()
+        })
+        .collect_vec();
+    let quoted = lines
+        .iter()
+        .map(|line| match line.is_empty() {
+            true => ">".to_string(),
+            false => format!("> {line}"),
+        })
+        .join("\n");
+    let range = Range::new(
+        Position::new(first as u32, 0),
+        Position::new(last as u32, lines[lines.len() - 1].chars().count() as u32),
+    );
+
+    WRAP_CALLOUT_TYPES
+        .into_iter()
+        .map(|callout| {
+            let mut title = callout.chars();
+            let title = title
+                .next()
+                .map(|first| first.to_uppercase().chain(title).collect::<String>())
+                .unwrap_or_default();
+
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Wrap in [!{}] callout", callout),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    document_changes: Some(DocumentChanges::Operations(vec![
+                        DocumentChangeOperation::Edit(TextDocumentEdit {
+                            text_document: OptionalVersionedTextDocumentIdentifier {
+                                uri: uri.clone(),
+                                version: None,
+                            },
+                            edits: vec![OneOf::Left(TextEdit {
+                                range,
+                                new_text: format!("> [!{}] {}\n{}", callout, title, quoted),
+                            })],
+                        }),
+                    ])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Move the tasks on the selected lines, or the cursor's line, to their next `task_states`
+fn toggle_task_action(
+    vault: &Vault,
+    settings: &Settings,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Option<CodeActionOrCommand> {
+    let Range { start, end } = params.range;
+    let last = match end.character == 0 && end.line > start.line {
+        true => end.line - 1,
+        false => end.line,
+    };
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Toggle task".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(toggle_tasks(vault, settings, path, start.line..=last)?),
+        ..Default::default()
+    }))
+}
+
+/// In-file links, like `[[#Heading]]` or `![[#^block|display]]`
+static ANCHOR_LINK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[\[#(?<anchor>[^\[\]\|]+)(?<display>\|[^\[\]]*)?\]\]").unwrap());
+
+/// Move the section of the heading on the requested line into a new note, replacing it with an
+/// embed or a link to the note
+fn extract_section_actions(
+    vault: &Vault,
+    settings: &Settings,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Vec<CodeActionOrCommand> {
+    let Some(heading) = vault.select_headings(path).and_then(|headings| {
+        headings
+            .iter()
+            .find(|heading| heading.range.start.line == params.range.start.line)
+    }) else {
+        return vec![];
+    };
+
+    [true, false]
+        .into_iter()
+        .flat_map(|embed| extract_section_action(vault, settings, path, heading, embed))
+        .collect()
+}
+
+fn extract_section_action(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    heading: &MDHeading,
+    embed: bool,
+) -> Option<CodeActionOrCommand> {
+    let rope = vault.ropes.get(path)?;
+    let md_file = vault.md_files.get(path)?;
+    let section = vault.select_heading_section(path, heading)?;
+
+    let new_name = extracted_note_name(vault, &heading.heading_text)?;
+    let new_folder = match settings.new_file_folder(vault.root_dir(), path) {
+        Some(folder) => folder,
+        None => path.parent()?.to_path_buf(),
+    };
+    let new_path = new_folder.join(format!("{new_name}.md"));
+
+    let file_name = path.file_stem()?.to_str()?;
+    let old_ref = match vault.select_duplicate_file_names().contains(file_name) {
+        true => get_obsidian_ref_path(vault.root_dir(), path)?,
+        false => file_name.to_string(),
+    };
+
+    // anchors that move along with the section; links to them must now go through the new note
+    let moved_anchors = md_file
+        .headings
+        .iter()
+        .filter(|heading| section.contains(&(heading.range.start.line as usize)))
+        .map(|heading| heading.heading_text.clone())
+        .chain(
+            md_file
+                .indexed_blocks
+                .iter()
+                .filter(|block| section.contains(&(block.range.start.line as usize)))
+                .map(|block| format!("^{}", block.index)),
+        )
+        .collect_vec();
+
+    let content = rope
+        .slice(rope.line_to_char(section.start)..rope.line_to_char(section.end))
+        .to_string();
+    let new_content = ANCHOR_LINK_RE
+        .replace_all(&content, |captures: &Captures| {
+            match moved_anchors.contains(&captures["anchor"].to_string()) {
+                true => captures[0].to_string(),
+                false => format!(
+                    "[[{}#{}{}]]",
+                    old_ref,
+                    &captures["anchor"],
+                    captures.name("display").map_or("", |m| m.as_str())
+                ),
+            }
+        })
+        .to_string();
+
+    let anchor_edits = (0..rope.len_lines())
+        .filter(|line| !section.contains(line))
+        .flat_map(|line| {
+            let text = rope.line(line).to_string();
+            ANCHOR_LINK_RE
+                .captures_iter(&text)
+                .filter(|captures| moved_anchors.contains(&captures["anchor"].to_string()))
+                .map(|captures| {
+                    let full = captures.get(0).unwrap();
+                    let start = text[..full.start()].chars().count() as u32;
+                    let end = text[..full.end()].chars().count() as u32;
+                    OneOf::Left(TextEdit {
+                        range: Range {
+                            start: Position {
+                                line: line as u32,
+                                character: start,
+                            },
+                            end: Position {
+                                line: line as u32,
+                                character: end,
+                            },
+                        },
+                        new_text: format!(
+                            "[[{}#{}{}]]",
+                            new_name,
+                            &captures["anchor"],
+                            captures.name("display").map_or("", |m| m.as_str())
+                        ),
+                    })
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+
+    let section_end = match section.end < rope.len_lines() {
+        true => Position {
+            line: section.end as u32,
+            character: 0,
+        },
+        false => Position {
+            line: section.end as u32 - 1,
+            character: rope.line(section.end - 1).len_chars() as u32,
+        },
+    };
+    let replacement = format!(
+        "{}[[{}]]{}",
+        if embed { "!" } else { "" },
+        new_name,
+        if content.ends_with('\n') { "\n" } else { "" }
+    );
+
+    let uri = Url::from_file_path(path).ok()?;
+    let new_uri = Url::from_file_path(&new_path).ok()?;
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!(
+            "Extract section \"{}\" to new note, {} as {}",
+            heading.heading_text,
+            if embed { "embedded" } else { "linked" },
+            replacement.trim_end()
+        ),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: new_uri.clone(),
+                    options: Some(CreateFileOptions {
+                        overwrite: Some(false),
+                        ignore_if_exists: Some(false),
+                    }),
+                    annotation_id: None,
+                })),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: new_uri,
+                        version: None,
+                    },
+                    edits: vec![OneOf::Left(TextEdit {
+                        range: Range::default(),
+                        new_text: new_content,
+                    })],
+                }),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                    edits: anchor_edits
+                        .into_iter()
+                        .chain([OneOf::Left(TextEdit {
+                            range: Range {
+                                start: Position {
+                                    line: section.start as u32,
+                                    character: 0,
+                                },
+                                end: section_end,
+                            },
+                            new_text: replacement,
+                        })])
+                        .collect(),
+                }),
+            ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// A note name for the heading that no file in the vault uses yet, appending a numeric suffix on
+/// collisions
+fn extracted_note_name(vault: &Vault, heading_text: &str) -> Option<String> {
+    let base = title_to_filename(FilenameStyle::Title, heading_text);
+
+    if base.is_empty() {
+        return None;
+    }
+
+    let taken = |name: &str| {
+        vault
+            .md_files
+            .keys()
+            .any(|path| path.file_stem().and_then(|stem| stem.to_str()) == Some(name))
+    };
+
+    std::iter::once(base.clone())
+        .chain((1..).map(|n| format!("{base} {n}")))
+        .find(|name| !taken(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        CodeActionContext, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+        DocumentChangeOperation, DocumentChanges, OneOf, PartialResultParams, Position, Range,
+        ResourceOp, TextDocumentIdentifier, TextEdit, Url, WorkDoneProgressParams,
+    };
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::{
+        ambiguous_link_actions, closest_heading_actions, code_actions, create_heading_action,
+        extract_section_action, missing_attachment_actions, wrap_in_callout_actions,
+    };
+
+    /// The params requesting the code actions for a range of the file, by (line, character)
+    fn action_params(path: &Path, start: (u32, u32), end: (u32, u32)) -> CodeActionParams {
+        CodeActionParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(path).unwrap(),
+            },
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        }
+    }
+
+    #[test]
+    fn test_quick_fixes_carry_diagnostics() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Setup Guide\n"),
+                ("note.md", "[[missing]] [[guide#Setup Gide]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let params = |character| action_params(&path, (0, character), (0, character));
+        let actions = |character| {
+            code_actions(&vault, &settings, &params(character), &path)
+                .unwrap()
+                .into_iter()
+                .filter_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) => Some((
+                        action.kind,
+                        action.diagnostics.map(|diagnostics| {
+                            diagnostics
+                                .into_iter()
+                                .map(|diagnostic| (diagnostic.range, diagnostic.message))
+                                .collect::<Vec<_>>()
+                        }),
+                    )),
+                    CodeActionOrCommand::Command(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // creating the missing file
+        assert_eq!(
+            actions(3),
+            vec![(
+                Some(CodeActionKind::QUICKFIX),
+                Some(vec![(
+                    Range::new(Position::new(0, 0), Position::new(0, 11)),
+                    "Unresolved Reference".to_string()
+                )])
+            )]
+        );
+
+        // linking to the closest heading or creating the missing one
+        let heading_actions = actions(15);
+        assert_eq!(heading_actions.len(), 2);
+        for (kind, diagnostics) in heading_actions {
+            assert_eq!(kind, Some(CodeActionKind::QUICKFIX));
+            assert_eq!(
+                diagnostics,
+                Some(vec![(
+                    Range::new(Position::new(0, 12), Position::new(0, 32)),
+                    "Heading \"Setup Gide\" not found in guide".to_string()
+                )])
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_attachment_actions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("note.md", "See ![[missing.png]] and [[gone.pdf|the pdf]]\n")],
+        );
+
+        let path = root_dir.join("note.md");
+        let params = action_params(&path, (0, 0), (0, 0));
+
+        let index = vault.resolution_index();
+        let actions = missing_attachment_actions(&vault, &index, &settings, &params, &path)
+            .into_iter()
+            .map(|action| {
+                let CodeActionOrCommand::CodeAction(action) = action else {
+                    panic!("expected a code action")
+                };
+                let Some(DocumentChanges::Edits(edits)) = action.edit.unwrap().document_changes
+                else {
+                    panic!("expected edits")
+                };
+                let OneOf::Left(edit) = &edits[0].edits[0] else {
+                    panic!("expected a text edit")
+                };
+                (
+                    action.title,
+                    edit.range.start.character,
+                    edit.range.end.character,
+                    action.diagnostics.unwrap()[0].message.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            actions,
+            vec![
+                (
+                    "Remove embed".to_string(),
+                    4,
+                    20,
+                    "Attachment not found: missing.png".to_string()
+                ),
+                (
+                    "Remove link".to_string(),
+                    25,
+                    45,
+                    "Attachment not found: gone.pdf".to_string()
+                ),
+            ]
+        );
+
+        // no note is offered to be created for them
+        let all = code_actions(&vault, &settings, &params, &path).unwrap();
+        assert_eq!(all.len(), 2);
+    }
+
+    #[test]
+    fn test_ambiguous_link_actions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("a/note.md", "# Part\n"),
+                ("b/note.md", "# Part\n"),
+                ("unique.md", ""),
+                (
+                    "links.md",
+                    "[[note#Part|the note]] [md](note.md) [[a/note]] [[unique]]\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let uri = Url::from_file_path(&path).unwrap();
+        let ambiguous = crate::diagnostics::diagnostics(&vault, &settings, (&path, &uri))
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.range.start.character, diagnostic.message))
+            .collect::<Vec<_>>();
+        let message = "Ambiguous link: matches a/note.md, b/note.md".to_string();
+        assert_eq!(ambiguous, vec![(0, message.clone()), (23, message)]);
+
+        let params = action_params(&path, (0, 0), (0, 0));
+        let index = vault.resolution_index();
+        let fixes = ambiguous_link_actions(&vault, &index, &settings, &params, &path)
+            .into_iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => {
+                    let Some(DocumentChanges::Operations(operations)) =
+                        action.edit?.document_changes
+                    else {
+                        return None;
+                    };
+                    let DocumentChangeOperation::Edit(edit) = &operations[0] else {
+                        return None;
+                    };
+                    let OneOf::Left(TextEdit { new_text, .. }) = &edit.edits[0] else {
+                        return None;
+                    };
+                    Some((action.title, new_text.clone()))
+                }
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let fix = |title: &str, new_text: &str| (title.to_string(), new_text.to_string());
+        assert_eq!(
+            fixes,
+            vec![
+                fix("Link to a/note", "[[a/note#Part|the note]]"),
+                fix("Link to b/note", "[[b/note#Part|the note]]"),
+                fix("Link to a/note", "[md](a/note.md)"),
+                fix("Link to b/note", "[md](b/note.md)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_in_callout_actions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("note.md", "# Title\n\nFirst line\n\nSecond line\nafter\n")],
+        );
+
+        let path = root_dir.join("note.md");
+        let params = |start, end| action_params(&path, start, end);
+        let wraps = |start, end| {
+            wrap_in_callout_actions(&vault, &params(start, end), &path)
+                .into_iter()
+                .filter_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) => {
+                        let Some(DocumentChanges::Operations(operations)) =
+                            action.edit?.document_changes
+                        else {
+                            return None;
+                        };
+                        let DocumentChangeOperation::Edit(edit) = &operations[0] else {
+                            return None;
+                        };
+                        let OneOf::Left(edit) = &edit.edits[0] else {
+                            return None;
+                        };
+                        Some((action.title, edit.clone()))
+                    }
+                    CodeActionOrCommand::Command(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the line the selection ends at the start of is left out
+        let actions = wraps((2, 3), (5, 0));
+        assert_eq!(
+            actions
+                .iter()
+                .map(|(title, _)| title.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Wrap in [!note] callout",
+                "Wrap in [!warning] callout",
+                "Wrap in [!tip] callout"
+            ]
+        );
+        assert_eq!(
+            actions[1].1,
+            TextEdit {
+                range: Range::new(Position::new(2, 0), Position::new(4, 11)),
+                new_text: "> [!warning] Warning\n> First line\n>\n> Second line".to_string(),
+            }
+        );
+
+        assert!(wraps((2, 3), (2, 3)).is_empty());
+    }
+
+    #[test]
+    fn test_closest_heading_actions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "guide.md",
+                    "# Introduction\n\n## Setup Guide\n\n## Usage\n\n## Set up CI\n",
+                ),
+                ("note.md", "See [[guide#Setup Gide]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let reference = &vault.select_references(Some(&path)).unwrap()[0].1;
+        let index = vault.resolution_index();
+
+        let titles = closest_heading_actions(&index, &path, reference)
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => action.title,
+                CodeActionOrCommand::Command(command) => command.title,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            titles,
+            vec![
+                "Link to heading \"Setup Guide\"",
+                "Link to heading \"Set up CI\"",
+                "Link to heading \"Usage\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_heading_action() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("docs/guide.md", "# Introduction\n\nSome text"),
+                (
+                    "note.md",
+                    "See [[guide#Missing Heading]] and [[guide#Introduction]]\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let references = vault.select_references(Some(&path)).unwrap();
+        let index = vault.resolution_index();
+
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            create_heading_action(&vault, &index, &path, references[0].1)
+        else {
+            panic!("expected a code action")
+        };
+        assert_eq!(action.title, "Create heading 'Missing Heading' in guide.md");
+
+        let Some(DocumentChanges::Operations(operations)) = action.edit.unwrap().document_changes
+        else {
+            panic!("expected document changes")
+        };
+        let [DocumentChangeOperation::Edit(edit)] = &operations[..] else {
+            panic!("expected a single edit")
+        };
+        assert_eq!(
+            edit.text_document.uri,
+            Url::from_file_path(root_dir.join("docs/guide.md")).unwrap()
+        );
+        assert_eq!(
+            edit.edits,
+            vec![OneOf::Left(TextEdit {
+                range: Range::new(Position::new(2, 9), Position::new(2, 9)),
+                new_text: "\n\n## Missing Heading\n".to_string(),
+            })]
+        );
+
+        assert!(create_heading_action(&vault, &index, &path, references[1].1).is_none());
+    }
+
+    #[test]
+    fn test_extract_section_action() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "big.md",
+                    "# Big\n\nSee [[#Details]]\n\n## Details\n\nBack to [[#Big]] or [[#Details]]\n\n## Other\n",
+                ),
+                ("Details.md", "taken\n"),
+            ],
+        );
+
+        let path = root_dir.join("big.md");
+        let heading = &vault.select_headings(&path).unwrap()[1];
+
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            extract_section_action(&vault, &settings, &path, heading, true)
+        else {
+            panic!("no action")
+        };
+
+        let Some(DocumentChanges::Operations(operations)) = action.edit.unwrap().document_changes
+        else {
+            panic!("no operations")
+        };
+
+        let edits = operations
+            .into_iter()
+            .flat_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => edit.edits,
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                    assert_eq!(
+                        create.uri.to_file_path().unwrap(),
+                        root_dir.join("Details 1.md")
+                    );
+                    vec![]
+                }
+                _ => vec![],
+            })
+            .map(|edit| match edit {
+                OneOf::Left(edit) => (edit.range.start.line, edit.new_text),
+                OneOf::Right(edit) => (edit.text_edit.range.start.line, edit.text_edit.new_text),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            edits,
+            vec![
+                (
+                    0,
+                    "## Details\n\nBack to [[big#Big]] or [[#Details]]\n\n".to_string()
+                ),
+                (2, "[[Details 1#Details]]".to_string()),
+                (4, "![[Details 1]]\n".to_string()),
+            ]
+        );
+    }
+}
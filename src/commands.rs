@@ -1,8 +1,31 @@
-use chrono::NaiveDateTime;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use chrono::offset::Local;
-use crate::config::Settings;
+use crate::codeactions::new_note_path;
+use crate::completion::matcher::{fuzzy_match, Matchable};
+use crate::config::{FilenameStyle, OpenSelection, Settings};
+use crate::daily::notebooks;
+use crate::rename::rename_referenceable;
+use crate::ui::backlinks;
+use crate::util::{append_edit, levenshtein, percent_decode, slugify};
+use crate::vault::{
+    get_obsidian_ref_path, link_name, MDTag, Rangeable, Reference, Referenceable, Vault,
+};
 use fuzzydate::parse;
-use tower_lsp::lsp_types::{ShowDocumentParams, Url};
+use itertools::Itertools;
+use pathdiff::diff_paths;
+use regex::{NoExpand, Regex};
+use ropey::Rope;
+use serde::Serialize;
+use tower_lsp::lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    CreateFile, CreateFileOptions, Position, Range, RenameFile, ResourceOp, ShowDocumentParams,
+    TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
 
 
 // fn file_to_datetime(filename: &str, format: &str) -> Result<NaiveDateTime> {
@@ -16,8 +39,9 @@ use tower_lsp::lsp_types::{ShowDocumentParams, Url};
 // }
 
 
-fn datetime_to_file(datetime: NaiveDateTime, format: &str) -> Option<Url> {
-    Url::parse(&format!("file:///home/kxnr/wiki/journal/{}", &datetime.format(format).to_string())).ok()
+/// The daily note for the datetime, in the folder
+pub fn datetime_to_file(folder: &Path, datetime: NaiveDateTime, format: &str) -> Option<Url> {
+    Url::from_file_path(folder.join(format!("{}.md", datetime.format(format)))).ok()
 }
 
 // fn increment_file(filename: &str, increment: JournalIncrement, format: &str) -> Result<String> {
@@ -34,22 +58,2648 @@ fn datetime_to_file(datetime: NaiveDateTime, format: &str) -> Option<Url> {
 //     datetime_to_file(next_as_datetime, format)
 // }
 
-pub fn jump(settings: &Settings, jump_to: Option<&str>) -> Option<ShowDocumentParams> {
+/// The date `jump` opens for the text: an offset from today in days or weeks, like `+3`, `-1d`, or
+/// `+2w`, or any date `fuzzydate` reads, like `tomorrow` or `2024-01-31`
+pub fn parse_date(text: &str) -> Result<NaiveDateTime, String> {
+    let text = text.trim();
+    let offset = text
+        .strip_prefix('+')
+        .map(|count| (1, count))
+        .or_else(|| text.strip_prefix('-').map(|count| (-1, count)));
+
+    if let Some((sign, count)) = offset {
+        let (count, days) = match count.strip_suffix('w') {
+            Some(weeks) => (weeks, 7),
+            None => (count.strip_suffix('d').unwrap_or(count), 1),
+        };
+
+        if !count.is_empty() && count.chars().all(|c| c.is_ascii_digit()) {
+            return count
+                .parse::<i64>()
+                .ok()
+                .and_then(|count| Duration::try_days(sign * count * days))
+                .and_then(|offset| Local::now().naive_local().checked_add_signed(offset))
+                .ok_or_else(|| format!("\"{text}\" is too far from today"));
+        }
+    }
+
+    parse(text).map_err(|_| {
+        format!(
+            "Couldn't read the date \"{text}\"; use an offset from today like +1, -3, +2d, or +1w, \
+             or a date like today, tomorrow, or 2024-01-31"
+        )
+    })
+}
+
+/// Open the note of the named notebook, or of the first, for the date; weekly and monthly notebooks
+/// open the note of the period containing it
+pub fn jump(
+    vault: &Vault,
+    settings: &Settings,
+    jump_to: Option<&str>,
+    notebook: Option<&str>,
+) -> Option<ShowDocumentParams> {
     // if jump_to is None, use the current time.
     // TODO: special syntax to reference the current file and the current time
     // TODO: make fuzzydate relative to any date
-    // TODO: create file
 
-    let note_file = match jump_to {
-        Some(jmp_str) => parse(jmp_str).ok().and_then(|dt| datetime_to_file(dt, &settings.dailynote)),
-        None => datetime_to_file(Local::now().naive_local(), &settings.dailynote)
+    let notebook = notebooks(settings)
+        .into_iter()
+        .find(|it| notebook.is_none() || notebook == Some(it.name.as_str()))?;
+
+    let datetime = match jump_to {
+        Some(jmp_str) => parse_date(jmp_str).ok(),
+        None => Some(Local::now().naive_local()),
     };
+    // formats can have the time, too
+    let note_file = datetime.and_then(|datetime| {
+        let datetime = notebook
+            .period_start(datetime.date())
+            .and_time(datetime.time());
+        datetime_to_file(
+            &notebook.folder(vault.root_dir()),
+            datetime,
+            &notebook.note_format,
+        )
+    });
 
-    note_file.map(|uri| ShowDocumentParams{ uri, 
+    note_file.map(|uri| ShowDocumentParams {
+        selection: uri.to_file_path().ok().and_then(|path| {
+            match &settings.daily_note_cursor_anchor {
+                // the note as it is, or as it will be created
+                Some(anchor) => {
+                    let text = match vault.ropes.get(&path) {
+                        Some(rope) => rope.to_string(),
+                        None => std::fs::read_to_string(&path)
+                            .ok()
+                            .or_else(|| daily_note_text(vault, settings, &path))
+                            .unwrap_or_default(),
+                    };
+                    Some(anchor_selection(&text, anchor))
+                }
+                None => open_selection(vault, settings, &path),
+            }
+        }),
+        uri,
         external: Some(false),
         take_focus: Some(true),
-        selection: None })
+    })
+}
+
+/// The document the link at the position points to, to show, with the edit creating its note first
+/// when the link is unresolved: where the "Create File" code action would, with the link's heading
+/// as its H1. A heading or block missing from an existing note opens the note. `None` when there is
+/// no link at the position, or it is to something that can't be created, like an attachment.
+pub fn open_or_create(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    position: Position,
+) -> Option<(Option<WorkspaceEdit>, ShowDocumentParams)> {
+    let reference = vault
+        .select_reference_at_position(path, position)
+        .filter(|reference| {
+            !matches!(
+                reference,
+                Reference::Tag(..) | Reference::Footnote(..) | Reference::LinkRef(..)
+            )
+        })?;
+    let show = |target: &Path, selection: Option<Range>, external: bool| {
+        Some(ShowDocumentParams {
+            uri: Url::from_file_path(target).ok()?,
+            external: Some(external),
+            take_focus: Some(true),
+            selection,
+        })
+    };
+
+    let index = vault.resolution_index();
+    let resolved = index
+        .resolve(reference, path)
+        .find(|referenceable| !referenceable.is_unresolved());
+    if let Some(referenceable) = resolved {
+        let target = referenceable.get_path();
+        return match referenceable {
+            Referenceable::Attachment(..) => Some((None, show(target, None, true)?)),
+            Referenceable::File(..) => Some((
+                None,
+                show(target, open_selection(vault, settings, target), false)?,
+            )),
+            _ => {
+                let start = referenceable.get_range()?.start;
+                Some((None, show(target, Some(Range::new(start, start)), false)?))
+            }
+        };
+    }
+
+    let (file_ref, heading) = match reference {
+        Reference::WikiFileLink(data) => (data.reference_text.clone(), None),
+        Reference::MDFileLink(data) => (percent_decode(&data.reference_text), None),
+        Reference::WikiHeadingLink(_, file_ref, heading) => (file_ref.clone(), Some(heading)),
+        Reference::MDHeadingLink(_, file_ref, heading) => (percent_decode(file_ref), Some(heading)),
+        Reference::WikiIndexedBlockLink(_, file_ref, _) => (file_ref.clone(), None),
+        Reference::MDIndexedBlockLink(_, file_ref, _) => (percent_decode(file_ref), None),
+        Reference::AttachmentLink(..)
+        | Reference::Tag(..)
+        | Reference::Footnote(..)
+        | Reference::LinkRef(..) => return None,
+    };
+
+    // the note is there, only its heading or block isn't
+    if let Some((target, _)) = index.linked_file(reference, path) {
+        return Some((
+            None,
+            show(target, open_selection(vault, settings, target), false)?,
+        ));
+    }
+
+    let new_path = new_note_path(vault, settings, path, &file_ref);
+    let text = heading.map(|heading| format!("# {}\n", heading));
+    let end = match text {
+        Some(_) => Position::new(1, 0),
+        None => Position::new(0, 0),
+    };
+
+    Some((
+        Some(create_note(&new_path, text)?),
+        show(&new_path, Some(Range::new(end, end)), false)?,
+    ))
 }
 
+/// Create the daily note at the path, filled in from its notebook's `template` or else the
+/// `daily_note_template`. `None` when the note already exists, so that it isn't overwritten.
+pub fn create_daily_note(vault: &Vault, settings: &Settings, path: &Path) -> Option<WorkspaceEdit> {
+    if vault.md_files.contains_key(path) || path.exists() {
+        return None;
+    }
+
+    let text = Some(daily_note_text(vault, settings, path)?).filter(|text| !text.is_empty());
+
+    create_note(path, text)
+}
+
+/// Create the note at the path, without overwriting it, and fill it with the text
+pub fn create_note(path: &Path, text: Option<String>) -> Option<WorkspaceEdit> {
+    let uri = Url::from_file_path(path).ok()?;
+    let create = DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+        uri: uri.clone(),
+        options: Some(CreateFileOptions {
+            overwrite: Some(false),
+            ignore_if_exists: Some(true),
+        }),
+        annotation_id: None,
+    }));
+    let fill = text.map(|text| {
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: vec![OneOf::Left(TextEdit {
+                range: Range::default(),
+                new_text: text,
+            })],
+        })
+    });
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(
+            std::iter::once(create).chain(fill).collect(),
+        )),
+        ..Default::default()
+    })
+}
+
+/// A new note named by the `unique_note_prefix` id for the datetime and the title, in the
+/// `unique_note_folder`: the edit creating it, with the title as its H1 with `title_headings`, and
+/// the document to show with the cursor after the title. `None` when the note already exists.
+pub fn unique_note(
+    vault: &Vault,
+    settings: &Settings,
+    title: &str,
+    datetime: NaiveDateTime,
+) -> Option<(WorkspaceEdit, ShowDocumentParams)> {
+    let id = datetime.format(&settings.unique_note_prefix).to_string();
+    let filename = match title_to_filename(settings.title_filename_style, title) {
+        name if name.is_empty() => id,
+        name => match settings.title_filename_style {
+            FilenameStyle::Title => format!("{id} {name}"),
+            FilenameStyle::Slug => format!("{id}-{name}"),
+        },
+    };
+
+    let folder = match &settings.unique_note_folder {
+        Some(folder) => vault.root_dir().join(folder),
+        None => vault.root_dir().to_path_buf(),
+    };
+    let path = folder.join(format!("{filename}.md"));
+    if vault.md_files.contains_key(&path) || path.exists() {
+        return None;
+    }
+
+    let text = Some(format!("# {}\n", title.trim()))
+        .filter(|_| settings.title_headings && !title.trim().is_empty());
+    let position = match text {
+        Some(_) => Position::new(1, 0),
+        None => Position::new(0, 0),
+    };
+
+    let edit = create_note(&path, text)?;
+    let show = ShowDocumentParams {
+        uri: Url::from_file_path(&path).ok()?,
+        external: Some(false),
+        take_focus: Some(true),
+        selection: Some(Range::new(position, position)),
+    };
+
+    Some((edit, show))
+}
+
+/// Append the text as a bullet to the end of the target note, a path from the vault root, or else
+/// the `inbox_note`, creating the note if it doesn't exist. With `capture_timestamp_format`, the
+/// bullet starts with the datetime in that format. `None` for a note outside the vault.
+pub fn capture(
+    vault: &Vault,
+    settings: &Settings,
+    text: &str,
+    target: Option<&str>,
+    datetime: NaiveDateTime,
+) -> Option<WorkspaceEdit> {
+    let target = target.or(settings.inbox_note.as_deref())?;
+    let path = vault_note_path(vault.root_dir(), target)?;
+
+    let line = match &settings.capture_timestamp_format {
+        Some(format) => format!("- {} {}\n", datetime.format(format), text.trim()),
+        None => format!("- {}\n", text.trim()),
+    };
+
+    let rope = match vault.ropes.get(&path) {
+        Some(rope) => rope.clone(),
+        None if path.exists() => Rope::from_str(&std::fs::read_to_string(&path).ok()?),
+        None => return create_note(&path, Some(line)),
+    };
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(vec![
+            DocumentChangeOperation::Edit(text_document_edit(&path, append_edit(&rope, &line))?),
+        ])),
+        ..Default::default()
+    })
+}
+
+/// The path of a note named from the vault root, with `.md` added when it doesn't end with it; `None`
+/// when `..` segments lead out of the vault
+pub fn vault_note_path(root_dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut path = root_dir.to_path_buf();
+    let mut depth = 0_usize;
+    for component in Path::new(name.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(segment) => {
+                path.push(segment);
+                depth += 1;
+            }
+            Component::ParentDir => {
+                depth = depth.checked_sub(1)?;
+                path.pop();
+            }
+            Component::CurDir => (),
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if depth == 0 {
+        return None;
+    }
+
+    if !name.ends_with(".md") {
+        path.set_file_name(format!("{}.md", path.file_name()?.to_string_lossy()));
+    }
+    Some(path)
+}
+
+/// The text a new daily note at the path starts with, from its notebook's `template` or else the
+/// `daily_note_template`; `None` when the path isn't a daily note's
+fn daily_note_text(vault: &Vault, settings: &Settings, path: &Path) -> Option<String> {
+    let root_dir = vault.root_dir();
+    let title = path.file_stem()?.to_str()?;
+
+    let notebook = notebooks(settings).into_iter().find_map(|notebook| {
+        let date = notebook.match_path(root_dir, path, true)?;
+        (notebook.date_to_path(root_dir, date) == path).then_some((notebook, date))
+    });
+    let (notebook, date) = match notebook {
+        Some((notebook, date)) => (Some(notebook), date),
+        None => (
+            None,
+            NaiveDate::parse_from_str(title, &settings.dailynote).ok()?,
+        ),
+    };
+
+    let text = notebook
+        .and_then(|notebook| notebook.template)
+        .or_else(|| settings.daily_note_template.clone())
+        .and_then(|template| std::fs::read_to_string(root_dir.join(template)).ok())
+        .map(|template| {
+            template
+                .replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+                .replace("{{title}}", title)
+                .replace("{{time}}", &Local::now().format("%H:%M").to_string())
+        })
+        .unwrap_or_default();
+
+    Some(text)
+}
+
+/// Where the cursor goes in a daily note with `daily_note_cursor_anchor`: the end of the section
+/// under the heading named `anchor`, ignoring case, or else the end of the note
+fn anchor_selection(text: &str, anchor: &str) -> Range {
+    let rope = Rope::from_str(text);
+    let lines = rope
+        .lines()
+        .map(|line| line.to_string().trim_end_matches(['\r', '\n']).to_string())
+        .collect_vec();
+    let end_of = |line: usize| Position::new(line as u32, lines[line].chars().count() as u32);
+    let level = |line: &str| {
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        (hashes > 0 && line[hashes..].starts_with(' ')).then_some(hashes)
+    };
+
+    let heading = lines.iter().enumerate().find_map(|(i, line)| {
+        let level = level(line)?;
+        (line[level..].trim().to_lowercase() == anchor.trim().to_lowercase()).then_some((i, level))
+    });
+
+    let position = match heading {
+        // after the section's last line of text, before the blank lines and the next heading
+        Some((heading_line, heading_level)) => {
+            let section_end = (heading_line + 1..lines.len())
+                .find(|i| level(&lines[*i]).is_some_and(|level| level <= heading_level))
+                .unwrap_or(lines.len());
+            let last = (heading_line..section_end)
+                .rev()
+                .find(|i| !lines[*i].trim().is_empty())
+                .unwrap_or(heading_line);
+
+            end_of(last)
+        }
+        None => end_of(lines.len() - 1),
+    };
+
+    Range::new(position, position)
+}
+
+/// The cursor position for a note opened by a command, per the `open_selection` setting
+fn open_selection(vault: &Vault, settings: &Settings, path: &Path) -> Option<Range> {
+    let position = match settings.open_selection {
+        OpenSelection::Start => Position::new(0, 0),
+        OpenSelection::End => {
+            let rope = match vault.ropes.get(path) {
+                Some(rope) => rope.clone(),
+                None => Rope::from_str(&std::fs::read_to_string(path).ok()?),
+            };
+            let last_line = rope.len_lines() - 1;
+
+            Position::new(last_line as u32, rope.line(last_line).len_chars() as u32)
+        }
+        OpenSelection::FirstHeading => vault
+            .select_headings(path)
+            .and_then(|headings| headings.first())
+            .map(|heading| heading.range.end)
+            .unwrap_or(Position::new(0, 0)),
+    };
+
+    Some(Range::new(position, position))
+}
 
 // TODO; next and prev
+
+/// Rename the note after its first H1 and rewrite the links to it. `None` when the note has no H1 or
+/// its filename already matches.
+pub fn sync_filename_to_title(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+) -> Option<WorkspaceEdit> {
+    let (path, file) = vault.md_files.get_key_value(path)?;
+    let title = file.headings.iter().find(|heading| heading.level.0 == 1)?;
+
+    let filename = title_to_filename(settings.title_filename_style, &title.heading_text);
+
+    if filename.is_empty() || path.file_stem()?.to_str()? == filename {
+        return None;
+    }
+
+    rename_referenceable(vault, &Referenceable::File(path, file), &filename)
+}
+
+/// The filename for a note titled `title`: the title without the characters filenames or links can't
+/// have, or its slug
+pub fn title_to_filename(style: FilenameStyle, title: &str) -> String {
+    match style {
+        FilenameStyle::Title => title
+            .chars()
+            .filter(|c| {
+                !matches!(
+                    c,
+                    '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']'
+                )
+            })
+            .collect::<String>()
+            .trim()
+            .to_string(),
+        FilenameStyle::Slug => slugify(title),
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct TagUsage {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Groups of similar tags that are candidates for merging, each ordered by usage with the most used
+/// tag, the likely merge target, first.
+pub fn suggest_tag_merges(vault: &Vault, settings: &Settings) -> Vec<Vec<TagUsage>> {
+    let counts = vault
+        .select_references(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(_, reference)| match reference {
+            Reference::Tag(data) => Some(data.reference_text.trim_start_matches('#')),
+            _ => None,
+        })
+        .counts();
+
+    let tags = counts.keys().copied().sorted().collect_vec();
+
+    // union find over the similar pairs
+    let mut groups = (0..tags.len()).collect_vec();
+    fn root(groups: &mut [usize], i: usize) -> usize {
+        let mut i = i;
+        while groups[i] != i {
+            groups[i] = groups[groups[i]];
+            i = groups[i];
+        }
+        i
+    }
+
+    for (i, j) in (0..tags.len()).tuple_combinations() {
+        if tag_similarity(tags[i], tags[j]) >= settings.tag_merge_threshold {
+            let (root_i, root_j) = (root(&mut groups, i), root(&mut groups, j));
+            groups[root_j] = root_i;
+        }
+    }
+
+    let mut merges: HashMap<usize, Vec<TagUsage>> = HashMap::new();
+    for (i, tag) in tags.iter().enumerate() {
+        merges
+            .entry(root(&mut groups, i))
+            .or_default()
+            .push(TagUsage {
+                tag: tag.to_string(),
+                count: counts[tag],
+            });
+    }
+
+    merges
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            group
+                .into_iter()
+                .sorted_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)))
+                .collect_vec()
+        })
+        .sorted_by(|a, b| a[0].tag.cmp(&b[0].tag))
+        .collect()
+}
+
+/// Rewrite every occurrence of the `from` tag, including its nested tags, to `to`
+pub fn apply_merge(vault: &Vault, from: &str, to: &str) -> Option<WorkspaceEdit> {
+    let from = MDTag {
+        tag_ref: from.trim_start_matches('#').to_string(),
+        range: Default::default(),
+    };
+
+    rename_referenceable(
+        vault,
+        &Referenceable::Tag(vault.root_dir(), &from),
+        to.trim_start_matches('#'),
+    )
+}
+
+/// 1.0 for identical tags or abbreviations (`ml` for `machine-learning`), otherwise the normalized
+/// edit distance between the tags' last segments. Tags under different parents are never similar.
+fn tag_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    let (a_parent, a_leaf) = a.rsplit_once('/').unwrap_or(("", &a));
+    let (b_parent, b_leaf) = b.rsplit_once('/').unwrap_or(("", &b));
+
+    if a_parent != b_parent {
+        return 0.0;
+    }
+
+    if is_abbreviation(a_leaf, b_leaf) || is_abbreviation(b_leaf, a_leaf) {
+        return 1.0;
+    }
+
+    let longest = a_leaf.chars().count().max(b_leaf.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    1.0 - levenshtein(a_leaf, b_leaf) as f64 / longest as f64
+}
+
+fn is_abbreviation(short: &str, long: &str) -> bool {
+    let words = long
+        .split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .collect_vec();
+
+    words.len() > 1
+        && words
+            .iter()
+            .filter_map(|word| word.chars().next())
+            .eq(short.chars())
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct CalendarDay {
+    /// The day as `%Y-%m-%d`
+    pub date: String,
+    pub exists: bool,
+    pub uri: Url,
+    /// Words in the note, if it exists
+    pub word_count: Option<usize>,
+}
+
+/// The daily note for every day of the month, `%Y-%m`, in the named notebook or the first configured
+/// one
+pub fn calendar(
+    vault: &Vault,
+    settings: &Settings,
+    month: &str,
+    notebook: Option<&str>,
+) -> Option<Vec<CalendarDay>> {
+    let first = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok()?;
+    let notebook = notebooks(settings)
+        .into_iter()
+        .find(|it| notebook.is_none() || notebook == Some(it.name.as_str()))?;
+
+    first
+        .iter_days()
+        .take_while(|date| date.month() == first.month())
+        .map(|date| {
+            let path = notebook.date_to_path(vault.root_dir(), date);
+            let rope = vault.ropes.get(&path);
+
+            Some(CalendarDay {
+                date: date.format("%Y-%m-%d").to_string(),
+                exists: rope.is_some() || path.exists(),
+                uri: Url::from_file_path(&path).ok()?,
+                word_count: rope.map(|rope| rope.to_string().split_whitespace().count()),
+            })
+        })
+        .collect()
+}
+
+/// Insert a link to `target` at the position in `source`. With `auto_backlink`, also add a link back
+/// to `source` under the `backlink_heading` of `target`, unless `target` already links to `source`.
+pub fn link_bidirectional(
+    vault: &Vault,
+    settings: &Settings,
+    source: &Path,
+    target: &Path,
+    position: Position,
+) -> Option<WorkspaceEdit> {
+    // a path from the vault root for notes that share their name with others, as `insert_link` does
+    let duplicate_names = vault.select_duplicate_file_names();
+    let name = |from: &Path, to: &Path| {
+        link_name(
+            vault.root_dir(),
+            settings.link_format,
+            from,
+            to,
+            &duplicate_names,
+        )
+    };
+    let source_name = name(target, source)?;
+    let target_name = name(source, target)?;
+
+    let forward_link = text_document_edit(
+        source,
+        TextEdit {
+            range: Range::new(position, position),
+            new_text: format!("[[{}]]", target_name),
+        },
+    )?;
+
+    let backlink = match settings.auto_backlink {
+        true => backlink_edit(vault, settings, source, &source_name, target),
+        false => None,
+    };
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(
+            std::iter::once(forward_link)
+                .chain(backlink)
+                .map(DocumentChangeOperation::Edit)
+                .collect(),
+        )),
+        ..Default::default()
+    })
+}
+
+fn backlink_edit(
+    vault: &Vault,
+    settings: &Settings,
+    source: &Path,
+    source_name: &str,
+    target: &Path,
+) -> Option<TextDocumentEdit> {
+    let (source, source_file) = vault.md_files.get_key_value(source)?;
+    let rope = vault.ropes.get(target)?;
+
+    let source_referenceable = Referenceable::File(source, source_file);
+    let already_linked = vault
+        .select_references(Some(target))?
+        .into_iter()
+        .any(|(path, reference)| source_referenceable.matches_reference(vault, reference, path));
+
+    if already_linked {
+        return None;
+    }
+
+    let backlink_heading = vault
+        .select_headings(target)?
+        .iter()
+        .find(|heading| heading.heading_text.trim() == settings.backlink_heading);
+
+    let edit = match backlink_heading {
+        Some(heading) => {
+            let section = vault.select_heading_section(target, heading)?;
+            // after the section's last line of text
+            let line = (section.start + 1..section.end)
+                .rev()
+                .find(|line| !rope.line(*line).to_string().trim().is_empty())
+                .unwrap_or(section.start)
+                + 1;
+
+            let needs_newline = line >= rope.len_lines() && !rope.to_string().ends_with('\n');
+            let position = match line < rope.len_lines() {
+                true => Position::new(line as u32, 0),
+                false => Position::new(
+                    (rope.len_lines() - 1) as u32,
+                    rope.line(rope.len_lines() - 1).len_chars() as u32,
+                ),
+            };
+
+            TextEdit {
+                range: Range::new(position, position),
+                new_text: format!(
+                    "{}[[{}]]\n",
+                    if needs_newline { "\n" } else { "" },
+                    source_name
+                ),
+            }
+        }
+        None => append_edit(
+            rope,
+            &format!("\n## {}\n[[{}]]\n", settings.backlink_heading, source_name),
+        ),
+    };
+
+    text_document_edit(target, edit)
+}
+
+/// A markdown list of the links to the note from other notes, each with the line it is on
+pub fn backlinks_markdown(vault: &Vault, path: &Path) -> Option<String> {
+    let referenceables = vault
+        .select_referenceable_nodes(Some(path))
+        .into_iter()
+        .filter(|referenceable| {
+            matches!(
+                referenceable,
+                Referenceable::File(..)
+                    | Referenceable::Heading(..)
+                    | Referenceable::IndexedBlock(..)
+            )
+        })
+        .collect_vec();
+    let duplicate_names = vault.select_duplicate_file_names();
+
+    Some(
+        backlinks(vault, &referenceables)
+            .into_iter()
+            .filter(|(reference_path, ..)| *reference_path != path)
+            .sorted_by_key(|(reference_path, reference, _)| {
+                (*reference_path, reference.data().range.start.line)
+            })
+            .unique_by(|(reference_path, reference, _)| {
+                (*reference_path, reference.data().range.start.line)
+            })
+            .flat_map(|(reference_path, _reference, line)| {
+                let name = reference_path.file_stem()?.to_str()?;
+                let link = match duplicate_names.contains(name) {
+                    true => get_obsidian_ref_path(vault.root_dir(), reference_path)?,
+                    false => name.to_string(),
+                };
+
+                Some(format!("- [[{}]]: {}\n", link, line.trim()))
+            })
+            .collect(),
+    )
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct BacklinkGroup {
+    pub uri: Url,
+    /// The source note as a link would name it
+    pub name: String,
+    pub backlinks: Vec<Backlink>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Backlink {
+    pub range: Range,
+    /// The text of the line the link is on
+    pub line_text: String,
+}
+
+/// The links to the note, or to one of its headings or blocks when `anchor` is given, grouped by
+/// the note they're in, for a client's backlinks panel
+pub fn backlinks_panel(
+    vault: &Vault,
+    path: &Path,
+    anchor: Option<&str>,
+) -> Option<Vec<BacklinkGroup>> {
+    let referenceables = vault
+        .select_referenceable_nodes(Some(path))
+        .into_iter()
+        .filter(|referenceable| match (referenceable, anchor) {
+            (
+                Referenceable::File(..)
+                | Referenceable::Heading(..)
+                | Referenceable::IndexedBlock(..),
+                None,
+            ) => true,
+            (Referenceable::Heading(_, heading), Some(anchor)) => {
+                vault.headings_match(anchor, &heading.heading_text)
+            }
+            (Referenceable::IndexedBlock(_, block), Some(anchor)) => {
+                anchor.strip_prefix('^') == Some(block.index.as_str())
+            }
+            _ => false,
+        })
+        .collect_vec();
+    if referenceables.is_empty() {
+        return None;
+    }
+    let duplicate_names = vault.select_duplicate_file_names();
+
+    Some(
+        backlinks(vault, &referenceables)
+            .into_iter()
+            .filter(|(reference_path, ..)| *reference_path != path)
+            .unique_by(|(reference_path, reference, _)| (*reference_path, reference.data().range))
+            .sorted_by_key(|(reference_path, reference, _)| {
+                (*reference_path, reference.data().range.start)
+            })
+            .group_by(|(reference_path, ..)| *reference_path)
+            .into_iter()
+            .flat_map(|(reference_path, references)| {
+                let name = reference_path.file_stem()?.to_str()?;
+
+                Some(BacklinkGroup {
+                    uri: Url::from_file_path(reference_path).ok()?,
+                    name: match duplicate_names.contains(name) {
+                        true => get_obsidian_ref_path(vault.root_dir(), reference_path)?,
+                        false => name.to_string(),
+                    },
+                    backlinks: references
+                        .map(|(_, reference, line)| Backlink {
+                            range: *reference.data().range,
+                            line_text: line.trim_end().to_string(),
+                        })
+                        .collect(),
+                })
+            })
+            .collect(),
+    )
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct NoteGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct GraphNode {
+    pub uri: Url,
+    pub title: String,
+    /// How many links away from the starting note, in either direction
+    pub depth: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub source: Url,
+    pub target: Url,
+    /// How many links the source note has to the target
+    pub count: usize,
+}
+
+/// The notes within `depth` links of the note, following links both out of and into each note,
+/// and the links between them. At most `max_nodes` notes are included, the closest first.
+pub fn note_graph(vault: &Vault, path: &Path, depth: usize, max_nodes: usize) -> Option<NoteGraph> {
+    let (path, _) = vault.md_files.get_key_value(path)?;
+
+    let outgoing = note_links(vault);
+    let incoming = outgoing
+        .iter()
+        .flat_map(|(source, targets)| targets.iter().map(move |target| (*target, *source)))
+        .into_group_map();
+
+    let mut depths = HashMap::from([(path.as_path(), 0)]);
+    let mut nodes = vec![path.as_path()];
+    let mut queue = VecDeque::from([path.as_path()]);
+    while let Some(node) = queue.pop_front() {
+        let node_depth = depths[node];
+        if node_depth >= depth {
+            continue;
+        }
+
+        let neighbors = outgoing
+            .get(node)
+            .into_iter()
+            .chain(incoming.get(node))
+            .flatten()
+            .copied()
+            .sorted()
+            .dedup()
+            .collect_vec();
+        for neighbor in neighbors {
+            if nodes.len() >= max_nodes {
+                break;
+            }
+            if depths.contains_key(neighbor) {
+                continue;
+            }
+
+            depths.insert(neighbor, node_depth + 1);
+            nodes.push(neighbor);
+            queue.push_back(neighbor);
+        }
+    }
+
+    let edges = nodes
+        .iter()
+        .flat_map(|source| {
+            outgoing
+                .get(source)
+                .into_iter()
+                .flatten()
+                .filter(|target| depths.contains_key(*target))
+                .counts()
+                .into_iter()
+                .sorted()
+                .map(|(target, count)| (*source, target, count))
+        })
+        .flat_map(|(source, target, count)| {
+            Some(GraphEdge {
+                source: Url::from_file_path(source).ok()?,
+                target: Url::from_file_path(target).ok()?,
+                count,
+            })
+        })
+        .collect();
+
+    let nodes = nodes
+        .into_iter()
+        .flat_map(|node| {
+            Some(GraphNode {
+                uri: Url::from_file_path(node).ok()?,
+                title: vault.md_files.get(node)?.title(),
+                depth: depths[node],
+            })
+        })
+        .collect();
+
+    Some(NoteGraph { nodes, edges })
+}
+
+/// For each note, the other notes its links resolve to, once for each link; resolved through one
+/// index rather than comparing each link with the whole vault
+fn note_links(vault: &Vault) -> HashMap<&Path, Vec<&Path>> {
+    let index = vault.resolution_index();
+
+    vault
+        .select_references(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, reference)| !matches!(reference, Reference::Tag(..)))
+        .flat_map(|(source, reference)| {
+            index
+                .resolve(reference, source)
+                .filter(|referenceable| {
+                    matches!(
+                        referenceable,
+                        Referenceable::File(..)
+                            | Referenceable::Heading(..)
+                            | Referenceable::IndexedBlock(..)
+                    )
+                })
+                .map(|referenceable| referenceable.get_path())
+                .filter(|target| *target != source)
+                .unique()
+                .flat_map(|target| vault.md_files.get_key_value(target))
+                .map(|(target, _)| (source, target.as_path()))
+                .collect_vec()
+        })
+        .into_group_map()
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct VaultStats {
+    pub notes: usize,
+    /// Links to notes, headings, blocks, and attachments; tags, footnotes, and link references
+    /// aren't counted
+    pub links: usize,
+    /// Links that resolve to nothing in the vault
+    pub unresolved_links: usize,
+    /// Notes that no other note links to, by path
+    pub orphans: Vec<Url>,
+    /// The notes most linked to from other notes, the most linked first
+    pub most_linked: Vec<LinkedNote>,
+    /// Every tag by how often it's used, the most used first
+    pub tags: Vec<TagUsage>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct LinkedNote {
+    pub uri: Url,
+    pub title: String,
+    /// How many links from other notes go to the note or its headings and blocks
+    pub count: usize,
+}
+
+/// Counts of the vault's notes, links, and tags, the notes nothing links to, and the `top` most
+/// linked notes
+pub fn stats(vault: &Vault, top: usize) -> VaultStats {
+    let index = vault.resolution_index();
+    let references = vault.select_references(None).unwrap_or_default();
+    let links = references
+        .iter()
+        .filter(|(_, reference)| {
+            !matches!(
+                reference,
+                Reference::Tag(..) | Reference::Footnote(..) | Reference::LinkRef(..)
+            )
+        })
+        .collect_vec();
+
+    let mut unresolved_links = 0;
+    let mut incoming = HashMap::<&Path, usize>::new();
+    for (path, reference) in &links {
+        let targets = index
+            .resolve(reference, path)
+            .take_while(|referenceable| !referenceable.is_unresolved())
+            .filter_map(|referenceable| vault.md_files.get_key_value(referenceable.get_path()))
+            .map(|(target, _)| target.as_path())
+            .filter(|target| target != path)
+            .unique()
+            .collect_vec();
+        if index
+            .resolve(reference, path)
+            .next()
+            .is_none_or(|referenceable| referenceable.is_unresolved())
+        {
+            unresolved_links += 1;
+        }
+
+        for target in targets {
+            *incoming.entry(target).or_default() += 1;
+        }
+    }
+
+    let orphans = vault
+        .md_files
+        .keys()
+        .filter(|path| !incoming.contains_key(path.as_path()))
+        .sorted()
+        .flat_map(|path| Url::from_file_path(path).ok())
+        .collect();
+
+    let most_linked = incoming
+        .into_iter()
+        .sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)))
+        .take(top)
+        .flat_map(|(path, count)| {
+            Some(LinkedNote {
+                uri: Url::from_file_path(path).ok()?,
+                title: vault.md_files.get(path)?.title(),
+                count,
+            })
+        })
+        .collect();
+
+    let tags = references
+        .iter()
+        .filter_map(|(_, reference)| match reference {
+            Reference::Tag(data) => Some(data.reference_text.trim_start_matches('#')),
+            _ => None,
+        })
+        .counts()
+        .into_iter()
+        .map(|(tag, count)| TagUsage {
+            tag: tag.to_string(),
+            count,
+        })
+        .sorted_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)))
+        .collect();
+
+    VaultStats {
+        notes: vault.md_files.len(),
+        links: links.len(),
+        unresolved_links,
+        orphans,
+        most_linked,
+        tags,
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub file: Url,
+    pub line: u32,
+    pub text: String,
+    pub done: bool,
+}
+
+/// The task list items of every note, by file and then line. With `tag`, only the tasks with the
+/// tag, or a tag nested under it, on their line.
+pub fn tasks(vault: &Vault, tag: Option<&str>) -> Vec<Task> {
+    let tag = tag.map(|tag| tag.trim_start_matches('#'));
+
+    vault
+        .md_files
+        .iter()
+        .sorted_by_key(|(path, _)| *path)
+        .flat_map(|(path, file)| {
+            let uri = Url::from_file_path(path).ok()?;
+            let tagged = move |line: u32| {
+                tag.is_none_or(|tag| {
+                    file.tags.iter().any(|other| {
+                        other.range.start.line == line
+                            && (other.tag_ref == tag
+                                || other.tag_ref.starts_with(&format!("{}/", tag)))
+                    })
+                })
+            };
+
+            Some(
+                file.tasks
+                    .iter()
+                    .filter(move |task| tagged(task.range().start.line))
+                    .map(move |task| Task {
+                        file: uri.clone(),
+                        line: task.range().start.line,
+                        text: task.text.clone(),
+                        done: task.done,
+                    }),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Move each task on the lines to the state after its own in `task_states`, or to the first state
+/// when its own isn't one of them; `None` when there are no tasks on the lines
+pub fn toggle_tasks(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    lines: RangeInclusive<u32>,
+) -> Option<WorkspaceEdit> {
+    let states = settings
+        .task_states
+        .iter()
+        .filter_map(|state| state.chars().next())
+        .collect_vec();
+    let first = *states.first()?;
+
+    let edits = vault
+        .md_files
+        .get(path)?
+        .tasks
+        .iter()
+        .filter(|task| lines.contains(&task.range().start.line))
+        .map(|task| {
+            let next = states
+                .iter()
+                .position(|state| *state == task.state)
+                .and_then(|i| states.get(i + 1))
+                .copied()
+                .unwrap_or(first);
+
+            OneOf::Left(TextEdit {
+                range: task.state_range(),
+                new_text: next.to_string(),
+            })
+        })
+        .collect_vec();
+    if edits.is_empty() {
+        return None;
+    }
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: Url::from_file_path(path).ok()?,
+                version: None,
+            },
+            edits,
+        }])),
+        ..Default::default()
+    })
+}
+
+/// Move the note into `folder`, relative to the vault root, and rewrite the path-qualified links to
+/// and from it. When the folder already has a note of that name, the moved note gets a number after
+/// its name.
+pub fn move_file(vault: &Vault, path: &Path, folder: &Path) -> Option<WorkspaceEdit> {
+    let root_dir = vault.root_dir();
+    let (path, _) = vault.md_files.get_key_value(path)?;
+    let folder = root_dir.join(folder);
+
+    if path.parent() == Some(folder.as_path()) {
+        return None;
+    }
+
+    let stem = path.file_stem()?.to_str()?;
+    let new_path = (0..)
+        .map(|n| match n {
+            0 => folder.join(format!("{stem}.md")),
+            n => folder.join(format!("{stem} {n}.md")),
+        })
+        .find(|candidate| !vault.md_files.contains_key(candidate) && !candidate.exists())?;
+
+    let index = vault.resolution_index();
+    let link_edits = vault
+        .select_references(None)?
+        .into_iter()
+        .filter_map(|(reference_path, reference)| {
+            let file_ref = match reference {
+                Reference::WikiFileLink(data) | Reference::MDFileLink(data) => &data.reference_text,
+                Reference::WikiHeadingLink(_, file_ref, _)
+                | Reference::WikiIndexedBlockLink(_, file_ref, _)
+                | Reference::MDHeadingLink(_, file_ref, _)
+                | Reference::MDIndexedBlockLink(_, file_ref, _) => file_ref,
+                _ => return None,
+            };
+
+            // links by bare name resolve wherever the note is
+            if !file_ref.contains('/') {
+                return None;
+            }
+
+            let (target, _) = index.linked_file(reference, reference_path)?;
+            if reference_path != path && target != path {
+                return None;
+            }
+
+            let new_target = if target == path { &new_path } else { target };
+            let new_folder = match reference_path == path {
+                true => folder.as_path(),
+                false => reference_path.parent()?,
+            };
+
+            let from_root = file_ref
+                .strip_prefix("./")
+                .or_else(|| file_ref.strip_prefix('/'))
+                .unwrap_or(file_ref)
+                .replace("%20", " ")
+                .replace(r"\ ", " ");
+            let new_file_ref = match Some(from_root) == get_obsidian_ref_path(root_dir, target) {
+                true => get_obsidian_ref_path(root_dir, new_target)?,
+                false => {
+                    let relative = diff_paths(new_target, new_folder)?
+                        .with_extension("")
+                        .to_str()?
+                        .replace('\\', "/");
+                    match relative.contains('/') {
+                        true => relative,
+                        false => format!("./{relative}"),
+                    }
+                }
+            };
+            let new_file_ref = match file_ref.contains("%20") {
+                true => new_file_ref.replace(' ', "%20"),
+                false => new_file_ref,
+            };
+
+            if new_file_ref == *file_ref {
+                return None;
+            }
+
+            let range = reference.data().range;
+            let line = vault
+                .ropes
+                .get(reference_path)?
+                .get_line(range.start.line as usize)?
+                .chars()
+                .collect_vec();
+            let text = String::from_iter(
+                line.get(range.start.character as usize..range.end.character as usize)?,
+            );
+
+            // the file ref comes right after the `[[` of a wikilink or the `](` of a markdown link
+            let start = match reference {
+                Reference::MDFileLink(..)
+                | Reference::MDHeadingLink(..)
+                | Reference::MDIndexedBlockLink(..) => text.find("](")? + 2,
+                _ => text.find("[[")? + 2,
+            };
+
+            text_document_edit(
+                reference_path,
+                TextEdit {
+                    range: *range,
+                    new_text: format!(
+                        "{}{}",
+                        &text[..start],
+                        text[start..].replacen(file_ref.as_str(), &new_file_ref, 1)
+                    ),
+                },
+            )
+        })
+        .map(DocumentChangeOperation::Edit);
+
+    let move_op = DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+        old_uri: Url::from_file_path(path).ok()?,
+        new_uri: Url::from_file_path(&new_path).ok()?,
+        options: None,
+        annotation_id: None,
+    }));
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(
+            link_edits.chain(std::iter::once(move_op)).collect(), // edits before the move
+        )),
+        ..Default::default()
+    })
+}
+
+/// Rename the note's heading from `old` to `new` and rewrite the anchors of the links to it, keeping
+/// the block of links to blocks under it, `[[note#Heading#^block]]`. Anchors written as slugs stay
+/// slugs. `None` when the note has no such heading.
+pub fn rename_heading(vault: &Vault, path: &Path, old: &str, new: &str) -> Option<WorkspaceEdit> {
+    let (path, file) = vault.md_files.get_key_value(path)?;
+    let heading = file
+        .headings
+        .iter()
+        .find(|heading| heading.heading_text == old)
+        .or_else(|| {
+            file.headings
+                .iter()
+                .find(|heading| vault.headings_match(old, &heading.heading_text))
+        })?;
+
+    rename_referenceable(vault, &Referenceable::Heading(path, heading), new)
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LinkReplacement {
+    /// The number of links rewritten
+    pub edits: usize,
+    pub edit: WorkspaceEdit,
+}
+
+/// Replace `from` with `to` in the targets of the vault's links, like `old name` in
+/// `[[old name#Heading|display]]` or `[display](old%20name.md)`, leaving display text and prose
+/// alone; with `regex`, `from` is a pattern and `to` can use its groups, as in `$1`
+pub fn replace_in_links(
+    vault: &Vault,
+    from: &str,
+    to: &str,
+    regex: bool,
+) -> Result<LinkReplacement, regex::Error> {
+    let pattern = match regex {
+        true => Regex::new(from)?,
+        false => Regex::new(&regex::escape(from))?,
+    };
+
+    let edits = vault
+        .select_references(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, reference)| {
+            !matches!(
+                reference,
+                Reference::Tag(..) | Reference::Footnote(..) | Reference::LinkRef(..)
+            )
+        })
+        .filter_map(|(path, reference)| {
+            let range = reference.data().range;
+            let line = vault
+                .ropes
+                .get(path)?
+                .get_line(range.start.line as usize)?
+                .chars()
+                .collect_vec();
+            let text = String::from_iter(
+                line.get(range.start.character as usize..range.end.character as usize)?,
+            );
+
+            // the target is between `[[` and the display text or `]]`, or in the parentheses
+            let (start, end) = match text.strip_prefix("[[") {
+                Some(link) => (2, 2 + link.find(['|', ']'])?),
+                None => (text.find("](")? + 2, text.rfind(')')?),
+            };
+            let target = text.get(start..end)?;
+            let replaced = match regex {
+                true => pattern.replace_all(target, to),
+                false => pattern.replace_all(target, NoExpand(to)),
+            };
+            if replaced == target {
+                return None;
+            }
+
+            text_document_edit(
+                path,
+                TextEdit {
+                    range: *range,
+                    new_text: format!("{}{}{}", &text[..start], replaced, &text[end..]),
+                },
+            )
+        })
+        .map(DocumentChangeOperation::Edit)
+        .collect_vec();
+
+    Ok(LinkReplacement {
+        edits: edits.len(),
+        edit: WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(edits)),
+            ..Default::default()
+        },
+    })
+}
+
+/// Insert a wikilink at the position to the note best matching `query`, in the `link_format`. A note
+/// named the query, or with the query as an alias, ignoring case, is preferred over fuzzy matches of
+/// the names and aliases; links to a note found by its alias show the alias.
+pub fn insert_link(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    position: Position,
+    query: &str,
+) -> Option<WorkspaceEdit> {
+    let candidate = resolve_note_query(vault, query)?;
+    let name = link_name(
+        vault.root_dir(),
+        settings.link_format,
+        path,
+        candidate.path,
+        &vault.select_duplicate_file_names(),
+    )?;
+
+    let new_text = match candidate.alias {
+        Some(alias) => format!("[[{}|{}]]", name, alias),
+        None => format!("[[{}]]", name),
+    };
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(vec![
+            DocumentChangeOperation::Edit(text_document_edit(
+                path,
+                TextEdit {
+                    range: Range::new(position, position),
+                    new_text,
+                },
+            )?),
+        ])),
+        ..Default::default()
+    })
+}
+
+/// A note's file name or one of its aliases, to match a query against
+struct NoteCandidate<'a> {
+    path: &'a Path,
+    match_string: &'a str,
+    alias: Option<&'a str>,
+}
+
+impl Matchable for NoteCandidate<'_> {
+    fn match_string(&self) -> &str {
+        self.match_string
+    }
+}
+
+fn resolve_note_query<'a>(vault: &'a Vault, query: &str) -> Option<NoteCandidate<'a>> {
+    let candidates = vault
+        .md_files
+        .iter()
+        .sorted_by_key(|(path, _)| *path)
+        .flat_map(|(path, file)| {
+            let file_name = path.file_stem().and_then(|stem| stem.to_str());
+            let aliases = file.metadata.iter().flat_map(|metadata| metadata.aliases());
+
+            file_name
+                .map(|file_name| NoteCandidate {
+                    path,
+                    match_string: file_name,
+                    alias: None,
+                })
+                .into_iter()
+                .chain(aliases.map(|alias| NoteCandidate {
+                    path,
+                    match_string: alias,
+                    alias: Some(alias),
+                }))
+        })
+        .collect_vec();
+
+    let query = query.trim();
+    let exact = candidates
+        .iter()
+        .positions(|candidate| candidate.match_string.to_lowercase() == query.to_lowercase())
+        .min_by_key(|index| candidates[*index].alias.is_some());
+
+    match exact {
+        Some(index) => candidates.into_iter().nth(index),
+        None => fuzzy_match(query, candidates)
+            .into_iter()
+            .min_by_key(|(_, score)| Reverse(*score))
+            .map(|(candidate, _)| candidate),
+    }
+}
+
+fn text_document_edit(path: &Path, edit: TextEdit) -> Option<TextDocumentEdit> {
+    Some(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: Url::from_file_path(path).ok()?,
+            version: None,
+        },
+        edits: vec![OneOf::Left(edit)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::{Days, NaiveDate};
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::{
+        DocumentChangeOperation, DocumentChanges, OneOf, Position, Range, ResourceOp, TextEdit,
+        Url, WorkspaceEdit,
+    };
+
+    use crate::config::{FilenameStyle, LinkFormat, OpenSelection, Settings};
+    use crate::daily::Notebook;
+    use crate::vault::Vault;
+
+    use super::{
+        anchor_selection, apply_merge, backlinks_markdown, backlinks_panel, calendar, capture,
+        create_daily_note, insert_link, link_bidirectional, move_file, note_graph, open_or_create,
+        open_selection, parse_date, rename_heading, replace_in_links, stats, suggest_tag_merges,
+        sync_filename_to_title, tasks, title_to_filename, toggle_tasks, unique_note, TagUsage,
+    };
+
+    #[test]
+    fn test_sync_filename_to_title() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("draft.md", "# Project Plan\n\nSome text"),
+                (
+                    "other.md",
+                    "See [[draft]] and [[draft#Project Plan|the plan]]",
+                ),
+            ],
+        );
+
+        let edit = sync_filename_to_title(&vault, &settings, &root_dir.join("draft.md")).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+
+        let new_texts = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .inspect(|edit| {
+                assert_eq!(
+                    edit.text_document.uri,
+                    Url::from_file_path("/vault/other.md").unwrap()
+                )
+            })
+            .flat_map(|edit| edit.edits.iter())
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.new_text.as_str(),
+                OneOf::Right(edit) => edit.text_edit.new_text.as_str(),
+            })
+            .sorted()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            new_texts,
+            vec!["[[Project Plan#Project Plan|the plan]]", "[[Project Plan]]"]
+        );
+
+        assert!(operations.iter().any(|operation| matches!(
+            operation,
+            DocumentChangeOperation::Op(ResourceOp::Rename(rename))
+                if rename.old_uri == Url::from_file_path("/vault/draft.md").unwrap()
+                && rename.new_uri == Url::from_file_path("/vault/Project Plan.md").unwrap()
+        )));
+
+        // a `.` in the title isn't taken for an extension
+        let vault = Vault::from_texts(&settings, root_dir, [("draft.md", "# v1.2 Release\n")]);
+        let edit = sync_filename_to_title(&vault, &settings, &root_dir.join("draft.md")).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+        assert!(operations.iter().any(|operation| matches!(
+            operation,
+            DocumentChangeOperation::Op(ResourceOp::Rename(rename))
+                if rename.new_uri == Url::from_file_path("/vault/v1.2 Release.md").unwrap()
+        )));
+    }
+
+    #[test]
+    fn test_sync_filename_to_title_noop() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("Project Plan.md", "# Project Plan"),
+                ("other.md", "[[Project Plan]]"),
+            ],
+        );
+
+        assert_eq!(
+            sync_filename_to_title(&vault, &settings, &root_dir.join("Project Plan.md")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_slug_filename() {
+        assert_eq!(
+            title_to_filename(FilenameStyle::Slug, "Project Plan: Q3 (draft)"),
+            "project-plan-q3-draft"
+        );
+        assert_eq!(
+            title_to_filename(FilenameStyle::Title, "Project Plan: Q3 (draft)"),
+            "Project Plan Q3 (draft)"
+        );
+    }
+
+    #[test]
+    fn test_capture() {
+        let settings = Settings {
+            inbox_note: Some("Inbox.md".into()),
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("Inbox.md", "# Inbox\n\n- first"), ("work/todo.md", "")],
+        );
+        let datetime = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+
+        let appended = |edit: WorkspaceEdit| {
+            let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+                panic!("expected document changes")
+            };
+            match &operations[..] {
+                [DocumentChangeOperation::Edit(edit)] => {
+                    (edit.text_document.uri.clone(), edit.edits.clone())
+                }
+                _ => panic!("expected an edit"),
+            }
+        };
+
+        // after the inbox's last line, which has no newline
+        assert_eq!(
+            appended(capture(&vault, &settings, " Call Sam ", None, datetime).unwrap()),
+            (
+                Url::from_file_path(root_dir.join("Inbox.md")).unwrap(),
+                vec![OneOf::Left(TextEdit {
+                    range: Range::new(Position::new(2, 7), Position::new(2, 7)),
+                    new_text: "\n- Call Sam\n".into(),
+                })]
+            )
+        );
+
+        let timestamped = Settings {
+            capture_timestamp_format: Some("%H:%M".into()),
+            ..settings.clone()
+        };
+        assert_eq!(
+            appended(capture(&vault, &timestamped, "Review", Some("work/todo"), datetime).unwrap()),
+            (
+                Url::from_file_path(root_dir.join("work/todo.md")).unwrap(),
+                vec![OneOf::Left(TextEdit {
+                    range: Range::default(),
+                    new_text: "- 09:30 Review\n".into(),
+                })]
+            )
+        );
+
+        // a missing note is created with the line
+        let edit = capture(&vault, &settings, "Idea", Some("new.md"), datetime).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document changes")
+        };
+        assert!(matches!(
+            &operations[0],
+            DocumentChangeOperation::Op(ResourceOp::Create(create))
+                if create.uri == Url::from_file_path(root_dir.join("new.md")).unwrap()
+        ));
+
+        assert_eq!(
+            capture(&vault, &Settings::default(), "Lost", None, datetime),
+            None
+        );
+        assert_eq!(
+            capture(&vault, &settings, "Out", Some("work/../../x.md"), datetime),
+            None
+        );
+    }
+
+    #[test]
+    fn test_toggle_tasks() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("todo.md");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("todo.md", "- [ ] open\n  - [x] done\n- [/] started\ntext\n")],
+        );
+
+        let toggled = |settings: &Settings, lines| {
+            let Some(DocumentChanges::Edits(edits)) =
+                toggle_tasks(&vault, settings, &path, lines)?.document_changes
+            else {
+                panic!("expected edits")
+            };
+            Some(
+                edits[0]
+                    .edits
+                    .iter()
+                    .map(|edit| match edit {
+                        OneOf::Left(edit) => (edit.range.start, edit.new_text.clone()),
+                        OneOf::Right(_) => panic!("expected a text edit"),
+                    })
+                    .collect_vec(),
+            )
+        };
+
+        assert_eq!(
+            toggled(&settings, 0..=2),
+            Some(vec![
+                (Position::new(0, 3), "x".to_string()),
+                (Position::new(1, 5), " ".to_string()),
+                // a state not in `task_states` starts them over
+                (Position::new(2, 3), " ".to_string()),
+            ])
+        );
+        assert_eq!(toggled(&settings, 3..=3), None);
+
+        let custom = Settings {
+            task_states: [" ", "/", "x", "-"].map(String::from).to_vec(),
+            ..Settings::default()
+        };
+        assert_eq!(
+            toggled(&custom, 0..=2),
+            Some(vec![
+                (Position::new(0, 3), "/".to_string()),
+                (Position::new(1, 5), "-".to_string()),
+                (Position::new(2, 3), "x".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_open_or_create() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("links.md");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Part\n"),
+                (
+                    "links.md",
+                    "[[note#Part]] [[note#Missing]] [[new/idea#Plan]] [[new note]] #tag\n",
+                ),
+            ],
+        );
+
+        let open = |character| {
+            open_or_create(&vault, &settings, &path, Position::new(0, character))
+                .map(|(edit, show)| (edit.is_some(), show.uri, show.selection))
+        };
+        let at = |line, character| {
+            Some(Range::new(
+                Position::new(line, character),
+                Position::new(line, character),
+            ))
+        };
+
+        assert_eq!(
+            open(3),
+            Some((
+                false,
+                Url::from_file_path(root_dir.join("note.md")).unwrap(),
+                at(2, 0)
+            ))
+        );
+        assert_eq!(
+            open(16),
+            Some((
+                false,
+                Url::from_file_path(root_dir.join("note.md")).unwrap(),
+                at(0, 0)
+            ))
+        );
+        assert_eq!(
+            open(33),
+            Some((
+                true,
+                Url::from_file_path(root_dir.join("new/idea.md")).unwrap(),
+                at(1, 0)
+            ))
+        );
+        assert_eq!(
+            open(52),
+            Some((
+                true,
+                Url::from_file_path(root_dir.join("new note.md")).unwrap(),
+                at(0, 0)
+            ))
+        );
+        assert_eq!(open(66), None);
+
+        let (Some(edit), _) =
+            open_or_create(&vault, &settings, &path, Position::new(0, 33)).unwrap()
+        else {
+            panic!("expected an edit")
+        };
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document changes")
+        };
+        let [_, DocumentChangeOperation::Edit(fill)] = &operations[..] else {
+            panic!("expected the note to be created and filled")
+        };
+        assert_eq!(
+            fill.edits,
+            vec![OneOf::Left(TextEdit {
+                range: Range::default(),
+                new_text: "# Plan\n".into(),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_unique_note() {
+        let settings = Settings {
+            unique_note_folder: Some("zettel".into()),
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(&settings, root_dir, [("zettel/202401151230 Taken.md", "")]);
+        let datetime = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+
+        let (edit, show) = unique_note(&vault, &settings, "Atomic: idea", datetime).unwrap();
+        let path = root_dir.join("zettel/202401151230 Atomic idea.md");
+        assert_eq!(show.uri, Url::from_file_path(&path).unwrap());
+        assert_eq!(
+            show.selection,
+            Some(Range::new(Position::new(1, 0), Position::new(1, 0)))
+        );
+
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document changes")
+        };
+        let DocumentChangeOperation::Edit(fill) = &operations[1] else {
+            panic!("expected the note's text")
+        };
+        assert_eq!(
+            fill.edits,
+            vec![OneOf::Left(TextEdit {
+                range: Range::default(),
+                new_text: "# Atomic: idea\n".into(),
+            })]
+        );
+
+        // untitled notes are named by their id alone and start empty
+        let (edit, show) = unique_note(&vault, &settings, "", datetime).unwrap();
+        assert_eq!(
+            show.uri,
+            Url::from_file_path(root_dir.join("zettel/202401151230.md")).unwrap()
+        );
+        assert!(matches!(
+            edit.document_changes,
+            Some(DocumentChanges::Operations(operations)) if operations.len() == 1
+        ));
+
+        assert!(unique_note(&vault, &settings, "Taken", datetime).is_none());
+    }
+
+    #[test]
+    fn test_open_selection_end() {
+        let settings = Settings {
+            open_selection: OpenSelection::End,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(&settings, root_dir, [("note.md", "# Note\n\nlast line")]);
+
+        assert_eq!(
+            open_selection(&vault, &settings, &root_dir.join("note.md")),
+            Some(Range::new(Position::new(2, 9), Position::new(2, 9)))
+        );
+    }
+
+    #[test]
+    fn test_anchor_selection() {
+        let text = "# 2024-01-01\n\n## Log\n- 9:00 standup\n\n## Tasks\n- [ ] review\n";
+        let at = |line, character| {
+            Range::new(
+                Position::new(line, character),
+                Position::new(line, character),
+            )
+        };
+
+        assert_eq!(anchor_selection(text, "log"), at(3, 14));
+        assert_eq!(anchor_selection("## Log\n\n## Tasks\n", "Log"), at(0, 6));
+        assert_eq!(anchor_selection(text, "Missing"), at(7, 0));
+    }
+
+    #[test]
+    fn test_tag_merges() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("one.md", "#ml\n\n#machine-learning\n\n#todo"),
+                ("two.md", "#ml\n\n#ml/deep\n\n#todos\n\n#project"),
+            ],
+        );
+
+        assert_eq!(
+            suggest_tag_merges(&vault, &settings),
+            vec![
+                vec![
+                    TagUsage {
+                        tag: "ml".to_string(),
+                        count: 2
+                    },
+                    TagUsage {
+                        tag: "machine-learning".to_string(),
+                        count: 1
+                    },
+                ],
+                vec![
+                    TagUsage {
+                        tag: "todo".to_string(),
+                        count: 1
+                    },
+                    TagUsage {
+                        tag: "todos".to_string(),
+                        count: 1
+                    },
+                ],
+            ]
+        );
+
+        let edit = apply_merge(&vault, "ml", "machine-learning").unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+
+        let new_texts = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .flat_map(|edit| edit.edits.iter())
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.new_text.as_str(),
+                OneOf::Right(edit) => edit.text_edit.new_text.as_str(),
+            })
+            .sorted()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            new_texts,
+            vec![
+                "#machine-learning",
+                "#machine-learning",
+                "#machine-learning/deep"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calendar() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("2024-02-01.md", "first day of the month"),
+                ("2024-02-29.md", "leap day"),
+                ("2024-03-01.md", ""),
+            ],
+        );
+
+        let days = calendar(&vault, &settings, "2024-02", None).unwrap();
+
+        assert_eq!(days.len(), 29);
+        assert_eq!(
+            days.iter()
+                .filter(|day| day.exists)
+                .map(|day| (day.date.as_str(), day.word_count))
+                .collect::<Vec<_>>(),
+            vec![("2024-02-01", Some(5)), ("2024-02-29", Some(2))]
+        );
+        assert_eq!(
+            days[1].uri,
+            Url::from_file_path("/vault/2024-02-02.md").unwrap()
+        );
+        assert_eq!(days[1].word_count, None);
+    }
+
+    #[test]
+    fn test_link_bidirectional() {
+        let settings = Settings {
+            auto_backlink: true,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("source.md", "See \n"),
+                (
+                    "target.md",
+                    "# Target\n\n## Backlinks\n[[other]]\n\n## Notes\n",
+                ),
+                ("linked.md", "[[source]]\n"),
+                ("other.md", ""),
+                ("archive/other.md", ""),
+            ],
+        );
+
+        let edits = |target: &str| {
+            let edit = link_bidirectional(
+                &vault,
+                &settings,
+                &root_dir.join("source.md"),
+                &root_dir.join(target),
+                Position::new(0, 4),
+            )
+            .unwrap();
+            let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+                panic!("expected document change operations")
+            };
+
+            operations
+                .into_iter()
+                .filter_map(|operation| match operation {
+                    DocumentChangeOperation::Edit(edit) => Some(edit),
+                    _ => None,
+                })
+                .flat_map(|edit| {
+                    let uri = edit.text_document.uri;
+                    edit.edits.into_iter().map(move |edit| match edit {
+                        OneOf::Left(edit) => (uri.clone(), edit),
+                        OneOf::Right(edit) => (uri.clone(), edit.text_edit),
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let target_edits = edits("target.md");
+        assert_eq!(
+            target_edits,
+            vec![
+                (
+                    Url::from_file_path("/vault/source.md").unwrap(),
+                    TextEdit {
+                        range: Range::new(Position::new(0, 4), Position::new(0, 4)),
+                        new_text: "[[target]]".to_string()
+                    }
+                ),
+                (
+                    Url::from_file_path("/vault/target.md").unwrap(),
+                    TextEdit {
+                        range: Range::new(Position::new(4, 0), Position::new(4, 0)),
+                        new_text: "[[source]]\n".to_string()
+                    }
+                ),
+            ]
+        );
+
+        // no duplicate backlink when the target already links to the source
+        assert_eq!(edits("linked.md").len(), 1);
+
+        // a note sharing its name with another is linked by its path
+        assert_eq!(edits("archive/other.md")[0].1.new_text, "[[archive/other]]");
+    }
+
+    #[test]
+    fn test_backlinks_markdown() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Part\n\nSee [[#Part]]\n"),
+                ("a.md", "Read [[note]] first\n\nThen [[note#Part]]\n"),
+                ("b/a.md", "  - [[note|the note]] and [[note#Part]]\n"),
+                ("unrelated.md", "[[a]]\n"),
+            ],
+        );
+
+        assert_eq!(
+            backlinks_markdown(&vault, &root_dir.join("note.md")).unwrap(),
+            "- [[a]]: Read [[note]] first\n\
+             - [[a]]: Then [[note#Part]]\n\
+             - [[b/a]]: - [[note|the note]] and [[note#Part]]\n"
+        );
+    }
+
+    #[test]
+    fn test_backlinks_panel() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Part\n\nSee [[#Part]]\n"),
+                ("a.md", "Read [[note]] first\n\nThen [[note#part]]\n"),
+                ("b/a.md", "  - [[note|the note]] and [[note#Part]]\n"),
+                ("unrelated.md", "[[a]]\n"),
+            ],
+        );
+
+        let panel = |anchor| {
+            backlinks_panel(&vault, &root_dir.join("note.md"), anchor)
+                .unwrap()
+                .into_iter()
+                .map(|group| {
+                    (
+                        group.name,
+                        group
+                            .backlinks
+                            .into_iter()
+                            .map(|backlink| (backlink.range.start.line, backlink.line_text))
+                            .collect_vec(),
+                    )
+                })
+                .collect_vec()
+        };
+
+        assert_eq!(
+            panel(None),
+            vec![
+                (
+                    "a".to_string(),
+                    vec![
+                        (0, "Read [[note]] first".to_string()),
+                        (2, "Then [[note#part]]".to_string())
+                    ]
+                ),
+                (
+                    "b/a".to_string(),
+                    vec![
+                        (0, "  - [[note|the note]] and [[note#Part]]".to_string()),
+                        (0, "  - [[note|the note]] and [[note#Part]]".to_string())
+                    ]
+                ),
+            ]
+        );
+        assert_eq!(
+            panel(Some("Part")),
+            vec![
+                ("a".to_string(), vec![(2, "Then [[note#part]]".to_string())]),
+                (
+                    "b/a".to_string(),
+                    vec![(0, "  - [[note|the note]] and [[note#Part]]".to_string())]
+                ),
+            ]
+        );
+        assert_eq!(
+            backlinks_panel(&vault, &root_dir.join("note.md"), Some("Missing")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rename_heading() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Old Name\n\ntext ^block\n"),
+                (
+                    "links.md",
+                    "[[note#Old Name|alias]] [[note#old-name]] [[note#Old Name#^block]] \
+                     [md](<note#Old Name>) [[note#Note]] [[other#Old Name]]\n",
+                ),
+                ("other.md", "## Old Name\n"),
+            ],
+        );
+
+        let edit =
+            rename_heading(&vault, &root_dir.join("note.md"), "Old Name", "New Name").unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+
+        let new_texts = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .flat_map(|edit| {
+                edit.edits.iter().map(|text_edit| match text_edit {
+                    OneOf::Left(text_edit) => (
+                        edit.text_document.uri.to_file_path().unwrap(),
+                        text_edit.new_text.as_str(),
+                    ),
+                    OneOf::Right(text_edit) => (
+                        edit.text_document.uri.to_file_path().unwrap(),
+                        text_edit.text_edit.new_text.as_str(),
+                    ),
+                })
+            })
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(
+            new_texts,
+            vec![
+                (root_dir.join("links.md"), "[[note#New Name#^block]]"),
+                (root_dir.join("links.md"), "[[note#New Name|alias]]"),
+                (root_dir.join("links.md"), "[[note#new-name]]"),
+                (root_dir.join("links.md"), "[md](<note#New Name>)"),
+                (root_dir.join("note.md"), "## New Name"),
+            ]
+        );
+
+        assert!(rename_heading(&vault, &root_dir.join("note.md"), "Missing", "New").is_none());
+    }
+
+    #[test]
+    fn test_move_file() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let texts = [
+            (
+                "notes/a.md",
+                "# H\n\n[rel](../b/target.md#Top) and [[b/target]] and [[target]]\n",
+            ),
+            (
+                "b/target.md",
+                "# Top\n\n[up](../notes/a) and [[notes/a#H]]\n",
+            ),
+            ("b/other.md", "[[b/target|Target]] and [t](./target)\n"),
+        ];
+        let vault = Vault::from_texts(&settings, root_dir, texts);
+
+        let edit = move_file(
+            &vault,
+            &root_dir.join("b/target.md"),
+            Path::new("archive/old"),
+        )
+        .unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+
+        let new_texts = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .flat_map(|edit| {
+                edit.edits.iter().map(|text_edit| match text_edit {
+                    OneOf::Left(text_edit) => (
+                        edit.text_document.uri.to_file_path().unwrap(),
+                        text_edit.new_text.as_str(),
+                    ),
+                    OneOf::Right(text_edit) => (
+                        edit.text_document.uri.to_file_path().unwrap(),
+                        text_edit.text_edit.new_text.as_str(),
+                    ),
+                })
+            })
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(
+            new_texts,
+            vec![
+                (root_dir.join("b/other.md"), "[[archive/old/target|Target]]"),
+                (root_dir.join("b/other.md"), "[t](../archive/old/target)"),
+                (root_dir.join("b/target.md"), "[up](../../notes/a)"),
+                (root_dir.join("notes/a.md"), "[[archive/old/target]]"),
+                (
+                    root_dir.join("notes/a.md"),
+                    "[rel](../archive/old/target.md#Top)"
+                ),
+            ]
+        );
+        assert!(matches!(
+            operations.last(),
+            Some(DocumentChangeOperation::Op(ResourceOp::Rename(rename)))
+                if rename.new_uri == Url::from_file_path("/vault/archive/old/target.md").unwrap()
+        ));
+
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("b/target.md", ""), ("archive/target.md", "")],
+        );
+        let edit = move_file(&vault, &root_dir.join("b/target.md"), Path::new("archive")).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations")
+        };
+
+        assert!(matches!(
+            operations.as_slice(),
+            [DocumentChangeOperation::Op(ResourceOp::Rename(rename))]
+                if rename.new_uri == Url::from_file_path("/vault/archive/target 1.md").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_tasks() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "b.md",
+                    "- [ ] plain\n- [x] done #work\n  - [ ] nested #work/client\n\n```\n- [ ] code\n```\n",
+                ),
+                ("a.md", "# A\n\n1. [ ] first #workshop\n"),
+            ],
+        );
+
+        let summary = |tag| {
+            tasks(&vault, tag)
+                .into_iter()
+                .map(|task| {
+                    let file = task.file.to_file_path().unwrap();
+                    let name = file.file_name().unwrap().to_string_lossy().to_string();
+                    (name, task.line, task.text, task.done)
+                })
+                .collect_vec()
+        };
+
+        let task =
+            |file: &str, line, text: &str, done| (file.to_string(), line, text.to_string(), done);
+        assert_eq!(
+            summary(None),
+            vec![
+                task("a.md", 2, "first #workshop", false),
+                task("b.md", 0, "plain", false),
+                task("b.md", 1, "done #work", true),
+                task("b.md", 2, "nested #work/client", false),
+            ]
+        );
+        assert_eq!(
+            summary(Some("#work")),
+            vec![
+                task("b.md", 1, "done #work", true),
+                task("b.md", 2, "nested #work/client", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_note_graph() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("start.md", "[[middle]] and [[middle#Part]]\n"),
+                ("middle.md", "# Part\n\n[[far]] [[start]]\n"),
+                ("far.md", "[[farther]]\n"),
+                ("farther.md", ""),
+                ("fan.md", "[[start]]\n"),
+            ],
+        );
+
+        let uri = |name: &str| Url::from_file_path(root_dir.join(name)).unwrap();
+        let graph = |depth, max_nodes| {
+            note_graph(&vault, &root_dir.join("start.md"), depth, max_nodes).unwrap()
+        };
+
+        let graph_one = graph(1, 100);
+        assert_eq!(
+            graph_one
+                .nodes
+                .iter()
+                .map(|node| (node.title.as_str(), node.depth))
+                .collect_vec(),
+            vec![("start", 0), ("fan", 1), ("Part", 1)]
+        );
+        assert_eq!(
+            graph_one
+                .edges
+                .iter()
+                .map(|edge| (edge.source.clone(), edge.target.clone(), edge.count))
+                .collect_vec(),
+            vec![
+                (uri("start.md"), uri("middle.md"), 2),
+                (uri("fan.md"), uri("start.md"), 1),
+                (uri("middle.md"), uri("start.md"), 1),
+            ]
+        );
+
+        assert_eq!(graph(2, 100).nodes.len(), 4);
+        assert_eq!(graph(3, 100).nodes.len(), 5);
+        assert_eq!(graph(3, 2).nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_link() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("projects/plan.md", "---\naliases: [\"Roadmap\"]\n---\n"),
+                ("planning.md", ""),
+                ("notes/today.md", ""),
+            ],
+        );
+
+        let inserted = |settings: &Settings, query: &str| {
+            let edit = insert_link(
+                &vault,
+                settings,
+                &root_dir.join("notes/today.md"),
+                Position::new(0, 0),
+                query,
+            )?;
+            let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+                return None;
+            };
+            match operations.first()? {
+                DocumentChangeOperation::Edit(edit) => match edit.edits.first()? {
+                    OneOf::Left(text_edit) => Some(text_edit.new_text.clone()),
+                    OneOf::Right(text_edit) => Some(text_edit.text_edit.new_text.clone()),
+                },
+                _ => None,
+            }
+        };
+
+        assert_eq!(inserted(&settings, "Plan").as_deref(), Some("[[plan]]"));
+        assert_eq!(
+            inserted(&settings, "planni").as_deref(),
+            Some("[[planning]]")
+        );
+        assert_eq!(
+            inserted(&settings, "roadmap").as_deref(),
+            Some("[[plan|Roadmap]]")
+        );
+        assert_eq!(
+            inserted(
+                &Settings {
+                    link_format: LinkFormat::Relative,
+                    ..settings.clone()
+                },
+                "plan"
+            )
+            .as_deref(),
+            Some("[[../projects/plan]]")
+        );
+        assert_eq!(inserted(&settings, "zzzz"), None);
+    }
+
+    #[test]
+    fn test_create_daily_note() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-template-{}", std::process::id()));
+        std::fs::create_dir_all(root_dir.join("templates")).unwrap();
+        std::fs::write(
+            root_dir.join("templates/daily.md"),
+            "# {{title}}\n\nDate: {{date}}\n",
+        )
+        .unwrap();
+        std::fs::write(root_dir.join("templates/work.md"), "# Work {{date}}\n").unwrap();
+
+        let settings = Settings {
+            dailynote: "%d.%m.%Y".to_string(),
+            daily_note_template: Some("templates/daily.md".to_string()),
+            ..Settings::default()
+        };
+        let vault = Vault::from_texts(&settings, &root_dir, [("01.02.2024.md", "Existing")]);
+
+        let created_text = |settings: &Settings, path: &Path| {
+            let edit = create_daily_note(&vault, settings, path)?;
+            let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+                return None;
+            };
+            assert!(matches!(
+                operations.first(),
+                Some(DocumentChangeOperation::Op(ResourceOp::Create(_)))
+            ));
+            operations.iter().find_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => match edit.edits.first()? {
+                    OneOf::Left(text_edit) => Some(text_edit.new_text.clone()),
+                    OneOf::Right(text_edit) => Some(text_edit.text_edit.new_text.clone()),
+                },
+                _ => None,
+            })
+        };
+
+        let global = created_text(&settings, &root_dir.join("02.02.2024.md"));
+        let existing = create_daily_note(&vault, &settings, &root_dir.join("01.02.2024.md"));
+
+        let notebook_settings = Settings {
+            notebooks: vec![Notebook {
+                name: "work".to_string(),
+                folder: "work".to_string(),
+                note_format: "%Y-%m-%d".to_string(),
+                template: Some("templates/work.md".to_string()),
+                match_folder: false,
+            }],
+            ..settings.clone()
+        };
+        let notebook = created_text(&notebook_settings, &root_dir.join("work/2024-02-03.md"));
+
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(
+            global.as_deref(),
+            Some("# 02.02.2024\n\nDate: 2024-02-02\n")
+        );
+        assert_eq!(existing, None);
+        assert_eq!(notebook.as_deref(), Some("# Work 2024-02-03\n"));
+    }
+
+    #[test]
+    fn test_parse_date_offsets() {
+        let today = chrono::Local::now().date_naive();
+        let date = |text| parse_date(text).map(|datetime| datetime.date());
+
+        assert_eq!(date("+3"), Ok(today + Days::new(3)));
+        assert_eq!(date("-1"), Ok(today - Days::new(1)));
+        assert_eq!(date("+2d"), Ok(today + Days::new(2)));
+        assert_eq!(date("-1w"), Ok(today - Days::new(7)));
+        assert_eq!(date(" +0 "), Ok(today));
+        assert_eq!(date("tomorrow"), Ok(today + Days::new(1)));
+        assert_eq!(
+            date("2024-01-31"),
+            Ok(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())
+        );
+
+        for text in ["+", "+3x", "-+3", "someday"] {
+            assert!(date(text).unwrap_err().contains("+1, -3, +2d, or +1w"));
+        }
+    }
+
+    #[test]
+    fn test_replace_in_links() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("Old Concept.md", "# Part\n"),
+                (
+                    "note.md",
+                    "Old Concept in prose [[Old Concept]] [[Old Concept#Part|Old Concept]]\n\
+                     ![[old concept]] [Old Concept](Old%20Concept.md) #OldConcept\n",
+                ),
+            ],
+        );
+
+        let new_texts = |from, to, regex| {
+            let replacement = replace_in_links(&vault, from, to, regex).unwrap();
+            let Some(DocumentChanges::Operations(operations)) = replacement.edit.document_changes
+            else {
+                panic!("expected operations")
+            };
+            assert_eq!(replacement.edits, operations.len());
+
+            operations
+                .into_iter()
+                .flat_map(|operation| match operation {
+                    DocumentChangeOperation::Edit(edit) => edit.edits,
+                    _ => vec![],
+                })
+                .map(|edit| match edit {
+                    OneOf::Left(edit) => edit.new_text,
+                    OneOf::Right(edit) => edit.text_edit.new_text,
+                })
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(
+            new_texts("Old Concept", "New Idea", false),
+            vec!["[[New Idea#Part|Old Concept]]", "[[New Idea]]"]
+        );
+        assert_eq!(
+            new_texts(r"(?i)old( |%20)concept", "New${1}Idea", true),
+            vec![
+                "[Old Concept](New%20Idea.md)",
+                "[[New Idea#Part|Old Concept]]",
+                "[[New Idea]]",
+                "[[New Idea]]"
+            ]
+        );
+        assert!(replace_in_links(&vault, "(", "", true).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "hub.md",
+                    "# Hub\n\n[[a]] [[b#Part]] [[missing]] ![[gone.png]] #tag #tag\n",
+                ),
+                ("a.md", "[[b]] [[hub]] [[a]] #other [^1]\n\n[^1]: note\n"),
+                ("b.md", "# B\n\n## Part\n"),
+                ("lonely.md", "[[b]] #tag\n"),
+            ],
+        );
+
+        let stats = stats(&vault, 2);
+        let uri = |name: &str| Url::from_file_path(root_dir.join(name)).unwrap();
+
+        assert_eq!(stats.notes, 4);
+        assert_eq!(stats.links, 8);
+        assert_eq!(stats.unresolved_links, 2);
+        // a link to the note itself doesn't count
+        assert_eq!(stats.orphans, vec![uri("lonely.md")]);
+        assert_eq!(
+            stats
+                .most_linked
+                .iter()
+                .map(|linked| (linked.title.as_str(), linked.count))
+                .collect_vec(),
+            vec![("B", 3), ("a", 1)]
+        );
+        assert_eq!(
+            stats.tags,
+            vec![
+                TagUsage {
+                    tag: "tag".to_string(),
+                    count: 3
+                },
+                TagUsage {
+                    tag: "other".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
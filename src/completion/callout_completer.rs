@@ -12,6 +12,9 @@ pub struct CalloutCompleter {
     line: u32,
     character: u32,
     preceding_text: String,
+    /// Range of the partial callout type after `> [!`, including the closing `]` if there is one
+    type_range: Option<std::ops::Range<u32>>,
+    custom_callouts: Vec<String>,
 }
 
 impl<'a> Completer<'a> for CalloutCompleter {
@@ -21,21 +24,34 @@ impl<'a> Completer<'a> for CalloutCompleter {
     {
         let line_chars = context.vault.select_line(context.path, line as isize)?;
 
+        // only the quote markers and the partial type, `> [!type`, are before the cursor
         static PARTIAL_CALLOUT: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"^(?<preceding>(> *)+)").unwrap()); // [display](relativePath)
+            Lazy::new(|| Regex::new(r"^(?<preceding>(> *)+)(\[!(?<type>[^\]\s]*))?$").unwrap());
 
-        let binding = String::from_iter(line_chars);
-        let captures = PARTIAL_CALLOUT.captures(&binding)?;
+        let line_to_cursor = String::from_iter(line_chars.get(0..character)?);
+        let captures = PARTIAL_CALLOUT.captures(&line_to_cursor)?;
 
-        let (full, preceding) = (captures.get(0)?, captures.name("preceding")?);
+        let preceding = captures.name("preceding")?;
 
         let nested_level = preceding.as_str().matches('>').count();
 
+        let type_range = captures.name("type").map(|partial_type| {
+            let start = line_to_cursor[..partial_type.start()].chars().count() as u32;
+            let end = match line_chars.get(character) {
+                Some(']') => character + 1,
+                _ => character,
+            };
+
+            start..end as u32
+        });
+
         return Some(Self {
             nested_level,
             preceding_text: preceding.as_str().to_string(),
             line: line as u32,
             character: character as u32,
+            type_range,
+            custom_callouts: context.settings.custom_callouts.clone(),
         });
     }
 
@@ -72,12 +88,22 @@ impl<'a> Completer<'a> for CalloutCompleter {
             CalloutCompletion::Quote,
             CalloutCompletion::Cite,
         ]
+        .into_iter()
+        .chain(
+            self.custom_callouts
+                .iter()
+                .map(|callout| CalloutCompletion::Custom(callout.to_string())),
+        )
+        .collect()
     }
 
     // TODO: get rid of this in the API
-    type FilterParams = &'static str;
+    type FilterParams = &'a str;
     fn completion_filter_text(&self, params: Self::FilterParams) -> String {
-        format!("{}{}", self.preceding_text, params)
+        match self.type_range {
+            Some(_) => params.to_string(),
+            None => format!("{}{}", self.preceding_text, params),
+        }
     }
 }
 
@@ -109,6 +135,8 @@ enum CalloutCompletion {
     Example,
     Quote,
     Cite,
+    /// A callout type from the `custom_callouts` setting
+    Custom(String),
 }
 
 impl Completable<'_, CalloutCompleter> for CalloutCompletion {
@@ -141,6 +169,7 @@ impl Completable<'_, CalloutCompleter> for CalloutCompletion {
             Self::Example => "example",
             Self::Quote => "quote",
             Self::Cite => "cite",
+            Self::Custom(name) => name,
         };
 
         let label_detail = match self {
@@ -155,6 +184,23 @@ impl Completable<'_, CalloutCompleter> for CalloutCompletion {
             _ => None,
         };
 
+        let description = match self {
+            Self::Note => Some("Neutral information"),
+            Self::Abstract | Self::Summary | Self::Tldr => Some("A summary of the content"),
+            Self::Info => Some("Supporting information"),
+            Self::Todo => Some("Something to be done"),
+            Self::Tip | Self::Hint | Self::Important => Some("Helpful advice"),
+            Self::Success | Self::Check | Self::Done => Some("A completed or positive outcome"),
+            Self::Question | Self::Help | Self::Faq => Some("An open question"),
+            Self::Warning | Self::Caution | Self::Attention => Some("A potential problem"),
+            Self::Failure | Self::Fail | Self::Missing => Some("A failed outcome"),
+            Self::Danger | Self::Error => Some("A critical problem"),
+            Self::Bug => Some("A known issue"),
+            Self::Example => Some("An illustrative example"),
+            Self::Quote | Self::Cite => Some("A quotation"),
+            Self::Custom(_) => Some("Custom callout"),
+        };
+
         let snippet = format!(
             "{prefix}[!{name}] ${{1:Title}}\n{prefix}${{2:Description}}",
             prefix = "> ".repeat(completer.nested_level)
@@ -162,15 +208,22 @@ impl Completable<'_, CalloutCompleter> for CalloutCompletion {
 
         let filter_text = completer.completion_filter_text(name);
 
-        let completion_item = CompletionItem {
-            label: name.to_string(),
-            label_details: label_detail.map(|detail| CompletionItemLabelDetails {
-                detail: Some(detail.to_string()),
-                description: None,
-            }),
-            insert_text_format: Some(InsertTextFormat::SNIPPET),
-            kind: Some(CompletionItemKind::SNIPPET),
-            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+        let text_edit = match &completer.type_range {
+            // only complete the type in `> [!type]`
+            Some(type_range) => TextEdit {
+                range: Range {
+                    start: Position {
+                        line: completer.line,
+                        character: type_range.start,
+                    },
+                    end: Position {
+                        line: completer.line,
+                        character: type_range.end,
+                    },
+                },
+                new_text: format!("{name}]"),
+            },
+            None => TextEdit {
                 range: Range {
                     start: Position {
                         line: completer.line,
@@ -182,7 +235,19 @@ impl Completable<'_, CalloutCompleter> for CalloutCompletion {
                     },
                 },
                 new_text: snippet,
-            })),
+            },
+        };
+
+        let completion_item = CompletionItem {
+            label: name.to_string(),
+            label_details: label_detail.map(|detail| CompletionItemLabelDetails {
+                detail: Some(detail.to_string()),
+                description: None,
+            }),
+            detail: description.map(|description| description.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            kind: Some(CompletionItemKind::SNIPPET),
+            text_edit: Some(CompletionTextEdit::Edit(text_edit)),
             filter_text: Some(filter_text),
             ..Default::default()
         };
@@ -190,3 +255,53 @@ impl Completable<'_, CalloutCompleter> for CalloutCompletion {
         Some(completion_item)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::CompletionTextEdit;
+
+    use crate::completion::{Completable, Completer, Context};
+    use crate::config::Settings;
+    use crate::vault::Vault;
+
+    use super::CalloutCompleter;
+
+    #[test]
+    fn test_callout_type_completion() {
+        let settings = Settings {
+            custom_callouts: vec!["recipe".to_string()],
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(&settings, root_dir, [("note.md", "> [!wa]\n")]);
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("note.md"),
+            settings: &settings,
+        };
+
+        let completer = CalloutCompleter::construct(context, 0, 6).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .collect::<Vec<_>>();
+
+        let warning = items.iter().find(|item| item.label == "warning").unwrap();
+        assert_eq!(warning.detail.as_deref(), Some("A potential problem"));
+        let Some(CompletionTextEdit::Edit(edit)) = &warning.text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "warning]");
+        assert_eq!(
+            (edit.range.start.character, edit.range.end.character),
+            (4, 7)
+        );
+
+        assert!(items.iter().any(|item| item.label == "recipe"));
+    }
+}
@@ -1,21 +1,27 @@
 use std::path::Path;
 
-use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit, Position,
+    Range, TextEdit,
+};
 
 use crate::{
-    ui::preview_referenceable,
-    vault::{MDFootnote, Preview, Referenceable, Vault},
+    config::Settings,
+    vault::{MDFootnote, Preview, Reference, Referenceable, Vault},
 };
 
-use super::{Completable, Completer};
-
-use rayon::prelude::*;
+use super::{preview_item, Completable, Completer};
 
 pub struct FootnoteCompleter<'a> {
     vault: &'a Vault,
     line: usize,
-    character: usize,
+    /// Range of the partial label after the `[`, including the closing `]` if there is one
+    label_range: std::ops::Range<usize>,
     path: &'a Path,
+    settings: &'a Settings,
 }
 
 impl<'a> Completer<'a> for FootnoteCompleter<'a> {
@@ -23,36 +29,64 @@ impl<'a> Completer<'a> for FootnoteCompleter<'a> {
     where
         Self: Sized + Completer<'a>,
     {
+        static PARTIAL_FOOTNOTE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"\[(?<label>\^[^\s\[\]]*)?$").unwrap()); // [^label
+
         let selected_line = context.vault.select_line(context.path, line as isize)?;
+        let line_to_cursor = String::from_iter(selected_line.get(..character)?);
+        let captures = PARTIAL_FOOTNOTE.captures(&line_to_cursor)?;
 
-        if character
-            .checked_sub(1)
-            .and_then(|start| selected_line.get(start..character))
-            == Some(&['['])
-        {
-            Some(FootnoteCompleter {
-                path: context.path,
-                character,
-                line,
-                vault: context.vault,
-            })
-        } else {
-            None
-        }
+        let start = line_to_cursor[..captures.get(0)?.start()].chars().count() + 1;
+        let end = match selected_line.get(character) {
+            Some(']') => character + 1,
+            _ => character,
+        };
+
+        Some(FootnoteCompleter {
+            path: context.path,
+            line,
+            label_range: start..end,
+            vault: context.vault,
+            settings: context.settings,
+        })
     }
 
     fn completions(&self) -> Vec<impl super::Completable<'a, Self>>
     where
         Self: Sized,
     {
+        // a label defined twice is offered once
         let path_footnotes = self
             .vault
             .select_referenceable_nodes(Some(self.path))
-            .into_par_iter()
-            .flat_map(|referenceable| FootnoteCompletion::from_referenceable(referenceable))
-            .collect::<Vec<_>>();
+            .into_iter()
+            .flat_map(FootnoteCompletion::from_referenceable)
+            .unique_by(|completion| completion.label().to_string())
+            .collect_vec();
+
+        // the next number not already a footnote in the file, defined or only used
+        let used = self
+            .vault
+            .select_references(Some(self.path))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(_, reference)| match reference {
+                Reference::Footnote(data) => Some(data.reference_text.clone()),
+                _ => None,
+            })
+            .chain(
+                path_footnotes
+                    .iter()
+                    .map(|completion| completion.label().to_string()),
+            )
+            .filter_map(|label| label.strip_prefix('^')?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0);
 
         path_footnotes
+            .into_iter()
+            .chain(Some(FootnoteCompletion::New(format!("^{}", used + 1))))
+            .collect()
     }
 
     type FilterParams = (&'a str, Referenceable<'a>);
@@ -64,40 +98,185 @@ impl<'a> Completer<'a> for FootnoteCompleter<'a> {
                 Preview::Empty => None,
             })
             .map(|preview_string| format!("{}{}", params.0, &preview_string))
-            .unwrap_or("".to_owned())
+            .unwrap_or(params.0.to_owned())
+    }
+}
+
+impl FootnoteCompleter<'_> {
+    /// The edit writing the label and closing bracket over the partial label
+    fn label_edit(&self, label: &str) -> CompletionTextEdit {
+        CompletionTextEdit::Edit(TextEdit {
+            range: Range::new(
+                Position::new(self.line as u32, self.label_range.start as u32),
+                Position::new(self.line as u32, self.label_range.end as u32),
+            ),
+            new_text: format!("{}]", label),
+        })
+    }
+
+    /// The edit appending an empty definition for the label to the end of the file, after a blank
+    /// line unless it follows other definitions
+    fn definition_edit(&self, label: &str) -> Option<TextEdit> {
+        let rope = self.vault.ropes.get(self.path)?;
+        let lines = rope
+            .lines()
+            .map(|line| line.to_string().trim_end_matches(['\r', '\n']).to_string())
+            .collect_vec();
+        let last = lines.len().saturating_sub(1);
+        let last_text = lines.last().cloned().unwrap_or_default();
+
+        let (position, newline) = match last_text.is_empty() {
+            true => (Position::new(last as u32, 0), ""),
+            false => (
+                Position::new(last as u32, last_text.chars().count() as u32),
+                "\n",
+            ),
+        };
+        let previous = lines.iter().rev().find(|line| !line.trim().is_empty());
+        let blank_line = match previous {
+            Some(line) if !line.starts_with("[^") => "\n",
+            _ => "",
+        };
+
+        Some(TextEdit {
+            range: Range::new(position, position),
+            new_text: format!("{}{}[{}]: ", newline, blank_line, label),
+        })
     }
 }
 
-struct FootnoteCompletion<'a> {
-    footnote: (&'a Path, &'a MDFootnote),
+enum FootnoteCompletion<'a> {
+    Existing(&'a Path, &'a MDFootnote),
+    /// The next free numbered label, defined at the end of the file
+    New(String),
 }
 
 impl FootnoteCompletion<'_> {
     fn from_referenceable(referenceable: Referenceable<'_>) -> Option<FootnoteCompletion<'_>> {
         match referenceable {
-            Referenceable::Footnote(path, footnote) => Some(FootnoteCompletion {
-                footnote: (path, footnote),
-            }),
+            Referenceable::Footnote(path, footnote) => {
+                Some(FootnoteCompletion::Existing(path, footnote))
+            }
             _ => None,
         }
     }
+
+    fn label(&self) -> &str {
+        match self {
+            FootnoteCompletion::Existing(_, footnote) => &footnote.index,
+            FootnoteCompletion::New(label) => label,
+        }
+    }
 }
 
 impl<'a> Completable<'a, FootnoteCompleter<'a>> for FootnoteCompletion<'a> {
     fn completions(&self, completer: &FootnoteCompleter<'a>) -> Option<CompletionItem> {
-        let refname = &self.footnote.1.index;
-
-        let path = self.footnote.0;
-        let path_buf = path.to_path_buf();
-        let self_referenceable = Referenceable::Footnote(&path_buf, self.footnote.1);
-
-        Some(CompletionItem {
-            label: refname.to_string(),
-            kind: Some(CompletionItemKind::REFERENCE),
-            documentation: preview_referenceable(completer.vault, &self_referenceable)
-                .map(Documentation::MarkupContent),
-            filter_text: Some(completer.completion_filter_text((refname, self_referenceable))),
-            ..Default::default()
-        })
+        let refname = self.label();
+
+        match self {
+            FootnoteCompletion::Existing(path, footnote) => {
+                let path_buf = path.to_path_buf();
+                let self_referenceable = Referenceable::Footnote(&path_buf, footnote);
+
+                Some(CompletionItem {
+                    label: refname.to_string(),
+                    kind: Some(CompletionItemKind::REFERENCE),
+                    text_edit: Some(completer.label_edit(refname)),
+                    filter_text: Some(
+                        completer.completion_filter_text((refname, self_referenceable.clone())),
+                    ),
+                    ..preview_item(completer.vault, completer.settings, &self_referenceable)
+                })
+            }
+            FootnoteCompletion::New(label) => Some(CompletionItem {
+                label: label.clone(),
+                label_details: Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some("New footnote".to_string()),
+                }),
+                kind: Some(CompletionItemKind::REFERENCE),
+                text_edit: Some(completer.label_edit(label)),
+                filter_text: Some(label.clone()),
+                additional_text_edits: Some(vec![completer.definition_edit(label)?]),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        CompletionItem, CompletionParams, CompletionResponse, CompletionTextEdit,
+        PartialResultParams, Position, TextDocumentIdentifier, TextDocumentPositionParams, Url,
+        WorkDoneProgressParams,
+    };
+
+    use crate::{completion::get_completions, config::Settings, vault::Vault};
+
+    #[test]
+    fn test_footnote_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("note.md");
+        let completions = |text: &str, position: Position| {
+            let vault = Vault::from_texts(&settings, root_dir, [("note.md", text)]);
+            let params = CompletionParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: None,
+            };
+
+            let Some(CompletionResponse::List(list)) =
+                get_completions(&vault, &[], &params, &path, &settings)
+            else {
+                panic!("expected completions")
+            };
+            list.items
+        };
+        let edit_text = |item: &CompletionItem| match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => edit.new_text.clone(),
+            _ => panic!("expected an edit"),
+        };
+
+        let items = completions(
+            "Text[^1] and [^4] and [^\n\n[^1]: one\n[^1]: again\n[^a]: named\n",
+            Position::new(0, 24),
+        );
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["^1", "^a", "^5"]
+        );
+        assert_eq!(edit_text(&items[0]), "^1]");
+
+        let new = &items[2];
+        let definition = &new.additional_text_edits.as_ref().unwrap()[0];
+        assert_eq!(definition.range.start, Position::new(5, 0));
+        assert_eq!(definition.new_text, "[^5]: ");
+
+        // a closing bracket is written over, and a definition ends the file after a blank line
+        let items = completions("See [^]", Position::new(0, 6));
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected an edit")
+        };
+        assert_eq!(
+            (edit.range.start.character, edit.range.end.character),
+            (5, 7)
+        );
+        assert_eq!(edit.new_text, "^1]");
+        let definition = &items[0].additional_text_edits.as_ref().unwrap()[0];
+        assert_eq!(definition.range.start, Position::new(0, 7));
+        assert_eq!(definition.new_text, "\n\n[^1]: ");
     }
 }
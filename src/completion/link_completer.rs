@@ -1,8 +1,8 @@
 use std::{
-    collections::HashSet,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
     iter::once,
     path::{Path, PathBuf},
-    time::SystemTime,
 };
 
 use chrono::{Duration, NaiveDate};
@@ -11,17 +11,17 @@ use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use regex::Regex;
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit,
-    Documentation, InsertTextFormat, Position, Range, TextEdit,
+    Command, CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit,
+    InsertTextFormat, Position, Range, TextEdit, Url, WorkspaceEdit,
 };
 
 use crate::{
-    completion::util::check_in_code_block, config::Settings, ui::preview_referenceable, vault::{MDFile, MDHeading, Rangeable, Reference, Referenceable, Vault}
+    commands::{create_daily_note, create_note, datetime_to_file}, completion::util::{check_in_code_block, random_block_index}, config::{HeadingLinkFormat, Settings}, daily::{match_notebook, match_notebook_path, relative_date_string}, util::{append_heading_edit, github_slug, levenshtein, slugify}, vault::{frontmatter_len, is_block_boundary, is_canvas, is_image, link_name, MDFile, MDHeading, Reference, Referenceable, Vault}
 };
 
 use super::{
-    matcher::{fuzzy_match_completions, Matchable, OrderedCompletion},
-    Completable, Completer, Context,
+    matcher::{fuzzy_match, Matchable, OrderedCompletion},
+    preview_item, Completable, Completer, Context,
 };
 
 /// Range on a single line; assumes that the line number is known.
@@ -43,15 +43,21 @@ pub struct MarkdownLinkCompleter<'a> {
     pub vault: &'a Vault,
     pub context_path: &'a Path,
     pub settings: &'a Settings,
+    /// The link is an embed, `![display](path)`
+    pub embed: bool,
 }
 
 pub trait LinkCompleter<'a>: Completer<'a> {
     fn settings(&self) -> &'a Settings;
     fn completion_text_edit(&self, display: Option<&str>, refname: &str) -> CompletionTextEdit;
     fn entered_refname(&self) -> String;
+    /// The entered file ref and, after a `#`, the entered infile ref
+    fn entered_link(&self) -> (String, Option<PartialInfileRef>);
     fn vault(&self) -> &'a Vault;
     fn position(&self) -> Position;
     fn path(&self) -> &'a Path;
+    /// Whether the link is an embed, which can also link to attachments
+    fn is_embed(&self) -> bool;
     fn link_completions(&self) -> Vec<LinkCompletion<'a>>
     where
         Self: Sync,
@@ -85,6 +91,7 @@ pub trait LinkCompleter<'a>: Completer<'a> {
         });
 
         let heading_completions = self.settings().heading_completions;
+        let duplicate_names = self.vault().select_duplicate_file_names();
 
         // Get and filter referenceables
         let completions = referenceables
@@ -98,7 +105,7 @@ pub trait LinkCompleter<'a>: Completer<'a> {
                     )
             })
             .flat_map(|referenceable| {
-                LinkCompletion::new(referenceable.clone(), self)
+                LinkCompletion::new(referenceable.clone(), self, &duplicate_names)
                     .into_iter()
                     .par_bridge()
             })
@@ -113,16 +120,274 @@ pub trait LinkCompleter<'a>: Completer<'a> {
 
         // Get daily notes for convienience
         let today = chrono::Local::now().date_naive();
+        let queried = MDDailyNote::from_query(self);
+        // dates entered as the link go to the daily note instead
+        let new_note = match queried {
+            None => self.new_note_completion(&completions),
+            Some(_) => None,
+        };
+        let queried = queried.filter(|queried| {
+            !completions
+                .iter()
+                .any(|completion| completion.match_string() == queried.match_string)
+        });
         let days = (-7..=7)
             .flat_map(|i| Some(today + Duration::try_days(i)?))
             .flat_map(|date| MDDailyNote::from_date(date, self))
             .filter(|date| !refnames.contains(&date.ref_name))
+            .filter(|date| {
+                queried
+                    .as_ref()
+                    .is_none_or(|queried| queried.ref_name != date.ref_name)
+            })
+            .collect_vec()
+            .into_iter()
+            .chain(queried)
             .map(LinkCompletion::DailyNote);
 
-        completions.into_iter().chain(days).collect::<Vec<_>>()
+        let missing = match self.settings().create_missing_on_complete {
+            true => self.missing_infile_completion(&completions),
+            false => None,
+        };
+        let new_blocks = self.new_block_completions(&completions);
+        let alias_headings = match heading_completions {
+            true => self.alias_heading_completions(&duplicate_names),
+            false => vec![],
+        };
+
+        completions
+            .into_iter()
+            .chain(alias_headings)
+            .chain(days)
+            .chain(missing)
+            .chain(new_blocks)
+            .chain(new_note)
+            .collect::<Vec<_>>()
+    }
+
+    /// The headings of the notes with the entered file ref as an alias, like `[[Alias#`, matched by
+    /// the alias but linking to the note
+    fn alias_heading_completions(
+        &self,
+        duplicate_names: &HashSet<String>,
+    ) -> Vec<LinkCompletion<'a>> {
+        let (file_ref, Some(PartialInfileRef::HeadingRef(_))) = self.entered_link() else {
+            return vec![];
+        };
+        let vault = self.vault();
+
+        vault
+            .md_files
+            .iter()
+            .flat_map(|(path, file)| {
+                let alias = file
+                    .metadata
+                    .iter()
+                    .flat_map(|metadata| metadata.aliases())
+                    .find(|alias| alias.to_lowercase() == file_ref.to_lowercase())?;
+                let filename = link_name(
+                    vault.root_dir(),
+                    self.settings().link_format,
+                    self.path(),
+                    path,
+                    duplicate_names,
+                )?;
+
+                Some(file.headings.iter().map(move |heading| AliasHeading {
+                    refname: format!(
+                        "{}#{}",
+                        filename,
+                        heading_anchor(self.settings(), &heading.heading_text)
+                    ),
+                    filename: filename.clone(),
+                    heading,
+                    match_string: format!("{}#{}", alias, heading.heading_text),
+                    referenceable: Referenceable::Heading(path, heading),
+                }))
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// A completion creating the note named by the entered link, when no note, alias, or id has the
+    /// name; the note goes where the create file code action puts it
+    fn new_note_completion(
+        &self,
+        completions: &[LinkCompletion<'a>],
+    ) -> Option<LinkCompletion<'a>> {
+        let (file_ref, None) = self.entered_link() else {
+            return None;
+        };
+        let name = file_ref.trim().trim_end_matches(".md");
+        let file_name = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+
+        let vault = self.vault();
+        let exists = vault.md_files.keys().any(|path| {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| stem.to_lowercase() == file_name)
+        }) || completions.iter().any(|completion| match completion {
+            Alias { match_string, .. } | Id { match_string, .. } => {
+                match_string.to_lowercase() == name.to_lowercase()
+            }
+            _ => false,
+        });
+        if name.is_empty() || name.contains('|') || exists {
+            return None;
+        }
+
+        let folder = match name.contains('/') {
+            true => vault.root_dir().to_path_buf(),
+            false => self
+                .settings()
+                .new_file_folder(vault.root_dir(), self.path())
+                .unwrap_or_else(|| vault.root_dir().to_path_buf()),
+        };
+        let path = folder.join(format!("{}.md", name.trim_start_matches('/')));
+        let refname = link_name(
+            vault.root_dir(),
+            self.settings().link_format,
+            self.path(),
+            &path,
+            &HashSet::new(),
+        )?;
+
+        Some(NewNote {
+            match_string: name.to_string(),
+            refname,
+            path,
+        })
+    }
+
+    /// A completion creating the entered heading or block when the linked file doesn't have it yet
+    fn missing_infile_completion(
+        &self,
+        completions: &[LinkCompletion<'a>],
+    ) -> Option<LinkCompletion<'a>> {
+        let (file_ref, Some(infile_ref)) = self.entered_link() else {
+            return None;
+        };
+
+        match &infile_ref {
+            PartialInfileRef::HeadingRef(heading) if !heading.trim().is_empty() => (),
+            PartialInfileRef::BlockRef(index)
+                if !index.is_empty() && index.chars().all(|c| c.is_alphanumeric() || c == '-') => {}
+            _ => return None,
+        }
+
+        let match_string = format!("{}#{}", file_ref, infile_ref.to_string());
+        let vault = self.vault();
+        let match_key = vault.refname_match_key(match_string.clone());
+        if completions
+            .iter()
+            .any(|completion| vault.refname_match_key(completion.refname()) == match_key)
+        {
+            return None;
+        }
+
+        completions.iter().find_map(|completion| match completion {
+            File {
+                match_string: file_match_string,
+                referenceable,
+                ..
+            } if *file_match_string == file_ref => Some(Missing {
+                match_string: match_string.clone(),
+                infile_ref: infile_ref.clone(),
+                referenceable: referenceable.clone(),
+            }),
+            _ => None,
+        })
+    }
+
+    /// For a heading or block link, `[[note#` or `[[note#^`, completions for the linked file's
+    /// paragraphs and list items that don't have an index yet, by their text; accepting one adds a
+    /// new index to the end of the block and links to it
+    fn new_block_completions(&self, completions: &[LinkCompletion<'a>]) -> Vec<LinkCompletion<'a>> {
+        let (file_ref, Some(infile_ref)) = self.entered_link() else {
+            return vec![];
+        };
+        // matched as entered, by the text after the `#` or the `#^`
+        let separator = match infile_ref {
+            PartialInfileRef::HeadingRef(_) => "#",
+            PartialInfileRef::BlockRef(_) => "#^",
+        };
+
+        let Some((file, referenceable)) =
+            completions.iter().find_map(|completion| match completion {
+                File {
+                    mdfile,
+                    match_string,
+                    referenceable,
+                } if *match_string == file_ref => Some((*mdfile, referenceable)),
+                _ => None,
+            })
+        else {
+            return vec![];
+        };
+        let Some(rope) = self.vault().ropes.get(&file.path) else {
+            return vec![];
+        };
+
+        let lines = rope.lines().map(|line| line.to_string()).collect_vec();
+
+        // frontmatter and fenced code aren't blocks
+        lines
+            .iter()
+            .enumerate()
+            .skip(frontmatter_len(rope))
+            .filter(|(line, text)| {
+                !text.trim().is_empty()
+                    && !text.trim_start().starts_with('#')
+                    && lines
+                        .get(line + 1)
+                        .is_none_or(|next| is_block_boundary(next))
+                    && !file.in_fenced_code(*line)
+                    && !file
+                        .indexed_blocks
+                        .iter()
+                        .any(|block| block.range.start.line as usize == *line)
+            })
+            .flat_map(|(line, text)| {
+                static LIST_MARKER_RE: Lazy<Regex> =
+                    Lazy::new(|| Regex::new(r"^(?:[-*+]|\d+[.)])[ \t]+(?:\[.\][ \t]+)?").unwrap());
+
+                let index = new_block_index(file)?;
+                let text = text.trim();
+                // list items by their text, without the bullet or checkbox
+                let text = LIST_MARKER_RE
+                    .find(text)
+                    .map_or(text, |marker| &text[marker.end()..]);
+
+                Some(NewBlock {
+                    match_string: format!("{}{}{}", file_ref, separator, text),
+                    text: text.to_string(),
+                    end: Position::new(line as u32, text_end(&lines[line])),
+                    refname: format!("{}#^{}", file_ref, index),
+                    index,
+                    referenceable: referenceable.clone(),
+                })
+            })
+            .collect()
     }
 }
 
+/// A short random block index that the file doesn't have yet
+fn new_block_index(file: &MDFile) -> Option<String> {
+    std::iter::repeat_with(random_block_index)
+        .take(100)
+        .find(|index| {
+            !file
+                .indexed_blocks
+                .iter()
+                .any(|block| block.index == *index)
+        })
+}
+
+/// The character after the last non-whitespace one on the line
+fn text_end(line: &str) -> u32 {
+    line.trim_end().chars().count() as u32
+}
+
 impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
     fn settings(&self) -> &'a Settings {
         self.settings
@@ -135,6 +400,10 @@ impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
         self.position
     }
 
+    fn is_embed(&self) -> bool {
+        self.embed
+    }
+
     fn vault(&self) -> &'a Vault {
         self.vault
     }
@@ -150,6 +419,15 @@ impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
         )
     }
 
+    fn entered_link(&self) -> (String, Option<PartialInfileRef>) {
+        (
+            self.path.0.clone(),
+            self.infile_ref
+                .as_ref()
+                .map(|(infile_ref, _)| infile_ref.clone()),
+        )
+    }
+
     /// Will add <$1> to the refname if it contains spaces
     fn completion_text_edit(&self, display: Option<&str>, refname: &str) -> CompletionTextEdit {
         let link_ref_text = match refname.contains(' ') {
@@ -220,7 +498,8 @@ impl<'a> Completer<'a> for MarkdownLinkCompleter<'a> {
             Some(
                 reference @ (Reference::MDFileLink(..)
                 | Reference::MDHeadingLink(..)
-                | Reference::MDIndexedBlockLink(..)),
+                | Reference::MDIndexedBlockLink(..)
+                | Reference::AttachmentLink(..)),
             ) => reference.range.start.character as usize..reference.range.end.character as usize,
             None if line_chars.get(character) == Some(&')') => {
                 full.range().start..full.range().end + 1
@@ -239,6 +518,12 @@ impl<'a> Completer<'a> for MarkdownLinkCompleter<'a> {
             }
         });
 
+        let embed = full_range
+            .start
+            .checked_sub(1)
+            .and_then(|before| line_chars.get(before))
+            == Some(&'!');
+
         let partial = Some(MarkdownLinkCompleter {
             path: (reftext.as_str().to_string(), reftext.range()),
             display: (display.as_str().to_string(), display.range()),
@@ -254,6 +539,7 @@ impl<'a> Completer<'a> for MarkdownLinkCompleter<'a> {
             vault,
             context_path: context.path,
             settings: context.settings,
+            embed,
         });
 
         partial
@@ -271,9 +557,7 @@ impl<'a> Completer<'a> for MarkdownLinkCompleter<'a> {
 
         let link_completions = self.link_completions();
 
-        let matches = fuzzy_match_completions(&filter_text, link_completions);
-
-        matches
+        rank_link_completions(self.vault, &filter_text, link_completions)
     }
 
     /// The completions refname
@@ -321,6 +605,8 @@ pub struct WikiLinkCompleter<'a> {
     context_path: &'a Path,
     settings: &'a Settings,
     chars_in_line: u32,
+    /// The link is an embed, `![[link]]`
+    embed: bool,
 }
 
 impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
@@ -339,6 +625,10 @@ impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
         }
     }
 
+    fn is_embed(&self) -> bool {
+        self.embed
+    }
+
     fn vault(&self) -> &'a Vault {
         self.vault
     }
@@ -347,6 +637,21 @@ impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
         String::from_iter(&self.cmp_text)
     }
 
+    fn entered_link(&self) -> (String, Option<PartialInfileRef>) {
+        let entered = self.entered_refname();
+
+        match entered.split_once('#') {
+            Some((file_ref, infile_ref)) => (
+                file_ref.to_string(),
+                Some(match infile_ref.strip_prefix('^') {
+                    Some(index) => PartialInfileRef::BlockRef(index.to_string()),
+                    None => PartialInfileRef::HeadingRef(infile_ref.to_string()),
+                }),
+            ),
+            None => (entered, None),
+        }
+    }
+
     fn completion_text_edit(&self, display: Option<&str>, refname: &str) -> CompletionTextEdit {
         CompletionTextEdit::Edit(TextEdit {
             range: Range {
@@ -361,7 +666,8 @@ impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
             },
             new_text: format!(
                 "{}{}]]${{2:}}",
-                refname,
+                // a `|` in the filename would start the display text
+                refname.replace('|', r"\|"),
                 display
                     .map(|display| format!("|{}", display))
                     .unwrap_or("".to_string())
@@ -421,6 +727,11 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
                 context_path: context.path,
                 settings: context.settings,
                 chars_in_line: line_chars.len() as u32,
+                // index is at the second `[`
+                embed: index
+                    .checked_sub(2)
+                    .and_then(|before| line_chars.get(before))
+                    == Some(&'!'),
             })
         })
     }
@@ -430,20 +741,17 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
         Self: Sized,
     {
         let WikiLinkCompleter { vault, .. } = self;
+        let duplicate_names = vault.select_duplicate_file_names();
 
         match *self.cmp_text {
             // Give recent referenceables; TODO: improve this;
             [] => self
                 .files
                 .iter()
-                .map(
-                    |path| match std::fs::metadata(path).and_then(|meta| meta.modified()) {
-                        Ok(modified) => (path, modified),
-                        Err(_) => (path, SystemTime::UNIX_EPOCH),
-                    },
-                )
-                .sorted_by_key(|(_, modified)| *modified)
-                .flat_map(|(path, modified)| {
+                .map(|path| (path, vault.modified(path)))
+                .sorted_by_key(|(_, modified)| Reverse(*modified))
+                .enumerate()
+                .flat_map(|(recency, (path, _))| {
                     let referenceables = vault
                         .select_referenceable_nodes(Some(path))
                         .into_iter()
@@ -457,21 +765,23 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
                         })
                         .collect::<Vec<_>>();
 
-                    let modified_string = modified
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .ok()?
-                        .as_secs()
-                        .to_string();
+                    let recency_string = format!("{:0>5}", recency);
 
                     Some(
                         referenceables
                             .into_iter()
-                            .flat_map(move |referenceable| LinkCompletion::new(referenceable, self))
+                            .flat_map(|referenceable| {
+                                LinkCompletion::new(referenceable, self, &duplicate_names)
+                            })
                             .flatten()
+                            .flat_map(|completion| {
+                                let piped = completion.piped();
+                                once(completion).chain(piped)
+                            })
                             .flat_map(move |completion| {
                                 Some(OrderedCompletion::<WikiLinkCompleter, LinkCompletion>::new(
                                     completion,
-                                    modified_string.clone(),
+                                    recency_string.clone(),
                                 ))
                             }),
                     )
@@ -481,12 +791,16 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
             ref filter_text @ [..] if !filter_text.contains(&']') => {
                 let filter_text = &self.cmp_text;
 
-                let link_completions = self.link_completions();
-
-                let matches =
-                    fuzzy_match_completions(&String::from_iter(filter_text), link_completions);
+                let link_completions = self
+                    .link_completions()
+                    .into_iter()
+                    .flat_map(|completion| {
+                        let piped = completion.piped();
+                        once(completion).chain(piped)
+                    })
+                    .collect_vec();
 
-                matches
+                rank_link_completions(vault, &String::from_iter(filter_text), link_completions)
             }
             _ => vec![],
         }
@@ -498,6 +812,59 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
     }
 }
 
+/// Order link completions by how well they match the filter text, after the notes named nearly as
+/// entered so that they're reused rather than created again; ties go to links without a display
+/// alias, then to filename matches over aliases, headings, and unresolved links, and then to the most
+/// recently modified file.
+fn rank_link_completions<'a, C>(
+    vault: &Vault,
+    filter_text: &str,
+    link_completions: Vec<LinkCompletion<'a>>,
+) -> Vec<OrderedCompletion<'a, C, LinkCompletion<'a>>>
+where
+    C: LinkCompleter<'a>,
+    LinkCompletion<'a>: Completable<'a, C>,
+{
+    // notes can be named with their extension, `note.md#Heading`, which completions leave out
+    let filter_text = &match filter_text.split_once('#') {
+        Some((file_ref, infile_ref)) => {
+            format!("{}#{}", file_ref.trim_end_matches(".md"), infile_ref)
+        }
+        None => filter_text.trim_end_matches(".md").to_string(),
+    };
+
+    // near matches are kept even when they don't fuzzy match, as with a typo in the name
+    let (near_matches, link_completions): (Vec<_>, Vec<_>) = link_completions
+        .into_iter()
+        .partition(|completion| completion.near_match_distance(filter_text).is_some());
+
+    near_matches
+        .into_iter()
+        .map(|completion| (completion, 0))
+        .chain(fuzzy_match(filter_text, link_completions))
+        .map(|(completion, score)| {
+            let modified = completion.path().and_then(|path| vault.modified(path));
+
+            (completion, score, modified)
+        })
+        .sorted_by_key(|(completion, score, modified)| {
+            (
+                completion
+                    .near_match_distance(filter_text)
+                    .unwrap_or(usize::MAX),
+                Reverse(*score),
+                matches!(completion, Piped(_)),
+                completion.match_kind_rank(),
+                Reverse(*modified),
+            )
+        })
+        .enumerate()
+        .map(|(rank, (completion, _, _))| {
+            OrderedCompletion::new(completion, format!("{:0>5}", rank))
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub enum LinkCompletion<'a> {
     File {
@@ -506,13 +873,31 @@ pub enum LinkCompletion<'a> {
         referenceable: Referenceable<'a>,
     },
     Alias {
-        filename: &'a str,
+        filename: String,
+        match_string: &'a str,
+        referenceable: Referenceable<'a>,
+    },
+    /// A note matched and linked by its frontmatter `id`
+    Id {
+        mdfile: &'a MDFile,
         match_string: &'a str,
         referenceable: Referenceable<'a>,
     },
     Heading {
         heading: &'a MDHeading,
         match_string: String,
+        /// The link to the heading, in the `heading_link_format`
+        refname: String,
+        referenceable: Referenceable<'a>,
+    },
+    /// A heading of a note, matched through the note's alias: `Alias#Heading`
+    AliasHeading {
+        /// The link to the note
+        filename: String,
+        heading: &'a MDHeading,
+        match_string: String,
+        /// The link to the heading, in the `heading_link_format`
+        refname: String,
         referenceable: Referenceable<'a>,
     },
     Block {
@@ -526,53 +911,121 @@ pub enum LinkCompletion<'a> {
         referenceable: Referenceable<'a>,
     },
     DailyNote(MDDailyNote<'a>),
+    /// A file or heading link that also starts a display alias, `[[note|alias]]`
+    Piped(Box<LinkCompletion<'a>>),
+    /// A heading or block that the linked file doesn't have yet; accepting the completion adds it
+    Missing {
+        match_string: String,
+        infile_ref: PartialInfileRef,
+        /// The linked file
+        referenceable: Referenceable<'a>,
+    },
+    /// A block of the linked file without an index; accepting the completion adds the new index
+    NewBlock {
+        /// The link to the block by its text, for matching
+        match_string: String,
+        text: String,
+        /// Where the index goes: the end of the block's last line
+        end: Position,
+        index: String,
+        /// The link to the block by its new index
+        refname: String,
+        /// The linked file
+        referenceable: Referenceable<'a>,
+    },
+    /// A note that doesn't exist yet, named by the entered link; accepting the completion creates it
+    NewNote {
+        match_string: String,
+        /// The link to the new note, in the `link_format`
+        refname: String,
+        path: PathBuf,
+    },
+    /// An image or PDF, completed in embeds, or a canvas
+    Attachment {
+        match_string: String,
+        referenceable: Referenceable<'a>,
+    },
 }
 
 use LinkCompletion::*;
 
 impl LinkCompletion<'_> {
+    /// `duplicate_names` are file names shared by several files; links to those files use the path
+    /// from the vault root to tell them apart
     fn new<'a>(
         referenceable: Referenceable<'a>,
         completer: &impl LinkCompleter<'a>,
+        duplicate_names: &HashSet<String>,
     ) -> Option<Vec<LinkCompletion<'a>>> {
+        let link_name = |path: &Path| -> Option<String> {
+            link_name(
+                completer.vault().root_dir(),
+                completer.settings().link_format,
+                completer.path(),
+                path,
+                duplicate_names,
+            )
+        };
+
         if let Some(daily) = MDDailyNote::from_referenceable(referenceable.clone(), completer) {
             Some(vec![DailyNote(daily)])
         } else {
             match referenceable {
-                Referenceable::File(_, mdfile) => {
+                Referenceable::File(path, mdfile) => {
+                    let link_name = link_name(path)?;
+
                     Some(
                         once(File {
                             mdfile,
-                            match_string: mdfile.file_name()?.to_string(),
+                            match_string: link_name.clone(),
                             referenceable: referenceable.clone(),
                         })
-                        .chain(mdfile.metadata.iter().flat_map(|it| it.aliases()).flat_map(
-                            |alias| {
-                                Some(Alias {
-                                    filename: mdfile.file_name()?,
+                        .chain(
+                            mdfile
+                                .metadata
+                                .iter()
+                                .flat_map(|it| it.aliases())
+                                .map(|alias| Alias {
+                                    filename: link_name.clone(),
                                     match_string: alias,
                                     referenceable: referenceable.clone(),
-                                })
-                            },
-                        ))
+                                }),
+                        )
+                        .chain(
+                            mdfile
+                                .metadata
+                                .as_ref()
+                                .and_then(|it| it.id())
+                                .filter(|id| *id != link_name)
+                                .map(|id| Id {
+                                    mdfile,
+                                    match_string: id,
+                                    referenceable: referenceable.clone(),
+                                }),
+                        )
+                        .collect(),
+                    )
+                }
+                Referenceable::Heading(path, mdheading) => {
+                    let link_name = link_name(path)?;
+
+                    Some(
+                        once(Heading {
+                            heading: mdheading,
+                            match_string: format!("{}#{}", link_name, mdheading.heading_text),
+                            refname: format!(
+                                "{}#{}",
+                                link_name,
+                                heading_anchor(completer.settings(), &mdheading.heading_text)
+                            ),
+                            referenceable,
+                        })
                         .collect(),
                     )
                 }
-                Referenceable::Heading(path, mdheading) => Some(
-                    once(Heading {
-                        heading: mdheading,
-                        match_string: format!(
-                            "{}#{}",
-                            path.file_stem()?.to_str()?,
-                            mdheading.heading_text
-                        ),
-                        referenceable,
-                    })
-                    .collect(),
-                ),
                 Referenceable::IndexedBlock(path, indexed) => Some(
                     once(Block {
-                        match_string: format!("{}#^{}", path.file_stem()?.to_str()?, indexed.index),
+                        match_string: format!("{}#^{}", link_name(path)?, indexed.index),
                         referenceable,
                     })
                     .collect(),
@@ -601,11 +1054,50 @@ impl LinkCompletion<'_> {
                     })
                     .collect(),
                 ),
+                // canvases are linked to like notes, other attachments only embedded
+                Referenceable::Attachment(path) if completer.is_embed() || is_canvas(path) => {
+                    let file_name = path.file_name()?;
+                    let duplicate = completer
+                        .vault()
+                        .attachments
+                        .iter()
+                        .filter(|other| other.file_name() == Some(file_name))
+                        .nth(1)
+                        .is_some();
+
+                    // like notes, attachments sharing a name are linked by their path from the root
+                    let match_string = match duplicate {
+                        true => {
+                            referenceable
+                                .get_refname(completer.vault().root_dir())?
+                                .full_refname
+                        }
+                        false => file_name.to_str()?.to_string(),
+                    };
+
+                    Some(vec![Attachment {
+                        match_string,
+                        referenceable,
+                    }])
+                }
                 _ => None,
             }
         }
     }
 
+    /// The completion again, inserting a display alias after a `|`, for files and headings, or the
+    /// size of an embedded image
+    fn piped(&self) -> Option<Self> {
+        match self {
+            File { .. } | Id { .. } | Heading { .. } => Some(Piped(Box::new(self.clone()))),
+            Attachment {
+                referenceable: Referenceable::Attachment(path),
+                ..
+            } if is_image(path) => Some(Piped(Box::new(self.clone()))),
+            _ => None,
+        }
+    }
+
     fn default_completion<'a>(
         &self,
         text_edit: CompletionTextEdit,
@@ -616,27 +1108,64 @@ impl LinkCompletion<'_> {
         let referenceable = match self {
             Self::File { referenceable, .. }
             | Self::Heading { referenceable, .. }
+            | Self::AliasHeading { referenceable, .. }
             | Self::Block { referenceable, .. }
             | Self::Unresolved { referenceable, .. }
+            | Self::Missing { referenceable, .. }
+            | Self::NewBlock { referenceable, .. }
+            | Self::Attachment { referenceable, .. }
+            | Self::Id { referenceable, .. }
             | Self::Alias { referenceable, .. } => referenceable.to_owned(),
             Self::DailyNote(daily) => daily.referenceable(completer),
+            Self::NewNote {
+                match_string, path, ..
+            } => Referenceable::UnresovledFile(path.clone(), match_string),
+            Self::Piped(completion) => {
+                return CompletionItem {
+                    label: format!("{}|", completion.match_string()),
+                    label_details: Some(CompletionItemLabelDetails {
+                        detail: Some(match completion.as_ref() {
+                            Attachment { .. } => " with size".into(),
+                            _ => " with alias".into(),
+                        }),
+                        description: None,
+                    }),
+                    preselect: Some(false),
+                    ..completion.default_completion(text_edit, filter_text, completer)
+                }
+            }
         };
 
         let label = self.match_string();
+        // a note named nearly, but not exactly, as entered
+        let did_you_mean = match completer.entered_link() {
+            (entered, None) => self
+                .near_match_distance(&entered)
+                .is_some_and(|distance| distance > 0),
+            _ => false,
+        };
 
         CompletionItem {
             label: label.to_string(),
-            kind: Some(match self {
-                Self::File { .. } => CompletionItemKind::FILE,
-                Self::Heading { .. } | Self::Block { .. } => CompletionItemKind::REFERENCE,
+            kind: match self {
+                Self::File { .. } | Self::Id { .. } | Self::Attachment { .. } => {
+                    Some(CompletionItemKind::FILE)
+                }
+                Self::Heading { .. } | Self::AliasHeading { .. } | Self::Block { .. } => {
+                    Some(CompletionItemKind::REFERENCE)
+                }
                 Self::Unresolved {
                     match_string: _,
                     infile_ref: _,
                     ..
-                } => CompletionItemKind::KEYWORD,
-                Self::Alias { .. } => CompletionItemKind::ENUM,
-                Self::DailyNote { .. } => CompletionItemKind::EVENT,
-            }),
+                } => Some(CompletionItemKind::KEYWORD),
+                Self::Alias { .. } => Some(CompletionItemKind::ENUM),
+                Self::DailyNote { .. } => Some(CompletionItemKind::EVENT),
+                Self::Missing { .. } | Self::NewNote { .. } => Some(CompletionItemKind::KEYWORD),
+                Self::NewBlock { .. } => Some(CompletionItemKind::TEXT),
+                // piped completions are built from the completion they wrap, above
+                Self::Piped(_) => None,
+            },
             label_details: match self {
                 Self::Unresolved {
                     match_string: _,
@@ -647,13 +1176,63 @@ impl LinkCompletion<'_> {
                     description: None,
                 }),
                 Alias { filename, .. } => Some(CompletionItemLabelDetails {
+                    detail: Some(format!("Alias: {}.md", filename)),
+                    description: did_you_mean.then(|| "Did you mean?".into()),
+                }),
+                AliasHeading { filename, .. } => Some(CompletionItemLabelDetails {
                     detail: Some(format!("Alias: {}.md", filename)),
                     description: None,
                 }),
+                File { .. } if did_you_mean => Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some("Did you mean?".into()),
+                }),
+                // the file of its name that links without a path go to
+                File { referenceable, .. } | Attachment { referenceable, .. }
+                    if vault.is_preferred(referenceable.get_path()) =>
+                {
+                    Some(CompletionItemLabelDetails {
+                        detail: None,
+                        description: Some("Preferred".into()),
+                    })
+                }
                 File { .. } => None,
+                Id { .. } => Some(CompletionItemLabelDetails {
+                    detail: Some(" Id".into()),
+                    description: None,
+                }),
                 Heading { .. } => None,
                 Block { .. } => None,
+                DailyNote(daily) if daily.create_on_accept => {
+                    daily
+                        .relative_name(completer)
+                        .map(|relative| CompletionItemLabelDetails {
+                            detail: Some(format!(" {}", relative)),
+                            description: None,
+                        })
+                }
                 DailyNote(_) => None,
+                Piped(_) => None,
+                Missing { infile_ref, .. } => Some(CompletionItemLabelDetails {
+                    detail: Some(match infile_ref {
+                        PartialInfileRef::HeadingRef(_) => " Create heading".into(),
+                        PartialInfileRef::BlockRef(_) => " Create block".into(),
+                    }),
+                    description: None,
+                }),
+                NewBlock { .. } => Some(CompletionItemLabelDetails {
+                    detail: Some(" Create block index".into()),
+                    description: None,
+                }),
+                NewNote { .. } => Some(CompletionItemLabelDetails {
+                    detail: Some(" Create note".into()),
+                    description: None,
+                }),
+                Attachment { .. } => None,
+            },
+            detail: match self {
+                File { mdfile, .. } | Id { mdfile, .. } => Some(mdfile.title()),
+                _ => None,
             },
             text_edit: Some(text_edit),
             preselect: Some(match self {
@@ -663,9 +1242,135 @@ impl LinkCompletion<'_> {
                 link_completion => link_completion.refname() == completer.entered_refname(),
             }),
             filter_text: Some(filter_text.to_string()),
-            documentation: preview_referenceable(vault, &referenceable)
-                .map(Documentation::MarkupContent),
-            ..Default::default()
+            // edits to the current file apply with the completion, unless they would overlap it;
+            // other files need a command
+            additional_text_edits: self
+                .missing_edit(vault)
+                .filter(|(path, edit)| {
+                    *path == completer.path() && edit.range.start.line != completer.position().line
+                })
+                .map(|(_, edit)| vec![edit]),
+            command: self
+                .missing_edit(vault)
+                .filter(|(path, _)| *path != completer.path())
+                .and_then(|(path, edit)| {
+                    let edit = WorkspaceEdit {
+                        changes: Some(HashMap::from([(
+                            Url::from_file_path(path).ok()?,
+                            vec![edit],
+                        )])),
+                        ..Default::default()
+                    };
+
+                    Some(Command {
+                        title: "Create missing heading or block".into(),
+                        command: "apply_edits".into(),
+                        arguments: Some(vec![serde_json::to_value(edit).ok()?]),
+                    })
+                })
+                .or_else(|| match self {
+                    DailyNote(daily) => daily.create_command(completer),
+                    NewNote { path, .. } => create_note_command(path),
+                    _ => None,
+                }),
+            ..preview_item(vault, completer.settings(), &referenceable)
+        }
+    }
+
+    /// For a missing heading or block, the linked file and the edit adding it: a heading at the end
+    /// of the file, or a block index at the end of its last line of text or of the new block
+    fn missing_edit<'b>(&self, vault: &'b Vault) -> Option<(&'b Path, TextEdit)> {
+        let (infile_ref, referenceable) = match self {
+            Missing {
+                infile_ref,
+                referenceable,
+                ..
+            } => (infile_ref, referenceable),
+            NewBlock {
+                end,
+                index,
+                referenceable,
+                ..
+            } => {
+                let path = vault.ropes.get_key_value(referenceable.get_path())?.0;
+                return Some((
+                    path.as_path(),
+                    TextEdit {
+                        range: Range::new(*end, *end),
+                        new_text: format!(" ^{}", index),
+                    },
+                ));
+            }
+            _ => return None,
+        };
+
+        let (path, rope) = vault.ropes.get_key_value(referenceable.get_path())?;
+
+        let edit = match infile_ref {
+            PartialInfileRef::HeadingRef(heading) => {
+                append_heading_edit(rope, vault.md_files.get(path)?, heading)
+            }
+            PartialInfileRef::BlockRef(index) => {
+                let line = (0..rope.len_lines())
+                    .rev()
+                    .find(|line| !rope.line(*line).to_string().trim().is_empty())?;
+                let line_text = rope.line(line).to_string();
+                let end = Position::new(line as u32, line_text.trim_end().chars().count() as u32);
+                TextEdit {
+                    range: Range::new(end, end),
+                    new_text: format!(" ^{}", index),
+                }
+            }
+        };
+
+        Some((path.as_path(), edit))
+    }
+
+    /// How many edits the entered link is from the note or alias name, when it's close enough to be
+    /// a typo of it; ignores case
+    fn near_match_distance(&self, entered: &str) -> Option<usize> {
+        let name = match self {
+            File { .. } | Alias { .. } => self.match_string().to_lowercase(),
+            _ => return None,
+        };
+        let entered = entered.to_lowercase();
+        if entered.chars().count() < 3 || entered.contains('#') {
+            return None;
+        }
+
+        let distance = levenshtein(&entered, &name);
+        (distance <= (entered.chars().count() / 4).clamp(1, 3)).then_some(distance)
+    }
+
+    /// Lower ranks first among equally good matches: filenames, then aliases, then in-file and unresolved links
+    fn match_kind_rank(&self) -> u8 {
+        match self {
+            File { .. } | DailyNote(_) | Attachment { .. } => 0,
+            Alias { .. } | Id { .. } => 1,
+            Heading { .. } | AliasHeading { .. } | Block { .. } => 2,
+            Unresolved { .. } | Missing { .. } | NewBlock { .. } | NewNote { .. } => 3,
+            Piped(completion) => completion.match_kind_rank(),
+        }
+    }
+
+    /// Path of the file the completion links to, if it exists
+    fn path(&self) -> Option<&Path> {
+        match self {
+            File { referenceable, .. }
+            | Alias { referenceable, .. }
+            | Id { referenceable, .. }
+            | Heading { referenceable, .. }
+            | AliasHeading { referenceable, .. }
+            | Block { referenceable, .. } => Some(referenceable.get_path()),
+            DailyNote(MDDailyNote {
+                real_referenceaable: Some(referenceable),
+                ..
+            }) => Some(referenceable.get_path()),
+            Missing { referenceable, .. }
+            | NewBlock { referenceable, .. }
+            | Attachment { referenceable, .. } => Some(referenceable.get_path()),
+            Unresolved { .. } | DailyNote(_) | NewNote { .. } => None,
+            Piped(completion) => completion.path(),
         }
     }
 
@@ -674,10 +1379,17 @@ impl LinkCompletion<'_> {
         match self {
             Self::DailyNote(MDDailyNote { ref_name, .. }) => ref_name.to_string(),
             File { match_string, .. }
-            | Heading { match_string, .. }
             | Block { match_string, .. }
-            | Unresolved { match_string, .. } => match_string.to_string(),
+            | Unresolved { match_string, .. }
+            | Missing { match_string, .. }
+            | Attachment { match_string, .. } => match_string.to_string(),
+            Heading { refname, .. }
+            | AliasHeading { refname, .. }
+            | NewBlock { refname, .. }
+            | NewNote { refname, .. } => refname.to_string(),
             Alias { filename, .. } => filename.to_string(),
+            Id { match_string, .. } => match_string.to_string(),
+            Piped(completion) => completion.refname(),
         }
     }
 }
@@ -687,7 +1399,32 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
         &self,
         markdown_link_completer: &MarkdownLinkCompleter<'a>,
     ) -> Option<CompletionItem> {
-        let refname = self.refname();
+        if let Self::Piped(completion) = self {
+            // markdown links always have a display text
+            return completion.completions(markdown_link_completer);
+        }
+
+        // markdown links name notes with their extension, `note.md#Heading`, and write headings in
+        // the `markdown_heading_link_format`
+        let refname = match (self, self.refname().split_once('#')) {
+            (Attachment { .. }, _) => self.refname(),
+            (Heading { heading, .. } | AliasHeading { heading, .. }, Some((file_ref, _))) => {
+                let settings = markdown_link_completer.settings();
+                let anchor = match settings.markdown_heading_link_format {
+                    HeadingLinkFormat::Text => heading.heading_text.clone(),
+                    HeadingLinkFormat::Slug => github_slug(&heading.heading_text),
+                };
+                match file_ref.is_empty() {
+                    true => format!("#{}", anchor),
+                    false => format!("{}.md#{}", file_ref, anchor),
+                }
+            }
+            (_, Some((file_ref, infile_ref))) if !file_ref.is_empty() => {
+                format!("{}.md#{}", file_ref, infile_ref)
+            }
+            (_, Some(_)) => self.refname(),
+            (_, None) => format!("{}.md", self.refname()),
+        };
         let match_string = self.match_string();
 
         let display = &markdown_link_completer.display;
@@ -698,37 +1435,42 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
                 match_string: _,
                 ..
             }
+            | Self::Id { .. }
             | Self::Block {
                 match_string: _, ..
-            } => None,
+            }
+            | Self::Attachment { .. } => None,
             Self::Alias { match_string, .. } => Some(match_string.to_string()),
             Self::DailyNote(daily) => daily.relative_name(markdown_link_completer),
             Self::Heading {
                 heading,
                 match_string: _,
                 ..
-            } => Some(heading.heading_text.to_string()),
+            }
+            | Self::AliasHeading { heading, .. } => Some(heading.heading_text.to_string()),
             Self::Unresolved {
                 match_string: _,
                 infile_ref,
                 ..
             } => infile_ref.clone(),
+            Self::Missing { infile_ref, .. } => Some(infile_ref.to_string()),
+            Self::NewBlock { text, .. } => Some(text.to_string()),
+            Self::NewNote { match_string, .. } => Some(match_string.to_string()),
+            Self::Piped(_) => None,
         };
 
         let binding = (display.0.as_str(), link_display_text);
         let link_display_text = match binding {
             ("", Some(ref infile)) => infile,
-            // Get the first heading of the file, if possible.
-            ("", None) if markdown_link_completer.settings().title_headings => match self {
-                Self::File { mdfile, .. } => mdfile
-                    .headings
-                    .first()
-                    .map(|heading| heading.heading_text.as_str())
-                    .unwrap_or(""),
+            // Get the title of the file, if it has one besides its filename.
+            ("", None) => match self {
+                Self::File { mdfile, .. } | Self::Id { mdfile, .. } => {
+                    mdfile.title.as_deref().unwrap_or("")
+                }
                 Self::Alias {
                     match_string: alias,
                     ..
-                } => alias,
+                } if markdown_link_completer.settings().title_headings => alias,
                 _ => "",
             },
             (display, _) => display,
@@ -755,11 +1497,30 @@ impl<'a> Completable<'a, WikiLinkCompleter<'a>> for LinkCompletion<'a> {
 
         let wikilink_display_text = match self {
             File { .. } => None,
+            Id { .. } => None,
             Alias { match_string, .. } => Some(format!("${{1:{}}}", match_string)),
             Heading { .. } => None,
+            AliasHeading { .. } => None,
             Block { .. } => None,
             Unresolved { .. } => None,
+            Missing { .. } => None,
+            NewBlock { .. } => None,
+            NewNote { .. } => None,
+            Attachment { .. } => None,
             DailyNote(_) => None,
+            // the title of the note, or the heading itself
+            Piped(completion) => Some(format!(
+                "${{1:{}}}",
+                match completion.as_ref() {
+                    File { mdfile, .. } | Id { mdfile, .. } => {
+                        mdfile.title.as_deref().unwrap_or("")
+                    }
+                    Heading { heading, .. } if completer.settings().title_headings => {
+                        heading.heading_text.as_str()
+                    }
+                    _ => "",
+                }
+            )),
         };
 
         let text_edit = completer.completion_text_edit(wikilink_display_text.as_deref(), &refname);
@@ -787,48 +1548,71 @@ impl Matchable for LinkCompletion<'_> {
                 match_string,
                 ..
             }
+            | AliasHeading { match_string, .. }
             | Block { match_string, .. }
             | Unresolved { match_string, .. }
+            | Missing { match_string, .. }
+            | NewBlock { match_string, .. }
+            | NewNote { match_string, .. }
+            | Attachment { match_string, .. }
             | DailyNote(MDDailyNote { match_string, .. }) => match_string,
-            Alias { match_string, .. } => match_string,
+            Alias { match_string, .. } | Id { match_string, .. } => match_string,
+            Piped(completion) => completion.match_string(),
         }
     }
 }
 
+/// The heading as links to it are completed, in the `heading_link_format`
+fn heading_anchor(settings: &Settings, heading: &str) -> String {
+    match settings.heading_link_format {
+        HeadingLinkFormat::Text => heading.to_string(),
+        HeadingLinkFormat::Slug => slugify(heading),
+    }
+}
+
+/// A command creating the empty note, leaving the note alone if it exists by then
+fn create_note_command(path: &Path) -> Option<Command> {
+    let edit = create_note(path, None)?;
+
+    Some(Command {
+        title: "Create note".into(),
+        command: "apply_edits".into(),
+        arguments: Some(vec![serde_json::to_value(edit).ok()?]),
+    })
+}
+
 #[derive(Clone, Debug)]
 pub struct MDDailyNote<'a> {
     match_string: String,
     ref_name: String,
     real_referenceaable: Option<Referenceable<'a>>,
+    /// Create the note when the completion is accepted and the note doesn't exist
+    create_on_accept: bool,
 }
 
 impl MDDailyNote<'_> {
     pub fn relative_name<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<String> {
         let self_date = self.get_self_date(completer)?;
 
-        Self::relative_date_string(self_date)
+        relative_date_string(
+            completer.settings(),
+            chrono::Local::now().date_naive(),
+            self_date,
+        )
     }
 
     pub fn get_self_date<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<NaiveDate> {
-        let dailynote_format = &completer.settings().dailynote;
-
-        chrono::NaiveDate::parse_from_str(&self.ref_name, dailynote_format).ok()
-    }
-
-    fn relative_date_string(date: NaiveDate) -> Option<String> {
-        let today = chrono::Local::now().date_naive();
-
-        if today == date {
-            Some("today".to_string())
-        } else {
-            match (date - today).num_days() {
-                1 => Some("tomorrow".to_string()),
-                2..=7 => Some(format!("next {}", date.format("%A"))),
-                -1 => Some("yesterday".to_string()),
-                -7..=-1 => Some(format!("last {}", date.format("%A"))),
-                _ => None,
-            }
-        }
+        self.real_referenceaable
+            .as_ref()
+            .and_then(|referenceable| {
+                match_notebook_path(
+                    completer.settings(),
+                    completer.vault().root_dir(),
+                    referenceable.get_path(),
+                )
+            })
+            .or_else(|| match_notebook(completer.settings(), &self.ref_name))
+            .map(|(_, date)| date)
     }
 
     /// The refname used for fuzzy matching a completion - not the actual inserted text
@@ -839,18 +1623,28 @@ impl MDDailyNote<'_> {
         let Some((filerefname, filter_refname)) = (match referenceable {
             Referenceable::File(&ref path, _) | Referenceable::UnresovledFile(ref path, _) => {
                 let filename = path.file_name();
-                let dailynote_format = &completer.settings().dailynote;
                 let (date, filename) = filename.and_then(|filename| {
                     let filename = filename.to_str()?;
                     let filename = filename.replace(".md", "");
-                    Some((
-                        chrono::NaiveDate::parse_from_str(&filename, dailynote_format).ok(),
-                        filename,
-                    ))
+                    // nested formats match the path from the notebook's folder
+                    let date = match_notebook_path(
+                        completer.settings(),
+                        completer.vault().root_dir(),
+                        path,
+                    )
+                    .or_else(|| match_notebook(completer.settings(), &filename))
+                    .map(|(_, date)| date);
+                    Some((date, filename))
                 })?;
 
-                date.and_then(Self::relative_date_string)
-                    .map(|thing| (filename.clone(), format!("{}: {}", thing, filename)))
+                date.and_then(|date| {
+                    relative_date_string(
+                        completer.settings(),
+                        chrono::Local::now().date_naive(),
+                        date,
+                    )
+                })
+                .map(|thing| (filename.clone(), format!("{}: {}", thing, filename)))
             }
             _ => None,
         }) else {
@@ -861,6 +1655,7 @@ impl MDDailyNote<'_> {
             match_string: filter_refname,
             ref_name: filerefname,
             real_referenceaable: Some(referenceable),
+            create_on_accept: false,
         })
     }
 
@@ -869,13 +1664,71 @@ impl MDDailyNote<'_> {
         completer: &impl LinkCompleter<'a>,
     ) -> Option<MDDailyNote<'a>> {
         let filerefname = date.format(&completer.settings().dailynote).to_string();
-        let match_string = format!("{}: {}", Self::relative_date_string(date)?, filerefname);
+        let relative = relative_date_string(
+            completer.settings(),
+            chrono::Local::now().date_naive(),
+            date,
+        )?;
+        let match_string = format!("{}: {}", relative, filerefname);
 
         // path on unresolved file is useless
         Some(MDDailyNote {
             match_string,
             ref_name: filerefname.clone(),
             real_referenceaable: None,
+            create_on_accept: false,
+        })
+    }
+
+    /// The daily note for a date entered as the link, like `2024-01-15` or `next friday`
+    fn from_query<'a>(completer: &impl LinkCompleter<'a>) -> Option<MDDailyNote<'a>> {
+        let (query, None) = completer.entered_link() else {
+            return None;
+        };
+        let query = query.trim();
+        if query.is_empty() {
+            return None;
+        }
+
+        let format = &completer.settings().dailynote;
+        let datetime = NaiveDate::parse_from_str(query, format)
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .or_else(|| fuzzydate::parse(query).ok())?;
+
+        let path = datetime_to_file(completer.vault().root_dir(), datetime, format)?
+            .to_file_path()
+            .ok()?;
+        let ref_name = datetime.format(format).to_string();
+
+        Some(MDDailyNote {
+            match_string: format!("{}: {}", query, ref_name),
+            ref_name,
+            real_referenceaable: completer
+                .vault()
+                .md_files
+                .get_key_value(&path)
+                .map(|(path, file)| Referenceable::File(path, file)),
+            create_on_accept: true,
+        })
+    }
+
+    /// A command creating the note, for completions that create it on accept
+    fn create_command<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<Command> {
+        if !self.create_on_accept || self.real_referenceaable.is_some() {
+            return None;
+        }
+
+        let edit = create_daily_note(
+            completer.vault(),
+            completer.settings(),
+            self.referenceable(completer).get_path(),
+        )?;
+
+        Some(Command {
+            title: "Create daily note".into(),
+            command: "apply_edits".into(),
+            arguments: Some(vec![serde_json::to_value(edit).ok()?]),
         })
     }
 
@@ -893,3 +1746,762 @@ impl MDDailyNote<'_> {
         unresolved_file
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::{
+        CompletionItem, CompletionItemKind, CompletionTextEdit, DocumentChangeOperation,
+        DocumentChanges, Position, Range, ResourceOp, TextEdit, Url, WorkspaceEdit,
+    };
+
+    use crate::completion::{Completable, Completer, Context};
+    use crate::config::{HeadingLinkFormat, LinkFormat, Settings};
+    use crate::vault::Vault;
+
+    use super::{MarkdownLinkCompleter, WikiLinkCompleter};
+
+    #[test]
+    fn test_link_completion_ranking() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("project.md", "# Overview"),
+                ("prj.md", ""),
+                ("other.md", "---\naliases: [prj]\n---\n"),
+                ("current.md", "[[prj\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 5).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+
+        // the exact filename beats the equally matching alias; both beat the weaker `project` match
+        assert_eq!(items[0].label, "prj");
+        assert_eq!(items[0].kind, Some(CompletionItemKind::FILE));
+        assert_eq!(items[1].label, "prj");
+        assert_eq!(items[1].kind, Some(CompletionItemKind::ENUM));
+        assert_eq!(items[2].label, "prj|");
+        assert_eq!(items[3].label, "project");
+    }
+
+    #[test]
+    fn test_near_match_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("meeting notes.md", ""),
+                ("notes.md", ""),
+                ("current.md", "[[meeting ntoes\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 15).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+
+        // the typo of an existing note comes before creating a new one
+        assert_eq!(items[0].label, "meeting notes");
+        assert_eq!(
+            items[0]
+                .label_details
+                .as_ref()
+                .and_then(|details| details.description.as_deref()),
+            Some("Did you mean?")
+        );
+        let new_note = items
+            .iter()
+            .position(|item| item.label == "meeting ntoes")
+            .unwrap();
+        assert!(new_note > 0);
+    }
+
+    #[test]
+    fn test_alias_heading_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "real-note.md",
+                    "---\naliases: [Project]\n---\n# Project\n\n## Goals\n",
+                ),
+                ("current.md", "[[Project#Goa\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 13).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+
+        // matched by the alias, linking to the note
+        assert_eq!(items[0].label, "Project#Goals");
+        assert_eq!(items[0].kind, Some(CompletionItemKind::REFERENCE));
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "real-note#Goals]]${2:}");
+    }
+
+    #[test]
+    fn test_slug_heading_completions() {
+        let settings = Settings {
+            heading_link_format: HeadingLinkFormat::Slug,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Goals & Plans\n"),
+                ("current.md", "[[note#Goa\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 10).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+
+        // matched by the heading text, inserting the slug
+        assert_eq!(items[0].label, "note#Goals & Plans");
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "note#goals-plans]]${2:}");
+    }
+
+    #[test]
+    fn test_md_extension_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Part\n"),
+                ("wiki.md", "[[note.md\n"),
+                ("markdown.md", "[text](note\n\n[text](note.md#Pa\n"),
+            ],
+        );
+
+        let wiki_path = root_dir.join("wiki.md");
+        let markdown_path = root_dir.join("markdown.md");
+        let context = |path| Context {
+            vault: &vault,
+            opened_files: &[],
+            path,
+            settings: &settings,
+        };
+        let new_text = |item: &CompletionItem| match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => edit.new_text.clone(),
+            _ => panic!("expected a text edit"),
+        };
+
+        // wikilinks leave the extension out, even when it's entered
+        let completer = WikiLinkCompleter::construct(context(&wiki_path), 0, 9).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+        assert_eq!(new_text(&items[0]), "note]]${2:}");
+
+        // markdown links include it
+        let completer = MarkdownLinkCompleter::construct(context(&markdown_path), 0, 11).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+        assert_eq!(new_text(&items[0]), "[${1:text}](note.md)");
+
+        let completer = MarkdownLinkCompleter::construct(context(&markdown_path), 2, 17).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .collect_vec();
+        assert_eq!(new_text(&items[0]), "[${1:text}](note.md#part)");
+    }
+
+    #[test]
+    fn test_path_qualified_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("a/note.md", ""),
+                ("b/note.md", ""),
+                ("c/notebook.md", ""),
+                ("current.md", "[[note\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 6).unwrap();
+        let labels = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .map(|item| item.label)
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(
+            labels,
+            vec![
+                "a/note",
+                "a/note|",
+                "b/note",
+                "b/note|",
+                "notebook",
+                "notebook|"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_format_completions() {
+        let root_dir = Path::new("/vault");
+        let files = [
+            ("notes/current.md", "[[note\n"),
+            ("notes/sub/note.md", ""),
+            ("archive/notebook.md", ""),
+        ];
+
+        let labels = |link_format| {
+            let settings = Settings {
+                link_format,
+                ..Settings::default()
+            };
+            let vault = Vault::from_texts(&settings, root_dir, files);
+            let context = Context {
+                vault: &vault,
+                opened_files: &[],
+                path: &root_dir.join("notes/current.md"),
+                settings: &settings,
+            };
+
+            let completer = WikiLinkCompleter::construct(context, 0, 6).unwrap();
+            completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .map(|item| item.label)
+                .filter(|label| !label.ends_with('|'))
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(labels(LinkFormat::Shortest), vec!["note", "notebook"]);
+        assert_eq!(
+            labels(LinkFormat::Relative),
+            vec!["../archive/notebook", "sub/note"]
+        );
+        assert_eq!(
+            labels(LinkFormat::Absolute),
+            vec!["archive/notebook", "notes/current", "notes/sub/note"]
+        );
+    }
+
+    #[test]
+    fn test_piped_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Getting Started\n"),
+                ("a|b.md", ""),
+                ("current.md", "[[guide\n[[a\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let new_text = |line: usize, character: usize, label: &str| {
+            let completer = WikiLinkCompleter::construct(context, line, character).unwrap();
+            let item = completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .find(|item| item.label == label);
+
+            item.and_then(|item| match item.text_edit? {
+                CompletionTextEdit::Edit(edit) => Some(edit.new_text),
+                CompletionTextEdit::InsertAndReplace(edit) => Some(edit.new_text),
+            })
+        };
+
+        assert_eq!(
+            new_text(0, 7, "guide|").as_deref(),
+            Some("guide|${1:Getting Started}]]${2:}")
+        );
+        assert_eq!(
+            new_text(0, 7, "guide#Getting Started|").as_deref(),
+            Some("guide#Getting Started|${1:Getting Started}]]${2:}")
+        );
+        assert_eq!(new_text(1, 3, "a|b").as_deref(), Some(r"a\|b]]${2:}"));
+    }
+
+    #[test]
+    fn test_create_missing_on_complete() {
+        let settings = Settings {
+            create_missing_on_complete: true,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Intro\n\nSome text\n"),
+                (
+                    "current.md",
+                    "[[guide#Next Steps\n[[current#^later\n\nSome paragraph\n",
+                ),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let missing = |line: usize, character: usize| {
+            let completer = WikiLinkCompleter::construct(context, line, character).unwrap();
+            let item = completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .find(|item| item.kind == Some(CompletionItemKind::KEYWORD));
+
+            item.unwrap()
+        };
+
+        // the heading is added to the linked file after the link is inserted
+        let heading = missing(0, 18);
+        let Some(CompletionTextEdit::Edit(edit)) = heading.text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "guide#Next Steps]]${2:}");
+
+        let command = heading.command.unwrap();
+        assert_eq!(command.command, "apply_edits");
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+        assert_eq!(
+            workspace_edit.changes.unwrap()[&Url::from_file_path("/vault/guide.md").unwrap()],
+            vec![TextEdit {
+                range: Range::new(Position::new(3, 0), Position::new(3, 0)),
+                new_text: "\n## Next Steps\n".to_string()
+            }]
+        );
+
+        // blocks in the current file are indexed along with the completion
+        let block = missing(1, 16);
+        assert_eq!(block.label, "current#^later");
+        assert_eq!(block.command, None);
+        assert_eq!(
+            block.additional_text_edits,
+            Some(vec![TextEdit {
+                range: Range::new(Position::new(3, 14), Position::new(3, 14)),
+                new_text: " ^later".to_string()
+            }])
+        );
+    }
+
+    #[test]
+    fn test_new_block_completion() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "guide.md",
+                    "# Guide\n\nFirst line\nsecond line  \n\nIndexed ^abcde\n",
+                ),
+                ("current.md", "[[guide#^\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 9).unwrap();
+        let new_blocks = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .filter(|item| item.kind == Some(CompletionItemKind::TEXT))
+            .collect_vec();
+
+        // only the paragraph's last line; the heading and the indexed block are left out
+        assert_eq!(new_blocks.len(), 1);
+        let block = &new_blocks[0];
+        assert_eq!(block.filter_text.as_deref(), Some("guide#^second line"));
+
+        let Some(CompletionTextEdit::Edit(edit)) = &block.text_edit else {
+            panic!("expected a text edit")
+        };
+        let index = edit
+            .new_text
+            .strip_prefix("guide#^")
+            .and_then(|text| text.strip_suffix("]]${2:}"))
+            .unwrap();
+        assert_eq!(index.len(), 5);
+        assert_ne!(index, "abcde");
+
+        let command = block.command.clone().unwrap();
+        assert_eq!(command.command, "apply_edits");
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+        assert_eq!(
+            workspace_edit.changes.unwrap()[&Url::from_file_path("/vault/guide.md").unwrap()],
+            vec![TextEdit {
+                range: Range::new(Position::new(3, 11), Position::new(3, 11)),
+                new_text: format!(" ^{}", index)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_new_block_completion_by_heading_link() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "guide.md",
+                    "# Guide\n\n- [ ] first task\n- second item ^abcde\n3. third item\n\nA paragraph\n",
+                ),
+                ("current.md", "[[guide#\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = WikiLinkCompleter::construct(context, 0, 8).unwrap();
+        let new_blocks = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .filter(|item| item.kind == Some(CompletionItemKind::TEXT))
+            .collect_vec();
+
+        // each list item is a block, by its text; the indexed one is left out
+        assert_eq!(
+            new_blocks
+                .iter()
+                .map(|item| item.label.as_str())
+                .collect_vec(),
+            vec!["guide#first task", "guide#third item", "guide#A paragraph"]
+        );
+
+        let block = &new_blocks[0];
+        let Some(CompletionTextEdit::Edit(edit)) = &block.text_edit else {
+            panic!("expected a text edit")
+        };
+        let index = edit
+            .new_text
+            .strip_prefix("guide#^")
+            .and_then(|text| text.strip_suffix("]]${2:}"))
+            .unwrap();
+
+        let command = block.command.clone().unwrap();
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+        assert_eq!(
+            workspace_edit.changes.unwrap()[&Url::from_file_path("/vault/guide.md").unwrap()],
+            vec![TextEdit {
+                range: Range::new(Position::new(2, 16), Position::new(2, 16)),
+                new_text: format!(" ^{}", index)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_new_note_completion() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", ""),
+                ("current.md", "[[Brand New Idea\n[[Guide\n"),
+            ],
+        );
+
+        let new_notes = |line, character| {
+            let context = Context {
+                vault: &vault,
+                opened_files: &[],
+                path: &root_dir.join("current.md"),
+                settings: &settings,
+            };
+            let completer = WikiLinkCompleter::construct(context, line, character).unwrap();
+            completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .filter(|item| item.kind == Some(CompletionItemKind::KEYWORD))
+                .collect_vec()
+        };
+
+        let items = new_notes(0, 16);
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.label, "Brand New Idea");
+
+        let Some(CompletionTextEdit::Edit(edit)) = &item.text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "Brand New Idea]]${2:}");
+
+        let command = item.command.clone().unwrap();
+        assert_eq!(command.command, "apply_edits");
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = workspace_edit.document_changes else {
+            panic!("expected document changes")
+        };
+        assert!(matches!(
+            &operations[..],
+            [DocumentChangeOperation::Op(ResourceOp::Create(create))]
+                if create.uri == Url::from_file_path("/vault/Brand New Idea.md").unwrap()
+        ));
+
+        // notes that exist aren't created again, whatever the case
+        assert!(new_notes(1, 7).is_empty());
+    }
+
+    #[test]
+    fn test_id_completion() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("plan.md", "---\nid: 20240115\n---\n# Project Plan\n"),
+                ("current.md", "[[2024011\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+        let completer = WikiLinkCompleter::construct(context, 0, 9).unwrap();
+        let item = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .find(|item| item.label == "20240115")
+            .unwrap();
+
+        assert_eq!(item.detail.as_deref(), Some("Project Plan"));
+        let Some(CompletionTextEdit::Edit(edit)) = &item.text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "20240115]]${2:}");
+    }
+
+    #[test]
+    fn test_attachment_completion() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("current.md", "![[ima\n[[ima\n"),
+                ("image.png", ""),
+                ("assets/image.png", ""),
+                ("doc.pdf", ""),
+            ],
+        );
+
+        let items = |line| {
+            let context = Context {
+                vault: &vault,
+                opened_files: &[],
+                path: &root_dir.join("current.md"),
+                settings: &settings,
+            };
+            let completer = WikiLinkCompleter::construct(context, line, 5 - line).unwrap();
+            completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .filter(|item| item.kind == Some(CompletionItemKind::FILE))
+                .map(|item| item.label)
+                .sorted()
+                .collect_vec()
+        };
+
+        // attachments sharing a name are told apart by their path; images can be given a size
+        assert_eq!(
+            items(0),
+            vec![
+                "assets/image.png",
+                "assets/image.png|",
+                "image.png",
+                "image.png|",
+            ]
+        );
+        // only embeds link to attachments
+        assert!(items(1).is_empty());
+    }
+
+    #[test]
+    fn test_date_query_completion() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("2024-01-15.md", "# Monday\n"),
+                ("current.md", "[[2024-01-15\n[[2023-03-02\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let queried = |line: usize| {
+            let completer = WikiLinkCompleter::construct(context, line, 12).unwrap();
+            let items = completer
+                .completions()
+                .into_iter()
+                .flat_map(|completable| completable.completions(&completer))
+                .filter(|item| item.kind == Some(CompletionItemKind::EVENT))
+                .collect_vec();
+
+            items
+        };
+
+        // an existing note is linked without creating it
+        let existing = queried(0);
+        assert_eq!(existing.len(), 1);
+        assert_eq!(existing[0].label, "2024-01-15: 2024-01-15");
+        assert_eq!(existing[0].command, None);
+
+        // a missing note is created when the completion is accepted
+        let missing = queried(1);
+        assert_eq!(missing.len(), 1);
+        let Some(CompletionTextEdit::Edit(edit)) = &missing[0].text_edit else {
+            panic!("expected a text edit")
+        };
+        assert_eq!(edit.new_text, "2023-03-02]]${2:}");
+
+        let command = missing[0].command.clone().unwrap();
+        assert_eq!(command.command, "apply_edits");
+        let workspace_edit: WorkspaceEdit =
+            serde_json::from_value(command.arguments.unwrap()[0].clone()).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = workspace_edit.document_changes else {
+            panic!("expected file operations")
+        };
+        assert!(matches!(
+            &operations[..],
+            [DocumentChangeOperation::Op(ResourceOp::Create(create))]
+                if create.uri == Url::from_file_path("/vault/2023-03-02.md").unwrap()
+        ));
+    }
+}
@@ -67,9 +67,11 @@ pub fn fuzzy_match_completions<'a, 'b, C: Completer<'a>, T: Matchable + Completa
 ) -> Vec<OrderedCompletion<'a, C, T>> {
     let normal_fuzzy_match = fuzzy_match(filter_text, items);
 
+    // matches come best first; sort text compares as a string, so pad the rank
     normal_fuzzy_match
         .into_iter()
-        .map(|(item, score)| OrderedCompletion::new(item, score.to_string()))
+        .enumerate()
+        .map(|(rank, (item, _))| OrderedCompletion::new(item, format!("{:0>5}", rank)))
         .collect::<Vec<_>>()
 }
 
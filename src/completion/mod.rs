@@ -1,10 +1,17 @@
 use std::path::{Path, PathBuf};
 
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use tower_lsp::lsp_types::{CompletionItem, CompletionList, CompletionParams, CompletionResponse};
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionList, CompletionParams, CompletionResponse, Documentation,
+};
 
-use crate::{config::Settings, vault::Vault};
+use crate::{
+    config::Settings,
+    ui::preview_referenceable,
+    vault::{Referenceable, Vault},
+};
 
 use self::callout_completer::CalloutCompleter;
 use self::link_completer::WikiLinkCompleter;
@@ -16,7 +23,7 @@ use self::{
 mod callout_completer;
 mod footnote_completer;
 mod link_completer;
-mod matcher;
+pub mod matcher;
 mod tag_completer;
 mod unindexed_block_completer;
 mod util;
@@ -52,6 +59,87 @@ pub trait Completable<'a, T: Completer<'a>>: Sized {
 /// Range indexes for one line of the file; NOT THE WHOLE FILE
 type LineRange<T> = std::ops::Range<T>;
 
+/// What `resolve_completion` needs to find a completion's referenceable again, kept in the item's
+/// `data`
+#[derive(Serialize, Deserialize)]
+struct ResolveData {
+    path: PathBuf,
+    refname: String,
+}
+
+/// The preview of the referenceable for its completion item. Previews list backlinks from across
+/// the vault, so when the client resolves completions, the item only keeps what
+/// `resolve_completion` needs to preview the highlighted one.
+fn preview_item(
+    vault: &Vault,
+    settings: &Settings,
+    referenceable: &Referenceable,
+) -> CompletionItem {
+    let data = settings
+        .lazy_completion_documentation
+        .then(|| {
+            serde_json::to_value(ResolveData {
+                path: referenceable.get_path().to_path_buf(),
+                refname: referenceable.get_refname(vault.root_dir())?.full_refname,
+            })
+            .ok()
+        })
+        .flatten();
+
+    match data {
+        Some(data) => CompletionItem {
+            data: Some(data),
+            ..Default::default()
+        },
+        None => CompletionItem {
+            documentation: preview_referenceable(vault, referenceable)
+                .map(Documentation::MarkupContent),
+            ..Default::default()
+        },
+    }
+}
+
+/// Fill in the preview left out of the completion item by `preview_item`
+pub fn resolve_completion(vault: &Vault, mut item: CompletionItem) -> CompletionItem {
+    let Some(ResolveData { path, refname }) = item
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value(data).ok())
+    else {
+        return item;
+    };
+
+    let is_referenceable = |referenceable: &Referenceable| {
+        referenceable
+            .get_refname(vault.root_dir())
+            .is_some_and(|other| other.full_refname == refname)
+            && referenceable.get_path() == path
+    };
+
+    // unresolved referenceables and attachments aren't in the file's referenceables
+    let referenceable = vault
+        .select_referenceable_nodes(Some(&path))
+        .into_iter()
+        .find(is_referenceable)
+        .or_else(|| {
+            vault
+                .select_referenceable_nodes(None)
+                .into_iter()
+                .find(is_referenceable)
+        });
+
+    item.documentation = referenceable
+        .and_then(|referenceable| preview_referenceable(vault, &referenceable))
+        .map(Documentation::MarkupContent);
+    item
+}
+
+/// The characters the server declares as completion triggers by default: `[`, `(`, and `|` for
+/// links, `#` and `^` for headings, blocks, and tags, a space for unindexed blocks, and `>` for
+/// callouts. Each completer checks the text before the cursor, so a trigger character outside
+/// what they complete, like `(` in prose, completes nothing
+pub const TRIGGER_CHARACTERS: [&str; 7] = ["[", "(", "#", "^", "|", " ", ">"];
+
 pub fn get_completions(
     vault: &Vault,
     initial_completion_files: &[PathBuf],
@@ -333,3 +421,113 @@ fn run_completer<'a, T: Completer<'a>>(
         items: completions,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        CompletionContext, CompletionParams, CompletionResponse, CompletionTriggerKind,
+        PartialResultParams, Position, TextDocumentIdentifier, TextDocumentPositionParams, Url,
+        WorkDoneProgressParams,
+    };
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::{get_completions, resolve_completion};
+
+    #[test]
+    fn test_trigger_character_completions() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "an aside (\n[[\na | table |\na ^\n> quoted \nsee [[other]] \n",
+                ),
+                ("other.md", "# Other\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let completions = |line, character, trigger_kind| {
+            let params = CompletionParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    position: Position::new(line, character),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: Some(CompletionContext {
+                    trigger_kind,
+                    trigger_character: None,
+                }),
+            };
+
+            get_completions(&vault, &[], &params, &path, &settings)
+        };
+
+        // trigger characters outside of what the completers complete, like `(` in prose
+        for (line, character) in [(0, 10), (2, 11), (3, 3), (4, 9), (5, 14)] {
+            assert!(
+                completions(line, character, CompletionTriggerKind::TRIGGER_CHARACTER).is_none()
+            );
+        }
+        assert!(completions(1, 2, CompletionTriggerKind::TRIGGER_CHARACTER).is_some());
+        assert!(completions(1, 2, CompletionTriggerKind::INVOKED).is_some());
+    }
+
+    #[test]
+    fn test_resolve_completion_documentation() {
+        let root_dir = Path::new("/vault");
+        let texts = [
+            ("note.md", "[[oth\n"),
+            ("other.md", "# Other\n\nSome text\n"),
+            ("third.md", "[[other]]\n"),
+        ];
+
+        let path = root_dir.join("note.md");
+        let other = |settings: &Settings| {
+            let vault = Vault::from_texts(settings, root_dir, texts);
+            let params = CompletionParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    position: Position::new(0, 5),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: None,
+            };
+
+            let Some(CompletionResponse::List(list)) =
+                get_completions(&vault, &[], &params, &path, settings)
+            else {
+                panic!("expected completions")
+            };
+            let item = list
+                .items
+                .into_iter()
+                .find(|item| item.label == "other")
+                .unwrap();
+
+            (item.clone(), resolve_completion(&vault, item))
+        };
+
+        let (eager, _) = other(&Settings {
+            lazy_completion_documentation: false,
+            ..Settings::default()
+        });
+        assert!(eager.documentation.is_some() && eager.data.is_none());
+
+        let (lazy, resolved) = other(&Settings::default());
+        assert!(lazy.documentation.is_none() && lazy.data.is_some());
+        assert_eq!(resolved.documentation, eager.documentation);
+    }
+}
@@ -1,20 +1,22 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit,
-    Documentation, Position, Range, TextEdit,
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit, Position,
+    Range, TextEdit,
 };
 
 use crate::{
-    completion::util::check_in_code_block, ui, vault::{MDTag, Referenceable, Vault}
+    completion::util::check_in_code_block,
+    config::{Settings, TagSources},
+    vault::{MDTag, Rangeable, Reference, Referenceable, Vault},
 };
 
 use super::{
-    matcher::{fuzzy_match_completions, Matchable},
-    Completable, Completer, LineRange,
+    matcher::{fuzzy_match, Matchable, OrderedCompletion},
+    preview_item, Completable, Completer, LineRange,
 };
 
 use rayon::prelude::*;
@@ -24,6 +26,9 @@ pub struct TagCompleter<'a> {
     /// Tag name and range not including the '#'
     inputted_tag: (String, LineRange<usize>),
     vault: &'a Vault,
+    settings: &'a Settings,
+    /// The file being completed in
+    path: &'a Path,
     line: usize,
     character: usize,
 }
@@ -39,6 +44,11 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
             return None
         }
 
+        // inline tags aren't tags when only the frontmatter's are
+        if context.settings.tag_sources == TagSources::Frontmatter {
+            return None;
+        }
+
 
         static PARTIAL_TAG_REGEX: Lazy<Regex> =
             Lazy::new(|| Regex::new(r"\#(?<text>[a-zA-Z0-9\/]*)").unwrap());
@@ -62,6 +72,8 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
                         full_range: full.range(),
                         inputted_tag: (tag_text.as_str().to_string(), tag_text.range()),
                         vault: context.vault,
+                        settings: context.settings,
+                        path: context.path,
                         line,
                         character,
                     })
@@ -76,13 +88,16 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
     where
         Self: Sized,
     {
+        let uses = self.tag_uses();
+
         let tag_referenceables = self
             .vault
             .select_referenceable_nodes(None)
             .into_par_iter()
-            .flat_map(TagCompletable::from_referenceable)
+            .flat_map(|referenceable| TagCompletable::from_referenceable(referenceable, &uses))
             .filter(|tag| {
-                !(tag.tag.1.range.start.line <= self.line as u32
+                !(tag.tag.0 == self.path
+                    && tag.tag.1.range.start.line <= self.line as u32
                     && tag.tag.1.range.start.character <= self.character as u32
                     && tag.tag.1.range.end.line >= self.line as u32
                     && tag.tag.1.range.end.character >= self.character as u32)
@@ -97,9 +112,18 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
 
         let filter_text = &self.inputted_tag.0;
 
-        let filtered = fuzzy_match_completions(filter_text, tag_referenceables);
-
-        filtered
+        // the best matches first, and the most used tags first among equal matches
+        fuzzy_match(filter_text, tag_referenceables)
+            .into_iter()
+            .sorted_by(|(a, a_score), (b, b_score)| {
+                b_score
+                    .cmp(a_score)
+                    .then(b.uses.cmp(&a.uses))
+                    .then(a.match_string().cmp(b.match_string()))
+            })
+            .enumerate()
+            .map(|(rank, (tag, _))| OrderedCompletion::new(tag, format!("{:0>5}", rank)))
+            .collect()
     }
 
     type FilterParams = &'a str;
@@ -109,14 +133,46 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
     }
 }
 
+impl TagCompleter<'_> {
+    /// How many times each tag, without the '#', is used in the vault; tags in code blocks only
+    /// count when `tags_in_codeblocks` is on
+    fn tag_uses(&self) -> HashMap<&str, usize> {
+        self.vault
+            .md_files
+            .values()
+            .flat_map(|file| {
+                file.references
+                    .iter()
+                    .filter(|reference| matches!(reference, Reference::Tag(_)))
+                    .filter(|reference| {
+                        self.settings.tags_in_codeblocks
+                            || !file
+                                .codeblocks
+                                .iter()
+                                .any(|codeblock| codeblock.includes(*reference))
+                    })
+            })
+            .map(|reference| reference.data().reference_text.trim_start_matches('#'))
+            .counts()
+    }
+}
+
 struct TagCompletable<'a> {
     tag: (&'a Path, &'a MDTag),
+    /// How many times the tag is used in the vault
+    uses: usize,
 }
 
 impl TagCompletable<'_> {
-    fn from_referenceable(referenceable: Referenceable<'_>) -> Option<TagCompletable<'_>> {
+    fn from_referenceable<'a>(
+        referenceable: Referenceable<'a>,
+        uses: &HashMap<&str, usize>,
+    ) -> Option<TagCompletable<'a>> {
         match referenceable {
-            Referenceable::Tag(path, tag) => Some(TagCompletable { tag: (path, tag) }),
+            Referenceable::Tag(path, tag) => Some(TagCompletable {
+                tag: (path, tag),
+                uses: uses.get(tag.tag_ref.as_str()).copied().unwrap_or(0),
+            }),
             _ => None,
         }
     }
@@ -148,27 +204,74 @@ impl<'a> Completable<'a, TagCompleter<'a>> for TagCompletable<'a> {
         let path_buf = path.to_path_buf();
         let self_as_referenceable = Referenceable::Tag(&path_buf, self.tag.1);
 
-        let num_references = completer
-            .vault
-            .select_references_for_referenceable(&self_as_referenceable)
-            .map(|references| references.len())
-            .unwrap_or(0);
-
         Some(CompletionItem {
             label: self.tag.1.tag_ref.clone(),
             kind: Some(CompletionItemKind::KEYWORD),
             filter_text: Some(completer.completion_filter_text(&self.tag.1.tag_ref.clone())),
-            documentation: ui::preview_referenceable(completer.vault, &self_as_referenceable)
-                .map(Documentation::MarkupContent),
             label_details: Some(CompletionItemLabelDetails {
-                detail: Some(match num_references {
-                    1 => "1 reference".to_string(),
-                    n => format!("{} references", n),
+                detail: Some(match self.uses {
+                    1 => "1 use".to_string(),
+                    n => format!("{} uses", n),
                 }),
                 description: None,
             }),
             text_edit: Some(text_edit),
-            ..Default::default()
+            ..preview_item(completer.vault, completer.settings, &self_as_referenceable)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+
+    use crate::completion::{Completable, Completer, Context};
+    use crate::config::Settings;
+    use crate::vault::Vault;
+
+    use super::TagCompleter;
+
+    #[test]
+    fn test_tag_completion_counts() {
+        let settings = Settings {
+            tags_in_codeblocks: false,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("a.md", "#rare #common\n"),
+                ("b.md", "#common #common\n\n```\n#rare #rare #rare\n```\n"),
+                ("current.md", "#\n"),
+            ],
+        );
+
+        let context = Context {
+            vault: &vault,
+            opened_files: &[],
+            path: &root_dir.join("current.md"),
+            settings: &settings,
+        };
+
+        let completer = TagCompleter::construct(context, 0, 1).unwrap();
+        let items = completer
+            .completions()
+            .into_iter()
+            .flat_map(|completable| completable.completions(&completer))
+            .sorted_by_key(|item| item.sort_text.clone())
+            .map(|item| (item.label, item.label_details.unwrap().detail.unwrap()))
+            .collect_vec();
+
+        assert_eq!(
+            items,
+            vec![
+                ("common".to_string(), "3 uses".to_string()),
+                ("rare".to_string(), "1 use".to_string()),
+            ]
+        );
+    }
+}
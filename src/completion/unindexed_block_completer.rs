@@ -9,11 +9,11 @@ use crate::{
     ui::preview_referenceable,
     vault::{get_obsidian_ref_path, Block, Referenceable},
 };
-use nanoid::nanoid;
 
 use super::{
     link_completer::{LinkCompleter, MarkdownLinkCompleter, WikiLinkCompleter},
     matcher::{fuzzy_match_completions, Matchable},
+    util::random_block_index,
     Completable, Completer,
 };
 
@@ -33,14 +33,9 @@ impl<'a, C: LinkCompleter<'a>> UnindexedBlockCompleter<'a, C> {
     }
 
     fn new(completer: C) -> Self {
-        let rand_id = nanoid!(
-            5,
-            &['a', 'b', 'c', 'd', 'e', 'f', 'g', '1', '2', '3', '4', '5', '6', '7', '8', '9']
-        );
-
         Self {
             link_completer: completer,
-            new_id: rand_id,
+            new_id: random_block_index(),
             __phantom: std::marker::PhantomData,
         }
     }
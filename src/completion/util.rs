@@ -1,3 +1,4 @@
+use nanoid::nanoid;
 use tower_lsp::lsp_types::Position;
 
 use crate::vault::Rangeable as _;
@@ -18,3 +19,13 @@ pub fn check_in_code_block(context: &Context, line: usize, character: usize) ->
     in_code_block
 
 }
+
+/// The characters of the indexes given to blocks linked by their text
+const BLOCK_INDEX_ALPHABET: [char; 16] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// A short random block index, like `3fa9c`
+pub fn random_block_index() -> String {
+    nanoid!(5, &BLOCK_INDEX_ALPHABET)
+}
@@ -1,30 +1,287 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 use anyhow::anyhow;
-use config::{Config, File};
+use chrono::Weekday;
+use config::{builder::DefaultState, Config, ConfigBuilder, File};
 use indexmap::IndexMap;
-use serde::Deserialize;
+use itertools::Itertools;
+use pathdiff::diff_paths;
+use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::ClientCapabilities;
 
-#[derive(Deserialize, Debug, Clone)]
+use crate::{daily::Notebook, util::glob_match};
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Settings {
     /// Format of daily notes
     pub dailynote: String,
     pub heading_completions: bool,
+    /// Complete links to headings and blocks that don't exist yet, adding them to the linked file
+    pub create_missing_on_complete: bool,
+    /// Go to the closest heading when a heading link doesn't match any heading exactly
+    pub fuzzy_heading_goto: bool,
+    /// Title notes by their first H1, when they have one, rather than their filename
     pub title_headings: bool,
+    /// Where a note's title comes from; see `MDFile::title`
+    pub title_source: TitleSource,
+    /// Match heading links to headings regardless of case, like Obsidian
+    pub case_insensitive_headings: bool,
+    /// Match links to files regardless of the case of their paths, as on the case-insensitive
+    /// filesystems of macOS and Windows, where it is on by default
+    pub case_insensitive_filenames: bool,
+    /// Folders, from the vault root, in the order a link naming a file without its path prefers
+    /// them when files in several folders share the name; `/` is the vault root itself
+    #[serde(default)]
+    pub link_resolution_priority: Vec<String>,
     pub unresolved_diagnostics: bool,
+    /// Hint at footnote definitions that nothing in their file uses
+    pub orphaned_footnote_diagnostics: bool,
+    /// Warn about links whose relative path leaves the vault root
+    pub external_link_diagnostics: bool,
+    /// Hint at unterminated or empty wikilinks and embeds, and at empty tags, and warn about
+    /// frontmatter that isn't valid YAML
+    pub syntax_diagnostics: bool,
+    /// Warn about embeds that lead back to their own note, which renderers expand forever
+    pub embed_cycle_diagnostics: bool,
+    /// Warn about links that match several files, like `[[note]]` with a `note.md` in two folders
+    pub ambiguous_link_diagnostics: bool,
+    /// `vault_lint` reports block ids used more than once in a note
+    pub duplicate_block_id_lint: bool,
+    /// `vault_lint` reports notes that no other note links to
+    pub orphaned_note_lint: bool,
+    /// `vault_lint` reports headings more than one level below the heading before them
+    pub heading_skip_lint: bool,
+    /// The source every diagnostic is published with, for clients that show or filter by it
+    pub diagnostics_source: String,
+    /// Which files `moxide.recomputeDiagnostics` publishes diagnostics for
+    pub diagnostics_scope: DiagnosticsScope,
     pub semantic_tokens: bool,
+    /// Outline nested list items as document symbols in notes with at most one heading, and fold
+    /// them
+    pub list_outline_symbols: bool,
+    /// The most symbols a workspace symbol search returns, the best matches for its query first
+    pub workspace_symbol_limit: usize,
+    /// Name the note a symbol in a note is in by its path from the vault root, and for blocks, tags,
+    /// and footnotes, follow it with the text of their line, where pickers show the container
+    pub workspace_symbol_detail: bool,
+    /// Which tags are indexed: inline `#tags`, the frontmatter's `tags`, or both
+    pub tag_sources: TagSources,
+    /// The folder, from the vault root, of notes for tags that going to a tag's definition opens,
+    /// like `tags/project.md` for `#project`
+    #[serde(default)]
+    pub tag_note_folder: Option<String>,
     pub tags_in_codeblocks: bool,
     pub references_in_codeblocks: bool,
+    /// Journals with their own folder and format; see `daily::notebooks` for the default
+    #[serde(default)]
+    pub notebooks: Vec<Notebook>,
+    /// Template for new daily notes, relative to the vault root; `{{date}}`, `{{title}}`, and
+    /// `{{time}}` are filled in
+    #[serde(default)]
+    pub daily_note_template: Option<String>,
+    /// The locale, like `de_DE`, of the weekday names in relative dates such as `next Friday`;
+    /// English when unset
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// The first day of the week, like `monday` or `sunday`; when set, relative dates name days
+    /// this, next, or last week by the week they fall in rather than by counting seven days
+    #[serde(default)]
+    pub week_start: Option<Weekday>,
+    /// Only treat a file as a daily note when its whole filename matches the format
+    pub strict_daily_match: bool,
+    /// How `sync_filename_to_title` turns a note's H1 into its filename
+    pub title_filename_style: FilenameStyle,
+    /// The strftime format of the id that `unique_note` names new notes with, before their title
+    pub unique_note_prefix: String,
+    /// The folder, from the vault root, that `unique_note` creates notes in; the vault root when
+    /// unset
+    #[serde(default)]
+    pub unique_note_folder: Option<String>,
+    /// The note, from the vault root, that `moxide.capture` appends to when it isn't given one
+    #[serde(default)]
+    pub inbox_note: Option<String>,
+    /// The strftime format of the timestamp `moxide.capture` starts captured lines with; none when
+    /// unset
+    #[serde(default)]
+    pub capture_timestamp_format: Option<String>,
+    /// The states that toggling a task cycles through, in order, each the character between the
+    /// brackets of `- [ ]`
+    pub task_states: Vec<String>,
+    /// Where the cursor lands when a command opens a note
+    pub open_selection: OpenSelection,
+    /// The heading of daily notes that `jump` puts the cursor at the end of, over `open_selection`
+    #[serde(default)]
+    pub daily_note_cursor_anchor: Option<String>,
+    /// How similar, from 0 to 1, two tags must be for `suggest_tag_merges` to group them
+    pub tag_merge_threshold: f64,
+    /// Callout types offered in completions alongside Obsidian's built in ones
+    #[serde(default)]
+    pub custom_callouts: Vec<String>,
+    /// `link_bidirectional` also links back from the target note
+    pub auto_backlink: bool,
+    /// Heading in the target note that backlinks are added under
+    pub backlink_heading: String,
+    /// How completions write the path of the linked file
+    pub link_format: LinkFormat,
+    /// How completions write the heading after `#`; links in either form resolve
+    pub heading_link_format: HeadingLinkFormat,
+    /// How completions write the heading after `#` in markdown links, where a slug is the GitHub-style
+    /// anchor that other renderers also resolve
+    pub markdown_heading_link_format: HeadingLinkFormat,
+    /// The characters that clients request completions after, sent once when the server starts
+    pub completion_trigger_characters: Vec<String>,
+    /// Leave completion previews for the client to resolve for the highlighted item; off for
+    /// clients that can't resolve documentation
+    pub lazy_completion_documentation: bool,
+    /// Answer formatting requests by normalizing headings, wikilinks, and footnote definitions; the
+    /// server only offers formatting to the client when this is on as it starts
+    pub format_on_request: bool,
+    /// Where new notes go, as in Obsidian's `.obsidian/app.json`; when unset, each action keeps
+    /// its own default
+    #[serde(default)]
+    pub new_file_location: Option<NewFileLocation>,
+    /// The folder, from the vault root, for the `folder` new file location
+    #[serde(default)]
+    pub new_file_folder_path: Option<String>,
+    /// Where new attachments go, in Obsidian's format: `/` for the vault root, `./` for the note's
+    /// folder, `./name` for a folder under it, or a folder from the vault root
+    #[serde(default)]
+    pub attachment_folder_path: Option<String>,
+    /// Globs of the files and folders, from the vault root, that aren't indexed; `.gitignore`d and
+    /// hidden files aren't either
+    #[serde(default)]
+    pub ignore_globs: Vec<String>,
+    /// Index each workspace folder the client opens as a vault of its own, with its own `.moxide`,
+    /// so that links don't cross between them; otherwise the other folders are indexed into the
+    /// first folder's vault and links cross freely. Read from the first folder's settings when the
+    /// server starts, and folders added after that are not indexed
+    pub separate_workspace_folders: bool,
+    /// Settings for the files under folders matching the glob keys, over the rest of the settings
+    #[serde(default)]
+    pub folder_overrides: HashMap<String, PartialSettings>,
+    /// The settings `for_path` merged with folder overrides, not a setting
+    #[serde(skip)]
+    pub overridden: OverriddenSettings,
+}
+
+/// Any subset of the settings, keyed by setting name
+pub type PartialSettings = serde_json::Map<String, serde_json::Value>;
+
+/// The settings merged with each set of folder overrides, keyed by their globs, so that
+/// `Settings::for_path` merges them once; shared by the clones of the settings, along with the
+/// settings they were merged from, as a clone may be changed
+#[derive(Debug, Clone, Default)]
+pub struct OverriddenSettings(Arc<RwLock<HashMap<Vec<String>, MergedSettings>>>);
+
+/// The settings the overrides were merged into, and the result
+type MergedSettings = (Settings, Settings);
+
+impl PartialEq for OverriddenSettings {
+    /// Only a cache of what the rest of the settings give
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticsScope {
+    /// The documents open in the editor
+    Open,
+    /// Every note in the vault
+    Workspace,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkFormat {
+    /// The filename, or the path from the vault root when several files share the filename
+    Shortest,
+    /// The path from the folder of the current file
+    Relative,
+    /// The path from the vault root
+    Absolute,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadingLinkFormat {
+    /// The heading text, `[[note#My Heading]]`
+    Text,
+    /// The heading's words lowercased and joined by `-`, `[[note#my-heading]]`
+    Slug,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NewFileLocation {
+    /// The vault root
+    Root,
+    /// The folder of the current file
+    Current,
+    /// `new_file_folder_path`
+    Folder,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilenameStyle {
+    /// The title as written, without the characters that can't appear in a filename or link
+    Title,
+    /// The title lowercased with words joined by `-`
+    Slug,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleSource {
+    /// The first H1, with `title_headings`
+    H1,
+    /// The filename, without the extension
+    Filename,
+    /// The `title` frontmatter field, or else the first H1
+    Frontmatter,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSources {
+    Inline,
+    /// Only the frontmatter's `tags`; an inline `#tag` is plain text
+    Frontmatter,
+    Both,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenSelection {
+    Start,
+    End,
+    FirstHeading,
 }
 
 impl Settings {
     pub fn new(root_dir: &Path, capabilities: &ClientCapabilities) -> anyhow::Result<Settings> {
+        Settings::read(root_dir, &global_settings_file(), capabilities)
+    }
+
+    /// The settings of the vault, with the global settings file, without its extension, over them
+    fn read(
+        root_dir: &Path,
+        global_file: &str,
+        capabilities: &ClientCapabilities,
+    ) -> anyhow::Result<Settings> {
         let obsidian_daily_note = obsidian_dailynote_converted(root_dir);
 
-        let expanded = shellexpand::tilde("~/.config/moxide/settings");
+        let builder = defaults(obsidian_daily_note.unwrap_or("%Y-%m-%d".to_string()))?;
+        let builder = obsidian_app_settings(root_dir)
+            .into_iter()
+            .try_fold(builder, |builder, (key, value)| {
+                builder.set_default(key, value)
+            })?;
 
-        let settings = Config::builder()
+        let settings = builder
             .add_source(
                 File::with_name(&format!(
                     "{}/.moxide",
@@ -34,17 +291,7 @@ impl Settings {
                 ))
                 .required(false),
             )
-            .add_source(File::with_name(&expanded).required(false))
-            .set_default(
-                "dailynote",
-                obsidian_daily_note.unwrap_or("%Y-%m-%d".to_string()),
-            )?
-            .set_default("heading_completions", true)?
-            .set_default("unresolved_diagnostics", true)?
-            .set_default("title_headings", true)?
-            .set_default("semantic_tokens", true)?
-            .set_default("tags_in_codeblocks", true)?
-            .set_default("references_in_codeblocks", true)?
+            .add_source(File::with_name(global_file).required(false))
             .set_override_option(
                 "semantic_tokens",
                 capabilities.text_document.as_ref().and_then(|it| {
@@ -54,13 +301,197 @@ impl Settings {
                     }
                 }),
             )?
+            .set_override_option(
+                "lazy_completion_documentation",
+                (!resolves_documentation(capabilities)).then_some(false),
+            )?
             .build()
             .map_err(|err| anyhow!("Build err: {err}"))?;
 
         let settings = settings.try_deserialize::<Settings>()?;
 
+        for (glob, overrides) in &settings.folder_overrides {
+            settings
+                .merged([overrides])
+                .map_err(|err| anyhow!("folder_overrides.{glob}: {err}"))?;
+        }
+
         anyhow::Ok(settings)
     }
+
+    /// The settings with the other workspace folders under the vault root ignored, as they are vaults
+    /// of their own
+    pub fn ignoring_folders(mut self, root_dir: &Path, folders: &[PathBuf]) -> Settings {
+        let nested = folders
+            .iter()
+            .filter(|folder| *folder != root_dir && folder.starts_with(root_dir))
+            .filter_map(|folder| diff_paths(folder, root_dir))
+            .map(|folder| folder.to_string_lossy().replace('\\', "/"));
+        self.ignore_globs.extend(nested);
+
+        self
+    }
+
+    /// The settings for the file; overrides for more specific folder globs take precedence
+    pub fn for_path(&self, root_dir: &Path, path: &Path) -> Settings {
+        let Some(relative) = diff_paths(path, root_dir) else {
+            return self.clone();
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        // the file itself and each folder it is in, like `a/b/note.md`, `a/b`, and `a`
+        let candidates = std::iter::successors(Some(relative.as_str()), |path| {
+            path.rsplit_once('/').map(|(folder, _)| folder)
+        })
+        .collect::<Vec<_>>();
+
+        let globs = self
+            .folder_overrides
+            .keys()
+            .filter(|glob| {
+                let glob = glob.trim_end_matches('/');
+                candidates
+                    .iter()
+                    .any(|candidate| glob_match(glob, candidate))
+            })
+            .sorted_by_key(|glob| (glob.len(), *glob))
+            .cloned()
+            .collect_vec();
+        if globs.is_empty() {
+            return self.clone();
+        }
+
+        let cached = self.overridden.0.read().ok().and_then(|cache| {
+            let (base, settings) = cache.get(&globs)?;
+            (base == self).then(|| settings.clone())
+        });
+        if let Some(settings) = cached {
+            return settings;
+        }
+
+        let overrides = globs
+            .iter()
+            .filter_map(|glob| self.folder_overrides.get(glob));
+        // without the cache, which the cache would otherwise keep alive
+        let base = Settings {
+            overridden: OverriddenSettings::default(),
+            ..self.clone()
+        };
+        let settings = self.merged(overrides).unwrap_or_else(|_| base.clone());
+        if let Ok(mut cache) = self.overridden.0.write() {
+            cache.insert(globs, (base, settings.clone()));
+        }
+        settings
+    }
+
+    /// The folder that new notes created from `path` go in, if `new_file_location` is set
+    pub fn new_file_folder(&self, root_dir: &Path, path: &Path) -> Option<PathBuf> {
+        match self.new_file_location? {
+            NewFileLocation::Root => Some(root_dir.to_path_buf()),
+            NewFileLocation::Current => path.parent().map(Path::to_path_buf),
+            NewFileLocation::Folder => Some(
+                root_dir.join(
+                    self.new_file_folder_path
+                        .as_deref()
+                        .unwrap_or_default()
+                        .trim_matches('/'),
+                ),
+            ),
+        }
+    }
+
+    fn merged<'a>(
+        &self,
+        overrides: impl IntoIterator<Item = &'a PartialSettings>,
+    ) -> anyhow::Result<Settings> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(settings) = value.as_object_mut() {
+            for overrides in overrides {
+                settings.extend(overrides.clone());
+            }
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        defaults("%Y-%m-%d".to_string())
+            .and_then(|builder| Ok(builder.build()?.try_deserialize::<Settings>()?))
+            .expect("default settings should deserialize")
+    }
+}
+
+fn defaults(dailynote: String) -> anyhow::Result<ConfigBuilder<DefaultState>> {
+    Ok(Config::builder()
+        .set_default("dailynote", dailynote)?
+        .set_default("heading_completions", true)?
+        .set_default("create_missing_on_complete", false)?
+        .set_default("fuzzy_heading_goto", false)?
+        .set_default("unresolved_diagnostics", true)?
+        .set_default("orphaned_footnote_diagnostics", false)?
+        .set_default("external_link_diagnostics", true)?
+        .set_default("syntax_diagnostics", true)?
+        .set_default("embed_cycle_diagnostics", true)?
+        .set_default("ambiguous_link_diagnostics", true)?
+        .set_default("duplicate_block_id_lint", true)?
+        .set_default("orphaned_note_lint", true)?
+        .set_default("heading_skip_lint", true)?
+        .set_default("diagnostics_source", "markdown-oxide")?
+        .set_default("diagnostics_scope", "open")?
+        .set_default("title_headings", true)?
+        .set_default("title_source", "h1")?
+        .set_default("case_insensitive_headings", true)?
+        .set_default(
+            "case_insensitive_filenames",
+            cfg!(any(target_os = "macos", target_os = "windows")),
+        )?
+        .set_default("semantic_tokens", true)?
+        .set_default("list_outline_symbols", false)?
+        .set_default("separate_workspace_folders", true)?
+        .set_default("workspace_symbol_limit", 200)?
+        .set_default("workspace_symbol_detail", false)?
+        .set_default("tag_sources", "both")?
+        .set_default("tags_in_codeblocks", true)?
+        .set_default("references_in_codeblocks", true)?
+        .set_default("strict_daily_match", true)?
+        .set_default("title_filename_style", "title")?
+        .set_default("unique_note_prefix", "%Y%m%d%H%M")?
+        .set_default("task_states", vec![" ", "x"])?
+        .set_default("open_selection", "start")?
+        .set_default("tag_merge_threshold", 0.8)?
+        .set_default("auto_backlink", false)?
+        .set_default("backlink_heading", "Backlinks")?
+        .set_default("link_format", "shortest")?
+        .set_default("heading_link_format", "text")?
+        .set_default("markdown_heading_link_format", "slug")?
+        .set_default(
+            "completion_trigger_characters",
+            crate::completion::TRIGGER_CHARACTERS.to_vec(),
+        )?
+        .set_default("lazy_completion_documentation", true)?
+        .set_default("format_on_request", false)?)
+}
+
+/// The global settings file, without its extension, as `config` looks it up
+fn global_settings_file() -> String {
+    shellexpand::tilde("~/.config/moxide/settings").to_string()
+}
+
+/// Globs of the files the settings are read from, for the client to watch
+pub fn settings_file_globs() -> Vec<String> {
+    vec![
+        "**/.moxide*".to_string(),
+        format!("{}*", global_settings_file()),
+    ]
+}
+
+/// Whether the settings are read from the file: the vault's `.moxide` or the global settings, in
+/// any of the formats `config` reads
+pub fn is_settings_file(root_dir: &Path, path: &Path) -> bool {
+    let file = path.with_extension("");
+    file == root_dir.join(".moxide") || file == Path::new(&global_settings_file())
 }
 
 fn obsidian_dailynote_converted(root_dir: &Path) -> Option<String> {
@@ -78,6 +509,46 @@ fn obsidian_dailynote_converted(root_dir: &Path) -> Option<String> {
     daily_note
 }
 
+/// Whether the client resolves the documentation of completion items it shows
+fn resolves_documentation(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .and_then(|it| it.completion.as_ref()?.completion_item.as_ref())
+        .and_then(|it| it.resolve_support.as_ref())
+        .is_some_and(|it| {
+            it.properties
+                .iter()
+                .any(|property| property == "documentation")
+        })
+}
+
+/// Obsidian's new note and attachment locations from `.obsidian/app.json`, as setting defaults
+fn obsidian_app_settings(root_dir: &Path) -> Vec<(&'static str, String)> {
+    let app_config_file = root_dir.join(".obsidian").join("app.json");
+    let Some(config) = std::fs::read(app_config_file)
+        .ok()
+        .and_then(|file| serde_json::from_slice::<HashMap<String, serde_json::Value>>(&file).ok())
+    else {
+        return vec![];
+    };
+
+    [
+        ("newFileLocation", "new_file_location"),
+        ("newFileFolderPath", "new_file_folder_path"),
+        ("attachmentFolderPath", "attachment_folder_path"),
+    ]
+    .into_iter()
+    .filter_map(|(obsidian_key, key)| {
+        let value = config.get(obsidian_key)?.as_str()?;
+        match key {
+            "new_file_location" if !["root", "current", "folder"].contains(&value) => None,
+            _ => Some((key, value.to_string())),
+        }
+    })
+    .collect()
+}
+
 use std::collections::HashMap;
 
 // GPT-4 code
@@ -102,16 +573,57 @@ fn momentjs_to_chrono_format_map() -> IndexMap<&'static str, &'static str> {
     map.insert("dddd", "%A");
     map.insert("ddd", "%a");
 
+    // Time
+    map.insert("HH", "%H");
+    map.insert("H", "%-H");
+    map.insert("hh", "%I");
+    map.insert("h", "%-I");
+    map.insert("mm", "%M");
+    map.insert("m", "%-M");
+    map.insert("ss", "%S");
+    map.insert("s", "%-S");
+    map.insert("a", "%P");
+    map.insert("A", "%p");
+
     map
 }
 
-// GPT-4 code
+/// Convert the format token by token, taking the longest token at each position; text in
+/// `[brackets]` is literal, as in moment
 fn convert_momentjs_to_chrono_format(moment_format: &str) -> String {
     let format_map = momentjs_to_chrono_format_map();
-    let mut chrono_format = moment_format.to_string();
+    let tokens = format_map
+        .iter()
+        .sorted_by_key(|(moment_token, _)| std::cmp::Reverse(moment_token.len()))
+        .collect_vec();
+
+    let mut chrono_format = String::new();
+    let mut rest = moment_format;
+    while let Some(c) = rest.chars().next() {
+        if c == '[' {
+            if let Some((literal, after)) = rest[1..].split_once(']') {
+                chrono_format.push_str(&literal.replace('%', "%%"));
+                rest = after;
+                continue;
+            }
+        }
 
-    for (moment_token, chrono_token) in format_map.iter() {
-        chrono_format = chrono_format.replace(moment_token, chrono_token);
+        match tokens
+            .iter()
+            .find(|(moment_token, _)| rest.starts_with(**moment_token))
+        {
+            Some((moment_token, chrono_token)) => {
+                chrono_format.push_str(chrono_token);
+                rest = &rest[moment_token.len()..];
+            }
+            None => {
+                match c {
+                    '%' => chrono_format.push_str("%%"),
+                    c => chrono_format.push(c),
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
     }
 
     chrono_format
@@ -119,8 +631,15 @@ fn convert_momentjs_to_chrono_format(moment_format: &str) -> String {
 
 #[cfg(test)]
 mod test {
+    use std::path::Path;
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::ClientCapabilities;
 
-    use crate::config::convert_momentjs_to_chrono_format;
+    use crate::config::{
+        convert_momentjs_to_chrono_format, is_settings_file, obsidian_app_settings,
+        NewFileLocation, Settings,
+    };
 
     #[test]
     fn test_format_conversion() {
@@ -128,4 +647,174 @@ mod test {
         let chrono_format = convert_momentjs_to_chrono_format(moment_format);
         assert_eq!(chrono_format, "%Y-%m-%d");
     }
+
+    #[test]
+    fn test_format_conversion_literals_and_time() {
+        assert_eq!(
+            convert_momentjs_to_chrono_format("YYYY-MM-DD [Daily]"),
+            "%Y-%m-%d Daily"
+        );
+        assert_eq!(
+            convert_momentjs_to_chrono_format("YYYY-MM-DD HHmm"),
+            "%Y-%m-%d %H%M"
+        );
+        assert_eq!(
+            convert_momentjs_to_chrono_format("[Week of] MMMM D, h:mm a"),
+            "Week of %B %-d, %-I:%M %P"
+        );
+        assert_eq!(
+            convert_momentjs_to_chrono_format("dddd [at] HH:mm:ss [100%]"),
+            "%A at %H:%M:%S 100%%"
+        );
+    }
+
+    #[test]
+    fn test_folder_overrides() {
+        let settings = Settings {
+            folder_overrides: [
+                (
+                    "journal".to_string(),
+                    serde_json::json!({ "dailynote": "%d.%m.%Y" }),
+                ),
+                (
+                    "work/**/log".to_string(),
+                    serde_json::json!({ "dailynote": "%Y%m%d" }),
+                ),
+            ]
+            .into_iter()
+            .map(|(glob, overrides)| (glob, overrides.as_object().unwrap().clone()))
+            .collect(),
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+
+        let dailynote = |path: &str| settings.for_path(root_dir, &root_dir.join(path)).dailynote;
+
+        assert_eq!(dailynote("journal/01.02.2024.md"), "%d.%m.%Y");
+        assert_eq!(dailynote("journal/2024/01.02.2024.md"), "%d.%m.%Y");
+        assert_eq!(dailynote("work/team/log/20240201.md"), "%Y%m%d");
+        assert_eq!(dailynote("work/team/notes.md"), "%Y-%m-%d");
+        assert_eq!(dailynote("2024-02-01.md"), "%Y-%m-%d");
+
+        // a changed clone doesn't get the merged settings cached for the original
+        assert_eq!(dailynote("journal/01.02.2024.md"), "%d.%m.%Y");
+        let changed = Settings {
+            heading_completions: false,
+            ..settings.clone()
+        };
+        let overridden = changed.for_path(root_dir, &root_dir.join("journal/01.02.2024.md"));
+        assert!(!overridden.heading_completions);
+        assert_eq!(overridden.dailynote, "%d.%m.%Y");
+    }
+
+    #[test]
+    fn test_obsidian_app_settings() {
+        let root_dir =
+            std::env::temp_dir().join(format!("moxide-app-settings-{}", std::process::id()));
+        std::fs::create_dir_all(root_dir.join(".obsidian")).unwrap();
+        std::fs::write(
+            root_dir.join(".obsidian").join("app.json"),
+            r#"{"newFileLocation": "folder", "newFileFolderPath": "Inbox/", "attachmentFolderPath": "./assets", "vimMode": true}"#,
+        )
+        .unwrap();
+
+        let app_settings = obsidian_app_settings(&root_dir);
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(
+            app_settings,
+            vec![
+                ("new_file_location", "folder".to_string()),
+                ("new_file_folder_path", "Inbox/".to_string()),
+                ("attachment_folder_path", "./assets".to_string()),
+            ]
+        );
+        assert!(obsidian_app_settings(&root_dir).is_empty());
+
+        let vault = Path::new("/vault");
+        let note = vault.join("projects/note.md");
+        let folder = |new_file_location| {
+            Settings {
+                new_file_location,
+                new_file_folder_path: Some("Inbox/".to_string()),
+                ..Settings::default()
+            }
+            .new_file_folder(vault, &note)
+        };
+
+        assert_eq!(folder(None), None);
+        assert_eq!(
+            folder(Some(NewFileLocation::Root)),
+            Some(vault.to_path_buf())
+        );
+        assert_eq!(
+            folder(Some(NewFileLocation::Current)),
+            Some(vault.join("projects"))
+        );
+        assert_eq!(
+            folder(Some(NewFileLocation::Folder)),
+            Some(vault.join("Inbox"))
+        );
+    }
+
+    #[test]
+    fn test_ignoring_folders() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-folders-{}", std::process::id()));
+        for file in ["note.md", "work/note.md", "work/vault/inner.md"] {
+            let path = root_dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let folders = [
+            root_dir.clone(),
+            root_dir.join("work/vault"),
+            std::env::temp_dir().join("elsewhere"),
+        ];
+        let settings = Settings::default().ignoring_folders(&root_dir, &folders);
+        let vault = crate::vault::Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(settings.ignore_globs, vec!["work/vault".to_string()]);
+        assert_eq!(
+            vault.md_files.keys().sorted().collect::<Vec<_>>(),
+            vec![&root_dir.join("note.md"), &root_dir.join("work/note.md")]
+        );
+    }
+
+    #[test]
+    fn test_settings_files_reload() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let capabilities = ClientCapabilities::default();
+        // rather than the developer's own global settings
+        let global_file = root_dir.join("global").to_string_lossy().to_string();
+
+        let before = Settings::read(&root_dir, &global_file, &capabilities).unwrap();
+        std::fs::write(
+            root_dir.join(".moxide.toml"),
+            "unresolved_diagnostics = false\n",
+        )
+        .unwrap();
+        let after = Settings::read(&root_dir, &global_file, &capabilities).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_ne!(before, after);
+        assert!(!after.unresolved_diagnostics);
+        assert_eq!(
+            Settings {
+                unresolved_diagnostics: before.unresolved_diagnostics,
+                ..after
+            },
+            before
+        );
+
+        assert!(is_settings_file(&root_dir, &root_dir.join(".moxide.toml")));
+        assert!(is_settings_file(&root_dir, &root_dir.join(".moxide")));
+        assert!(!is_settings_file(
+            &root_dir,
+            &root_dir.join("folder/.moxide.toml")
+        ));
+        assert!(!is_settings_file(&root_dir, &root_dir.join("note.md")));
+    }
 }
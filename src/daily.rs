@@ -0,0 +1,414 @@
+use std::path::{Path, PathBuf};
+
+use chrono::format::{Fixed, Item, Numeric, Parsed, StrftimeItems};
+use chrono::{Datelike, Locale, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+
+/// A set of dated notes (a journal) that share a folder and a filename format.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Notebook {
+    /// Name used to tell notebooks apart, e.g. `work` in "today (work)"
+    pub name: String,
+    /// Folder of the notebook, relative to the vault root; empty for the root itself
+    #[serde(default)]
+    pub folder: String,
+    /// Format of the notes' filenames; https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+    pub note_format: String,
+    /// Template for new notes, relative to the vault root, over the `daily_note_template`
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Only recognize notes within the folder as the notebook's, so that a `15.md` elsewhere isn't
+    /// taken for the note of the 15th; otherwise the filename alone can match
+    #[serde(default)]
+    pub match_folder: bool,
+}
+
+/// The span of time that each of a notebook's notes covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// Weeks starting on the weekday: monday for `%V` and `%W`, sunday for `%U`
+    Week(Weekday),
+    Month,
+    Year,
+}
+
+impl Notebook {
+    /// The period of the notes, from the finest date field in the format; `%G-W%V` is weekly
+    pub fn period(&self) -> Period {
+        StrftimeItems::new(&self.note_format).fold(Period::Year, |period, item| match item {
+            Item::Numeric(
+                Numeric::Day | Numeric::Ordinal | Numeric::WeekdayFromMon | Numeric::NumDaysFromSun,
+                _,
+            )
+            | Item::Fixed(
+                Fixed::ShortWeekdayName | Fixed::LongWeekdayName | Fixed::RFC2822 | Fixed::RFC3339,
+            ) => Period::Day,
+            Item::Numeric(Numeric::IsoWeek | Numeric::WeekFromMon, _) if period != Period::Day => {
+                Period::Week(Weekday::Mon)
+            }
+            Item::Numeric(Numeric::WeekFromSun, _) if period != Period::Day => {
+                Period::Week(Weekday::Sun)
+            }
+            Item::Numeric(Numeric::Month, _)
+            | Item::Fixed(Fixed::ShortMonthName | Fixed::LongMonthName)
+                if period == Period::Year =>
+            {
+                Period::Month
+            }
+            _ => period,
+        })
+    }
+
+    /// The first day of the period containing the date, which dates the period's note
+    pub fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        match self.period() {
+            Period::Day => date,
+            Period::Week(start) => date.week(start).first_day(),
+            Period::Month => date.with_day(1).unwrap_or(date),
+            Period::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    /// The absolute folder that the notebook's notes are created in
+    pub fn folder(&self, root_dir: &Path) -> PathBuf {
+        root_dir.join(&self.folder)
+    }
+
+    /// The refname of the note for the date; the filename without the extension
+    pub fn date_to_filename(&self, date: NaiveDate) -> String {
+        self.period_start(date)
+            .format(&self.note_format)
+            .to_string()
+    }
+
+    pub fn date_to_path(&self, root_dir: &Path, date: NaiveDate) -> PathBuf {
+        self.folder(root_dir)
+            .join(format!("{}.md", self.date_to_filename(date)))
+    }
+
+    /// The date of a note with this filename, without the extension; the first day of its period.
+    /// When `strict`, the format must consume the entire filename; otherwise trailing characters
+    /// are ignored.
+    pub fn match_filename(&self, filename: &str, strict: bool) -> Option<NaiveDate> {
+        let mut parsed = Parsed::new();
+        let items = StrftimeItems::new(&self.note_format);
+        match strict {
+            true => chrono::format::parse(&mut parsed, filename, items),
+            false => chrono::format::parse_and_remainder(&mut parsed, filename, items)
+                .map(|_remainder| ()),
+        }
+        .ok()?;
+
+        // the format leaves out the fields finer than its period
+        match self.period() {
+            Period::Day => Ok(()),
+            Period::Week(start) => parsed.set_weekday(start),
+            Period::Month => parsed.set_day(1),
+            Period::Year => parsed.set_ordinal(1),
+        }
+        .ok()?;
+
+        parsed.to_naive_date().ok()
+    }
+
+    /// The date of the note at the path, when it's in the notebook's folder: the path from the
+    /// folder, without the extension, is matched, so formats like `%Y/%m/%Y-%m-%d` nest notes in
+    /// folders
+    pub fn match_path(&self, root_dir: &Path, path: &Path, strict: bool) -> Option<NaiveDate> {
+        let relative = path.strip_prefix(self.folder(root_dir)).ok()?;
+        let name = relative
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()?
+            .join("/");
+
+        self.match_filename(&name, strict)
+    }
+}
+
+/// The configured notebooks, or a single notebook at the vault root using the `dailynote` format
+pub fn notebooks(settings: &Settings) -> Vec<Notebook> {
+    match settings.notebooks.as_slice() {
+        [] => vec![Notebook {
+            name: "daily".to_string(),
+            folder: String::new(),
+            note_format: settings.dailynote.clone(),
+            template: None,
+            match_folder: false,
+        }],
+        notebooks => notebooks.to_vec(),
+    }
+}
+
+/// The first notebook whose format matches the filename, without the extension, and the note's
+/// date; notebooks that `match_folder` need the note's path, for `match_notebook_path`
+pub fn match_notebook(settings: &Settings, filename: &str) -> Option<(Notebook, NaiveDate)> {
+    notebooks(settings)
+        .into_iter()
+        .filter(|notebook| !notebook.match_folder)
+        .find_map(|notebook| {
+            let date = notebook.match_filename(filename, settings.strict_daily_match)?;
+            Some((notebook, date))
+        })
+}
+
+/// The first notebook with the note at the path, and the note's date
+pub fn match_notebook_path(
+    settings: &Settings,
+    root_dir: &Path,
+    path: &Path,
+) -> Option<(Notebook, NaiveDate)> {
+    notebooks(settings).into_iter().find_map(|notebook| {
+        let date = notebook.match_path(root_dir, path, settings.strict_daily_match)?;
+        Some((notebook, date))
+    })
+}
+
+/// How completions and symbols name a date near today: `today`, `tomorrow`, or `yesterday`, or
+/// else the weekday in the `locale` a week either side. Without a `week_start`, that is `next` or
+/// `last` the weekday; with one, `this` within today's week and `next` or `last` past its bounds.
+pub fn relative_date_string(
+    settings: &Settings,
+    today: NaiveDate,
+    date: NaiveDate,
+) -> Option<String> {
+    let days = (date - today).num_days();
+    let weekday = || weekday_name(settings, date);
+
+    match (days, settings.week_start) {
+        (0, _) => Some("today".to_string()),
+        (1, _) => Some("tomorrow".to_string()),
+        (-1, _) => Some("yesterday".to_string()),
+        (2..=7, None) => Some(format!("next {}", weekday())),
+        (-7..=-2, None) => Some(format!("last {}", weekday())),
+        (-7..=7, Some(start)) => {
+            let weeks = (date.week(start).first_day() - today.week(start).first_day()).num_weeks();
+            match weeks {
+                0 => Some(format!("this {}", weekday())),
+                1 => Some(format!("next {}", weekday())),
+                -1 => Some(format!("last {}", weekday())),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The date's weekday in the `locale`, like `de_DE`, or in English
+fn weekday_name(settings: &Settings, date: NaiveDate) -> String {
+    match settings
+        .locale
+        .as_ref()
+        .and_then(|locale| locale.replace('-', "_").parse::<Locale>().ok())
+    {
+        Some(locale) => date.format_localized("%A", locale).to_string(),
+        None => date.format("%A").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use chrono::{NaiveDate, Weekday};
+
+    use crate::config::Settings;
+
+    use super::{match_notebook, match_notebook_path, relative_date_string, Notebook, Period};
+
+    #[test]
+    fn test_notebook_path() {
+        let notebook = Notebook {
+            name: "work".to_string(),
+            folder: "work-journal".to_string(),
+            note_format: "%Y-%m-%d".to_string(),
+            template: None,
+            match_folder: false,
+        };
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+
+        assert_eq!(
+            notebook.date_to_path(Path::new("/home/vault"), date),
+            PathBuf::from("/home/vault/work-journal/2024-01-15.md")
+        );
+    }
+
+    #[test]
+    fn test_nested_note_format() {
+        let notebook = Notebook {
+            name: "journal".to_string(),
+            folder: "journal".to_string(),
+            note_format: "%Y/%m/%Y-%m-%d".to_string(),
+            template: None,
+            match_folder: false,
+        };
+
+        let root_dir = Path::new("/home/vault");
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let path = PathBuf::from("/home/vault/journal/2024/01/2024-01-15.md");
+
+        assert_eq!(notebook.date_to_path(root_dir, date), path);
+        assert_eq!(notebook.match_path(root_dir, &path, true), Some(date));
+        assert_eq!(
+            notebook.match_path(
+                root_dir,
+                Path::new("/home/vault/journal/2024-01-15.md"),
+                true
+            ),
+            None
+        );
+        assert_eq!(
+            notebook.match_path(
+                root_dir,
+                Path::new("/home/vault/other/2024/01/2024-01-15.md"),
+                true
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_strict_match_filename() {
+        let notebook = Notebook {
+            name: "daily".to_string(),
+            folder: String::new(),
+            note_format: "%Y-%m-%d".to_string(),
+            template: None,
+            match_folder: false,
+        };
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15);
+
+        assert_eq!(notebook.match_filename("2024-01-15", true), date);
+        assert_eq!(
+            notebook.match_filename("2024-01-15 meeting notes", true),
+            None
+        );
+        assert_eq!(
+            notebook.match_filename("2024-01-15 meeting notes", false),
+            date
+        );
+    }
+
+    #[test]
+    fn test_period_notebooks() {
+        let notebook = |note_format: &str| Notebook {
+            name: "journal".to_string(),
+            folder: String::new(),
+            note_format: note_format.to_string(),
+            template: None,
+            match_folder: false,
+        };
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+
+        let weekly = notebook("%G-W%V");
+        assert_eq!(weekly.period(), Period::Week(Weekday::Mon));
+        // a wednesday is in the week of monday the 15th
+        assert_eq!(weekly.period_start(date(1, 17)), date(1, 15));
+        assert_eq!(weekly.date_to_filename(date(1, 17)), "2024-W03");
+        assert_eq!(weekly.match_filename("2024-W03", true), Some(date(1, 15)));
+
+        let monthly = notebook("%Y-%m");
+        assert_eq!(monthly.period(), Period::Month);
+        assert_eq!(monthly.match_filename("2024-03", true), Some(date(3, 1)));
+        assert_eq!(notebook("%B %Y").period_start(date(3, 20)), date(3, 1));
+
+        assert_eq!(
+            notebook("%Y").match_filename("2024", true),
+            Some(date(1, 1))
+        );
+        assert_eq!(notebook("%Y-W%U").period(), Period::Week(Weekday::Sun));
+        assert_eq!(notebook("%Y-%m-%d %a").period(), Period::Day);
+    }
+
+    #[test]
+    fn test_match_folder() {
+        let notebook = |match_folder| Notebook {
+            name: "journal".to_string(),
+            folder: "journal".to_string(),
+            note_format: "%Y-%m-%d".to_string(),
+            template: None,
+            match_folder,
+        };
+        let settings = |match_folder| Settings {
+            notebooks: vec![notebook(match_folder)],
+            ..Settings::default()
+        };
+
+        let root_dir = Path::new("/home/vault");
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let in_folder = Path::new("/home/vault/journal/2024-01-15.md");
+        let elsewhere = Path::new("/home/vault/meetings/2024-01-15.md");
+
+        assert_eq!(
+            match_notebook(&settings(false), "2024-01-15"),
+            Some((notebook(false), date))
+        );
+        assert_eq!(match_notebook(&settings(true), "2024-01-15"), None);
+        assert_eq!(
+            match_notebook_path(&settings(true), root_dir, in_folder),
+            Some((notebook(true), date))
+        );
+        assert_eq!(
+            match_notebook_path(&settings(true), root_dir, elsewhere),
+            None
+        );
+    }
+
+    #[test]
+    fn test_relative_date_string() {
+        // a wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 1, 17).unwrap();
+        let relative = |settings: &Settings, days: i64| {
+            let date = today + chrono::Duration::try_days(days).unwrap();
+            relative_date_string(settings, today, date)
+        };
+
+        let settings = Settings::default();
+        assert_eq!(relative(&settings, 0).as_deref(), Some("today"));
+        assert_eq!(relative(&settings, 1).as_deref(), Some("tomorrow"));
+        assert_eq!(relative(&settings, -1).as_deref(), Some("yesterday"));
+        assert_eq!(relative(&settings, 2).as_deref(), Some("next Friday"));
+        assert_eq!(relative(&settings, 7).as_deref(), Some("next Wednesday"));
+        assert_eq!(relative(&settings, -3).as_deref(), Some("last Sunday"));
+        assert_eq!(relative(&settings, 8), None);
+
+        let german = Settings {
+            locale: Some("de_DE".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(relative(&german, 0).as_deref(), Some("today"));
+        assert_eq!(relative(&german, 2).as_deref(), Some("next Freitag"));
+
+        let french_monday = Settings {
+            locale: Some("fr-FR".to_string()),
+            week_start: Some(Weekday::Mon),
+            ..Settings::default()
+        };
+        assert_eq!(relative(&french_monday, 1).as_deref(), Some("tomorrow"));
+        assert_eq!(
+            relative(&french_monday, 2).as_deref(),
+            Some("this vendredi")
+        );
+        assert_eq!(relative(&french_monday, 5).as_deref(), Some("next lundi"));
+        assert_eq!(relative(&french_monday, -2).as_deref(), Some("this lundi"));
+        assert_eq!(
+            relative(&french_monday, -3).as_deref(),
+            Some("last dimanche")
+        );
+
+        // the sunday three days ago starts this week
+        let sunday = Settings {
+            week_start: Some(Weekday::Sun),
+            ..Settings::default()
+        };
+        assert_eq!(relative(&sunday, -3).as_deref(), Some("this Sunday"));
+        assert_eq!(relative(&sunday, 4).as_deref(), Some("next Sunday"));
+        assert_eq!(relative(&sunday, -4).as_deref(), Some("last Saturday"));
+    }
+}
@@ -1,76 +1,1054 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
+use itertools::Itertools;
+use pathdiff::diff_paths;
 use rayon::prelude::*;
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
 
 use crate::{
-    config::Settings,
-    vault::{self, Reference, Referenceable, Vault},
+    config::{Settings, TagSources},
+    vault::{
+        self, frontmatter_len, MDFootnote, Rangeable, Reference, Referenceable, ResolutionIndex,
+        Vault,
+    },
 };
 
+/// Unresolved references in the file, resolved through the `index` of the vault
 pub fn path_unresolved_references<'a>(
     vault: &'a Vault,
+    index: &ResolutionIndex,
     path: &'a Path,
 ) -> Option<Vec<(&'a Path, &'a Reference)>> {
-    let referenceables = vault.select_referenceable_nodes(None);
-    let pathreferences = vault.select_references(Some(path))?;
+    select_unresolved_references(vault, index, Some(path))
+}
+
+/// Unresolved references in the file if path is some, else in the whole vault
+pub fn unresolved_references<'a>(
+    vault: &'a Vault,
+    path: Option<&'a Path>,
+) -> Option<Vec<(&'a Path, &'a Reference)>> {
+    select_unresolved_references(vault, &vault.resolution_index(), path)
+}
+
+fn select_unresolved_references<'a>(
+    vault: &'a Vault,
+    index: &ResolutionIndex,
+    path: Option<&'a Path>,
+) -> Option<Vec<(&'a Path, &'a Reference)>> {
+    let pathreferences = vault.select_references(path)?;
 
     let unresolved = pathreferences
         .into_par_iter()
-        .filter(|(path, reference)| {
-            let matched_option = referenceables
+        .filter(|(path, reference)| index.is_unresolved(reference, path))
+        .collect::<Vec<_>>();
+
+    Some(unresolved)
+}
+
+/// Whether two references are uses of the same link; footnotes are only the same within a file
+pub fn is_same_reference(
+    (path, reference): (&Path, &Reference),
+    (other_path, other_reference): (&Path, &Reference),
+) -> bool {
+    other_reference.matches_type(reference)
+        && (!matches!(reference, vault::Reference::Footnote(_)) || other_path == path)
+        && other_reference.data().reference_text == reference.data().reference_text
+}
+
+/// The embeds of whole notes in the vault, by the note embedding them, with the notes they embed
+type Embeds<'a> = HashMap<&'a Path, Vec<(&'a Reference, &'a Path)>>;
+
+/// Whether the link is an embed of a whole note, `![[note]]`, the only embeds that count for cycles
+fn is_note_embed(vault: &Vault, path: &Path, reference: &Reference) -> bool {
+    matches!(
+        reference,
+        Reference::WikiFileLink(..) | Reference::MDFileLink(..)
+    ) && vault.is_embed(path, reference)
+}
+
+fn select_embeds<'a>(vault: &'a Vault, index: &ResolutionIndex<'a>) -> Embeds<'a> {
+    vault
+        .select_references(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(path, reference)| is_note_embed(vault, path, reference))
+        .filter_map(
+            |(path, reference)| match index.resolve(reference, path).next()? {
+                Referenceable::File(target, _) => Some((path, (reference, target.as_path()))),
+                _ => None,
+            },
+        )
+        .into_group_map()
+}
+
+/// Embeds of notes in the file, `![[note]]`, that lead back to the file through the notes' own
+/// embeds, with the notes from the file around the cycle back to it; a note embedding itself is the
+/// shortest cycle. Only embeds of whole notes count, as embedding a section of a note rarely
+/// embeds the embed itself. The vault's embeds are only selected for a file with embeds of its own,
+/// once for all the files sharing `embeds`.
+fn embed_cycles<'a>(
+    vault: &'a Vault,
+    index: &ResolutionIndex<'a>,
+    embeds: &OnceLock<Embeds<'a>>,
+    path: &Path,
+) -> Option<Vec<(&'a Reference, Vec<&'a Path>)>> {
+    let path = vault.md_files.get_key_value(path)?.0.as_path();
+    if !vault
+        .select_references(Some(path))?
+        .into_iter()
+        .any(|(path, reference)| is_note_embed(vault, path, reference))
+    {
+        return Some(vec![]);
+    }
+    let embeds = embeds.get_or_init(|| select_embeds(vault, index));
+
+    let cycles = embeds
+        .get(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|&(reference, target)| {
+            // the notes embedded from the target, breadth first, by the note embedding them
+            let mut embedded_by = HashMap::from([(target, path)]);
+            let mut queue = VecDeque::from([target]);
+            while let Some(note) = queue.pop_front() {
+                if note == path {
+                    let mut cycle = vec![path];
+                    let mut note = embedded_by[path];
+                    while note != path {
+                        cycle.push(note);
+                        note = embedded_by[note];
+                    }
+                    cycle.push(path);
+                    cycle.reverse();
+
+                    return Some((reference, cycle));
+                }
+
+                for &(_, next) in embeds.get(note).into_iter().flatten() {
+                    if !embedded_by.contains_key(next) {
+                        embedded_by.insert(next, note);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            None
+        })
+        .collect();
+
+    Some(cycles)
+}
+
+/// Links in the file whose file part, written without a path, names several files, like `[[note]]`
+/// with both `a/note.md` and `b/note.md` in the vault, with the files in order. Unless
+/// `link_resolution_priority` picks one, the link goes to whichever file is indexed first, which
+/// may not be the one meant.
+pub fn ambiguous_links<'a>(
+    vault: &'a Vault,
+    index: &ResolutionIndex,
+    path: &'a Path,
+) -> Option<Vec<(&'a Reference, Vec<PathBuf>)>> {
+    let ambiguous = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter(|(_, reference)| {
+            let file_ref = match reference {
+                Reference::WikiFileLink(data)
+                | Reference::MDFileLink(data)
+                | Reference::AttachmentLink(data) => &data.reference_text,
+                Reference::WikiHeadingLink(_, file_ref, _)
+                | Reference::WikiIndexedBlockLink(_, file_ref, _)
+                | Reference::MDHeadingLink(_, file_ref, _)
+                | Reference::MDIndexedBlockLink(_, file_ref, _) => file_ref,
+                _ => return false,
+            };
+
+            !file_ref.is_empty() && !file_ref.contains('/')
+        })
+        .filter_map(|(reference_path, reference)| {
+            let candidates = index
+                .resolve(reference, reference_path)
+                .filter(|referenceable| !referenceable.is_unresolved())
+                .map(|referenceable| referenceable.get_path().to_path_buf())
+                .unique()
+                .sorted()
+                .collect_vec();
+
+            (candidates.len() > 1).then_some((reference, candidates))
+        })
+        .collect();
+
+    Some(ambiguous)
+}
+
+/// The diagnostic for a link matching each of the `candidates`, which names them from the vault root
+pub fn ambiguous_link_diagnostic(
+    vault: &Vault,
+    settings: &Settings,
+    reference: &Reference,
+    candidates: &[PathBuf],
+) -> Diagnostic {
+    Diagnostic {
+        range: *reference.data().range,
+        message: format!(
+            "Ambiguous link: matches {}",
+            candidates
                 .iter()
-                .find(|referenceable| reference.references(vault.root_dir(), path, referenceable));
-
-            matched_option.is_some_and(|matched| {
-                matches!(
-                    matched,
-                    Referenceable::UnresovledIndexedBlock(..)
-                        | Referenceable::UnresovledFile(..)
-                        | Referenceable::UnresolvedHeading(..)
+                .filter_map(|candidate| diff_paths(candidate, vault.root_dir()))
+                .map(|candidate| candidate.to_string_lossy().into_owned())
+                .join(", ")
+        ),
+        source: Some(settings.diagnostics_source.clone()),
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..Default::default()
+    }
+}
+
+/// Footnote definitions in the file that no footnote in the file uses
+pub fn orphaned_footnotes<'a>(vault: &'a Vault, path: &'a Path) -> Option<Vec<&'a MDFootnote>> {
+    let file = vault.md_files.get(path)?;
+    let references = vault.select_references(Some(path))?;
+
+    let orphaned = file
+        .footnotes
+        .iter()
+        .filter(|footnote| {
+            let referenceable = Referenceable::Footnote(&file.path, footnote);
+
+            !references.iter().any(|(reference_path, reference)| {
+                referenceable.matches_reference(vault, reference, reference_path)
+            })
+        })
+        .collect();
+
+    Some(orphaned)
+}
+
+/// Reference style links, like `[text][ref]`, without a `[ref]: target` definition in their file
+pub fn undefined_link_references<'a>(
+    vault: &'a Vault,
+    path: &'a Path,
+) -> Option<Vec<&'a Reference>> {
+    let file = vault.md_files.get(path)?;
+    let references = vault.select_references(Some(path))?;
+
+    let undefined = references
+        .into_iter()
+        .filter(|(_, reference)| matches!(reference, Reference::LinkRef(_)))
+        .filter(|(reference_path, reference)| {
+            !file.link_reference_definitions.iter().any(|definition| {
+                reference.references(
+                    vault,
+                    reference_path,
+                    &Referenceable::LinkRefDef(&file.path, definition),
                 )
             })
         })
-        .collect::<Vec<_>>();
+        .map(|(_, reference)| reference)
+        .collect();
 
-    Some(unresolved)
+    Some(undefined)
+}
+
+/// Links to attachments, like `![[image.png]]`, that no file in the vault matches
+pub fn missing_attachments<'a>(
+    vault: &'a Vault,
+    index: &ResolutionIndex,
+    path: &'a Path,
+) -> Option<Vec<&'a Reference>> {
+    let missing = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter(|(_, reference)| matches!(reference, Reference::AttachmentLink(_)))
+        .filter(|(reference_path, reference)| {
+            index.resolve(reference, reference_path).next().is_none()
+        })
+        .map(|(_, reference)| reference)
+        .collect();
+
+    Some(missing)
+}
+
+/// Unlike an unresolved note link, there's no note to create for a missing attachment, so the
+/// message says what's missing
+pub fn missing_attachment_diagnostic(settings: &Settings, reference: &Reference) -> Diagnostic {
+    Diagnostic {
+        range: *reference.data().range,
+        message: format!("Attachment not found: {}", reference.data().reference_text),
+        source: Some(settings.diagnostics_source.clone()),
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..Default::default()
+    }
+}
+
+/// Links whose relative path leaves the vault, like `[note](../../outside.md)`; they can't resolve
+/// to an indexed file
+pub fn external_links<'a>(vault: &'a Vault, path: &'a Path) -> Option<Vec<&'a Reference>> {
+    let root_dir = vault.root_dir();
+    let folder_depth = diff_paths(path.parent()?, root_dir)?.components().count();
+
+    let external = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter(|(_, reference)| {
+            let file_ref = match reference {
+                Reference::WikiFileLink(data) | Reference::MDFileLink(data) => &data.reference_text,
+                Reference::WikiHeadingLink(_, file_ref, _)
+                | Reference::WikiIndexedBlockLink(_, file_ref, _)
+                | Reference::MDHeadingLink(_, file_ref, _)
+                | Reference::MDIndexedBlockLink(_, file_ref, _) => file_ref,
+                _ => return false,
+            };
+
+            // links starting with `/` are from the vault root
+            let depth = match file_ref.starts_with('/') {
+                true => 0,
+                false => folder_depth,
+            };
+
+            file_ref
+                .split('/')
+                .try_fold(depth, |depth, segment| match segment {
+                    "" | "." => Some(depth),
+                    ".." => depth.checked_sub(1),
+                    _ => Some(depth + 1),
+                })
+                .is_none()
+        })
+        .map(|(_, reference)| reference)
+        .collect();
+
+    Some(external)
+}
+
+/// Wikilinks and embeds without their closing `]]` or without a target, like `[[note]` and
+/// `[[ ]]`, and lone `#`s where a tag was likely meant; the parser skips these rather than
+/// indexing them. Code and frontmatter are ignored.
+pub fn malformed_syntax(vault: &Vault, path: &Path) -> Option<Vec<(Range, &'static str)>> {
+    let file = vault.md_files.get(path)?;
+    let rope = vault.ropes.get(path)?;
+    let in_code = |position: Position| {
+        file.codeblocks
+            .iter()
+            .any(|codeblock| codeblock.includes_position(position))
+    };
+
+    let malformed = rope
+        .lines()
+        .enumerate()
+        .skip(frontmatter_len(rope))
+        .flat_map(|(line, text)| {
+            let chars = text
+                .chars()
+                .take_while(|c| !matches!(c, '\r' | '\n'))
+                .collect_vec();
+            let range = |start: usize, end: usize| {
+                Range::new(
+                    Position::new(line as u32, start as u32),
+                    Position::new(line as u32, end as u32),
+                )
+            };
+            let opens_at = |i: usize| chars.get(i) == Some(&'[') && chars.get(i + 1) == Some(&'[');
+            let closes_at = |i: usize| chars.get(i) == Some(&']') && chars.get(i + 1) == Some(&']');
+
+            let mut malformed = vec![];
+            let mut i = 0;
+            while i < chars.len() {
+                if !opens_at(i) {
+                    i += 1;
+                    continue;
+                }
+
+                let embed = i > 0 && chars[i - 1] == '!';
+                let start = if embed { i - 1 } else { i };
+                let end = (i + 2..chars.len())
+                    .find(|j| closes_at(*j) || opens_at(*j))
+                    .unwrap_or(chars.len());
+
+                if closes_at(end) {
+                    let target = chars[i + 2..end]
+                        .iter()
+                        .take_while(|c| **c != '|')
+                        .collect::<String>();
+                    if target.trim().is_empty() {
+                        let message = if embed { "Empty embed" } else { "Empty link" };
+                        malformed.push((range(start, end + 2), message));
+                    }
+                    i = end + 2;
+                } else {
+                    // up to a single closing bracket, or else the next link
+                    let text_end = match (i + 2..end).find(|j| chars[*j] == ']') {
+                        Some(bracket) => bracket + 1,
+                        None => {
+                            end - chars[i + 2..end]
+                                .iter()
+                                .rev()
+                                .take_while(|c| c.is_whitespace())
+                                .count()
+                        }
+                    };
+                    let message = if embed {
+                        "Unterminated embed"
+                    } else {
+                        "Unterminated link"
+                    };
+                    malformed.push((range(start, text_end), message));
+                    i = end;
+                }
+            }
+
+            // an empty tag is a `#` after a space with nothing after it, or a character no tag
+            // has; a `#` starting a line or closing a heading line isn't a tag, nor is a `#` before
+            // a space, like `Item # 3`
+            let text = String::from_iter(&chars);
+            let heading = text.trim_start().starts_with('#')
+                && text.trim_start().trim_start_matches('#').starts_with(' ');
+            let empty_tags = chars.iter().enumerate().filter(|(i, c)| {
+                **c == '#'
+                    && chars[..*i].iter().any(|c| !c.is_whitespace())
+                    && chars[*i - 1].is_whitespace()
+                    && chars.get(i + 1).is_none_or(|next| {
+                        !next.is_whitespace()
+                            && !next.is_alphanumeric()
+                            && !matches!(next, '_' | '-' | '/' | '#')
+                    })
+                    && !(heading && chars[*i..].iter().all(|c| *c == '#' || c.is_whitespace()))
+            });
+            malformed.extend(empty_tags.map(|(i, _)| (range(i, i + 1), "Empty tag")));
+
+            malformed
+        })
+        .filter(|(range, _)| !in_code(range.start))
+        .collect();
+
+    Some(malformed)
+}
+
+/// What the diagnostics of the files in one pass over them share, built once for all of them: the
+/// resolution index, and the vault's embeds once a file needs them
+pub struct DiagnosticsPass<'a> {
+    vault: &'a Vault,
+    index: ResolutionIndex<'a>,
+    embeds: OnceLock<Embeds<'a>>,
 }
 
+/// The diagnostics of a single file
+#[cfg(test)]
 pub fn diagnostics(
     vault: &Vault,
     settings: &Settings,
-    (path, _uri): (&PathBuf, &Url),
+    file: (&PathBuf, &Url),
 ) -> Option<Vec<Diagnostic>> {
-    if !settings.unresolved_diagnostics {
-        return None;
+    DiagnosticsPass::new(vault).diagnostics(settings, file)
+}
+
+impl<'a> DiagnosticsPass<'a> {
+    pub fn new(vault: &'a Vault) -> DiagnosticsPass<'a> {
+        DiagnosticsPass {
+            vault,
+            index: vault.resolution_index(),
+            embeds: OnceLock::new(),
+        }
     }
 
-    let unresolved = path_unresolved_references(vault, path)?;
+    pub fn diagnostics(
+        &self,
+        settings: &Settings,
+        (path, _uri): (&PathBuf, &Url),
+    ) -> Option<Vec<Diagnostic>> {
+        let DiagnosticsPass { vault, index, .. } = self;
+        let vault = *vault;
+
+        if !settings.unresolved_diagnostics
+            && !settings.orphaned_footnote_diagnostics
+            && !settings.external_link_diagnostics
+            && !settings.syntax_diagnostics
+            && !settings.embed_cycle_diagnostics
+            && !settings.ambiguous_link_diagnostics
+        {
+            return None;
+        }
+
+        let external = match settings.external_link_diagnostics {
+            true => external_links(vault, path)?,
+            false => vec![],
+        };
+
+        let unresolved = match settings.unresolved_diagnostics {
+            true => unresolved_diagnostics(vault, index, settings, path, &external)?
+                .into_iter()
+                .chain(
+                    undefined_link_references(vault, path)?
+                        .into_iter()
+                        .map(|reference| Diagnostic {
+                            range: *reference.data().range,
+                            message: format!(
+                                "No definition for link reference [{}]",
+                                reference.data().reference_text
+                            ),
+                            source: Some(settings.diagnostics_source.clone()),
+                            severity: Some(DiagnosticSeverity::INFORMATION),
+                            ..Default::default()
+                        }),
+                )
+                .chain(
+                    missing_attachments(vault, index, path)?
+                        .into_iter()
+                        .map(|reference| missing_attachment_diagnostic(settings, reference)),
+                )
+                .collect(),
+            false => vec![],
+        };
+
+        let orphaned = match settings.orphaned_footnote_diagnostics {
+            true => orphaned_footnotes(vault, path)?
+                .into_iter()
+                .map(|footnote| Diagnostic {
+                    range: *footnote.range,
+                    message: "Unused footnote definition".to_string(),
+                    source: Some(settings.diagnostics_source.clone()),
+                    severity: Some(DiagnosticSeverity::HINT),
+                    ..Default::default()
+                })
+                .collect(),
+            false => vec![],
+        };
+
+        let external = external.into_iter().map(|reference| Diagnostic {
+            range: *reference.data().range,
+            message: format!(
+                "Link to {} points outside the vault, so it won't be indexed",
+                reference.data().reference_text
+            ),
+            source: Some(settings.diagnostics_source.clone()),
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..Default::default()
+        });
+
+        let malformed = match settings.syntax_diagnostics {
+            true => malformed_syntax(vault, path)?
+                .into_iter()
+                .filter(|(_, message)| {
+                    *message != "Empty tag" || settings.tag_sources != TagSources::Frontmatter
+                })
+                .map(|(range, message)| Diagnostic {
+                    range,
+                    message: message.to_string(),
+                    source: Some(settings.diagnostics_source.clone()),
+                    severity: Some(DiagnosticSeverity::HINT),
+                    ..Default::default()
+                })
+                .collect(),
+            false => vec![],
+        };
+
+        let frontmatter = match settings.syntax_diagnostics {
+            true => vault
+                .md_files
+                .get(path)?
+                .metadata_error
+                .as_ref()
+                .map(|error| Diagnostic {
+                    range: *error.range,
+                    message: format!("Invalid frontmatter: {}", error.message),
+                    source: Some(settings.diagnostics_source.clone()),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    ..Default::default()
+                }),
+            false => None,
+        };
+
+        let cycles = match settings.embed_cycle_diagnostics {
+            true => embed_cycles(vault, index, &self.embeds, path)?
+                .into_iter()
+                .map(|(reference, cycle)| Diagnostic {
+                    range: *reference.data().range,
+                    message: format!(
+                        "Embed cycle: {}",
+                        cycle
+                            .iter()
+                            .filter_map(|path| path.file_stem())
+                            .map(|stem| stem.to_string_lossy())
+                            .join(" > ")
+                    ),
+                    source: Some(settings.diagnostics_source.clone()),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    ..Default::default()
+                })
+                .collect(),
+            false => vec![],
+        };
+
+        let ambiguous = match settings.ambiguous_link_diagnostics {
+            true => ambiguous_links(vault, index, path)?
+                .into_iter()
+                .map(|(reference, candidates)| {
+                    ambiguous_link_diagnostic(vault, settings, reference, &candidates)
+                })
+                .collect(),
+            false => vec![],
+        };
+
+        Some(
+            unresolved
+                .into_iter()
+                .chain(orphaned)
+                .chain(external)
+                .chain(malformed)
+                .chain(frontmatter)
+                .chain(cycles)
+                .chain(ambiguous)
+                .collect(),
+        )
+    }
+}
+
+/// Diagnostics for the unresolved references in the file, except the `skipped` ones that have their
+/// own diagnostic
+fn unresolved_diagnostics(
+    vault: &Vault,
+    index: &ResolutionIndex,
+    settings: &Settings,
+    path: &Path,
+    skipped: &[&Reference],
+) -> Option<Vec<Diagnostic>> {
+    let unresolved = path_unresolved_references(vault, index, path)?;
 
     let allreferences = vault.select_references(None)?;
 
     let diags: Vec<Diagnostic> = unresolved
         .into_par_iter()
-        .map(|(path, reference)| Diagnostic {
-            range: *reference.data().range,
-            message: match allreferences
+        .filter(|(_, reference)| !skipped.contains(reference))
+        .flat_map(|(path, reference)| {
+            unresolved_diagnostic(index, settings, path, reference, &allreferences)
+        })
+        .collect();
+
+    Some(diags)
+}
+
+/// The diagnostic for an unresolved reference: a missing heading names the file it was looked for
+/// in, and other links count their uses among `allreferences`
+pub fn unresolved_diagnostic(
+    index: &ResolutionIndex,
+    settings: &Settings,
+    path: &Path,
+    reference: &Reference,
+    allreferences: &[(&Path, &Reference)],
+) -> Option<Diagnostic> {
+    Some(Diagnostic {
+        range: *reference.data().range,
+        message: match (reference, index.linked_file(reference, path)) {
+            (
+                Reference::WikiHeadingLink(.., heading) | Reference::MDHeadingLink(.., heading),
+                Some((file_path, _)),
+            ) => format!(
+                "Heading \"{}\" not found in {}",
+                heading,
+                file_path.file_stem()?.to_string_lossy()
+            ),
+            _ => match allreferences
                 .iter()
                 .filter(|(other_path, otherreference)| {
-                    otherreference.matches_type(reference)
-                        && (!matches!(reference, vault::Reference::Footnote(_))
-                            || **other_path == *path)
-                        && otherreference.data().reference_text == reference.data().reference_text
+                    is_same_reference((path, reference), (other_path, otherreference))
                 })
                 .count()
             {
                 num if num > 1 => format!("Unresolved Reference used {} times", num),
                 _ => "Unresolved Reference".to_string(),
             },
-            source: Some("Obsidian LS".into()),
-            severity: Some(DiagnosticSeverity::INFORMATION),
-            ..Default::default()
-        })
-        .collect();
+        },
+        source: Some(settings.diagnostics_source.clone()),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        ..Default::default()
+    })
+}
 
-    Some(diags)
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::{diagnostics, DiagnosticsPass};
+
+    #[test]
+    fn test_missing_heading_diagnostic() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Setup Guide\n"),
+                ("note.md", "[[guide#Setup Gide]] [[missing#Heading]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let messages = diagnostics(&vault, &settings, (&path, &uri))
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "Heading \"Setup Gide\" not found in guide",
+                "Unresolved Reference"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orphaned_footnote_diagnostic() {
+        let settings = Settings {
+            orphaned_footnote_diagnostics: true,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "note.md",
+                "Text[^used]\n\n[^used]: Used\n[^unused]: Unused\n",
+            )],
+        );
+
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let diagnostics = diagnostics(&vault, &settings, (&path, &uri)).unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unused footnote definition");
+        assert_eq!(diagnostics[0].range.start.line, 3);
+    }
+
+    #[test]
+    fn test_undefined_link_reference_diagnostic() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "note.md",
+                "See [the docs][docs] and [the spec][spec]\n\n[DOCS]: https://example.com\n",
+            )],
+        );
+
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let diagnostics = diagnostics(&vault, &settings, (&path, &uri)).unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "No definition for link reference [spec]"
+        );
+        assert_eq!(diagnostics[0].range.start.character, 25);
+    }
+
+    #[test]
+    fn test_diagnostics_source() {
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let sources = |settings: &Settings| {
+            let vault = Vault::from_texts(
+                settings,
+                root_dir,
+                [("note.md", "[[missing]] [[ ]]\n\n[^unused]: Unused\n")],
+            );
+
+            diagnostics(&vault, settings, (&path, &uri))
+                .unwrap()
+                .into_iter()
+                .map(|diagnostic| diagnostic.source)
+                .collect::<Vec<_>>()
+        };
+        let settings = Settings {
+            orphaned_footnote_diagnostics: true,
+            ..Settings::default()
+        };
+
+        assert_eq!(
+            sources(&settings),
+            vec![Some("markdown-oxide".to_string()); 4]
+        );
+        assert_eq!(
+            sources(&Settings {
+                diagnostics_source: "Obsidian LS".to_string(),
+                ..settings
+            }),
+            vec![Some("Obsidian LS".to_string()); 4]
+        );
+    }
+
+    #[test]
+    fn test_external_link_diagnostic() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("other.md", ""),
+                (
+                    "notes/note.md",
+                    "[out](../../outside.md) [[../../elsewhere#Heading]] [ok](../other)\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("notes/note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let diagnostics = diagnostics(&vault, &settings, (&path, &uri)).unwrap();
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity, diagnostic.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Some(DiagnosticSeverity::WARNING),
+                    "Link to ../../elsewhere#Heading points outside the vault, so it won't be indexed"
+                ),
+                (
+                    Some(DiagnosticSeverity::WARNING),
+                    "Link to ../../outside points outside the vault, so it won't be indexed"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_attachment_diagnostic() {
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+        let messages = |settings: &Settings| {
+            let vault = Vault::from_texts(
+                settings,
+                root_dir,
+                [
+                    ("image.png", ""),
+                    (
+                        "note.md",
+                        "![[image.png]] ![[missing.png]] [[missing]]\n\n```\n![[code.png]]\n```\n",
+                    ),
+                ],
+            );
+
+            diagnostics(&vault, settings, (&path, &uri))
+                .unwrap()
+                .into_iter()
+                .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+                .collect::<Vec<_>>()
+        };
+
+        let settings = Settings {
+            references_in_codeblocks: false,
+            ..Settings::default()
+        };
+        assert_eq!(
+            messages(&settings),
+            vec![
+                (
+                    Some(DiagnosticSeverity::INFORMATION),
+                    "Unresolved Reference".to_string()
+                ),
+                (
+                    Some(DiagnosticSeverity::WARNING),
+                    "Attachment not found: missing.png".to_string()
+                ),
+            ]
+        );
+
+        let in_codeblocks = messages(&Settings::default());
+        assert_eq!(in_codeblocks.len(), 3);
+        assert_eq!(
+            in_codeblocks[2].1,
+            "Attachment not found: code.png".to_string()
+        );
+    }
+
+    #[test]
+    fn test_syntax_diagnostics() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Title\n\nSee [[note] and [[note]]\n[[ ]] ![[|alias]] #\n\n`[[code`\n\n```\n[[ ]] #\n```\n## Closed #\nItem # 3 and #)\n"),
+                ("other.md", ""),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let malformed = |settings: &Settings| {
+            diagnostics(&vault, settings, (&path, &uri))
+                .unwrap()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::HINT))
+                .map(|diagnostic| {
+                    (
+                        diagnostic.message,
+                        diagnostic.range.start.line,
+                        diagnostic.range.start.character,
+                        diagnostic.range.end.character,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            malformed(&settings),
+            vec![
+                ("Unterminated link".to_string(), 2, 4, 11),
+                ("Empty link".to_string(), 3, 0, 5),
+                ("Empty embed".to_string(), 3, 6, 17),
+                ("Empty tag".to_string(), 3, 18, 19),
+                ("Empty tag".to_string(), 11, 13, 14),
+            ]
+        );
+        assert_eq!(
+            malformed(&Settings {
+                syntax_diagnostics: false,
+                ..settings.clone()
+            }),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_diagnostics() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "note.md",
+                "---\ntitle: Plan\naliases: [one\n---\n# Plan\n\n## Part\n",
+            )],
+        );
+
+        let path = root_dir.join("note.md");
+        let uri = Url::from_file_path(&path).unwrap();
+        let invalid = |settings: &Settings| {
+            diagnostics(&vault, settings, (&path, &uri))
+                .unwrap()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.message.starts_with("Invalid frontmatter"))
+                .map(|diagnostic| diagnostic.range.start.line)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(invalid(&settings), vec![3]);
+        assert_eq!(
+            invalid(&Settings {
+                syntax_diagnostics: false,
+                ..settings.clone()
+            }),
+            Vec::<u32>::new()
+        );
+
+        // the rest of the note is still indexed
+        let file = vault.md_files.get(&path).unwrap();
+        assert_eq!(file.metadata, None);
+        assert_eq!(file.headings.len(), 2);
+    }
+
+    #[test]
+    fn test_embed_cycle_diagnostics() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "a.md",
+                    "![[b]]
+
+![[c]] [[self]]
+",
+                ),
+                (
+                    "b.md", "![[a]]
+",
+                ),
+                (
+                    "c.md",
+                    "![[b#Heading]]
+",
+                ),
+                (
+                    "self.md",
+                    "![[self]]
+",
+                ),
+            ],
+        );
+
+        // one pass over the files shares the vault's embeds between them
+        let pass = DiagnosticsPass::new(&vault);
+        let cycles = |name: &str, settings: &Settings| {
+            let path = root_dir.join(name);
+            let uri = Url::from_file_path(&path).unwrap();
+
+            pass.diagnostics(settings, (&path, &uri))
+                .unwrap()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.message.starts_with("Embed cycle"))
+                .map(|diagnostic| {
+                    assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::WARNING));
+                    (diagnostic.message, diagnostic.range.start.line)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            cycles("a.md", &settings),
+            vec![("Embed cycle: a > b > a".to_string(), 0)]
+        );
+        assert_eq!(
+            cycles("b.md", &settings),
+            vec![("Embed cycle: b > a > b".to_string(), 0)]
+        );
+        assert_eq!(cycles("c.md", &settings), vec![]);
+        assert_eq!(
+            cycles("self.md", &settings),
+            vec![("Embed cycle: self > self".to_string(), 0)]
+        );
+        assert_eq!(
+            cycles(
+                "a.md",
+                &Settings {
+                    embed_cycle_diagnostics: false,
+                    ..settings.clone()
+                }
+            ),
+            vec![]
+        );
+    }
 }
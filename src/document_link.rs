@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::DocumentLink;
+
+use crate::{
+    gotodef::referenceable_location,
+    vault::{Reference, Referenceable, Vault},
+};
+
+/// Clickable links for the resolved wiki and markdown links in the file. Links to headings and
+/// blocks carry a `#L<line>` fragment, which clients that support it use to open the file at the
+/// anchor; unresolved links are left out.
+pub fn document_link(vault: &Vault, path: &Path) -> Option<Vec<DocumentLink>> {
+    let references = vault.select_references(Some(path))?;
+    let index = vault.resolution_index();
+
+    let links = references
+        .into_iter()
+        .filter(|(_, reference)| {
+            !matches!(
+                reference,
+                Reference::Tag(..) | Reference::Footnote(..) | Reference::LinkRef(..)
+            )
+        })
+        .filter_map(|(reference_path, reference)| {
+            let referenceable = index
+                .resolve(reference, reference_path)
+                .next()
+                .filter(|referenceable| !referenceable.is_unresolved())?;
+
+            let location = referenceable_location(referenceable)?;
+            let mut target = location.uri;
+            let tooltip = match referenceable {
+                Referenceable::Heading(_, heading) => {
+                    target.set_fragment(Some(&format!("L{}", location.range.start.line + 1)));
+                    Some(format!("# {}", heading.heading_text))
+                }
+                Referenceable::IndexedBlock(_, block) => {
+                    target.set_fragment(Some(&format!("L{}", location.range.start.line + 1)));
+                    Some(format!("^{}", block.index))
+                }
+                _ => None,
+            };
+
+            Some(DocumentLink {
+                range: *reference.data().range,
+                target: Some(target),
+                tooltip,
+                data: None,
+            })
+        })
+        .collect();
+
+    Some(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::Position;
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::document_link;
+
+    #[test]
+    fn test_document_links() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Guide\n\n## Setup\n\nA block ^blk\n"),
+                (
+                    "note.md",
+                    "[[guide]] [[guide#Setup]] [guide](guide#^blk) [[missing]] #tag\n",
+                ),
+            ],
+        );
+
+        let links = document_link(&vault, &root_dir.join("note.md")).unwrap();
+
+        assert_eq!(links.len(), 3);
+
+        let targets = links
+            .iter()
+            .map(|link| {
+                let target = link.target.clone().unwrap();
+                (
+                    target.to_file_path().unwrap(),
+                    target.fragment().map(ToString::to_string),
+                )
+            })
+            .collect::<Vec<_>>();
+        let guide = root_dir.join("guide.md");
+        assert_eq!(
+            targets,
+            vec![
+                (guide.clone(), None),
+                (guide.clone(), Some("L3".to_string())),
+                (guide, Some("L5".to_string())),
+            ]
+        );
+
+        assert_eq!(links[1].range.start, Position::new(0, 10));
+        assert_eq!(links[1].tooltip.as_deref(), Some("# Setup"));
+    }
+}
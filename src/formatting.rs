@@ -0,0 +1,209 @@
+use std::{cmp::Reverse, path::Path};
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::{
+    config::Settings,
+    vault::{Rangeable, Reference, Vault},
+};
+
+/// Edits normalizing the constructs the vault parses, and nothing else: a single space after a
+/// heading's `#`s, no spaces inside wikilinks (`[[ note | text ]]` to `[[note|text]]`), and a block
+/// of footnote definitions in the order the footnotes are first used. Prose and code are left as
+/// they are, and formatting the result again changes nothing.
+pub fn formatting(vault: &Vault, settings: &Settings, path: &Path) -> Option<Vec<TextEdit>> {
+    if !settings.format_on_request {
+        return None;
+    }
+
+    let file = vault.md_files.get(path)?;
+    let rope = vault.ropes.get(path)?;
+    let references = vault.select_references(Some(path))?;
+
+    let original = rope
+        .lines()
+        .map(|line| line.to_string().trim_end_matches(['\r', '\n']).to_string())
+        .collect_vec();
+    let mut lines = original.clone();
+
+    // from the end of each line, so that the ranges of the links before stay put
+    let wikilinks = references
+        .iter()
+        .map(|(_, reference)| *reference)
+        .filter(|reference| {
+            matches!(
+                reference,
+                Reference::WikiFileLink(..)
+                    | Reference::WikiHeadingLink(..)
+                    | Reference::WikiIndexedBlockLink(..)
+                    | Reference::AttachmentLink(..)
+            )
+        })
+        .filter(|reference| {
+            let range = reference.range();
+            range.start.line == range.end.line
+                && !file
+                    .codeblocks
+                    .iter()
+                    .any(|codeblock| codeblock.includes(*reference))
+        })
+        .sorted_by_key(|reference| {
+            let start = reference.range().start;
+            (start.line, Reverse(start.character))
+        });
+
+    for reference in wikilinks {
+        let range = reference.range();
+        let line = &mut lines[range.start.line as usize];
+        let chars = line.chars().collect_vec();
+        let (start, end) = (range.start.character as usize, range.end.character as usize);
+        let Some(text) = chars.get(start..end).map(String::from_iter) else {
+            continue;
+        };
+        let Some(inner) = text
+            .strip_prefix("[[")
+            .and_then(|text| text.strip_suffix("]]"))
+        else {
+            continue;
+        };
+
+        let link = match inner.split_once('|') {
+            Some((target, display)) => format!("[[{}|{}]]", target.trim(), display.trim()),
+            None => format!("[[{}]]", inner.trim()),
+        };
+        *line = format!(
+            "{}{}{}",
+            String::from_iter(&chars[..start]),
+            link,
+            String::from_iter(&chars[end..])
+        );
+    }
+
+    for heading in &file.headings {
+        let line = heading.range.start.line as usize;
+        if heading.range.start.character != 0 || file.in_fenced_code(line) {
+            continue;
+        }
+
+        let text = lines[line].trim_start_matches('#').trim().to_string();
+        lines[line] = format!("{} {}", "#".repeat(heading.level.0), text);
+    }
+
+    // only definitions on consecutive lines of their own are reordered, as one block, each with
+    // the indented lines continuing it
+    let definitions = file
+        .footnotes
+        .iter()
+        .sorted_by_key(|footnote| footnote.range.start.line)
+        .map(|footnote| {
+            let start = footnote.range.start.line as usize;
+            let continuation = lines[start + 1..]
+                .iter()
+                .take_while(|line| line.starts_with([' ', '\t']) && !line.trim().is_empty())
+                .count();
+            (footnote, start..start + 1 + continuation)
+        })
+        .collect_vec();
+    let is_block = definitions.iter().all(|(footnote, _)| {
+        footnote.range.start.character == 0
+            && footnote.range.start.line == footnote.range.end.line
+            && !file.in_fenced_code(footnote.range.start.line as usize)
+    }) && definitions
+        .iter()
+        .tuple_windows()
+        .all(|((_, first), (_, next))| next.start == first.end);
+
+    if let (Some((_, first)), Some((_, last)), true) =
+        (definitions.first(), definitions.last(), is_block)
+    {
+        let block = first.start..last.end;
+        let first_use = |index: &str| {
+            references
+                .iter()
+                .filter(|(_, reference)| {
+                    matches!(reference, Reference::Footnote(data) if data.reference_text == index)
+                })
+                .map(|(_, reference)| {
+                    let start = reference.range().start;
+                    (start.line, start.character)
+                })
+                .min()
+        };
+
+        let ordered = definitions
+            .iter()
+            .enumerate()
+            .sorted_by_key(|(i, (footnote, _))| {
+                let first_use = first_use(&footnote.index);
+                (first_use.is_none(), first_use, *i)
+            })
+            .flat_map(|(_, (_, definition))| lines[definition.clone()].to_vec())
+            .collect_vec();
+        lines.splice(block, ordered);
+    }
+
+    Some(
+        original
+            .iter()
+            .zip(&lines)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(line, (old, new))| TextEdit {
+                range: Range::new(
+                    Position::new(line as u32, 0),
+                    Position::new(line as u32, old.chars().count() as u32),
+                ),
+                new_text: new.clone(),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::formatting;
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let settings = Settings {
+            format_on_request: true,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let path = root_dir.join("note.md");
+
+        let format = |text: &str| {
+            let vault = Vault::from_texts(&settings, root_dir, [("note.md", text)]);
+            let edits = formatting(&vault, &settings, &path).unwrap();
+
+            let mut lines = text.lines().map(str::to_string).collect_vec();
+            for edit in &edits {
+                assert_eq!(edit.range.start.line, edit.range.end.line);
+                lines[edit.range.start.line as usize] = edit.new_text.clone();
+            }
+            (edits.len(), lines.join("\n") + "\n")
+        };
+
+        let text = "#  Title  \n\nSee [[ note | the note ]] and [[other]][^2] then [^1]\n\
+                    `[[ code ]]`\n\n```\n#  not a heading\n[[ in code ]]\n```\n\n\
+                    ## Part [[ note#Part ]]\n\n[^1]: first\n    continued\n[^2]: second\n";
+        let expected = "# Title\n\nSee [[note|the note]] and [[other]][^2] then [^1]\n\
+                        `[[ code ]]`\n\n```\n#  not a heading\n[[ in code ]]\n```\n\n\
+                        ## Part [[note#Part]]\n\n[^2]: second\n[^1]: first\n    continued\n";
+
+        let (edits, formatted) = format(text);
+        assert_eq!(formatted, expected);
+        assert_eq!(edits, 6);
+        assert_eq!(format(&formatted), (0, formatted.clone()));
+
+        let vault = Vault::from_texts(&Settings::default(), root_dir, [("note.md", text)]);
+        assert_eq!(formatting(&vault, &Settings::default(), &path), None);
+    }
+}
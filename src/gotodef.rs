@@ -1,44 +1,250 @@
 use std::path::Path;
 
+use itertools::Itertools;
 use tower_lsp::lsp_types::{Location, Position, Url};
 
-use crate::vault::{Referenceable, Vault};
+use crate::{
+    config::Settings,
+    util::levenshtein,
+    vault::{Reference, Referenceable, ResolutionIndex, Vault},
+};
+
+pub enum Definition {
+    Resolved(Vec<Location>),
+    /// A heading link that didn't resolve, sent to the closest heading in the linked file
+    ClosestHeading(Location),
+}
 
 pub fn goto_definition(
     vault: &Vault,
+    settings: &Settings,
     cursor_position: Position,
     path: &Path,
-) -> Option<Vec<Location>> {
+) -> Option<Definition> {
     // First, find the link that the cursor is in. Get a links for the file and match the cursor position up to one of them
     let reference = vault.select_reference_at_position(path, cursor_position)?;
+
+    if let Some(location) = tag_note_location(vault, settings, reference) {
+        return Some(Definition::Resolved(vec![location]));
+    }
     // Now we have the reference text. We need to find where this is actually referencing, or if it is referencing anything.
     // Lets get all of the referenceable nodes
 
-    let referenceables = vault.select_referenceables_for_reference(reference, path);
-
-    Some(
-        referenceables
-            .into_iter()
-            .filter_map(|linkable| {
-                let range = match linkable {
-                    Referenceable::File(..) => tower_lsp::lsp_types::Range {
-                        start: Position {
-                            line: 0,
-                            character: 0,
-                        },
-                        end: Position {
-                            line: 0,
-                            character: 1,
-                        },
-                    },
-                    _ => *linkable.get_range()?,
-                };
-
-                Some(Location {
-                    uri: Url::from_file_path(linkable.get_path().to_str()?).unwrap(),
-                    range,
-                })
-            })
-            .collect(),
-    )
+    let index = vault.resolution_index();
+    let locations = index
+        .resolve(reference, path)
+        .filter_map(referenceable_location)
+        .collect_vec();
+
+    match locations.is_empty() && settings.fuzzy_heading_goto {
+        true => closest_heading(&index, reference, path).map(Definition::ClosestHeading),
+        false => Some(Definition::Resolved(locations)),
+    }
+}
+
+/// Where a link to the referenceable goes; the start of the file for file links
+pub fn referenceable_location(referenceable: &Referenceable) -> Option<Location> {
+    let range = match referenceable {
+        Referenceable::File(..) | Referenceable::Attachment(..) => tower_lsp::lsp_types::Range {
+            start: Position {
+                line: 0,
+                character: 0,
+            },
+            end: Position {
+                line: 0,
+                character: 1,
+            },
+        },
+        _ => *referenceable.get_range()?,
+    };
+
+    Some(Location {
+        uri: Url::from_file_path(referenceable.get_path()).ok()?,
+        range,
+    })
+}
+
+/// The note for the tag in the `tag_note_folder`, like `tags/project/active.md` for `#project/active`,
+/// ignoring case
+pub fn tag_note_location(
+    vault: &Vault,
+    settings: &Settings,
+    reference: &Reference,
+) -> Option<Location> {
+    let (Reference::Tag(data), Some(folder)) = (reference, &settings.tag_note_folder) else {
+        return None;
+    };
+
+    let tag = data.reference_text.trim_start_matches('#');
+    let note = vault
+        .root_dir()
+        .join(folder)
+        .join(format!("{tag}.md"))
+        .to_string_lossy()
+        .to_lowercase();
+
+    vault
+        .md_files
+        .iter()
+        .find(|(path, _)| path.to_string_lossy().to_lowercase() == note)
+        .and_then(|(path, file)| referenceable_location(&Referenceable::File(path, file)))
+}
+
+/// The heading in the linked file whose text is closest to the link's heading, ignoring case and
+/// punctuation, if it is close enough
+fn closest_heading(
+    index: &ResolutionIndex,
+    reference: &Reference,
+    path: &Path,
+) -> Option<Location> {
+    let heading = match reference {
+        Reference::WikiHeadingLink(.., heading) | Reference::MDHeadingLink(.., heading) => heading,
+        _ => return None,
+    };
+
+    let (linked_path, linked_file) = index.linked_file(reference, path)?;
+
+    let normalize = |text: &str| {
+        text.chars()
+            .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase()
+            .split_whitespace()
+            .join(" ")
+    };
+    let heading = normalize(heading);
+
+    let (closest, distance) = linked_file
+        .headings
+        .iter()
+        .map(|existing| {
+            let existing_text = normalize(&existing.heading_text);
+            (existing, levenshtein(&existing_text, &heading))
+        })
+        .min_by_key(|(_, distance)| *distance)?;
+
+    let longest = heading.chars().count().max(1);
+    if distance as f64 / longest as f64 > 0.2 {
+        return None;
+    }
+
+    Some(Location {
+        uri: Url::from_file_path(linked_path).ok()?,
+        range: *closest.range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::Position;
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::{goto_definition, Definition};
+
+    #[test]
+    fn test_fuzzy_heading_goto() {
+        let settings = Settings {
+            fuzzy_heading_goto: true,
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("guide.md", "# Intro\n\n## What's New?\n\n## Setup\n"),
+                ("note.md", "[[guide#whats new]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+
+        let Some(Definition::ClosestHeading(location)) =
+            goto_definition(&vault, &settings, Position::new(0, 3), &path)
+        else {
+            panic!("expected the closest heading")
+        };
+        assert_eq!(location.range.start.line, 2);
+
+        let settings = Settings::default();
+        assert!(matches!(
+            goto_definition(&vault, &settings, Position::new(0, 3), &path),
+            Some(Definition::Resolved(locations)) if locations.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_tag_note_goto() {
+        let settings = Settings {
+            tag_note_folder: Some("tags".to_string()),
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("tags/Project/active.md", "# Active projects\n"),
+                ("note.md", "#project/active #other\n"),
+                ("other.md", "#other\n"),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let definition = |settings: &Settings, character| match goto_definition(
+            &vault,
+            settings,
+            Position::new(0, character),
+            &path,
+        ) {
+            Some(Definition::Resolved(locations)) => locations
+                .into_iter()
+                .map(|location| location.uri.to_file_path().unwrap())
+                .sorted()
+                .collect_vec(),
+            _ => panic!("expected resolved locations"),
+        };
+
+        assert_eq!(
+            definition(&settings, 3),
+            vec![root_dir.join("tags/Project/active.md")]
+        );
+        // without a note, the tag's uses
+        assert_eq!(
+            definition(&settings, 18),
+            vec![root_dir.join("note.md"), root_dir.join("other.md")]
+        );
+        assert_eq!(
+            definition(&Settings::default(), 3),
+            vec![root_dir.join("note.md")]
+        );
+    }
+
+    #[test]
+    fn test_link_reference_goto() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "note.md",
+                "See [the docs][docs] for more\n\n[docs]: https://example.com\n",
+            )],
+        );
+
+        let path = root_dir.join("note.md");
+
+        let Some(Definition::Resolved(locations)) =
+            goto_definition(&vault, &settings, Position::new(0, 8), &path)
+        else {
+            panic!("expected the definition")
+        };
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].range.start.line, 2);
+    }
 }
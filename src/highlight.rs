@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Position};
+
+use crate::{diagnostics::is_same_reference, vault::Vault};
+
+pub fn document_highlight(
+    vault: &Vault,
+    cursor_position: Position,
+    path: &Path,
+) -> Option<Vec<DocumentHighlight>> {
+    let reference = vault.select_reference_at_position(path, cursor_position)?;
+
+    let references = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter(|other| is_same_reference((path, reference), *other))
+        .map(|(_, other)| DocumentHighlight {
+            range: *other.data().range,
+            kind: Some(DocumentHighlightKind::READ),
+        });
+
+    // Headings, blocks, ... that the reference links to, when they are in this file
+    let definitions = vault
+        .select_referenceables_for_reference(reference, path)
+        .into_iter()
+        .filter(|referenceable| referenceable.get_path() == path)
+        .flat_map(|referenceable| referenceable.get_range())
+        .map(|range| DocumentHighlight {
+            range: *range,
+            kind: Some(DocumentHighlightKind::WRITE),
+        });
+
+    Some(
+        references
+            .chain(definitions)
+            .unique_by(|highlight| {
+                (
+                    highlight.range.start.line,
+                    highlight.range.start.character,
+                    highlight.range.end.line,
+                    highlight.range.end.character,
+                )
+            })
+            .collect(),
+    )
+}
@@ -1,19 +1,33 @@
 use std::path::Path;
 
-use tower_lsp::lsp_types::{Hover, HoverContents, HoverParams};
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Hover, HoverContents, HoverParams, MarkupContent, MarkupKind};
 
 use crate::{
+    config::Settings,
+    gotodef::tag_note_location,
     ui::{preview_reference, preview_referenceable},
-    vault::Vault,
+    vault::{Reference, Vault},
 };
 
-pub fn hover(vault: &Vault, params: &HoverParams, path: &Path) -> Option<Hover> {
+pub fn hover(
+    vault: &Vault,
+    settings: &Settings,
+    params: &HoverParams,
+    path: &Path,
+) -> Option<Hover> {
     let cursor_position = params.text_document_position_params.position;
 
     match (
         vault.select_reference_at_position(path, cursor_position),
         vault.select_referenceable_at_position(path, cursor_position),
     ) {
+        (Some(reference @ Reference::Tag(..)), _) => {
+            tag_usage(vault, settings, reference).map(|markup| Hover {
+                contents: HoverContents::Markup(markup),
+                range: None,
+            })
+        }
         (Some(reference), _) => preview_reference(vault, path, reference).map(|markup| Hover {
             contents: HoverContents::Markup(markup),
             range: None,
@@ -27,3 +41,163 @@ pub fn hover(vault: &Vault, params: &HoverParams, path: &Path) -> Option<Hover>
         _ => None,
     }
 }
+
+/// How widely the tag is used, counting its nested tags as references to it do, with a link to
+/// its note in the `tag_note_folder`
+fn tag_usage(vault: &Vault, settings: &Settings, reference: &Reference) -> Option<MarkupContent> {
+    let tag = &reference.data().reference_text;
+    let tag_parts = tag.split('/').collect_vec();
+
+    let uses = vault
+        .select_references(None)?
+        .into_iter()
+        .filter(|(_, other)| match other {
+            Reference::Tag(data) => data
+                .reference_text
+                .split('/')
+                .take(tag_parts.len())
+                .eq(tag_parts.iter().copied()),
+            _ => false,
+        })
+        .collect_vec();
+    let notes = uses.iter().map(|(path, _)| path).unique().count();
+
+    let plural = |count: usize, noun: &str| match count {
+        1 => format!("1 {noun}"),
+        _ => format!("{count} {noun}s"),
+    };
+    let usage = format!(
+        "Tag `{tag}` — {} across {}",
+        plural(uses.len(), "use"),
+        plural(notes, "note")
+    );
+
+    let tag_note = tag_note_location(vault, settings, reference).and_then(|location| {
+        let name = location
+            .uri
+            .to_file_path()
+            .ok()?
+            .file_stem()?
+            .to_string_lossy()
+            .to_string();
+        Some(format!("\n\nTag note: [{name}]({})", location.uri))
+    });
+
+    Some(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: format!("{usage}{}", tag_note.unwrap_or_default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        HoverContents, HoverParams, Position, TextDocumentIdentifier, TextDocumentPositionParams,
+        Url,
+    };
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::hover;
+
+    #[test]
+    fn test_block_embed_hover() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "# Note\n\nBefore\n\nfirst line\nsecond line ^blk\n\nAfter\n",
+                ),
+                ("embed.md", "![[note#^blk]]\n![[note#^nope]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("embed.md");
+        let hover_text = |line| {
+            let params = HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    position: Position::new(line, 4),
+                },
+                work_done_progress_params: Default::default(),
+            };
+
+            match hover(&vault, &settings, &params, &path).unwrap().contents {
+                HoverContents::Markup(markup) => markup.value,
+                _ => panic!("expected markup"),
+            }
+        };
+
+        let found = hover_text(0);
+        assert!(found.starts_with("`Block Preview:`\n\nfirst line\nsecond line\n\n"));
+        assert!(!found.contains("Before") && !found.contains("After"));
+
+        assert!(hover_text(1).starts_with("Block `^nope` not found in `note`"));
+    }
+
+    #[test]
+    fn test_tag_hover() {
+        let settings = Settings {
+            tag_note_folder: Some("tags".into()),
+            ..Settings::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "#project and #project/active
+#other
+",
+                ),
+                (
+                    "other.md",
+                    "#project #projects
+",
+                ),
+                (
+                    "tags/project.md",
+                    "# Project
+",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let hover_text = |line, character| {
+            let params = HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    position: Position::new(line, character),
+                },
+                work_done_progress_params: Default::default(),
+            };
+
+            match hover(&vault, &settings, &params, &path).unwrap().contents {
+                HoverContents::Markup(markup) => markup.value,
+                _ => panic!("expected markup"),
+            }
+        };
+
+        assert_eq!(
+            hover_text(0, 3),
+            format!(
+                "Tag `#project` — 3 uses across 2 notes\n\nTag note: [project]({})",
+                Url::from_file_path(root_dir.join("tags/project.md")).unwrap()
+            )
+        );
+        assert_eq!(hover_text(1, 3), "Tag `#other` — 1 use across 1 note");
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+use crate::{
+    config::Settings,
+    diagnostics::{orphaned_footnotes, unresolved_references},
+    vault::{MyRange, Reference, Vault},
+};
+
+/// The issues found in the whole vault, by category
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LintReport {
+    pub total: usize,
+    pub categories: BTreeMap<&'static str, LintCategory>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LintCategory {
+    pub count: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LintIssue {
+    pub location: Location,
+    pub message: String,
+}
+
+type Check = fn(&Vault) -> Vec<LintIssue>;
+
+/// Run the enabled checks over the vault in parallel
+pub fn vault_lint(vault: &Vault, settings: &Settings) -> LintReport {
+    let checks: [(&'static str, bool, Check); 6] = [
+        (
+            "unresolved_links",
+            settings.unresolved_diagnostics,
+            |vault| unresolved_links(vault, false),
+        ),
+        ("broken_embeds", settings.unresolved_diagnostics, |vault| {
+            unresolved_links(vault, true)
+        }),
+        (
+            "orphaned_footnotes",
+            settings.orphaned_footnote_diagnostics,
+            orphaned_footnote_issues,
+        ),
+        (
+            "duplicate_block_ids",
+            settings.duplicate_block_id_lint,
+            duplicate_block_ids,
+        ),
+        (
+            "orphaned_notes",
+            settings.orphaned_note_lint,
+            orphaned_notes,
+        ),
+        ("heading_skips", settings.heading_skip_lint, heading_skips),
+    ];
+
+    let categories: BTreeMap<_, _> = checks
+        .into_par_iter()
+        .filter(|(_, enabled, _)| *enabled)
+        .map(|(name, _, check)| {
+            let issues = check(vault)
+                .into_iter()
+                .sorted_by_key(|issue| {
+                    (
+                        issue.location.uri.to_string(),
+                        issue.location.range.start.line,
+                        issue.location.range.start.character,
+                    )
+                })
+                .collect_vec();
+
+            (
+                name,
+                LintCategory {
+                    count: issues.len(),
+                    issues,
+                },
+            )
+        })
+        .collect();
+
+    LintReport {
+        total: categories.values().map(|category| category.count).sum(),
+        categories,
+    }
+}
+
+fn issue(path: &Path, range: MyRange, message: String) -> Option<LintIssue> {
+    Some(LintIssue {
+        location: Location {
+            uri: Url::from_file_path(path).ok()?,
+            range: *range,
+        },
+        message,
+    })
+}
+
+/// Unresolved links; `embeds` selects the `![[embedded]]` links instead of the rest
+fn unresolved_links(vault: &Vault, embeds: bool) -> Vec<LintIssue> {
+    let Some(unresolved) = unresolved_references(vault, None) else {
+        return vec![];
+    };
+
+    unresolved
+        .into_iter()
+        .filter(|(_, reference)| !matches!(reference, Reference::Tag(_)))
+        .filter(|(path, reference)| vault.is_embed(path, reference) == embeds)
+        .flat_map(|(path, reference)| {
+            issue(
+                path,
+                reference.data().range,
+                format!(
+                    "Unresolved {} \"{}\"",
+                    if embeds { "embed" } else { "link" },
+                    reference.data().reference_text
+                ),
+            )
+        })
+        .collect()
+}
+
+fn orphaned_footnote_issues(vault: &Vault) -> Vec<LintIssue> {
+    vault
+        .md_files
+        .keys()
+        .flat_map(|path| {
+            orphaned_footnotes(vault, path)
+                .into_iter()
+                .flatten()
+                .flat_map(move |footnote| {
+                    issue(
+                        path,
+                        footnote.range,
+                        format!("Unused footnote definition [{}]", footnote.index),
+                    )
+                })
+        })
+        .collect()
+}
+
+fn duplicate_block_ids(vault: &Vault) -> Vec<LintIssue> {
+    vault
+        .md_files
+        .iter()
+        .flat_map(|(path, file)| {
+            file.indexed_blocks
+                .iter()
+                .into_group_map_by(|block| &block.index)
+                .into_iter()
+                .filter(|(_, blocks)| blocks.len() > 1)
+                .flat_map(|(index, blocks)| {
+                    blocks.into_iter().flat_map(move |block| {
+                        issue(
+                            path,
+                            block.range,
+                            format!("Block id ^{} is used more than once in the file", index),
+                        )
+                    })
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
+/// Notes that no other note links to
+fn orphaned_notes(vault: &Vault) -> Vec<LintIssue> {
+    let Some(references) = vault.select_references(None) else {
+        return vec![];
+    };
+
+    let index = vault.resolution_index();
+
+    let linked = references
+        .par_iter()
+        .flat_map(|(path, reference)| {
+            index
+                .resolve(reference, path)
+                .filter(|referenceable| !referenceable.is_unresolved())
+                .filter(|referenceable| referenceable.get_path() != *path)
+                .map(|referenceable| referenceable.get_path())
+                .collect_vec()
+        })
+        .collect::<HashSet<_>>();
+
+    vault
+        .md_files
+        .keys()
+        .filter(|path| !linked.contains(path.as_path()))
+        .flat_map(|path| {
+            issue(
+                path,
+                Range::default().into(),
+                "No other note links to this note".to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Headings more than one level below the heading before them, like `###` right after `#`
+fn heading_skips(vault: &Vault) -> Vec<LintIssue> {
+    vault
+        .md_files
+        .iter()
+        .flat_map(|(path, file)| {
+            file.headings
+                .iter()
+                .sorted_by_key(|heading| heading.range.start.line)
+                .tuple_windows()
+                .filter(|(previous, heading)| heading.level.0 > previous.level.0 + 1)
+                .flat_map(|(previous, heading)| {
+                    issue(
+                        path,
+                        heading.range,
+                        format!(
+                            "Heading level {} follows heading level {}",
+                            heading.level.0, previous.level.0
+                        ),
+                    )
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::vault_lint;
+
+    #[test]
+    fn test_vault_lint() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "# Note\n\n### Skipped\n\nSee [[missing]] and ![[gone]]\n\nfirst ^id\n\nsecond ^id\n",
+                ),
+                ("index.md", "[[note]]\n"),
+            ],
+        );
+
+        let report = vault_lint(&vault, &settings);
+        let count = |category: &str| report.categories.get(category).map(|c| c.count);
+
+        assert_eq!(count("unresolved_links"), Some(1));
+        assert_eq!(count("broken_embeds"), Some(1));
+        assert_eq!(count("duplicate_block_ids"), Some(2));
+        assert_eq!(count("heading_skips"), Some(1));
+        assert_eq!(count("orphaned_notes"), Some(1));
+        // off by default
+        assert_eq!(count("orphaned_footnotes"), None);
+        assert_eq!(
+            report.categories["orphaned_notes"].issues[0]
+                .location
+                .uri
+                .to_file_path()
+                .unwrap(),
+            root_dir.join("index.md")
+        );
+        assert_eq!(report.total, 6);
+
+        let disabled = Settings {
+            unresolved_diagnostics: false,
+            duplicate_block_id_lint: false,
+            orphaned_note_lint: false,
+            heading_skip_lint: false,
+            ..Settings::default()
+        };
+        let report = vault_lint(&vault, &disabled);
+        assert!(report.categories.is_empty());
+    }
+}
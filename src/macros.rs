@@ -26,3 +26,14 @@ macro_rules! params_position_path {
         Result::Ok(path)
     }};
 }
+
+/// Hand the request or notification on to the backend of the other workspace folder its document is
+/// in, if it is in one
+#[macro_export]
+macro_rules! route_to_folder {
+    ( $self:ident, $uri:expr, $method:ident($params:expr) ) => {
+        if let Some(folder) = $self.folder_for_uri(&$uri).await {
+            return folder.$method($params).await;
+        }
+    };
+}
@@ -1,47 +1,96 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use completion::get_completions;
-use config::Settings;
-use diagnostics::diagnostics;
+use completion::{get_completions, resolve_completion};
+use config::{is_settings_file, settings_file_globs, DiagnosticsScope, Settings};
+use diagnostics::DiagnosticsPass;
+use document_link::document_link;
+use formatting::formatting;
 use itertools::Itertools;
 use rayon::prelude::*;
 use references::references;
+use selection_range::selection_range;
 use serde_json::Value;
-use symbol::{document_symbol, workspace_symbol};
+use symbol::{best_symbols, document_symbol, folding_range, workspace_symbol};
 use tokio::sync::RwLock;
 
-use gotodef::goto_definition;
+use gotodef::{goto_definition, Definition};
 use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
 
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
-use vault::Vault;
+use vault::{IndexProgress, Vault};
 
 mod codeactions;
 mod codelens;
 mod completion;
 mod commands;
 mod config;
+mod daily;
 mod diagnostics;
+mod document_link;
+mod formatting;
 mod gotodef;
+mod highlight;
 mod hover;
+mod lint;
 mod macros;
 mod references;
 mod rename;
+mod selection_range;
 mod symbol;
 mod tokens;
 mod ui;
+mod util;
 mod vault;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Backend {
     client: Client,
     vault: Arc<RwLock<Option<Vault>>>,
     opened_files: Arc<RwLock<HashSet<PathBuf>>>,
+    /// The text of the open documents as the editor last sent it, which may not be saved yet; it
+    /// is replayed into a vault indexed from the disk
+    open_texts: Arc<RwLock<HashMap<PathBuf, String>>>,
     settings: Arc<RwLock<Option<Settings>>>,
+    /// What the client supports, which some settings depend on; kept to read the settings again
+    capabilities: Arc<RwLock<Option<ClientCapabilities>>>,
+    root_dir: Arc<RwLock<Option<PathBuf>>>,
+    /// File counts of the initial vault construction; the vault is `None` until it finishes
+    indexing: Arc<IndexProgress>,
+    /// Backends for the workspace folders besides the first, each with its own vault and settings,
+    /// with `separate_workspace_folders`; requests for their files are handed on to them
+    folders: Arc<RwLock<Vec<Backend>>>,
+    /// The roots of every backend's vault, shared between them
+    workspace_folders: Arc<RwLock<Vec<PathBuf>>>,
+    /// The other workspace folders, indexed into this backend's vault without
+    /// `separate_workspace_folders`
+    joined_folders: Arc<RwLock<Vec<PathBuf>>>,
+}
+
+/// Put the open documents' text, which may not be saved, into a vault indexed from the disk
+fn replay_open_texts(
+    settings: &Settings,
+    vault: &mut Vault,
+    open_texts: &HashMap<PathBuf, String>,
+) {
+    for (path, text) in open_texts {
+        let path = vault.vault_path(path);
+        Vault::update_vault(settings, vault, (&path, text));
+    }
+}
+
+/// Sets the flag when dropped, as a request's state is when the client cancels the request
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 }
 
 struct TextDocumentItem {
@@ -66,9 +115,16 @@ impl Backend {
             return;
         };
 
+        // kept for the vault being indexed, which fails the update below
+        self.open_texts
+            .write()
+            .await
+            .insert(path.clone(), params.text.clone());
+
         let guard = self
             .bind_vault_mut(|vault| {
                 let text = &params.text;
+                let path = vault.vault_path(&path);
                 Vault::update_vault(&settings, vault, (&path, text));
 
                 Ok(())
@@ -97,6 +153,206 @@ impl Backend {
         }
     }
 
+    /// A backend for another workspace folder, sharing the client and the workspace folders
+    fn folder_backend(&self, root_dir: PathBuf, settings: Settings) -> Backend {
+        Backend {
+            client: self.client.clone(),
+            vault: Arc::new(None.into()),
+            opened_files: Arc::new(HashSet::new().into()),
+            open_texts: Arc::new(HashMap::new().into()),
+            settings: Arc::new(Some(settings).into()),
+            capabilities: self.capabilities.clone(),
+            root_dir: Arc::new(Some(root_dir).into()),
+            indexing: Arc::new(IndexProgress::default()),
+            folders: Arc::new(Vec::new().into()),
+            workspace_folders: self.workspace_folders.clone(),
+            joined_folders: Arc::new(Vec::new().into()),
+        }
+    }
+
+    /// The backend of the other workspace folder the file is in, unless the file is in this
+    /// backend's root and that is the deeper of the two
+    async fn folder_for(&self, path: &Path) -> Option<Backend> {
+        let depth = |root: &Path| root.components().count();
+        let own_depth = self
+            .root_dir
+            .read()
+            .await
+            .as_deref()
+            .filter(|root| path.starts_with(root))
+            .map(depth);
+
+        let mut deepest: Option<(usize, Backend)> = None;
+        for folder in self.folders.read().await.iter() {
+            let Some(root) = folder.root_dir.read().await.clone() else {
+                continue;
+            };
+            let folder_depth = depth(&root);
+            if path.starts_with(&root)
+                && own_depth.is_none_or(|own_depth| folder_depth > own_depth)
+                && deepest
+                    .as_ref()
+                    .is_none_or(|(depth, _)| folder_depth > *depth)
+            {
+                deepest = Some((folder_depth, folder.clone()));
+            }
+        }
+
+        deepest.map(|(_, folder)| folder)
+    }
+
+    async fn folder_for_uri(&self, uri: &Url) -> Option<Backend> {
+        self.folder_for(&uri.to_file_path().ok()?).await
+    }
+
+    /// The vault's workspace symbols for the query, searched off of the request threads
+    async fn search_symbols(
+        &self,
+        params: &WorkspaceSymbolParams,
+    ) -> Result<Vec<SymbolInformation>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+
+        // a cancelled request is dropped at its next await, and dropping the guard stops the search
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _cancel_on_drop = CancelOnDrop(cancelled.clone());
+        let vault = self.vault.clone();
+        let params = params.clone();
+        let symbols = tokio::task::spawn_blocking(move || {
+            let guard = vault.blocking_read();
+            guard
+                .as_ref()
+                .map(|vault| workspace_symbol(&settings, vault, &params, &cancelled))
+        })
+        .await
+        .map_err(|_| Error::internal_error())?;
+
+        symbols
+            .ok_or_else(|| self.indexing_error())
+            .map(Option::unwrap_or_default)
+    }
+
+    /// The symbols with their containers prefixed by the name of this backend's root folder
+    async fn folder_symbols(&self, symbols: Vec<SymbolInformation>) -> Vec<SymbolInformation> {
+        let Some(name) = self
+            .root_dir
+            .read()
+            .await
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            return symbols;
+        };
+
+        symbols
+            .into_iter()
+            .map(|symbol| SymbolInformation {
+                container_name: Some(match &symbol.container_name {
+                    Some(container) => format!("{}/{}", name, container),
+                    None => name.clone(),
+                }),
+                ..symbol
+            })
+            .collect()
+    }
+
+    /// Construct the vault off of the request threads, reporting the indexed files to the client
+    async fn index_vault(&self) {
+        let (Ok(settings), Some(root_dir)) = (
+            self.bind_settings(|settings| Ok(settings.clone())).await,
+            self.root_dir.read().await.clone(),
+        ) else {
+            return;
+        };
+        let joined_folders = self.joined_folders.read().await.clone();
+
+        let progress = self
+            .client
+            .progress(
+                ProgressToken::String(format!("indexing {}", root_dir.display())),
+                "Indexing notes…",
+            )
+            .with_percentage(0)
+            .begin()
+            .await;
+
+        let timer = std::time::Instant::now();
+
+        let indexing = self.indexing.clone();
+        let construction_settings = settings.clone();
+        let mut construction = tokio::task::spawn_blocking(move || {
+            Vault::construct_vault_with_progress(
+                &construction_settings,
+                &root_dir,
+                &joined_folders,
+                &indexing,
+            )
+        });
+
+        let vault = loop {
+            tokio::select! {
+                vault = &mut construction => break vault,
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                    let (done, total) = self.indexing.counts();
+                    progress
+                        .report_with_message(
+                            format!("{done}/{total} notes"),
+                            (done * 100).checked_div(total).unwrap_or(0) as u32,
+                        )
+                        .await;
+                }
+            }
+        };
+
+        let Ok(Ok(mut vault)) = vault else {
+            progress.finish_with_message("Failed to index notes").await;
+            self.client
+                .log_message(MessageType::ERROR, "Failed to construct the vault")
+                .await;
+            return;
+        };
+
+        {
+            // holding the texts until the vault is set keeps the changes made meanwhile
+            let open_texts = self.open_texts.read().await;
+            replay_open_texts(&settings, &mut vault, &open_texts);
+            *self.vault.write().await = Some(vault);
+        }
+
+        let (_, total) = self.indexing.counts();
+        progress
+            .finish_with_message(format!(
+                "Indexed {total} notes in {}ms",
+                timer.elapsed().as_millis()
+            ))
+            .await;
+
+        match self.publish_diagnostics().await {
+            Ok(_) => (),
+            Err(e) => {
+                self.client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("Failed calculating diagnostics on vault indexing {:?}", e),
+                    )
+                    .await
+            }
+        };
+
+        let _ = self.client.semantic_tokens_refresh().await;
+    }
+
+    /// The error for requests that need the vault before it has been indexed
+    fn indexing_error(&self) -> Error {
+        let (done, total) = self.indexing.counts();
+
+        Error {
+            code: ErrorCode::ServerError(0),
+            message: format!("Still indexing notes ({done}/{total})").into(),
+            data: None,
+        }
+    }
+
     async fn reconstruct_vault(&self) {
         let progress = self
             .client
@@ -110,13 +366,18 @@ impl Backend {
             return;
         };
 
+        let open_texts = self.open_texts.read().await.clone();
+
         {
             let _ = self
                 .bind_vault_mut(|vault| {
-                    let Ok(new_vault) = Vault::construct_vault(&settings, vault.root_dir()) else {
+                    let Ok(mut new_vault) =
+                        Vault::construct_vault(&settings, vault.root_dir(), vault.folders())
+                    else {
                         return Err(Error::new(ErrorCode::ServerError(0)));
                     };
 
+                    replay_open_texts(&settings, &mut new_vault, &open_texts);
                     *vault = new_vault;
 
                     Ok(())
@@ -157,33 +418,96 @@ impl Backend {
         let _ = self.client.semantic_tokens_refresh().await;
     }
 
+    /// Read the settings again, as when `.moxide` or the global settings change, and rebuild the
+    /// vault with them, which publishes diagnostics and refreshes semantic tokens for open files
+    async fn reload_settings(&self) {
+        // the global settings apply to every workspace folder
+        for folder in self.folders.read().await.clone() {
+            Box::pin(folder.reload_settings()).await;
+        }
+
+        let (Some(root_dir), Some(capabilities)) = (
+            self.root_dir.read().await.clone(),
+            self.capabilities.read().await.clone(),
+        ) else {
+            return;
+        };
+        let workspace_folders = self.workspace_folders.read().await.clone();
+
+        let read_settings = match Settings::new(&root_dir, &capabilities) {
+            Ok(settings) => settings.ignoring_folders(&root_dir, &workspace_folders),
+            Err(e) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to read settings {:?}", e),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        {
+            let mut settings = self.settings.write().await;
+            if settings.as_ref() == Some(&read_settings) {
+                return;
+            }
+            *settings = Some(read_settings);
+        }
+
+        self.client
+            .log_message(MessageType::INFO, "Settings reloaded")
+            .await;
+
+        // what is indexed, like tags and ignored files, depends on the settings
+        self.reconstruct_vault().await;
+    }
+
     async fn publish_diagnostics(&self) -> Result<()> {
+        let paths = self
+            .bind_opened_files(|files| Ok(files.iter().cloned().collect::<Vec<_>>()))
+            .await?;
+
+        self.publish_diagnostics_for(&paths).await
+    }
+
+    /// Publish the diagnostics of every note in the vault
+    async fn publish_workspace_diagnostics(&self) -> Result<()> {
+        let paths = self
+            .bind_vault(|vault| Ok(vault.md_files.keys().cloned().collect::<Vec<_>>()))
+            .await?;
+
+        self.publish_diagnostics_for(&paths).await
+    }
+
+    async fn publish_diagnostics_for(&self, paths: &[PathBuf]) -> Result<()> {
         let timer = std::time::Instant::now();
 
         self.client
             .log_message(MessageType::WARNING, "Diagnostics Started")
             .await;
 
-        let uris = self
-            .bind_opened_files(|files| {
-                Ok(files
-                    .into_par_iter()
-                    .filter_map(|url| Url::from_file_path(url).ok())
-                    .collect::<Vec<_>>())
-            })
-            .await?;
+        let uris = paths
+            .par_iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .collect::<Vec<_>>();
 
         let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
 
         let diagnostics = self
             .bind_vault(|vault| {
+                let pass = DiagnosticsPass::new(vault);
                 Ok(uris
                     .par_iter()
                     .filter_map(|uri| {
-                        let path = uri.to_file_path().ok()?;
-
-                        diagnostics(vault, &settings, (&path, uri))
-                            .map(|diags| (uri.clone(), diags))
+                        let path = vault.vault_path(&uri.to_file_path().ok()?);
+                        let settings = settings.for_path(vault.root_dir(), &path);
+
+                        // none clears what was published, as after diagnostics are turned off
+                        let diags = pass
+                            .diagnostics(&settings, (&path, uri))
+                            .unwrap_or_default();
+                        Some((uri.clone(), diags))
                     })
                     .collect::<Vec<_>>())
             })
@@ -221,7 +545,7 @@ impl Backend {
     async fn bind_vault<T>(&self, callback: impl FnOnce(&Vault) -> Result<T>) -> Result<T> {
         let guard = self.vault.read().await;
         let Some(vault) = guard.deref() else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
+            return Err(self.indexing_error());
         };
 
         callback(vault)
@@ -243,7 +567,7 @@ impl Backend {
 
         let mut guard = self.vault.write().await;
         let Some(ref mut vault) = *guard else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
+            return Err(self.indexing_error());
         };
 
         callback(vault)
@@ -291,11 +615,21 @@ impl Backend {
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
     async fn initialize(&self, i: InitializeParams) -> Result<InitializeResult> {
-        let root_dir = match i.root_uri {
-            Some(uri) => uri
+        let workspace_folders = i
+            .workspace_folders
+            .iter()
+            .flatten()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect_vec();
+
+        let root_dir = match (i.root_uri, workspace_folders.first()) {
+            (Some(uri), _) => uri
                 .to_file_path()
                 .or(Err(Error::new(ErrorCode::InvalidParams)))?,
-            None => std::env::current_dir().or(Err(Error::new(ErrorCode::InvalidParams)))?,
+            (None, Some(folder)) => folder.clone(),
+            (None, None) => {
+                std::env::current_dir().or(Err(Error::new(ErrorCode::InvalidParams)))?
+            }
         };
 
         let read_settings = match Settings::new(&root_dir, &i.capabilities) {
@@ -311,12 +645,48 @@ impl LanguageServer for Backend {
             }
         };
 
-        let Ok(vault) = Vault::construct_vault(&read_settings, &root_dir) else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
+        // the other workspace folders are vaults of their own, with their own settings, or else
+        // indexed into this vault
+        let other_folders = workspace_folders
+            .into_iter()
+            .filter(|folder| *folder != root_dir)
+            .unique()
+            .collect_vec();
+        let (folders, joined_folders) = match read_settings.separate_workspace_folders {
+            true => (other_folders, vec![]),
+            false => (vec![], other_folders),
         };
-        let mut value = self.vault.write().await;
-        *value = Some(vault);
+        let roots = std::iter::once(root_dir.clone())
+            .chain(folders.iter().cloned())
+            .collect_vec();
+        let read_settings = read_settings.ignoring_folders(&root_dir, &roots);
+
+        let mut folder_backends = vec![];
+        for folder in folders {
+            match Settings::new(&folder, &i.capabilities) {
+                Ok(settings) => folder_backends.push(
+                    self.folder_backend(folder.clone(), settings.ignoring_folders(&folder, &roots)),
+                ),
+                Err(e) => {
+                    self.client
+                        .log_message(
+                            MessageType::ERROR,
+                            format!("Failed to read settings of {:?} {:?}", folder, e),
+                        )
+                        .await
+                }
+            }
+        }
+
+        // the vault is indexed once the client is initialized and can show progress
+        *self.root_dir.write().await = Some(root_dir);
+        *self.capabilities.write().await = Some(i.capabilities);
+        *self.workspace_folders.write().await = roots;
+        *self.folders.write().await = folder_backends;
+        *self.joined_folders.write().await = joined_folders;
 
+        let trigger_characters = read_settings.completion_trigger_characters.clone();
+        let format_on_request = read_settings.format_on_request;
         let mut settings = self.settings.write().await;
         *settings = Some(read_settings);
 
@@ -339,14 +709,8 @@ impl LanguageServer for Backend {
                     TextDocumentSyncKind::FULL,
                 )),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec![
-                        "[".into(),
-                        " ".into(),
-                        "(".into(),
-                        "#".into(),
-                        ">".into(),
-                    ]),
+                    resolve_provider: Some(true),
+                    trigger_characters: Some(trigger_characters),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
                     completion_item: None,
@@ -354,9 +718,17 @@ impl LanguageServer for Backend {
                 // definition: Some(GotoCapability::default()),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 rename_provider: Some(OneOf::Left(true)),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_formatting_provider: format_on_request.then_some(OneOf::Left(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 workspace: Some(WorkspaceServerCapabilities {
@@ -372,24 +744,39 @@ impl LanguageServer for Backend {
                     resolve_provider: None,
                 }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec!["apply_edits".into(), "jump".into()],
+                    commands: vec![
+                        "apply_edits".into(),
+                        "jump".into(),
+                        "unique_note".into(),
+                        "sync_filename_to_title".into(),
+                        "suggest_tag_merges".into(),
+                        "apply_merge".into(),
+                        "calendar".into(),
+                        "indexing_status".into(),
+                        "backlinks_markdown".into(),
+                        "moxide.backlinks".into(),
+                        "moxide.noteGraph".into(),
+                        "moxide.tasks".into(),
+                        "vault_lint".into(),
+                        "moxide.recomputeDiagnostics".into(),
+                        "link_bidirectional".into(),
+                        "move_file".into(),
+                        "rename_heading".into(),
+                        "moxide.replaceInLinks".into(),
+                        "insert_link".into(),
+                        "moxide.capture".into(),
+                        "moxide.toggleTask".into(),
+                        "moxide.openOrCreate".into(),
+                        "moxide.stats".into(),
+                    ],
                     ..Default::default()
                 }),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
                             full: Some(SemanticTokensFullOptions::Bool(true)),
-                            range: Some(false),
-                            legend: SemanticTokensLegend {
-                                token_types: vec![
-                                    SemanticTokenType::DECORATOR,
-                                    SemanticTokenType::COMMENT,
-                                ],
-                                token_modifiers: vec![
-                                    SemanticTokenModifier::DECLARATION,
-                                    SemanticTokenModifier::DEPRECATED,
-                                ],
-                            },
+                            range: Some(true),
+                            legend: tokens::legend(),
                             ..Default::default()
                         },
                     ),
@@ -405,10 +792,15 @@ impl LanguageServer for Backend {
     }
 
     async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        route_to_folder!(self, params.text_document.uri, code_lens(params));
+
         let path = params_path!(params)?;
 
-        self.bind_vault(|vault| Ok(codelens::code_lens(vault, &path, &params)))
-            .await
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&path);
+            Ok(codelens::code_lens(vault, &path, &params))
+        })
+        .await
     }
 
     async fn initialized(&self, _: InitializedParams) {
@@ -420,7 +812,13 @@ impl LanguageServer for Backend {
             .log_message(MessageType::WARNING, format!("Settings: {:?}", settings))
             .await;
 
-        let Ok(root_path) = self.bind_vault(|vault| Ok(vault.root_dir().clone())).await else {
+        let backend = self.clone();
+        tokio::spawn(async move { backend.index_vault().await });
+        for folder in self.folders.read().await.clone() {
+            tokio::spawn(async move { folder.index_vault().await });
+        }
+
+        let Some(root_path) = self.root_dir.read().await.clone() else {
             return;
         };
 
@@ -429,10 +827,28 @@ impl LanguageServer for Backend {
         };
 
         let value = serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
-            watchers: vec![FileSystemWatcher {
-                glob_pattern: GlobPattern::String("**/*.md".into()),
-                kind: None,
-            }],
+            watchers: vec![
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/*.md".into()),
+                    kind: None,
+                },
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(
+                        "**/*.{png,jpg,jpeg,gif,svg,webp,bmp,pdf,canvas}".into(),
+                    ),
+                    kind: None,
+                },
+            ]
+            .into_iter()
+            .chain(
+                settings_file_globs()
+                    .into_iter()
+                    .map(|glob| FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(glob),
+                        kind: None,
+                    }),
+            )
+            .collect(),
         })
         .unwrap();
 
@@ -449,6 +865,8 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        route_to_folder!(self, params.text_document.uri, did_open(params));
+
         {
             let _new_files = self
                 .bind_opened_files_mut(|files| {
@@ -486,6 +904,8 @@ impl LanguageServer for Backend {
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        route_to_folder!(self, params.text_document.uri, did_close(params));
+
         let removed_file = self
             .bind_opened_files_mut(|files| {
                 let path = params_path!(params)?;
@@ -494,6 +914,10 @@ impl LanguageServer for Backend {
             })
             .await;
 
+        if let Ok(path) = params.text_document.uri.to_file_path() {
+            self.open_texts.write().await.remove(&path);
+        }
+
         if let Ok(Some(file)) = removed_file {
             self.client
                 .log_message(MessageType::LOG, format!("Remove file {:?}", file))
@@ -502,6 +926,8 @@ impl LanguageServer for Backend {
     }
 
     async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        route_to_folder!(self, params.text_document.uri, did_change(params));
+
         self.update_vault(TextDocumentItem {
             uri: params.text_document.uri,
             text: params.content_changes.remove(0).text,
@@ -509,37 +935,208 @@ impl LanguageServer for Backend {
         .await;
     }
 
-    async fn did_change_watched_files(&self, _params: DidChangeWatchedFilesParams) {
-        self.reconstruct_vault().await
+    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+        self.reload_settings().await;
+    }
+
+    /// Keep the vault in sync with notes and attachments created, changed, or deleted outside the
+    /// editor, like by a `git pull`, and the settings with their files
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let Some(root_dir) = self.root_dir.read().await.clone() else {
+            return;
+        };
+
+        // the changes in other workspace folders go to their backends
+        let mut own_changes = vec![];
+        let mut folder_changes: Vec<(Backend, Vec<FileEvent>)> = vec![];
+        for event in params.changes {
+            match self.folder_for_uri(&event.uri).await {
+                Some(folder) => match folder_changes
+                    .iter_mut()
+                    .find(|(other, _)| Arc::ptr_eq(&other.vault, &folder.vault))
+                {
+                    Some((_, changes)) => changes.push(event),
+                    None => folder_changes.push((folder, vec![event])),
+                },
+                None => own_changes.push(event),
+            }
+        }
+        for (folder, changes) in folder_changes {
+            Box::pin(folder.did_change_watched_files(DidChangeWatchedFilesParams { changes }))
+                .await;
+        }
+
+        let (settings_changes, changes): (Vec<_>, Vec<_>) = own_changes
+            .into_iter()
+            .filter_map(|event| Some((event.uri.to_file_path().ok()?, event.typ)))
+            .partition(|(path, _)| is_settings_file(&root_dir, path));
+
+        if !settings_changes.is_empty() {
+            self.reload_settings().await;
+        }
+        if changes.is_empty() {
+            return;
+        }
+
+        let Ok(settings) = self.bind_settings(|settings| Ok(settings.clone())).await else {
+            return;
+        };
+        let Ok(opened_files) = self.bind_opened_files(|files| Ok(files.clone())).await else {
+            return;
+        };
+
+        // a folder moved into the vault can hold any number of files
+        if changes
+            .iter()
+            .any(|(path, typ)| *typ == FileChangeType::CREATED && path.is_dir())
+        {
+            return self.reconstruct_vault().await;
+        }
+
+        let failed = self
+            .bind_vault_mut(|vault| {
+                Ok(changes
+                    .iter()
+                    .filter_map(|(path, typ)| match *typ {
+                        FileChangeType::DELETED => {
+                            vault.remove_path(path);
+                            None
+                        }
+                        // the editor has newer text for open notes than the disk
+                        _ if opened_files.contains(path) => None,
+                        _ => Vault::index_file(&settings, vault, path)
+                            .err()
+                            .map(|e| (path, e)),
+                    })
+                    .collect_vec())
+            })
+            .await
+            .unwrap_or_default();
+
+        for (path, e) in failed {
+            self.client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("Failed to index {:?}: {:?}", path, e),
+                )
+                .await
+        }
+
+        for (path, _) in changes
+            .iter()
+            .filter(|(_, typ)| *typ == FileChangeType::DELETED)
+        {
+            if let Ok(uri) = Url::from_file_path(path) {
+                self.client.publish_diagnostics(uri, vec![], None).await;
+            }
+        }
+
+        if let Err(e) = self.publish_diagnostics().await {
+            self.client
+                .log_message(
+                    MessageType::ERROR,
+                    format!(
+                        "Failed calculating diagnostics on watched file change {:?}",
+                        e
+                    ),
+                )
+                .await
+        }
+
+        if settings.semantic_tokens {
+            let _ = self.client.semantic_tokens_refresh().await;
+        }
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        self.bind_vault(|vault| {
-            let path = params_path!(params.text_document_position_params)?;
-            Ok(
-                goto_definition(vault, params.text_document_position_params.position, &path)
-                    .map(GotoDefinitionResponse::Array),
-            )
-        })
-        .await
+        route_to_folder!(
+            self,
+            params.text_document_position_params.text_document.uri,
+            goto_definition(params)
+        );
+
+        let settings = self
+            .bind_settings(|settings| Ok(settings.to_owned()))
+            .await?;
+        let definition = self
+            .bind_vault(|vault| {
+                let path = vault.vault_path(&params_path!(params.text_document_position_params)?);
+                Ok(goto_definition(
+                    vault,
+                    &settings.for_path(vault.root_dir(), &path),
+                    params.text_document_position_params.position,
+                    &path,
+                ))
+            })
+            .await?;
+
+        match definition {
+            Some(Definition::Resolved(locations)) => {
+                Ok(Some(GotoDefinitionResponse::Array(locations)))
+            }
+            Some(Definition::ClosestHeading(location)) => {
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        "Heading not found; went to the closest matching heading",
+                    )
+                    .await;
+                Ok(Some(GotoDefinitionResponse::Scalar(location)))
+            }
+            None => Ok(None),
+        }
     }
 
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        route_to_folder!(
+            self,
+            params.text_document_position.text_document.uri,
+            references(params)
+        );
+
         self.bind_vault(|vault| {
-            let path = params_position_path!(params)?;
+            let path = vault.vault_path(&params_position_path!(params)?);
             Ok(references(
                 vault,
                 params.text_document_position.position,
                 &path,
+                params.context.include_declaration,
+            ))
+        })
+        .await
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        route_to_folder!(
+            self,
+            params.text_document_position_params.text_document.uri,
+            document_highlight(params)
+        );
+
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&params_path!(params.text_document_position_params)?);
+            Ok(highlight::document_highlight(
+                vault,
+                params.text_document_position_params.position,
+                &path,
             ))
         })
         .await
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        route_to_folder!(
+            self,
+            params.text_document_position.text_document.uri,
+            completion(params)
+        );
+
         self.client
             .log_message(MessageType::WARNING, "Completions Started")
             .await;
@@ -556,7 +1153,11 @@ impl LanguageServer for Backend {
         }; // TODO: this is bad
 
         let res = self
-            .bind_vault(|vault| Ok(get_completions(vault, &files, &params, &path, &settings)))
+            .bind_vault(|vault| {
+                let path = vault.vault_path(&path);
+                let settings = settings.for_path(vault.root_dir(), &path);
+                Ok(get_completions(vault, &files, &params, &path, &settings))
+            })
             .await;
 
         let elapsed = timer.elapsed();
@@ -571,7 +1172,31 @@ impl LanguageServer for Backend {
         res
     }
 
+    async fn completion_resolve(&self, params: CompletionItem) -> Result<CompletionItem> {
+        if let Some(path) = params
+            .data
+            .as_ref()
+            .and_then(|data| data.get("path")?.as_str())
+        {
+            if let Some(folder) = self.folder_for(Path::new(path)).await {
+                return folder.completion_resolve(params).await;
+            }
+        }
+
+        self.bind_vault(|vault| Ok(resolve_completion(vault, params.clone())))
+            .await
+    }
+
     async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        // commands about a document in another workspace folder run in its vault
+        if let Some(uri) = params
+            .arguments
+            .iter()
+            .find_map(|arg| Url::parse(arg.as_str()?).ok())
+        {
+            route_to_folder!(self, uri, execute_command(params));
+        }
+
         match params {
             ExecuteCommandParams { command, .. } if *command == *"apply_edits" => {
                 let edits = params
@@ -589,21 +1214,599 @@ impl LanguageServer for Backend {
             // TODO: do I need to call client.showdocument?
             ExecuteCommandParams { command, .. } if *command == *"jump" => {
                 let jump_to = params.arguments.first().and_then(|val| val.as_str());
+                if let Some(Err(message)) = jump_to.map(commands::parse_date) {
+                    return Err(Error::invalid_params(message));
+                }
+                let notebook = params.arguments.get(1).and_then(|val| val.as_str());
+                // with `create` false, only existing notes are opened
+                let create = params
+                    .arguments
+                    .get(2)
+                    .and_then(|val| val.as_bool())
+                    .unwrap_or(true);
                 let settings = self.bind_settings(|settings| Ok(settings.to_owned())).await?;
-                if let Some(doc) = commands::jump(&settings, jump_to) {
+                let doc = self
+                    .bind_vault(|vault| Ok(commands::jump(vault, &settings, jump_to, notebook)))
+                    .await?;
+                if let Some(doc) = doc {
+                    if !create {
+                        let exists = self
+                            .bind_vault(|vault| {
+                                Ok(doc.uri.to_file_path().is_ok_and(|path| {
+                                    vault.md_files.contains_key(&path) || path.exists()
+                                }))
+                            })
+                            .await?;
+                        if !exists {
+                            return Err(Error::invalid_params(format!(
+                                "{} doesn't exist",
+                                doc.uri
+                            )));
+                        }
+                    }
+
+                    let create = self
+                        .bind_vault(|vault| {
+                            let path = doc.uri.to_file_path().ok();
+                            Ok(path.and_then(|path| {
+                                commands::create_daily_note(vault, &settings, &path)
+                            }))
+                        })
+                        .await?;
+                    if let Some(edit) = create {
+                        // formats like `%Y/%m/%Y-%m-%d` nest notes in folders that may not exist yet
+                        if let Some(folder) = doc
+                            .uri
+                            .to_file_path()
+                            .ok()
+                            .and_then(|path| path.parent().map(|folder| folder.to_path_buf()))
+                        {
+                            let _ = std::fs::create_dir_all(folder);
+                        }
+                        let _ = self.client.apply_edit(edit).await;
+                    }
+
                     self.client.show_document(doc).await?;
                 };
                 Ok(None)
                 // Ok(do)
             },
+            ExecuteCommandParams { command, .. } if *command == *"unique_note" => {
+                let title = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_str())
+                    .unwrap_or_default();
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let note = self
+                    .bind_vault(|vault| {
+                        Ok(commands::unique_note(
+                            vault,
+                            &settings,
+                            title,
+                            chrono::Local::now().naive_local(),
+                        ))
+                    })
+                    .await?;
+                let Some((edit, doc)) = note else {
+                    return Err(Error::invalid_params(format!(
+                        "a note for \"{}\" already exists",
+                        title
+                    )));
+                };
+
+                let _ = self.client.apply_edit(edit).await;
+                self.client.show_document(doc).await?;
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"sync_filename_to_title" => {
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_str())
+                    .and_then(|uri| Url::parse(uri).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Ok(None);
+                };
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let edit = self
+                    .bind_vault(|vault| {
+                        let settings = settings.for_path(vault.root_dir(), &path);
+                        Ok(commands::sync_filename_to_title(vault, &settings, &path))
+                    })
+                    .await?;
+
+                if let Some(edit) = edit {
+                    let _ = self.client.apply_edit(edit).await;
+                }
+
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"suggest_tag_merges" => {
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let merges = self
+                    .bind_vault(|vault| Ok(commands::suggest_tag_merges(vault, &settings)))
+                    .await?;
+
+                Ok(serde_json::to_value(merges).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"apply_merge" => {
+                let [from, to] = params.arguments.as_slice() else {
+                    return Ok(None);
+                };
+                let (Some(from), Some(to)) = (from.as_str(), to.as_str()) else {
+                    return Ok(None);
+                };
+
+                let edit = self
+                    .bind_vault(|vault| Ok(commands::apply_merge(vault, from, to)))
+                    .await?;
+
+                if let Some(edit) = edit {
+                    let _ = self.client.apply_edit(edit).await;
+                }
+
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"calendar" => {
+                let Some(month) = params.arguments.first().and_then(|val| val.as_str()) else {
+                    return Ok(None);
+                };
+                let notebook = params.arguments.get(1).and_then(|val| val.as_str());
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let days = self
+                    .bind_vault(|vault| Ok(commands::calendar(vault, &settings, month, notebook)))
+                    .await?;
+
+                Ok(days.and_then(|days| serde_json::to_value(days).ok()))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"indexing_status" => {
+                let (done, total) = self.indexing.counts();
+                let indexed = self.vault.read().await.is_some();
+
+                Ok(Some(serde_json::json!({
+                    "done": done,
+                    "total": total,
+                    "indexed": indexed,
+                })))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"backlinks_markdown" => {
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_str())
+                    .and_then(|uri| Url::parse(uri).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Ok(None);
+                };
+
+                let markdown = self
+                    .bind_vault(|vault| Ok(commands::backlinks_markdown(vault, &path)))
+                    .await?;
+
+                Ok(markdown.map(Value::String))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.backlinks" => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_str())
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Ok(None);
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Ok(None);
+                };
+                // a heading or block of the note, like `file:///vault/note.md#Heading`
+                let anchor = uri.fragment().map(util::percent_decode);
+
+                let groups = self
+                    .bind_vault(|vault| {
+                        Ok(commands::backlinks_panel(vault, &path, anchor.as_deref()))
+                    })
+                    .await?;
+
+                Ok(groups.and_then(|groups| serde_json::to_value(groups).ok()))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.noteGraph" => {
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_str())
+                    .and_then(|uri| Url::parse(uri).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Ok(None);
+                };
+                let depth = params
+                    .arguments
+                    .get(1)
+                    .and_then(|val| val.as_u64())
+                    .unwrap_or(1);
+                let max_nodes = params
+                    .arguments
+                    .get(2)
+                    .and_then(|val| val.as_u64())
+                    .unwrap_or(100);
+
+                let graph = self
+                    .bind_vault(|vault| {
+                        Ok(commands::note_graph(
+                            vault,
+                            &path,
+                            depth as usize,
+                            max_nodes as usize,
+                        ))
+                    })
+                    .await?;
+
+                Ok(graph.and_then(|graph| serde_json::to_value(graph).ok()))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.tasks" => {
+                // only the tasks with the tag, like `#work` or `work`, on their line
+                let tag = params.arguments.first().and_then(|val| val.as_str());
+
+                let tasks = self
+                    .bind_vault(|vault| Ok(commands::tasks(vault, tag)))
+                    .await?;
+
+                Ok(serde_json::to_value(tasks).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.stats" => {
+                // how many of the most linked notes to list
+                let top = params
+                    .arguments
+                    .first()
+                    .and_then(|val| val.as_u64())
+                    .unwrap_or(10);
+
+                let stats = self
+                    .bind_vault(|vault| Ok(commands::stats(vault, top as usize)))
+                    .await?;
+
+                Ok(serde_json::to_value(stats).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"vault_lint" => {
+                let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+                let report = self
+                    .bind_vault(|vault| Ok(lint::vault_lint(vault, &settings)))
+                    .await?;
+
+                Ok(serde_json::to_value(report).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.recomputeDiagnostics" => {
+                let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+
+                match settings.diagnostics_scope {
+                    DiagnosticsScope::Open => self.publish_diagnostics().await?,
+                    DiagnosticsScope::Workspace => self.publish_workspace_diagnostics().await?,
+                }
+                for folder in self.folders.read().await.clone() {
+                    folder
+                        .execute_command(ExecuteCommandParams {
+                            command: "moxide.recomputeDiagnostics".into(),
+                            arguments: vec![],
+                            work_done_progress_params: Default::default(),
+                        })
+                        .await?;
+                }
+
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"link_bidirectional" => {
+                let [source, target, position] = params.arguments.as_slice() else {
+                    return Ok(None);
+                };
+                let (Some(source), Some(target), Ok(position)) = (
+                    source
+                        .as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    target
+                        .as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    serde_json::from_value::<Position>(position.clone()),
+                ) else {
+                    return Ok(None);
+                };
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let edit = self
+                    .bind_vault(|vault| {
+                        let settings = settings.for_path(vault.root_dir(), &source);
+                        Ok(commands::link_bidirectional(
+                            vault, &settings, &source, &target, position,
+                        ))
+                    })
+                    .await?;
+
+                if let Some(edit) = edit {
+                    let _ = self.client.apply_edit(edit).await;
+                }
+
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"move_file" => {
+                let [uri, folder] = params.arguments.as_slice() else {
+                    return Ok(None);
+                };
+                let (Some(path), Some(folder)) = (
+                    uri.as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    folder.as_str(),
+                ) else {
+                    return Ok(None);
+                };
+
+                let edit = self
+                    .bind_vault(|vault| Ok(commands::move_file(vault, &path, Path::new(folder))))
+                    .await?;
+
+                if let Some(edit) = edit {
+                    let _ = self.client.apply_edit(edit).await;
+                }
+
+                Ok(None)
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.replaceInLinks" => {
+                let (Some(from), Some(to)) = (
+                    params.arguments.first().and_then(|val| val.as_str()),
+                    params.arguments.get(1).and_then(|val| val.as_str()),
+                ) else {
+                    return Ok(None);
+                };
+                let regex = params
+                    .arguments
+                    .get(2)
+                    .and_then(|val| val.as_bool())
+                    .unwrap_or(false);
+                // without `apply`, only the edit is returned, as a preview
+                let apply = params
+                    .arguments
+                    .get(3)
+                    .and_then(|val| val.as_bool())
+                    .unwrap_or(false);
+
+                let replacement = self
+                    .bind_vault(|vault| {
+                        commands::replace_in_links(vault, from, to, regex)
+                            .map_err(|err| Error::invalid_params(err.to_string()))
+                    })
+                    .await?;
+
+                if apply && replacement.edits > 0 {
+                    let _ = self.client.apply_edit(replacement.edit.clone()).await;
+                }
+                if apply {
+                    self.client
+                        .show_message(
+                            MessageType::INFO,
+                            format!("Replaced \"{}\" in {} links", from, replacement.edits),
+                        )
+                        .await;
+                }
+
+                Ok(serde_json::to_value(replacement).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"rename_heading" => {
+                let [uri, old, new] = params.arguments.as_slice() else {
+                    return Ok(None);
+                };
+                let (Some(path), Some(old), Some(new)) = (
+                    uri.as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    old.as_str(),
+                    new.as_str(),
+                ) else {
+                    return Ok(None);
+                };
+
+                let edit = self
+                    .bind_vault(|vault| {
+                        let path = vault.vault_path(&path);
+                        Ok(commands::rename_heading(vault, &path, old, new))
+                    })
+                    .await?;
+
+                match edit {
+                    Some(edit) => {
+                        let _ = self.client.apply_edit(edit).await;
+                        Ok(None)
+                    }
+                    None => Err(Error::invalid_params(format!(
+                        "no heading \"{}\" in {}",
+                        old,
+                        path.display()
+                    ))),
+                }
+            },
+            ExecuteCommandParams { command, .. } if *command == *"insert_link" => {
+                let [uri, position, query] = params.arguments.as_slice() else {
+                    return Ok(None);
+                };
+                let (Some(path), Ok(position), Some(query)) = (
+                    uri.as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    serde_json::from_value::<Position>(position.clone()),
+                    query.as_str(),
+                ) else {
+                    return Ok(None);
+                };
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let edit = self
+                    .bind_vault(|vault| {
+                        let settings = settings.for_path(vault.root_dir(), &path);
+                        Ok(commands::insert_link(
+                            vault, &settings, &path, position, query,
+                        ))
+                    })
+                    .await?;
+
+                if let Some(edit) = &edit {
+                    let _ = self.client.apply_edit(edit.clone()).await;
+                }
+
+                Ok(edit.and_then(|edit| serde_json::to_value(edit).ok()))
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.capture" => {
+                let Some(text) = params.arguments.first().and_then(|val| val.as_str()) else {
+                    return Err(Error::invalid_params("expected the text to capture"));
+                };
+                let target = params.arguments.get(1).and_then(|val| val.as_str());
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let edit = self
+                    .bind_vault(|vault| {
+                        if target.is_some_and(|target| {
+                            commands::vault_note_path(vault.root_dir(), target).is_none()
+                        }) {
+                            return Err(Error::invalid_params(
+                                "the note to capture to must be in the vault",
+                            ));
+                        }
+
+                        Ok(commands::capture(
+                            vault,
+                            &settings,
+                            text,
+                            target,
+                            chrono::Local::now().naive_local(),
+                        ))
+                    })
+                    .await?;
+                let Some(edit) = edit else {
+                    return Err(Error::invalid_params(
+                        "no note to capture to: pass one or set inbox_note",
+                    ));
+                };
+
+                // the client applies the edit
+                Ok(serde_json::to_value(edit).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.openOrCreate" => {
+                let [uri, position] = params.arguments.as_slice() else {
+                    return Err(Error::invalid_params("expected a file uri and a position"));
+                };
+                let (Some(path), Ok(position)) = (
+                    uri.as_str()
+                        .and_then(|uri| Url::parse(uri).ok()?.to_file_path().ok()),
+                    serde_json::from_value::<Position>(position.clone()),
+                ) else {
+                    return Err(Error::invalid_params("expected a file uri and a position"));
+                };
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let target = self
+                    .bind_vault(|vault| {
+                        let path = vault.vault_path(&path);
+                        Ok(commands::open_or_create(
+                            vault,
+                            &settings.for_path(vault.root_dir(), &path),
+                            &path,
+                            position,
+                        ))
+                    })
+                    .await?;
+                let Some((create, doc)) = target else {
+                    return Err(Error::invalid_params("no link to a note at the position"));
+                };
+
+                if let Some(edit) = create {
+                    // the edit's `CreateFile` also creates the folders of links with a path that
+                    // don't exist yet; the note is only shown once the client has created it
+                    let applied = self
+                        .client
+                        .apply_edit(edit)
+                        .await
+                        .is_ok_and(|response| response.applied);
+                    if !applied {
+                        return Err(Error {
+                            code: ErrorCode::InternalError,
+                            message: "The client didn't create the note".into(),
+                            data: None,
+                        });
+                    }
+                }
+
+                let uri = doc.uri.clone();
+                self.client.show_document(doc).await?;
+                Ok(serde_json::to_value(uri).ok())
+            },
+            ExecuteCommandParams { command, .. } if *command == *"moxide.toggleTask" => {
+                // the line, or the first and last lines, of the tasks
+                let (Some(path), Some(first)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(|uri| Url::parse(uri.as_str()?).ok()?.to_file_path().ok()),
+                    params.arguments.get(1).and_then(|line| line.as_u64()),
+                ) else {
+                    return Err(Error::invalid_params("expected a file uri and a line"));
+                };
+                let last = params
+                    .arguments
+                    .get(2)
+                    .and_then(|line| line.as_u64())
+                    .unwrap_or(first);
+
+                let settings = self
+                    .bind_settings(|settings| Ok(settings.to_owned()))
+                    .await?;
+                let edit = self
+                    .bind_vault(|vault| {
+                        let path = vault.vault_path(&path);
+                        Ok(commands::toggle_tasks(
+                            vault,
+                            &settings.for_path(vault.root_dir(), &path),
+                            &path,
+                            first as u32..=last as u32,
+                        ))
+                    })
+                    .await?;
+                let Some(edit) = edit else {
+                    return Err(Error::invalid_params("no task on the lines"));
+                };
+
+                let _ = self.client.apply_edit(edit.clone()).await;
+                Ok(serde_json::to_value(edit).ok())
+            },
             _ => Ok(None),
         }
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        route_to_folder!(
+            self,
+            params.text_document_position_params.text_document.uri,
+            hover(params)
+        );
+
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
         self.bind_vault(|vault| {
-            let path = params_path!(params.text_document_position_params)?;
-            Ok(hover::hover(vault, &params, &path))
+            let path = vault.vault_path(&params_path!(params.text_document_position_params)?);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(hover::hover(vault, &settings, &params, &path))
         })
         .await
     }
@@ -612,9 +1815,60 @@ impl LanguageServer for Backend {
         &self,
         params: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
+        route_to_folder!(self, params.text_document.uri, document_symbol(params));
+
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
         self.bind_vault(|vault| {
-            let path = params_path!(params)?;
-            Ok(document_symbol(vault, &params, &path))
+            let path = vault.vault_path(&params_path!(params)?);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(document_symbol(vault, &settings, &params, &path))
+        })
+        .await
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        route_to_folder!(self, params.text_document.uri, folding_range(params));
+
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&params_path!(params)?);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(folding_range(vault, &settings, &path))
+        })
+        .await
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        route_to_folder!(self, params.text_document.uri, formatting(params));
+
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&params_path!(params)?);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(formatting(vault, &settings, &path))
+        })
+        .await
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        route_to_folder!(self, params.text_document.uri, selection_range(params));
+
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&params_path!(params)?);
+            Ok(selection_range(vault, &path, &params))
+        })
+        .await
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        route_to_folder!(self, params.text_document.uri, document_link(params));
+
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&params_path!(params)?);
+            Ok(document_link(vault, &path))
         })
         .await
     }
@@ -624,22 +1878,63 @@ impl LanguageServer for Backend {
         params: WorkspaceSymbolParams,
     ) -> Result<Option<Vec<SymbolInformation>>> {
         let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
-        self.bind_vault(|vault| Ok(workspace_symbol(&settings, vault, &params)))
-            .await
+        let progress = match params.work_done_progress_params.work_done_token.clone() {
+            Some(token) => Some(
+                self.client
+                    .progress(token, "Searching symbols…")
+                    .begin()
+                    .await,
+            ),
+            None => None,
+        };
+
+        let symbols = self.search_symbols(&params).await;
+        let folders = self.folders.read().await.clone();
+        let symbols = match folders.is_empty() {
+            true => symbols,
+            // with several workspace folders, symbols are told apart by the folder they are in
+            false => {
+                let mut all = self.folder_symbols(symbols?).await;
+                for folder in folders {
+                    let symbols = folder.search_symbols(&params).await.unwrap_or_default();
+                    all.extend(folder.folder_symbols(symbols).await);
+                }
+
+                Ok(best_symbols(&settings, &params.query, all))
+            }
+        };
+
+        if let Some(progress) = progress {
+            progress.finish().await;
+        }
+
+        symbols.map(Some)
     }
 
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        route_to_folder!(
+            self,
+            params.text_document_position.text_document.uri,
+            rename(params)
+        );
+
         self.bind_vault(|vault| {
-            let path = params_position_path!(params)?;
+            let path = vault.vault_path(&params_position_path!(params)?);
             Ok(rename::rename(vault, &params, &path))
         })
         .await
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        route_to_folder!(self, params.text_document.uri, code_action(params));
+
+        let settings = self
+            .bind_settings(|settings| Ok(settings.to_owned()))
+            .await?;
         self.bind_vault(|vault| {
-            let path = params_path!(params)?;
-            Ok(codeactions::code_actions(vault, &params, &path))
+            let path = vault.vault_path(&params_path!(params)?);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(codeactions::code_actions(vault, &settings, &params, &path))
         })
         .await
     }
@@ -648,6 +1943,8 @@ impl LanguageServer for Backend {
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
+        route_to_folder!(self, params.text_document.uri, semantic_tokens_full(params));
+
         let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
 
         let timer = std::time::Instant::now();
@@ -655,6 +1952,8 @@ impl LanguageServer for Backend {
         let path = params_path!(params)?;
         let res = self
             .bind_vault(|vault| {
+                let path = vault.vault_path(&path);
+                let settings = settings.for_path(vault.root_dir(), &path);
                 Ok(tokens::semantic_tokens_full(
                     vault, &path, params, &settings,
                 ))
@@ -672,6 +1971,29 @@ impl LanguageServer for Backend {
 
         return res;
     }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        route_to_folder!(
+            self,
+            params.text_document.uri,
+            semantic_tokens_range(params)
+        );
+
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+
+        let path = params_path!(params)?;
+        self.bind_vault(|vault| {
+            let path = vault.vault_path(&path);
+            let settings = settings.for_path(vault.root_dir(), &path);
+            Ok(tokens::semantic_tokens_range(
+                vault, &path, params, &settings,
+            ))
+        })
+        .await
+    }
 }
 
 #[tokio::main]
@@ -683,7 +2005,14 @@ async fn main() {
         client,
         vault: Arc::new(None.into()),
         opened_files: Arc::new(HashSet::new().into()),
+        open_texts: Arc::new(HashMap::new().into()),
         settings: Arc::new(None.into()),
+        capabilities: Arc::new(None.into()),
+        root_dir: Arc::new(None.into()),
+        indexing: Arc::new(IndexProgress::default()),
+        folders: Arc::new(Vec::new().into()),
+        workspace_folders: Arc::new(Vec::new().into()),
+        joined_folders: Arc::new(Vec::new().into()),
     });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
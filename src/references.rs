@@ -3,42 +3,148 @@ use std::path::Path;
 use itertools::Itertools;
 use tower_lsp::lsp_types::{Location, Position, Url};
 
-use crate::vault::{Referenceable, Vault};
+use crate::gotodef::referenceable_location;
+use crate::vault::{Reference, Referenceable, Vault};
 
-pub fn references(vault: &Vault, cursor_position: Position, path: &Path) -> Option<Vec<Location>> {
-    let references = match (
+/// Every link, embed, and alias link pointing at the referenceable under the cursor (or at what the
+/// reference under the cursor links to); with `include_declaration`, the referenceables themselves
+/// come first
+pub fn references(
+    vault: &Vault,
+    cursor_position: Position,
+    path: &Path,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    let referenceables = match (
         vault.select_referenceable_at_position(path, cursor_position),
         vault.select_reference_at_position(path, cursor_position),
     ) {
         (Some(referenceable @ Referenceable::Tag(..)), Some(_)) | (Some(referenceable), None) => {
-            vault.select_references_for_referenceable(&referenceable)
+            vec![referenceable]
         }
-        (_, Some(reference)) => {
-            let referenceables = vault.select_referenceables_for_reference(reference, path);
-            let references = referenceables
-                .into_iter()
-                .filter_map(|referenceable| {
-                    vault.select_references_for_referenceable(&referenceable)
-                }) // drop the Nones on the options
-                .flatten()
-                .collect_vec();
-
-            Some(references)
-        }
-        (None, None) => None,
-    }?;
+        (_, Some(reference)) => vault.select_referenceables_for_reference(reference, path),
+        (None, None) => return None,
+    };
+
+    let references = referenceables
+        .iter()
+        .filter_map(|referenceable| {
+            let references = vault.select_references_for_referenceable(referenceable)?;
+            Some(
+                references
+                    .into_iter()
+                    .chain(alias_references(vault, referenceable)),
+            )
+        }) // drop the Nones on the options
+        .flatten()
+        .unique_by(|(path, reference)| (*path, reference.data().range))
+        .filter_map(|link| {
+            Url::from_file_path(link.0)
+                .map(|good| Location {
+                    uri: good,
+                    range: *link.1.data().range, // TODO: Why can't I use .into() here?
+                })
+                .ok()
+        });
+
+    let declarations = referenceables
+        .iter()
+        .filter(|_| include_declaration)
+        .filter(|referenceable| !referenceable.is_unresolved())
+        .filter_map(referenceable_location);
+
+    Some(declarations.chain(references).collect())
+}
+
+/// File links whose text is one of the file's aliases, for links that don't resolve to another file
+fn alias_references<'a>(
+    vault: &'a Vault,
+    referenceable: &Referenceable,
+) -> Vec<(&'a Path, &'a Reference)> {
+    let Referenceable::File(_, mdfile) = referenceable else {
+        return vec![];
+    };
+    let Some(aliases) = mdfile.metadata.as_ref().map(|metadata| metadata.aliases()) else {
+        return vec![];
+    };
+
+    let index = vault.resolution_index();
+    vault
+        .select_references(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(path, reference)| match reference {
+            Reference::WikiFileLink(data) | Reference::MDFileLink(data) => {
+                aliases.contains(&data.reference_text)
+                    && index.linked_file(reference, path).is_none()
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::Position;
+
+    use crate::config::Settings;
+    use crate::vault::Vault;
 
-    Some(
-        references
+    use super::references;
+
+    #[test]
+    fn test_references_include_embeds_and_aliases() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "---\naliases: [\"Other Name\"]\n---\n# Note\n"),
+                (
+                    "links.md",
+                    "[[note]]\n\n![[note]]\n\n[md](note)\n\n[[Other Name]]\n\n[[note#Note]]\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let lines = |include_declaration| {
+            references(
+                &vault,
+                Position {
+                    line: 0,
+                    character: 3,
+                },
+                &path,
+                include_declaration,
+            )
+            .unwrap()
             .into_iter()
-            .filter_map(|link| {
-                Url::from_file_path(link.0)
-                    .map(|good| Location {
-                        uri: good,
-                        range: *link.1.data().range, // TODO: Why can't I use .into() here?
-                    })
-                    .ok()
+            .map(|location| {
+                (
+                    location.uri.to_file_path().unwrap(),
+                    location.range.start.line,
+                )
             })
-            .collect::<Vec<_>>(),
-    )
+            .sorted()
+            .collect_vec()
+        };
+
+        assert_eq!(
+            lines(false),
+            vec![
+                (path.clone(), 0),
+                (path.clone(), 2),
+                (path.clone(), 4),
+                (path.clone(), 6),
+                (path.clone(), 8)
+            ]
+        );
+        assert_eq!(lines(true).len(), 6);
+        assert!(lines(true).contains(&(root_dir.join("note.md"), 0)));
+    }
 }
@@ -1,21 +1,32 @@
 use std::iter;
 use std::path::Path;
 
+use itertools::Itertools;
 use tower_lsp::lsp_types::{
     DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
     RenameFile, RenameParams, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
 };
 
+use crate::util::slugify;
 use crate::vault::{MDHeading, Reference, Referenceable, Vault};
 
 pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<WorkspaceEdit> {
     let position = params.text_document_position.position;
     let referenceable = vault.select_referenceable_at_position(path, position)?;
 
+    rename_referenceable(vault, &referenceable, &params.new_name)
+}
+
+/// Rename the referenceable to `new_name` and rewrite every reference to it
+pub fn rename_referenceable(
+    vault: &Vault,
+    referenceable: &Referenceable,
+    new_name: &str,
+) -> Option<WorkspaceEdit> {
     let (referenceable_document_change, new_ref_name): (Option<DocumentChangeOperation>, String) =
-        match referenceable {
+        match *referenceable {
             Referenceable::Heading(path, heading) => {
-                let new_text = format!("{} {}", "#".repeat(heading.level.0), params.new_name); // move this obsidian syntax specific stuff to the vault
+                let new_text = format!("{} {}", "#".repeat(heading.level.0), new_name); // move this obsidian syntax specific stuff to the vault
 
                 let change_op = DocumentChangeOperation::Edit(TextDocumentEdit {
                     text_document: tower_lsp::lsp_types::OptionalVersionedTextDocumentIdentifier {
@@ -31,7 +42,7 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 let name = Referenceable::Heading(
                     path,
                     &MDHeading {
-                        heading_text: params.new_name.clone(),
+                        heading_text: new_name.to_string(),
                         ..heading.clone()
                     },
                 )
@@ -40,7 +51,8 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 (Some(change_op), name.to_string())
             }
             Referenceable::File(path, file) => {
-                let new_path = path.with_file_name(&params.new_name).with_extension("md");
+                // not `with_extension`, which would cut a name like `v1.2 Release` at its last `.`
+                let new_path = path.with_file_name(format!("{new_name}.md"));
 
                 let change_op = DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
                     old_uri: Url::from_file_path(path).ok()?,
@@ -54,7 +66,7 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 (Some(change_op), name.to_string())
             }
             Referenceable::Tag(_path, _tag) => {
-                let new_ref_name = params.new_name.clone();
+                let new_ref_name = new_name.to_string();
 
                 let _new_tag = format!("#{}", new_ref_name);
 
@@ -63,10 +75,40 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
             _ => return None,
         };
 
-    let references = vault.select_references_for_referenceable(&referenceable)?;
+    let references = vault.select_references_for_referenceable(referenceable)?;
+
+    // links to the blocks under a heading, `[[note#Heading#^block]]`, name the heading too
+    let scoped_block_links = match referenceable {
+        Referenceable::Heading(heading_path, heading) => {
+            let index = vault.resolution_index();
+            vault
+                .select_references(None)?
+                .into_iter()
+                .filter(|(path, reference)| {
+                    reference
+                        .block_scope()
+                        .is_some_and(|scope| vault.headings_match(scope, &heading.heading_text))
+                        && index
+                            .linked_file(reference, path)
+                            .is_some_and(|(target, _)| target == *heading_path)
+                })
+                .collect()
+        }
+        _ => vec![],
+    };
 
     let references_changes = references
         .into_iter()
+        .chain(scoped_block_links)
+        // links by the note's id still resolve to it under its new name
+        .filter(|(_, reference)| match referenceable {
+            Referenceable::File(path, _) => {
+                let text = &reference.data().reference_text;
+                let file_ref = text.split_once('#').map_or(text.as_str(), |(file, _)| file);
+                !vault.is_note_id(file_ref, path)
+            }
+            _ => true,
+        })
         .filter_map(|(path, reference)| {
             // update references
 
@@ -100,6 +142,11 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 | Reference::WikiIndexedBlockLink(data, _file, infile)
                     if matches!(referenceable, Referenceable::File(..)) =>
                 {
+                    // keeps the `^` of blocks and the heading of blocks under headings
+                    let infile = data
+                        .reference_text
+                        .split_once('#')
+                        .map_or(infile.as_str(), |(_, infile)| infile);
                     let new_text = format!(
                         "[[{}#{}{}]]",
                         new_ref_name,
@@ -122,33 +169,24 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                         })],
                     })
                 }
-                Reference::WikiHeadingLink(data, _file, _heading)
+                Reference::WikiHeadingLink(..)
+                | Reference::MDHeadingLink(..)
+                | Reference::WikiIndexedBlockLink(..)
+                | Reference::MDIndexedBlockLink(..)
                     if matches!(referenceable, Referenceable::Heading(..)) =>
                 {
-                    let new_text = format!(
-                        "[[{}{}]]",
-                        new_ref_name,
-                        data.display_text
-                            .as_ref()
-                            .map(|text| format!("|{text}"))
-                            .unwrap_or_else(|| String::from(""))
-                    );
-
-                    Some(TextDocumentEdit {
-                        text_document:
-                            tower_lsp::lsp_types::OptionalVersionedTextDocumentIdentifier {
-                                uri: Url::from_file_path(path).ok()?,
-                                version: None,
-                            },
-                        edits: vec![OneOf::Left(TextEdit {
-                            range: *data.range,
-                            new_text,
-                        })],
-                    })
+                    heading_link_edit(vault, path, reference, referenceable, new_name)
                 }
                 Reference::Tag(data) => {
+                    // frontmatter tags may be written without the `#`
+                    let hash = vault
+                        .select_line(path, data.range.start.line as isize)
+                        .and_then(|line| line.get(data.range.start.character as usize).copied())
+                        .filter(|c| *c == '#')
+                        .map_or("", |_| "#");
                     let new_text = format!(
-                        "#{}",
+                        "{}{}",
+                        hash,
                         data.reference_text.replacen(
                             &*referenceable.get_refname(vault.root_dir())?,
                             &new_ref_name,
@@ -194,6 +232,10 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 | Reference::MDIndexedBlockLink(data, _file, infile)
                     if matches!(referenceable, Referenceable::File(..)) =>
                 {
+                    let infile = data
+                        .reference_text
+                        .split_once('#')
+                        .map_or(infile.as_str(), |(_, infile)| infile);
                     let new_text = format!(
                         "[{}]({}#{})",
                         data.display_text
@@ -216,30 +258,6 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                         })],
                     })
                 }
-                Reference::WikiHeadingLink(data, _file, _heading)
-                    if matches!(referenceable, Referenceable::Heading(..)) =>
-                {
-                    let new_text = format!(
-                        "[{}]({})",
-                        data.display_text
-                            .as_ref()
-                            .map(|text| format!("|{text}"))
-                            .unwrap_or_else(|| String::from("")),
-                        new_ref_name,
-                    );
-
-                    Some(TextDocumentEdit {
-                        text_document:
-                            tower_lsp::lsp_types::OptionalVersionedTextDocumentIdentifier {
-                                uri: Url::from_file_path(path).ok()?,
-                                version: None,
-                            },
-                        edits: vec![OneOf::Left(TextEdit {
-                            range: *data.range,
-                            new_text,
-                        })],
-                    })
-                }
                 Reference::MDHeadingLink(_, _, _) => None,
                 Reference::MDIndexedBlockLink(_, _, _) => None,
                 Reference::WikiFileLink(..) => None,
@@ -248,6 +266,7 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 Reference::MDFileLink(..) => None,
                 Reference::Footnote(..) => None,
                 Reference::LinkRef(_) => None,
+                Reference::AttachmentLink(_) => None,
             }
         })
         .map(DocumentChangeOperation::Edit);
@@ -261,3 +280,51 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
         ..Default::default()
     })
 }
+
+/// Rewrite the heading the link names, `Heading` in `[[note#Heading|display]]` or
+/// `[[note#Heading#^block]]`, to `new_name`, leaving the rest of the link as written; headings
+/// written as slugs stay slugs
+fn heading_link_edit(
+    vault: &Vault,
+    path: &Path,
+    reference: &Reference,
+    referenceable: &Referenceable,
+    new_name: &str,
+) -> Option<TextDocumentEdit> {
+    let Referenceable::Heading(_, heading) = referenceable else {
+        return None;
+    };
+    let link_heading = match reference {
+        Reference::WikiHeadingLink(.., link_heading)
+        | Reference::MDHeadingLink(.., link_heading) => link_heading.as_str(),
+        _ => reference.block_scope()?,
+    };
+
+    let new_heading = match link_heading != heading.heading_text
+        && link_heading == slugify(&heading.heading_text)
+    {
+        true => slugify(new_name),
+        false => new_name.to_string(),
+    };
+
+    let range = reference.data().range;
+    let line = vault
+        .ropes
+        .get(path)?
+        .get_line(range.start.line as usize)?
+        .chars()
+        .collect_vec();
+    let text =
+        String::from_iter(line.get(range.start.character as usize..range.end.character as usize)?);
+
+    Some(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: Url::from_file_path(path).ok()?,
+            version: None,
+        },
+        edits: vec![OneOf::Left(TextEdit {
+            range: *range,
+            new_text: text.replacen(&format!("#{link_heading}"), &format!("#{new_heading}"), 1),
+        })],
+    })
+}
@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::{Position, Range, SelectionRange, SelectionRangeParams};
+
+use crate::vault::{Rangeable, Vault};
+
+/// For each position, the nested ranges that expanding the selection goes through: the word, the
+/// link or other inline element, the paragraph, each heading section around it, and the document
+pub fn selection_range(
+    vault: &Vault,
+    path: &Path,
+    params: &SelectionRangeParams,
+) -> Option<Vec<SelectionRange>> {
+    let rope = vault.ropes.get(path)?;
+    let headings = vault.select_headings(path)?;
+    let references = vault.select_references(Some(path))?;
+
+    let lines = rope
+        .lines()
+        .map(|line| line.to_string().trim_end_matches(['\r', '\n']).to_string())
+        .collect::<Vec<_>>();
+    let line_end = |line: usize| {
+        Position::new(
+            line as u32,
+            lines.get(line).map_or(0, |text| text.chars().count()) as u32,
+        )
+    };
+    let is_heading = |line: usize| {
+        headings
+            .iter()
+            .any(|heading| heading.range().start.line as usize == line)
+    };
+
+    let document = Range::new(Position::new(0, 0), line_end(lines.len().saturating_sub(1)));
+
+    let ranges = params.positions.iter().map(|&position| {
+        let line = position.line as usize;
+        let chars = lines
+            .get(line)
+            .map(|text| text.chars().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let character = (position.character as usize).min(chars.len());
+        let word_start = character
+            - chars[..character]
+                .iter()
+                .rev()
+                .take_while(|c| is_word(c))
+                .count();
+        let word_end = character + chars[character..].iter().take_while(|c| is_word(c)).count();
+        let word = (word_start < word_end).then(|| {
+            Range::new(
+                Position::new(position.line, word_start as u32),
+                Position::new(position.line, word_end as u32),
+            )
+        });
+
+        // links include their brackets and display text
+        let inline = references
+            .iter()
+            .map(|(_, reference)| *reference.data().range)
+            .find(|range| range.start <= position && position <= range.end);
+
+        // the lines around the position up to blank lines and headings; a heading is its own paragraph
+        let paragraph = lines
+            .get(line)
+            .filter(|text| !text.trim().is_empty())
+            .map(|_| {
+                let in_paragraph =
+                    |line: &usize| !lines[*line].trim().is_empty() && !is_heading(*line);
+                let (first, last) = match is_heading(line) {
+                    true => (line, line),
+                    false => (
+                        (0..line)
+                            .rev()
+                            .take_while(in_paragraph)
+                            .last()
+                            .unwrap_or(line),
+                        (line + 1..lines.len())
+                            .take_while(in_paragraph)
+                            .last()
+                            .unwrap_or(line),
+                    ),
+                };
+
+                Range::new(Position::new(first as u32, 0), line_end(last))
+            });
+
+        // from the innermost heading out; a section ends at the next heading of the same or higher level
+        let sections = headings
+            .iter()
+            .enumerate()
+            .filter(|(_, heading)| heading.range().start.line <= position.line)
+            .map(|(i, heading)| {
+                let end = headings[i + 1..]
+                    .iter()
+                    .find(|next| next.level.0 <= heading.level.0)
+                    .map_or(document.end, |next| {
+                        Position::new(next.range().start.line, 0)
+                    });
+
+                Range::new(Position::new(heading.range().start.line, 0), end)
+            })
+            .filter(|section| position < section.end || section.end == document.end)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev();
+
+        let ranges = word
+            .into_iter()
+            .chain(inline)
+            .chain(paragraph)
+            .chain(sections)
+            .chain(Some(document))
+            .fold(Vec::<Range>::new(), |mut ranges, range| {
+                // each range strictly contains the last
+                let contains = |inner: &Range| {
+                    range.start <= inner.start && inner.end <= range.end && range != *inner
+                };
+                if ranges.last().is_none_or(contains) {
+                    ranges.push(range);
+                }
+                ranges
+            });
+
+        ranges
+            .into_iter()
+            .rev()
+            .fold(None, |parent, range| {
+                Some(SelectionRange {
+                    range,
+                    parent: parent.map(Box::new),
+                })
+            })
+            .unwrap_or(SelectionRange {
+                range: document,
+                parent: None,
+            })
+    });
+
+    Some(ranges.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        PartialResultParams, Position, Range, SelectionRange, SelectionRangeParams,
+        TextDocumentIdentifier, Url, WorkDoneProgressParams,
+    };
+
+    use crate::{config::Settings, vault::Vault};
+
+    use super::selection_range;
+
+    #[test]
+    fn test_selection_range() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "note.md",
+                "# Title\n\nIntro\n\n## Part\n\nSee [[other note|the other]] here\nand more\n\n## Next\n\nText\n",
+            )],
+        );
+
+        let path = root_dir.join("note.md");
+        let params = SelectionRangeParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&path).unwrap(),
+            },
+            positions: vec![Position::new(6, 10)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        let mut ranges = vec![];
+        let mut selection = selection_range(&vault, &path, &params)
+            .unwrap()
+            .into_iter()
+            .next();
+        while let Some(SelectionRange { range, parent }) = selection {
+            ranges.push(range);
+            selection = parent.map(|parent| *parent);
+        }
+
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+        };
+        assert_eq!(
+            ranges,
+            vec![
+                range((6, 6), (6, 11)), // the word
+                range((6, 4), (6, 28)), // the link
+                range((6, 0), (7, 8)),  // the paragraph
+                range((4, 0), (9, 0)),  // ## Part
+                range((0, 0), (12, 0)), // # Title, to the end of the document
+            ]
+        );
+    }
+}
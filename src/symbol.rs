@@ -1,25 +1,47 @@
 use chrono::{Duration, NaiveDate};
-use std::{iter, path::Path};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    iter,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use tower_lsp::lsp_types::{
-    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Location, SymbolInformation,
-    SymbolKind, Url, WorkspaceSymbolParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, FoldingRange, FoldingRangeKind,
+    Location, Position, Range, SymbolInformation, SymbolKind, Url, WorkspaceSymbolParams,
 };
 
 use crate::{
+    completion::matcher::{fuzzy_match, Matchable},
     config::Settings,
-    vault::{MDHeading, Referenceable, Vault},
+    daily::{notebooks, relative_date_string, Notebook},
+    util::strip_markdown,
+    vault::{frontmatter_len, MDHeading, Rangeable, Referenceable, Vault},
 };
 
+/// The symbols best matching the query, at most `workspace_symbol_limit` of them; `None` once
+/// `cancelled` is set, as when the client cancels the request
 pub fn workspace_symbol(
     settings: &Settings,
     vault: &Vault,
-    _params: &WorkspaceSymbolParams,
+    params: &WorkspaceSymbolParams,
+    cancelled: &AtomicBool,
 ) -> Option<Vec<SymbolInformation>> {
+    // headings are named by their path through the note's outline, so that searches match it
+    let breadcrumbs = vault
+        .md_files
+        .iter()
+        .map(|(path, file)| (path.as_path(), heading_breadcrumbs(&file.headings)))
+        .collect::<HashMap<_, _>>();
+
     let referenceables = vault.select_referenceable_nodes(None);
     let mut symbol_informations = referenceables
         .into_iter()
+        .take_while(|_| !cancelled.load(Ordering::Relaxed))
         .flat_map(|referenceable| {
             let range = match referenceable {
                 Referenceable::File(..) => tower_lsp::lsp_types::Range {
@@ -35,62 +57,78 @@ pub fn workspace_symbol(
                 _ => *referenceable.get_range()?,
             };
 
+            // files are grouped by their folder, and everything in a file by the file
+            let container_name = match referenceable {
+                Referenceable::File(path, _) => path
+                    .parent()
+                    .and_then(|folder| folder.strip_prefix(vault.root_dir()).ok())
+                    .map(|folder| folder.to_string_lossy().replace('\\', "/"))
+                    .filter(|folder| !folder.is_empty()),
+                _ if settings.workspace_symbol_detail => {
+                    symbol_detail(vault, &referenceable, range.start.line)
+                }
+                _ => referenceable
+                    .get_path()
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string()),
+            };
+
             Some(SymbolInformation {
-                name: referenceable.get_refname(vault.root_dir())?.to_string(),
-                kind: match referenceable {
-                    Referenceable::File(_, _) => SymbolKind::FILE,
-                    Referenceable::Tag(_, _) => SymbolKind::CONSTANT,
-                    _ => SymbolKind::KEY,
+                name: match referenceable {
+                    Referenceable::File(_, mdfile) => mdfile.title(),
+                    Referenceable::Heading(path, heading) => {
+                        match breadcrumbs.get(path.as_path()).and_then(|breadcrumbs| {
+                            breadcrumbs
+                                .iter()
+                                .find(|(range, _)| *range == *heading.range)
+                        }) {
+                            Some((_, breadcrumb)) => {
+                                format!("{} > {}", path.file_stem()?.to_string_lossy(), breadcrumb)
+                            }
+                            None => referenceable.get_refname(vault.root_dir())?.to_string(),
+                        }
+                    }
+                    _ => referenceable.get_refname(vault.root_dir())?.to_string(),
                 },
+                kind: symbol_kind(&referenceable),
                 location: Location {
                     uri: Url::from_file_path(referenceable.get_path()).ok()?,
                     range,
                 },
-                container_name: None,
+                container_name,
                 tags: None,
                 deprecated: None,
             })
         })
         .collect_vec();
 
-    fn date_to_filename(settings: &Settings, date: NaiveDate) -> String {
-        date.format(settings.dailynote.as_str()).to_string()
-    }
-
-    fn relative_date_string(date: NaiveDate) -> Option<String> {
-        let today = chrono::Local::now().date_naive();
-
-        if today == date {
-            Some("today".to_string())
-        } else {
-            match (date - today).num_days() {
-                1 => Some("tomorrow".to_string()),
-                2..=7 => Some(format!("next {}", date.format("%A"))),
-                -1 => Some("yesterday".to_string()),
-                -7..=-1 => Some(format!("last {}", date.format("%A"))),
-                _ => None,
-            }
-        }
-    }
-
-    fn date_to_match_string(settings: &Settings, date: NaiveDate) -> Option<String> {
-        let refname = date_to_filename(settings, date);
-        format!("{}: {}", relative_date_string(date)?, refname).into()
-    }
-
     let today = chrono::Local::now().date_naive();
-    let days = (-7..=7)
-        .flat_map(|i| Some(today + Duration::try_days(i)?))
+    let date_to_match_string = |notebook: &Notebook, date: NaiveDate| -> Option<String> {
+        let refname = notebook.date_to_filename(date);
+        format!(
+            "{} ({}): {}",
+            relative_date_string(settings, today, date)?,
+            notebook.name,
+            refname
+        )
+        .into()
+    };
+
+    let days = notebooks(settings)
+        .into_iter()
+        .flat_map(|notebook| {
+            (-7..=7).flat_map(move |i| Some((notebook.clone(), today + Duration::try_days(i)?)))
+        })
         // .flat_map(|date| relative_date_string(date))
         // TODO: this filters out duplicates, which may not actually be desirable here?
         // .filter(|date| !refnames.contains(&date.ref_name))
         // TODO: collect Symbol information here
-        .filter_map(|date| {
+        .filter_map(|(notebook, date)| {
             Some(SymbolInformation {
-                name: date_to_match_string(settings, date)?,
+                name: date_to_match_string(&notebook, date)?,
                 kind: SymbolKind::FILE,
                 location: Location {
-                    uri: Url::from_file_path(date_to_filename(settings, date)).ok()?,
+                    uri: Url::from_file_path(notebook.date_to_path(vault.root_dir(), date)).ok()?,
                     range: tower_lsp::lsp_types::Range {
                         start: tower_lsp::lsp_types::Position {
                             line: 0,
@@ -102,36 +140,290 @@ pub fn workspace_symbol(
                         },
                     },
                 },
-                container_name: None,
+                container_name: Some(notebook.folder.clone()).filter(|folder| !folder.is_empty()),
                 tags: None,
                 deprecated: None,
             })
         });
 
     symbol_informations.extend(days);
-    Some(symbol_informations)
+    if cancelled.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(best_symbols(settings, &params.query, symbol_informations))
+}
+
+/// The symbols best matching the query, in order, at most `workspace_symbol_limit` of them
+pub fn best_symbols(
+    settings: &Settings,
+    query: &str,
+    symbols: Vec<SymbolInformation>,
+) -> Vec<SymbolInformation> {
+    let symbols = match query.trim() {
+        "" => symbols,
+        query => fuzzy_match(query, symbols)
+            .into_iter()
+            .map(|(symbol, _)| symbol)
+            .collect(),
+    };
+
+    symbols
+        .into_iter()
+        .take(settings.workspace_symbol_limit)
+        .collect()
+}
+
+impl Matchable for SymbolInformation {
+    fn match_string(&self) -> &str {
+        &self.name
+    }
 }
 
 pub fn document_symbol(
     vault: &Vault,
+    settings: &Settings,
     _params: &DocumentSymbolParams,
     path: &Path,
 ) -> Option<DocumentSymbolResponse> {
     let headings = vault.select_headings(path)?;
+    let mut symbols = construct_tree(headings)
+        .map(map_to_lsp_tree)
+        .unwrap_or_default();
 
-    let tree = construct_tree(headings)?;
-    let lsp = map_to_lsp_tree(tree);
+    // outline notes have a title at most; their list items nest under it
+    if settings.list_outline_symbols && headings.len() <= 1 {
+        let (before, after): (Vec<_>, Vec<_>) =
+            list_outline(vault, path).into_iter().partition(|item| {
+                symbols
+                    .first()
+                    .is_none_or(|title| item.range.start.line < title.range.start.line)
+            });
+
+        match symbols.first_mut() {
+            Some(title) if !after.is_empty() => {
+                title.children.get_or_insert_with(Vec::new).extend(after)
+            }
+            _ => symbols.extend(after),
+        }
+        symbols.splice(0..0, before);
+    }
+
+    match symbols.is_empty() {
+        true => None,
+        false => Some(DocumentSymbolResponse::Nested(symbols)),
+    }
+}
+
+/// The note's path from the vault root, without its extension, and for symbols other than
+/// headings, the text of their line, shortened to `DETAIL_LENGTH` characters
+fn symbol_detail(vault: &Vault, referenceable: &Referenceable, line: u32) -> Option<String> {
+    const DETAIL_LENGTH: usize = 60;
+
+    let path = referenceable.get_path();
+    let relative = path.with_extension("");
+    let relative = relative
+        .strip_prefix(vault.root_dir())
+        .ok()?
+        .to_string_lossy()
+        .replace('\\', "/");
+    if matches!(referenceable, Referenceable::Heading(..)) {
+        return Some(relative);
+    }
+
+    let text = String::from_iter(vault.select_line(path, line as isize)?);
+    let text = text.split_whitespace().join(" ");
+    let detail = match text.chars().count() > DETAIL_LENGTH {
+        true => format!("{}…", text.chars().take(DETAIL_LENGTH).collect::<String>()),
+        false => text,
+    };
 
-    Some(DocumentSymbolResponse::Nested(lsp))
+    Some(format!("{}: {}", relative, detail))
+}
+
+/// The kind of symbol for the referenceable, so that pickers show meaningful icons
+fn symbol_kind(referenceable: &Referenceable) -> SymbolKind {
+    match referenceable {
+        Referenceable::File(..) | Referenceable::Attachment(..) => SymbolKind::FILE,
+        Referenceable::Tag(..) => SymbolKind::CONSTANT,
+        Referenceable::Heading(_, heading) => heading_kind(heading.level.0),
+        Referenceable::Footnote(..) => SymbolKind::STRING,
+        _ => SymbolKind::KEY,
+    }
+}
+
+/// Title headings are namespaces for the structs under them
+fn heading_kind(level: usize) -> SymbolKind {
+    match level {
+        1 => SymbolKind::NAMESPACE,
+        _ => SymbolKind::STRUCT,
+    }
+}
+
+/// Folding ranges for heading sections, up to their last line that isn't blank, for fenced code
+/// blocks, and when `list_outline_symbols` is on, for list items with nested items
+pub fn folding_range(vault: &Vault, settings: &Settings, path: &Path) -> Option<Vec<FoldingRange>> {
+    let (file, rope) = (vault.md_files.get(path)?, vault.ropes.get(path)?);
+
+    fn fold(start_line: u32, end_line: u32) -> Option<FoldingRange> {
+        (start_line < end_line).then(|| FoldingRange {
+            start_line,
+            end_line,
+            kind: Some(FoldingRangeKind::Region),
+            ..Default::default()
+        })
+    }
+
+    fn list_ranges(symbols: Vec<DocumentSymbol>) -> Vec<FoldingRange> {
+        symbols
+            .into_iter()
+            .flat_map(|symbol| {
+                fold(symbol.range.start.line, symbol.range.end.line)
+                    .into_iter()
+                    .chain(list_ranges(symbol.children.unwrap_or_default()))
+            })
+            .collect()
+    }
+
+    let sections = file.headings.iter().filter_map(|heading| {
+        let end = vault
+            .select_heading_section(path, heading)?
+            .rev()
+            .find(|line| {
+                rope.get_line(*line)
+                    .is_some_and(|text| text.chars().any(|c| !c.is_whitespace()))
+            })?;
+        fold(heading.range.start.line, end as u32)
+    });
+    let code_blocks = file
+        .codeblocks
+        .iter()
+        .filter_map(|codeblock| fold(codeblock.range().start.line, codeblock.range().end.line));
+    let lists = match settings.list_outline_symbols {
+        true => list_ranges(list_outline(vault, path)),
+        false => vec![],
+    };
+
+    Some(
+        sections
+            .chain(code_blocks)
+            .chain(lists)
+            .sorted_by_key(|range| (range.start_line, Reverse(range.end_line)))
+            .collect(),
+    )
+}
+
+/// The file's list items as symbols, nested by indentation
+fn list_outline(vault: &Vault, path: &Path) -> Vec<DocumentSymbol> {
+    static LIST_ITEM_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(?<indent>[ \t]*)(?:[-*+]|\d+[.)])[ \t]+(?<text>\S.*)").unwrap()
+    });
+
+    let (Some(file), Some(rope)) = (vault.md_files.get(path), vault.ropes.get(path)) else {
+        return vec![];
+    };
+
+    let items = rope
+        .lines()
+        .enumerate()
+        .skip(frontmatter_len(rope))
+        .filter(|(line, _)| !file.in_fenced_code(*line))
+        .filter_map(|(line, text)| {
+            let text = text.to_string();
+            let captures = LIST_ITEM_RE.captures(text.trim_end())?;
+            let indent = captures["indent"]
+                .chars()
+                .map(|c| if c == '\t' { 4 } else { 1 })
+                .sum();
+
+            Some(ListItem {
+                indent,
+                text: captures["text"].to_string(),
+                range: Range {
+                    start: Position::new(line as u32, 0),
+                    end: Position::new(line as u32, text.trim_end().chars().count() as u32),
+                },
+            })
+        })
+        .collect_vec();
+
+    construct_tree(&items)
+        .map(map_to_lsp_tree)
+        .unwrap_or_default()
+}
+
+/// A bulleted or numbered list item, on a single line
+#[derive(Clone, PartialEq, Debug)]
+struct ListItem {
+    /// Leading whitespace width, counting a tab as four spaces
+    indent: usize,
+    text: String,
+    range: Range,
+}
+
+/// An item of a document outline, nested under the items before it with a lower level
+trait OutlineItem: Clone {
+    fn level(&self) -> usize;
+    fn symbol(self, children: Option<Vec<DocumentSymbol>>) -> DocumentSymbol;
+}
+
+impl OutlineItem for MDHeading {
+    fn level(&self) -> usize {
+        self.level.0
+    }
+
+    #[allow(deprecated)]
+    fn symbol(self, children: Option<Vec<DocumentSymbol>>) -> DocumentSymbol {
+        DocumentSymbol {
+            kind: heading_kind(self.level.0),
+            // links and tags in the heading shown as text; anchors still match the raw text
+            name: strip_markdown(&self.heading_text),
+            deprecated: None,
+            tags: None,
+            range: *self.range,
+            detail: None,
+            selection_range: *self.range,
+            children,
+        }
+    }
+}
+
+impl OutlineItem for ListItem {
+    fn level(&self) -> usize {
+        self.indent
+    }
+
+    /// The item's range spans its nested items, so that they can be folded
+    #[allow(deprecated)]
+    fn symbol(self, children: Option<Vec<DocumentSymbol>>) -> DocumentSymbol {
+        let end = children
+            .as_ref()
+            .and_then(|children| children.last())
+            .map_or(self.range.end, |last| last.range.end);
+
+        DocumentSymbol {
+            name: self.text,
+            kind: SymbolKind::KEY,
+            deprecated: None,
+            tags: None,
+            range: Range {
+                start: self.range.start,
+                end,
+            },
+            detail: None,
+            selection_range: self.range,
+            children,
+        }
+    }
 }
 
 #[derive(PartialEq, Debug)]
-struct Node {
-    heading: MDHeading,
-    children: Option<Vec<Node>>,
+struct Node<T = MDHeading> {
+    heading: T,
+    children: Option<Vec<Node<T>>>,
 }
 
-fn construct_tree(headings: &[MDHeading]) -> Option<Vec<Node>> {
+fn construct_tree<T: OutlineItem>(headings: &[T]) -> Option<Vec<Node<T>>> {
     match &headings {
         [only] => {
             let node = Node {
@@ -143,7 +435,7 @@ fn construct_tree(headings: &[MDHeading]) -> Option<Vec<Node>> {
         [first, rest @ ..] => {
             let break_index = rest
                 .iter()
-                .find_position(|heading| first.level >= heading.level);
+                .find_position(|heading| first.level() >= heading.level());
 
             match break_index.map(|(index, _)| (&rest[..index], &rest[index..])) {
                 Some((to_next, rest)) => {
@@ -172,26 +464,53 @@ fn construct_tree(headings: &[MDHeading]) -> Option<Vec<Node>> {
     }
 }
 
-fn map_to_lsp_tree(tree: Vec<Node>) -> Vec<DocumentSymbol> {
+/// Each heading's range with its ancestor headings and itself, like `Section > Sub`
+fn heading_breadcrumbs(headings: &[MDHeading]) -> Vec<(Range, String)> {
+    fn walk(nodes: Vec<Node>, parent: Option<&str>, breadcrumbs: &mut Vec<(Range, String)>) {
+        for node in nodes {
+            let text = strip_markdown(&node.heading.heading_text);
+            let breadcrumb = match parent {
+                Some(parent) => format!("{} > {}", parent, text),
+                None => text,
+            };
+            walk(
+                node.children.unwrap_or_default(),
+                Some(&breadcrumb),
+                breadcrumbs,
+            );
+            breadcrumbs.push((*node.heading.range, breadcrumb));
+        }
+    }
+
+    let mut breadcrumbs = vec![];
+    walk(
+        construct_tree(headings).unwrap_or_default(),
+        None,
+        &mut breadcrumbs,
+    );
+    breadcrumbs
+}
+
+fn map_to_lsp_tree<T: OutlineItem>(tree: Vec<Node<T>>) -> Vec<DocumentSymbol> {
     tree.into_iter()
-        .map(|node| DocumentSymbol {
-            name: node.heading.heading_text,
-            kind: SymbolKind::STRUCT,
-            deprecated: None,
-            tags: None,
-            range: *node.heading.range,
-            detail: None,
-            selection_range: *node.heading.range,
-            children: node.children.map(map_to_lsp_tree),
-        })
+        .map(|node| node.heading.symbol(node.children.map(map_to_lsp_tree)))
         .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use std::{path::Path, sync::atomic::AtomicBool};
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::{
+        DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, SymbolKind,
+        TextDocumentIdentifier, Url, WorkspaceSymbolParams,
+    };
+
     use crate::{
+        config::{Settings, TitleSource},
         symbol,
-        vault::{HeadingLevel, MDHeading},
+        vault::{HeadingLevel, MDHeading, Vault},
     };
 
     #[test]
@@ -360,4 +679,367 @@ mod test {
 
         assert_eq!(tree, Some(expected))
     }
+
+    #[test]
+    fn test_workspace_symbol_containers() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [("projects/work/plan.md", "# Goals\n"), ("inbox.md", "")],
+        );
+
+        let symbols = symbol::workspace_symbol(
+            &settings,
+            &vault,
+            &Default::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let container = |name: &str| {
+            symbols
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .unwrap()
+                .container_name
+                .clone()
+        };
+
+        // the note is named by its title
+        assert_eq!(container("Goals"), Some("projects/work".into()));
+        assert_eq!(container("inbox"), None);
+        assert_eq!(container("plan > Goals"), Some("plan".into()));
+    }
+
+    #[test]
+    fn test_workspace_symbol_detail() {
+        let settings = Settings {
+            workspace_symbol_detail: true,
+            ..Default::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "projects/plan.md",
+                "# Goals\n\nShip   the beta #todo\n\nA very long block of text that goes on well past where the detail stops ^blk\n",
+            )],
+        );
+
+        let symbols = symbol::workspace_symbol(
+            &settings,
+            &vault,
+            &Default::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let container = |name: &str| {
+            symbols
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .unwrap()
+                .container_name
+                .clone()
+                .unwrap()
+        };
+
+        assert_eq!(container("Goals"), "projects");
+        assert_eq!(container("plan > Goals"), "projects/plan");
+        assert_eq!(container("#todo"), "projects/plan: Ship the beta #todo");
+        assert_eq!(
+            container("projects/plan#^blk"),
+            "projects/plan: A very long block of text that goes on well past where the d…"
+        );
+    }
+
+    #[test]
+    fn test_workspace_symbol_kinds() {
+        let settings = Settings::default();
+        let vault = Vault::from_texts(
+            &settings,
+            Path::new("/vault"),
+            [(
+                "note.md",
+                "# Title\n\n## Part\n\nA block ^blk #tag\n\n[^1]: a footnote\n",
+            )],
+        );
+
+        let symbols = symbol::workspace_symbol(
+            &settings,
+            &vault,
+            &Default::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let kind = |name: &str| {
+            symbols
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .map(|symbol| symbol.kind)
+        };
+
+        assert_eq!(kind("Title"), Some(SymbolKind::FILE));
+        assert_eq!(kind("note > Title"), Some(SymbolKind::NAMESPACE));
+        assert_eq!(kind("note > Title > Part"), Some(SymbolKind::STRUCT));
+        assert_eq!(kind("note#^blk"), Some(SymbolKind::KEY));
+        assert_eq!(kind("#tag"), Some(SymbolKind::CONSTANT));
+        assert_eq!(kind("^1"), Some(SymbolKind::STRING));
+    }
+
+    #[test]
+    fn test_workspace_symbol_breadcrumbs() {
+        let settings = Settings::default();
+        let vault = Vault::from_texts(
+            &settings,
+            Path::new("/vault"),
+            [(
+                "note.md",
+                "# Note\n\n## Section\n\n### Sub\n\n## Other\n\n# Appendix\n",
+            )],
+        );
+
+        let names = symbol::workspace_symbol(
+            &settings,
+            &vault,
+            &Default::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap()
+        .into_iter()
+        .filter(|symbol| symbol.name.starts_with("note > "))
+        .map(|symbol| symbol.name)
+        .collect_vec();
+
+        assert_eq!(
+            names,
+            vec![
+                "note > Note",
+                "note > Note > Section",
+                "note > Note > Section > Sub",
+                "note > Note > Other",
+                "note > Appendix",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_workspace_symbol_titles() {
+        let files = [
+            ("plan.md", "---\ntitle: Project Plan\n---\n\n# Goals\n"),
+            ("notes.md", "## Not a title\n"),
+        ];
+        let titles = |settings: Settings| {
+            let vault = Vault::from_texts(&settings, Path::new("/vault"), files);
+            symbol::workspace_symbol(
+                &settings,
+                &vault,
+                &Default::default(),
+                &AtomicBool::new(false),
+            )
+            .unwrap()
+            .into_iter()
+            .filter(|symbol| symbol.kind == SymbolKind::FILE)
+            .filter(|symbol| {
+                files
+                    .iter()
+                    .any(|(file, _)| symbol.location.uri.path().ends_with(file))
+            })
+            .map(|symbol| symbol.name)
+            .sorted()
+            .collect_vec()
+        };
+
+        assert_eq!(titles(Settings::default()), vec!["Goals", "notes"]);
+        assert_eq!(
+            titles(Settings {
+                title_source: TitleSource::Frontmatter,
+                ..Default::default()
+            }),
+            vec!["Project Plan", "notes"]
+        );
+        assert_eq!(
+            titles(Settings {
+                title_headings: false,
+                ..Default::default()
+            }),
+            vec!["notes", "plan"]
+        );
+        assert_eq!(
+            titles(Settings {
+                title_source: TitleSource::Filename,
+                ..Default::default()
+            }),
+            vec!["notes", "plan"]
+        );
+    }
+
+    #[test]
+    fn test_workspace_symbol_query() {
+        let settings = Settings {
+            workspace_symbol_limit: 2,
+            ..Default::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("plan.md", "# Plan\n\n## Budget\n\n## Timeline\n"),
+                ("notes.md", "# Notes\n\n## Budget review\n"),
+            ],
+        );
+
+        let search = |query: &str, cancelled: bool| {
+            let params = WorkspaceSymbolParams {
+                query: query.to_string(),
+                ..Default::default()
+            };
+            symbol::workspace_symbol(&settings, &vault, &params, &AtomicBool::new(cancelled))
+                .map(|symbols| symbols.into_iter().map(|symbol| symbol.name).collect_vec())
+        };
+
+        assert_eq!(
+            search("budget", false)
+                .unwrap()
+                .into_iter()
+                .sorted()
+                .collect_vec(),
+            vec!["notes > Notes > Budget review", "plan > Plan > Budget"]
+        );
+        assert_eq!(
+            search("timeline", false),
+            Some(vec!["plan > Plan > Timeline".to_string()])
+        );
+        assert_eq!(search("", false).map(|names| names.len()), Some(2));
+        assert_eq!(search("budget", true), None);
+    }
+
+    #[test]
+    fn test_list_outline_symbols() {
+        let settings = Settings {
+            list_outline_symbols: true,
+            ..Default::default()
+        };
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [(
+                "outline.md",
+                "---\ntags:\n  - outline\n---\n# Title\n\n- one\n  - two\n\t- three\n  1. four\n- five\n\n```\n- code\n```\n",
+            )],
+        );
+        let path = root_dir.join("outline.md");
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&path).unwrap(),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        fn names(symbols: &[DocumentSymbol]) -> Vec<String> {
+            symbols
+                .iter()
+                .map(|symbol| match &symbol.children {
+                    Some(children) => format!("{} ({})", symbol.name, names(children).join(", ")),
+                    None => symbol.name.clone(),
+                })
+                .collect()
+        }
+
+        let Some(DocumentSymbolResponse::Nested(symbols)) =
+            symbol::document_symbol(&vault, &settings, &params, &path)
+        else {
+            panic!("expected nested symbols")
+        };
+        assert_eq!(
+            names(&symbols),
+            vec!["Title (one (two (three), four), five)"]
+        );
+
+        let folds = symbol::folding_range(&vault, &settings, &path)
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect::<Vec<_>>();
+        assert_eq!(folds, vec![(4, 14), (6, 9), (7, 8), (12, 14)]);
+
+        // notes with headings keep the heading outline
+        let settings = Settings::default();
+        let Some(DocumentSymbolResponse::Nested(symbols)) =
+            symbol::document_symbol(&vault, &settings, &params, &path)
+        else {
+            panic!("expected nested symbols")
+        };
+        assert_eq!(names(&symbols), vec!["Title"]);
+
+        // without list folds, the heading's section and the code block still fold
+        let folds = symbol::folding_range(&vault, &settings, &path)
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect::<Vec<_>>();
+        assert_eq!(folds, vec![(4, 14), (12, 14)]);
+    }
+
+    #[test]
+    fn test_heading_symbol_names() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "# See [[Other]] #ref\n\n## **Bold** [text](other.md) and `code`\n\n## [[other#Part|The part]] ![[image.png]]\n\n## #only\n",
+                ),
+                ("other.md", "# Other\n\n## Part\n"),
+            ],
+        );
+        let path = root_dir.join("note.md");
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&path).unwrap(),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let Some(DocumentSymbolResponse::Nested(symbols)) =
+            symbol::document_symbol(&vault, &settings, &params, &path)
+        else {
+            panic!("expected nested symbols")
+        };
+        assert_eq!(symbols[0].name, "See Other");
+        assert_eq!(
+            symbols[0]
+                .children
+                .iter()
+                .flatten()
+                .map(|symbol| symbol.name.as_str())
+                .collect_vec(),
+            // a heading of only a tag keeps it
+            vec!["Bold text and code", "The part image.png", "#only"]
+        );
+
+        let names = symbol::workspace_symbol(
+            &settings,
+            &vault,
+            &Default::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|symbol| symbol.name)
+        .collect_vec();
+        assert!(names.contains(&"note > See Other > Bold text and code".to_string()));
+
+        // links to the heading still match its raw text
+        let heading = &vault.md_files.get(&path).unwrap().headings[0];
+        assert_eq!(heading.heading_text, "See [[Other]] #ref");
+    }
 }
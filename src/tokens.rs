@@ -1,10 +1,70 @@
-use std::{collections::HashSet, iter, path::Path};
+use std::{cmp::Reverse, collections::HashSet, path::Path};
 
 use itertools::Itertools;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use tower_lsp::lsp_types::{SemanticToken, SemanticTokensParams, SemanticTokensResult};
+use tower_lsp::lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensRangeParams,
+    SemanticTokensRangeResult, SemanticTokensResult,
+};
 
-use crate::{config::Settings, diagnostics::path_unresolved_references, vault::Vault};
+use crate::{
+    config::Settings,
+    diagnostics::path_unresolved_references,
+    vault::{Rangeable, Reference, Vault},
+};
+
+/// Token types, indexed by `SemanticToken::token_type`
+const TOKEN_TYPES: [SemanticTokenType; 5] = [
+    SemanticTokenType::DECORATOR, // links and embeds
+    SemanticTokenType::new("heading"),
+    SemanticTokenType::new("tag"),
+    SemanticTokenType::new("footnote"),
+    SemanticTokenType::new("codeFence"),
+];
+
+const LINK: u32 = 0;
+const HEADING: u32 = 1;
+const TAG: u32 = 2;
+const FOOTNOTE: u32 = 3;
+const CODE_FENCE: u32 = 4;
+
+/// Token modifiers, each a bit of `SemanticToken::token_modifiers_bitset`
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 8] = [
+    SemanticTokenModifier::DECLARATION, // footnote definitions
+    SemanticTokenModifier::DEPRECATED,  // unresolved links, so themes can gray them out
+    SemanticTokenModifier::new("level1"),
+    SemanticTokenModifier::new("level2"),
+    SemanticTokenModifier::new("level3"),
+    SemanticTokenModifier::new("level4"),
+    SemanticTokenModifier::new("level5"),
+    SemanticTokenModifier::new("level6"),
+];
+
+const DECLARATION: u32 = 1;
+const UNRESOLVED: u32 = 1 << 1;
+
+/// The heading level's modifier bit
+fn level(level: usize) -> u32 {
+    1 << (1 + level.clamp(1, 6))
+}
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A token on a single line, from `start` to `end` in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token {
+    line: u32,
+    start: u32,
+    end: u32,
+    token_type: u32,
+    modifiers: u32,
+}
 
 pub fn semantic_tokens_full(
     vault: &Vault,
@@ -16,69 +76,271 @@ pub fn semantic_tokens_full(
         return None;
     }
 
-    let references_in_file = vault.select_references(Some(path))?;
+    Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: encode(&file_tokens(vault, path)?),
+    }))
+}
+
+pub fn semantic_tokens_range(
+    vault: &Vault,
+    path: &Path,
+    params: SemanticTokensRangeParams,
+    settings: &Settings,
+) -> Option<SemanticTokensRangeResult> {
+    if !settings.semantic_tokens {
+        return None;
+    }
+
+    let Range { start, end } = params.range;
+    let tokens = file_tokens(vault, path)?
+        .into_iter()
+        .filter(|token| (start.line..=end.line).contains(&token.line))
+        .collect_vec();
+
+    Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: encode(&tokens),
+    }))
+}
+
+/// The file's tokens in order, without overlaps
+fn file_tokens(vault: &Vault, path: &Path) -> Option<Vec<Token>> {
+    let file = vault.md_files.get(path)?;
+    let rope = vault.ropes.get(path)?;
 
     let path_unresolved: Option<HashSet<_>> =
-        path_unresolved_references(vault, path).map(|thing| {
+        path_unresolved_references(vault, &vault.resolution_index(), path).map(|thing| {
             thing
                 .into_par_iter()
                 .map(|(_, reference)| reference)
                 .collect()
         });
 
-    let tokens = references_in_file
-        .into_iter()
-        .sorted_by_key(|(_, reference)| {
-            (
-                reference.data().range.start.line,
-                reference.data().range.start.character,
-            )
+    let single_line = |range: &Range, token_type: u32, modifiers: u32| {
+        (range.start.line == range.end.line).then_some(Token {
+            line: range.start.line,
+            start: range.start.character,
+            end: range.end.character,
+            token_type,
+            modifiers,
         })
-        .fold(vec![], |acc, (path, reference)| {
-            let range = reference.data().range;
-
-            let is_unresolved = path_unresolved
-                .as_ref()
-                .is_some_and(|unresolved| unresolved.contains(reference));
-
-            match acc[..] {
-                [] => vec![(
-                    reference,
-                    SemanticToken {
-                        delta_line: range.start.line,
-                        delta_start: range.start.character,
-                        length: range.end.character - range.start.character,
-                        token_type: if is_unresolved { 1 } else { 0 },
-                        token_modifiers_bitset: 0,
-                    },
-                )],
-                [.., (prev_ref, _)] => acc
-                    .into_iter()
-                    .chain(iter::once((
-                        reference,
-                        SemanticToken {
-                            delta_line: range.start.line - prev_ref.data().range.start.line,
-                            delta_start: if range.start.line == prev_ref.data().range.start.line {
-                                range.start.character - prev_ref.data().range.start.character
-                            } else {
-                                range.start.character
-                            },
-                            length: range.end.character - range.start.character,
-                            token_type: if is_unresolved { 1 } else { 0 },
-                            token_modifiers_bitset: 0,
-                        },
-                    )))
-                    .collect_vec(),
+    };
+
+    let references =
+        vault
+            .select_references(Some(path))?
+            .into_iter()
+            .filter_map(|(_, reference)| {
+                let (token_type, modifiers) = match reference {
+                    Reference::Tag(..) => (TAG, 0),
+                    Reference::Footnote(..) => (FOOTNOTE, 0),
+                    _ if path_unresolved
+                        .as_ref()
+                        .is_some_and(|unresolved| unresolved.contains(reference)) =>
+                    {
+                        (LINK, UNRESOLVED)
+                    }
+                    _ => (LINK, 0),
+                };
+
+                single_line(&reference.data().range, token_type, modifiers)
+            });
+
+    let headings = file
+        .headings
+        .iter()
+        .filter_map(|heading| single_line(heading.range(), HEADING, level(heading.level.0)));
+
+    let footnotes = file
+        .footnotes
+        .iter()
+        .filter_map(|footnote| single_line(footnote.range(), FOOTNOTE, DECLARATION));
+
+    // the opening and closing lines of fenced code blocks; inline code spans are single line
+    let code_fences = file
+        .codeblocks
+        .iter()
+        .map(|codeblock| codeblock.range())
+        .filter(|range| range.start.line != range.end.line)
+        .flat_map(|range| {
+            let opening_end = rope
+                .get_line(range.start.line as usize)
+                .map(|line| {
+                    line.to_string()
+                        .trim_end_matches(['\r', '\n'])
+                        .chars()
+                        .count()
+                })
+                .unwrap_or_default() as u32;
+
+            [
+                Token {
+                    line: range.start.line,
+                    start: range.start.character,
+                    end: opening_end,
+                    token_type: CODE_FENCE,
+                    modifiers: 0,
+                },
+                Token {
+                    line: range.end.line,
+                    start: 0,
+                    end: range.end.character,
+                    token_type: CODE_FENCE,
+                    modifiers: 0,
+                },
+            ]
+        });
+
+    Some(flatten(
+        references
+            .chain(headings)
+            .chain(footnotes)
+            .chain(code_fences)
+            .collect(),
+    ))
+}
+
+/// Order the tokens and split them so that none overlap, as clients expect; a token containing
+/// others, like a heading with a link in it, is split around them
+fn flatten(tokens: Vec<Token>) -> Vec<Token> {
+    tokens
+        .into_iter()
+        .filter(|token| token.start < token.end)
+        .sorted_by_key(|token| (token.line, token.start, Reverse(token.end)))
+        .fold(Vec::new(), |mut flat: Vec<Token>, mut token| {
+            match flat.last().copied() {
+                Some(last) if last.line == token.line && last.end > token.start => {
+                    if last.start > token.start {
+                        // overlaps a token already split around another
+                    } else if last.end >= token.end {
+                        flat.pop();
+                        let before = Token {
+                            end: token.start,
+                            ..last
+                        };
+                        let after = Token {
+                            start: token.end,
+                            ..last
+                        };
+                        flat.extend(
+                            [before, token, after]
+                                .into_iter()
+                                .filter(|token| token.start < token.end),
+                        );
+                    } else {
+                        token.start = last.end;
+                        flat.push(token);
+                    }
+                }
+                _ => flat.push(token),
             }
+
+            flat
         })
-        .into_par_iter()
-        .map(|(_, token)| token)
-        .collect::<Vec<_>>(); // TODO: holy this is bad
-
-    Some(SemanticTokensResult::Tokens(
-        tower_lsp::lsp_types::SemanticTokens {
-            result_id: None,
-            data: tokens,
-        },
-    ))
+}
+
+/// Encode the ordered tokens relative to each previous one, as the protocol sends them
+fn encode(tokens: &[Token]) -> Vec<SemanticToken> {
+    tokens
+        .iter()
+        .scan(None, |previous: &mut Option<Token>, token| {
+            let (delta_line, delta_start) = match *previous {
+                Some(previous) if previous.line == token.line => (0, token.start - previous.start),
+                Some(previous) => (token.line - previous.line, token.start),
+                None => (token.line, token.start),
+            };
+            *previous = Some(*token);
+
+            Some(SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.end - token.start,
+                token_type: token.token_type,
+                token_modifiers_bitset: token.modifiers,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{
+        PartialResultParams, Position, Range, SemanticTokensRangeParams, SemanticTokensRangeResult,
+        TextDocumentIdentifier, Url, WorkDoneProgressParams,
+    };
+
+    use crate::config::Settings;
+    use crate::vault::Vault;
+
+    use super::{
+        file_tokens, level, semantic_tokens_range, Token, CODE_FENCE, DECLARATION, FOOTNOTE,
+        HEADING, LINK, TAG, UNRESOLVED,
+    };
+
+    #[test]
+    fn test_file_tokens() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "## See [[other]]\n\n[[missing]] #tag [^1]\n\n```rust\nlet x = 1;\n```\n\n[^1]: a footnote\n",
+                ),
+                ("other.md", ""),
+            ],
+        );
+
+        let token = |line, start, end, token_type, modifiers| Token {
+            line,
+            start,
+            end,
+            token_type,
+            modifiers,
+        };
+
+        assert_eq!(
+            file_tokens(&vault, &root_dir.join("note.md")).unwrap(),
+            vec![
+                token(0, 0, 7, HEADING, level(2)),
+                token(0, 7, 16, LINK, 0),
+                token(2, 0, 11, LINK, UNRESOLVED),
+                token(2, 12, 16, TAG, 0),
+                token(2, 17, 21, FOOTNOTE, 0),
+                token(4, 0, 7, CODE_FENCE, 0),
+                token(6, 0, 3, CODE_FENCE, 0),
+                token(8, 0, 16, FOOTNOTE, DECLARATION),
+            ]
+        );
+
+        let Some(SemanticTokensRangeResult::Tokens(range_tokens)) = semantic_tokens_range(
+            &vault,
+            &root_dir.join("note.md"),
+            SemanticTokensRangeParams {
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                text_document: TextDocumentIdentifier {
+                    uri: Url::from_file_path(root_dir.join("note.md")).unwrap(),
+                },
+                range: Range::new(Position::new(2, 0), Position::new(2, 30)),
+            },
+            &settings,
+        ) else {
+            panic!("expected range tokens")
+        };
+
+        assert_eq!(
+            range_tokens
+                .data
+                .iter()
+                .map(|token| (token.delta_line, token.delta_start, token.length))
+                .collect::<Vec<_>>(),
+            vec![(2, 0, 11), (0, 12, 4), (0, 5, 4)]
+        );
+    }
 }
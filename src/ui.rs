@@ -1,9 +1,11 @@
 use std::path::Path;
 
 use itertools::Itertools;
-use tower_lsp::lsp_types::{MarkupContent, MarkupKind};
+use tower_lsp::lsp_types::{MarkupContent, MarkupKind, Url};
 
-use crate::vault::{get_obsidian_ref_path, Preview, Reference, Referenceable, Vault};
+use crate::vault::{
+    get_obsidian_ref_path, is_canvas, is_image, Preview, Reference, Referenceable, Vault,
+};
 
 fn referenceable_string(vault: &Vault, referenceables: &[Referenceable]) -> Option<String> {
     let referenceable = referenceables.first()?;
@@ -13,29 +15,31 @@ fn referenceable_string(vault: &Vault, referenceables: &[Referenceable]) -> Opti
     let written_text_preview = match preview {
         Some(Preview::Empty) => "No Text".into(),
         Some(Preview::Text(text)) => match referenceable {
-            Referenceable::File(_, _) => format!("`File Preview:`\n\n{}", text),
+            Referenceable::File(_, mdfile) => format!("`{}`\n\n{}", mdfile.title(), text),
             Referenceable::Heading(_, _) => format!("`Heading Preview:`\n\n{}", text),
             Referenceable::IndexedBlock(_, _) => format!("`Block Preview:`\n\n{}", text),
             Referenceable::Footnote(_, _) => format!("`Footnote Preview:`\n\n{}", text),
             _ => format!("`Preview:`\n{}", text),
         },
-        None => "No Preview".into(),
+        None => match referenceable {
+            Referenceable::UnresovledIndexedBlock(_, file, index) => {
+                format!("Block `^{}` not found in `{}`", index, file)
+            }
+            Referenceable::Attachment(path) if is_image(path) => {
+                format!("![]({})", Url::from_file_path(path).ok()?)
+            }
+            Referenceable::Attachment(path) if is_canvas(path) => {
+                canvas_preview(path).unwrap_or_else(|| "Empty Canvas".into())
+            }
+            _ => "No Preview".into(),
+        },
     };
 
-    let backlinks_preview = match referenceables
-        .iter()
-        .flat_map(|i| vault.select_references_for_referenceable(i))
-        .flatten()
-        .collect_vec()
-    {
-        references if !references.is_empty() => references
+    let backlinks_preview = match backlinks(vault, referenceables) {
+        backlinks if !backlinks.is_empty() => backlinks
             .into_iter()
             .take(20)
-            .flat_map(|(path, reference)| {
-                let line = String::from_iter(
-                    vault.select_line(path, reference.data().range.start.line as isize)?,
-                );
-
+            .flat_map(|(path, _reference, line)| {
                 let path = get_obsidian_ref_path(vault.root_dir(), path)?;
 
                 Some(format!("- `{}`: `{}`", path, line)) // and select indented list
@@ -50,6 +54,54 @@ fn referenceable_string(vault: &Vault, referenceables: &[Referenceable]) -> Opti
     ))
 }
 
+/// The cards of an Obsidian canvas, from its JSON: the text of text cards, and the files, links,
+/// and group labels of the others
+fn canvas_preview(path: &Path) -> Option<String> {
+    let canvas: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+
+    let cards = canvas
+        .get("nodes")?
+        .as_array()?
+        .iter()
+        .filter_map(|node| {
+            let field = |name: &str| node.get(name)?.as_str();
+            match field("type")? {
+                "text" => field("text").map(str::to_string),
+                "file" => field("file").map(|file| format!("`{}`", file)),
+                "link" => field("url").map(|url| format!("<{}>", url)),
+                "group" => field("label").map(|label| format!("**{}**", label)),
+                _ => None,
+            }
+        })
+        .filter(|card| !card.trim().is_empty())
+        .join("\n\n---\n\n");
+
+    match cards.is_empty() {
+        true => None,
+        false => Some(format!("`{}`\n\n{}", path.file_stem()?.to_str()?, cards)),
+    }
+}
+
+/// References to any of the referenceables, with the text of the line each reference is on
+pub fn backlinks<'a>(
+    vault: &'a Vault,
+    referenceables: &[Referenceable],
+) -> Vec<(&'a Path, &'a Reference, String)> {
+    referenceables
+        .iter()
+        .flat_map(|i| vault.select_references_for_referenceable(i))
+        .flatten()
+        .flat_map(|(path, reference)| {
+            let line = String::from_iter(
+                vault.select_line(path, reference.data().range.start.line as isize)?,
+            );
+
+            Some((path, reference, line))
+        })
+        .collect()
+}
+
 pub fn preview_referenceable(
     vault: &Vault,
     referenceable: &Referenceable,
@@ -77,7 +129,8 @@ pub fn preview_reference(
         | MDFileLink(..)
         | MDHeadingLink(..)
         | MDIndexedBlockLink(..)
-        | LinkRef(..) => {
+        | LinkRef(..)
+        | AttachmentLink(..) => {
             let referenceables_for_reference =
                 vault.select_referenceables_for_reference(reference, reference_path);
 
@@ -0,0 +1,168 @@
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::vault::MDFile;
+
+/// The number of single character edits needed to turn one string into the other
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect_vec();
+    let mut row = (0..=b.len()).collect_vec();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(a_char != *b_char))
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The text's words lowercased and joined by `-`, like `my-heading` for `My Heading!`
+pub fn slugify(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .join("-")
+}
+
+/// A heading's anchor as GitHub writes it: lowercased, with punctuation dropped and each space made
+/// a `-`, like `my-heading` for `My Heading!` and `q--a` for `Q & A`
+pub fn github_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Markdown, like a heading's text, as plain text for display: links and embeds as their display
+/// text or else their target, like `note > Part` for `[[note#Part]]`, without inline tags, and
+/// without emphasis and code markers. Text that would be left empty is kept as it is.
+pub fn strip_markdown(text: &str) -> String {
+    static WIKILINK_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"!?\[\[(?<target>[^\[\]\|]*)(\|(?<display>[^\[\]]*))?\]\]").unwrap()
+    });
+    static MD_LINK_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"!?\[(?<display>[^\[\]]*)\]\([^()]*\)").unwrap());
+    static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(^|\s)#[^\s#\[\]]+").unwrap());
+    static MARKER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\*\*|__|~~|==|`|\*").unwrap());
+
+    let stripped =
+        WIKILINK_RE.replace_all(text, |captures: &Captures| match captures.name("display") {
+            Some(display) => display.as_str().to_string(),
+            None => captures["target"]
+                .split('#')
+                .filter(|part| !part.is_empty())
+                .join(" > "),
+        });
+    let stripped = MD_LINK_RE.replace_all(&stripped, "${display}");
+    let stripped = TAG_RE.replace_all(&stripped, "${1}");
+    let stripped = MARKER_RE
+        .replace_all(&stripped, "")
+        .split_whitespace()
+        .join(" ");
+
+    match stripped.is_empty() {
+        true => text.to_string(),
+        false => stripped,
+    }
+}
+
+/// The text with its `%XX` escapes decoded, like `My Heading` for `My%20Heading`; a `%` not
+/// starting an escape is kept
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Whether the path, with `/` separators, matches the glob; `*` and `?` match within a path segment
+/// and `**` matches across segments
+pub fn glob_match(glob: &str, path: &str) -> bool {
+    fn matches(glob: &[char], path: &[char]) -> bool {
+        match glob {
+            [] => path.is_empty(),
+            ['*', '*', '/', rest @ ..] => {
+                matches(rest, path)
+                    || (0..path.len())
+                        .filter(|&i| path[i] == '/')
+                        .any(|i| matches(rest, &path[i + 1..]))
+            }
+            ['*', '*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            ['*', rest @ ..] => (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != '/')
+                .any(|i| matches(rest, &path[i..])),
+            ['?', rest @ ..] => {
+                matches!(path, [first, ..] if *first != '/') && matches(rest, &path[1..])
+            }
+            [first, rest @ ..] => path.first() == Some(first) && matches(rest, &path[1..]),
+        }
+    }
+
+    matches(&glob.chars().collect_vec(), &path.chars().collect_vec())
+}
+
+/// The edit adding the text at the end of the file, starting on a line of its own
+pub fn append_edit(rope: &Rope, text: &str) -> TextEdit {
+    let last_line = rope.len_lines() - 1;
+    let end = Position::new(last_line as u32, rope.line(last_line).len_chars() as u32);
+    let newline = match rope.len_chars() == 0 || rope.char(rope.len_chars() - 1) == '\n' {
+        true => "",
+        false => "\n",
+    };
+
+    TextEdit {
+        range: Range::new(end, end),
+        new_text: format!("{newline}{text}"),
+    }
+}
+
+/// The edit appending a missing heading to the end of the note, after a blank line unless the note
+/// is empty; one level below the note's top heading, or `##` without headings, so that it doesn't
+/// become a second title or the note's title
+pub fn append_heading_edit(rope: &Rope, file: &MDFile, heading: &str) -> TextEdit {
+    let level = file
+        .headings
+        .iter()
+        .map(|existing| existing.level.0)
+        .min()
+        .map_or(2, |top| (top + 1).min(6));
+    let blank_line = match rope.chars().all(char::is_whitespace) {
+        true => "",
+        false => "\n",
+    };
+
+    append_edit(
+        rope,
+        &format!("{}{} {}\n", blank_line, "#".repeat(level), heading.trim()),
+    )
+}
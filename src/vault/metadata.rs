@@ -1,14 +1,31 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use tower_lsp::lsp_types::{Position, Range};
 
-#[derive(Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+use super::MyRange;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct MDMetadata {
     aliases: Vec<String>,
+    id: Option<String>,
+    tags: Vec<String>,
+    /// Every field of the frontmatter, the ones above included, as written
+    fields: Mapping,
+}
+
+/// Frontmatter that isn't valid YAML
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct MDMetadataError {
+    /// From where the parser stopped to the end of that line
+    pub range: MyRange,
+    pub message: String,
 }
 
 impl MDMetadata {
-    pub fn new(text: &str) -> Option<MDMetadata> {
+    /// The frontmatter at the beginning of the file, if there is one. Known fields of an unexpected
+    /// type are left out rather than making the whole frontmatter an error.
+    pub fn new(text: &str) -> Option<Result<MDMetadata, MDMetadataError>> {
         // find text between --- at the beginning of the file
 
         static RE: Lazy<Regex> =
@@ -18,14 +35,113 @@ impl MDMetadata {
 
         let metadata_match = metadata_match?;
 
-        let md_metadata = serde_yaml::from_str::<MDMetadata>(metadata_match.as_str());
+        let value = match serde_yaml::from_str::<Value>(metadata_match.as_str()) {
+            Ok(value) => value,
+            Err(error) => return Some(Err(MDMetadataError::new(text, &error))),
+        };
+        // empty frontmatter, or frontmatter that isn't a map, has no fields
+        let fields = match value {
+            Value::Mapping(fields) => fields,
+            _ => Mapping::new(),
+        };
 
-        md_metadata.ok()
+        Some(Ok(MDMetadata {
+            aliases: texts(fields.get("aliases"))
+                .into_iter()
+                .map(|alias| alias.trim().to_string())
+                .filter(|alias| !alias.is_empty())
+                .collect(),
+            id: fields
+                .get("id")
+                .and_then(text_value)
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty()),
+            tags: tags(fields.get("tags")),
+            fields,
+        }))
     }
 
     pub fn aliases(&self) -> &[String] {
         &self.aliases
     }
+
+    pub fn title(&self) -> Option<&str> {
+        self.field("title")?.as_str()
+    }
+
+    /// The stable id that links can name the note by instead of its filename
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The tags, without their `#`
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Any field of the frontmatter, like `status` or `created`
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+}
+
+impl MDMetadataError {
+    fn new(text: &str, error: &serde_yaml::Error) -> MDMetadataError {
+        static LOCATION: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r" at line \d+ column \d+").unwrap());
+
+        // the frontmatter starts on the line after the opening `---`
+        let (line, character) = match error.location() {
+            Some(location) => (location.line(), location.column().saturating_sub(1)),
+            None => (0, 0),
+        };
+        let line_len = text
+            .lines()
+            .nth(line)
+            .map_or(0, |line| line.chars().count());
+
+        MDMetadataError {
+            range: MyRange(Range::new(
+                Position::new(line as u32, character.min(line_len) as u32),
+                Position::new(line as u32, line_len as u32),
+            )),
+            message: LOCATION.replace_all(&error.to_string(), "").to_string(),
+        }
+    }
+}
+
+/// A string, or a number as its text, like the `202401151200` timestamps ids often are
+fn text_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// A list of texts, or a single one, as Obsidian accepts for aliases and tags
+fn texts(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Sequence(values)) => values.iter().filter_map(text_value).collect(),
+        Some(value) => text_value(value).into_iter().collect(),
+        None => vec![],
+    }
+}
+
+/// Tags are a list, or a single string of tags separated by commas or spaces
+fn tags(value: Option<&Value>) -> Vec<String> {
+    let tags = match value {
+        Some(Value::Sequence(_)) => texts(value),
+        Some(value) => text_value(value)
+            .map(|text| text.split([',', ' ']).map(str::to_string).collect())
+            .unwrap_or_default(),
+        None => vec![],
+    };
+
+    tags.iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
 }
 
 #[cfg(test)]
@@ -34,7 +150,9 @@ mod tests {
 
     #[test]
     fn test_aliases() {
-        let metadata = MDMetadata::new("---\naliases: [\"alias1\", \"alias2\"]\n---").unwrap();
+        let metadata = MDMetadata::new("---\naliases: [\"alias1\", \"alias2\"]\n---")
+            .unwrap()
+            .unwrap();
         assert_eq!(metadata.aliases, vec!["alias1", "alias2"]);
     }
 
@@ -47,7 +165,91 @@ aliases:
     - alias2
 ---",
         )
+        .unwrap()
         .unwrap();
         assert_eq!(metadata.aliases(), &["alias1", "alias2"]);
     }
+
+    #[test]
+    fn test_single_alias() {
+        let metadata = MDMetadata::new("---\naliases: one\ntags: project\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.aliases(), &["one"]);
+        assert_eq!(metadata.tags(), &["project"]);
+    }
+
+    #[test]
+    fn test_fields() {
+        let metadata = MDMetadata::new("---\nstatus: draft\npriority: 2\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            metadata.field("status").and_then(|status| status.as_str()),
+            Some("draft")
+        );
+        assert_eq!(
+            metadata
+                .field("priority")
+                .and_then(|priority| priority.as_u64()),
+            Some(2)
+        );
+        assert_eq!(metadata.field("missing"), None);
+    }
+
+    #[test]
+    fn test_title_without_aliases() {
+        let metadata = MDMetadata::new("---\ntitle: Project Plan\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.title(), Some("Project Plan"));
+        assert!(metadata.aliases().is_empty());
+    }
+
+    #[test]
+    fn test_tags() {
+        let list = MDMetadata::new("---\ntags:\n  - project\n  - \"#status/active\"\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(list.tags(), &["project", "status/active"]);
+
+        let text = MDMetadata::new("---\ntags: project, status/active\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(text.tags(), &["project", "status/active"]);
+    }
+
+    #[test]
+    fn test_id() {
+        let text = MDMetadata::new("---\nid: project-plan\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(text.id(), Some("project-plan"));
+
+        let number = MDMetadata::new("---\nid: 202401151200\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(number.id(), Some("202401151200"));
+
+        let none = MDMetadata::new("---\ntitle: Plan\n---").unwrap().unwrap();
+        assert_eq!(none.id(), None);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = MDMetadata::new("---\ntitle: Plan\naliases: [one\n---\n# Plan\n")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.range.start.line, 3);
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+
+        // a known field of an unexpected type is left out, keeping the others
+        let metadata = MDMetadata::new("---\ntitle: Plan\naliases:\n  key: value\n---")
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.title(), Some("Plan"));
+        assert!(metadata.aliases().is_empty());
+
+        assert_eq!(MDMetadata::new("# Plan\n"), None);
+    }
 }
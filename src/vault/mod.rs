@@ -1,5 +1,6 @@
 mod metadata;
 mod parsing;
+mod resolution;
 
 use std::{
     char,
@@ -8,9 +9,15 @@ use std::{
     iter,
     ops::{Deref, DerefMut, Not, Range},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
     time::SystemTime,
 };
 
+use ignore::gitignore::Gitignore;
+use ignore::{WalkBuilder, WalkState};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use pathdiff::diff_paths;
@@ -19,51 +26,142 @@ use regex::{Captures, Match, Regex};
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::Position;
-use walkdir::WalkDir;
 
 impl Vault {
-    pub fn construct_vault(context: &Settings, root_dir: &Path) -> Result<Vault, std::io::Error> {
-        let md_file_paths = WalkDir::new(root_dir)
-            .into_iter()
-            .filter_entry(|e| {
-                !e.file_name()
-                    .to_str()
-                    .map(|s| s.starts_with('.') || s == "logseq") // TODO: This is a temporary fix; a hidden config is better
-                    .unwrap_or(false)
+    pub fn construct_vault(
+        context: &Settings,
+        root_dir: &Path,
+        folders: &[PathBuf],
+    ) -> Result<Vault, std::io::Error> {
+        Self::construct_vault_with_progress(context, root_dir, folders, &IndexProgress::default())
+    }
+
+    /// Construct the vault, counting the files in `progress` as they are read and parsed. The
+    /// `folders` outside the root dir are indexed along with it, so that links can cross between
+    /// them, while paths stay relative to the root dir.
+    pub fn construct_vault_with_progress(
+        context: &Settings,
+        root_dir: &Path,
+        folders: &[PathBuf],
+        progress: &IndexProgress,
+    ) -> Result<Vault, std::io::Error> {
+        let folders = folders
+            .iter()
+            .filter(|folder| !folder.starts_with(root_dir) && !root_dir.starts_with(folder))
+            .cloned()
+            .collect_vec();
+
+        // walk the folders in parallel, then sort so that the vault is the same between runs
+        let md_file_paths = Mutex::new(Vec::new());
+        let attachment_paths = Mutex::new(Vec::new());
+        let mut walk = WalkBuilder::new(root_dir);
+        for folder in &folders {
+            walk.add(folder);
+        }
+        walk.hidden(true)
+            .require_git(false)
+            // symlinked folders are indexed at their paths in the vault, like Obsidian does
+            .follow_links(true)
+            .filter_entry({
+                let root_dir = root_dir.to_path_buf();
+                let ignore_globs = context.ignore_globs.clone();
+                move |e| {
+                    !matches_ignore_globs(&ignore_globs, &root_dir, e.path())
+                        && e.file_name().to_str() != Some("logseq") // TODO: This is a temporary fix; a hidden config is better
+                }
             })
-            .flatten()
-            .filter(|f| f.path().extension().and_then(|e| e.to_str()) == Some("md"))
+            .build_parallel()
+            .run(|| {
+                Box::new(|entry| {
+                    if let Ok(entry) = entry {
+                        if entry.path().extension().and_then(|e| e.to_str()) == Some("md") {
+                            if let Ok(mut paths) = md_file_paths.lock() {
+                                paths.push(entry.into_path());
+                            }
+                        } else if is_attachment(entry.path()) {
+                            if let Ok(mut paths) = attachment_paths.lock() {
+                                paths.push(entry.into_path());
+                            }
+                        }
+                    }
+
+                    WalkState::Continue
+                })
+            });
+        let md_file_paths = md_file_paths
+            .into_inner()
+            .unwrap_or_default()
+            .into_iter()
+            .sorted()
+            .collect_vec();
+        let attachments = attachment_paths
+            .into_inner()
+            .unwrap_or_default()
+            .into_iter()
+            .sorted()
             .collect_vec();
 
-        let md_files: HashMap<PathBuf, MDFile> = md_file_paths
+        progress.total.store(md_file_paths.len(), Ordering::Relaxed);
+
+        let (md_files, ropes): (HashMap<PathBuf, MDFile>, HashMap<PathBuf, Rope>) = md_file_paths
             .par_iter()
-            .flat_map(|p| {
-                let text = std::fs::read_to_string(p.path())?;
-                let md_file = MDFile::new(context, &text, PathBuf::from(p.path()));
+            .map(|p| {
+                let text = std::fs::read_to_string(p)?;
+                let md_file = MDFile::new(context, &text, p.clone());
+                let rope = Rope::from_str(&text);
 
-                return Ok::<(PathBuf, MDFile), std::io::Error>((p.path().into(), md_file));
+                Ok::<_, std::io::Error>(((p.clone(), md_file), (p.clone(), rope)))
+            })
+            .inspect(|_| {
+                progress.done.fetch_add(1, Ordering::Relaxed);
+            })
+            .flatten()
+            .unzip();
+
+        // a path that can't be canonicalized, like one through a broken link, is kept as it is
+        let symlinked = md_files
+            .keys()
+            .chain(&attachments)
+            .par_bridge()
+            .filter_map(|path| {
+                let canonical = std::fs::canonicalize(path).ok()?;
+                (canonical != *path).then(|| (canonical, path.clone()))
             })
             .collect();
 
-        let ropes: HashMap<PathBuf, Rope> = md_file_paths
-            .iter()
-            .flat_map(|p| {
-                let text = std::fs::read_to_string(p.path())?;
-                let rope = Rope::from_str(&text);
-
-                return Ok::<(PathBuf, Rope), std::io::Error>((p.path().into(), rope));
+        let modified = md_files
+            .keys()
+            .chain(&attachments)
+            .par_bridge()
+            .filter_map(|path| {
+                let modified = std::fs::metadata(path).and_then(|meta| meta.modified());
+                Some((path.clone(), modified.ok()?))
             })
             .collect();
 
-        Ok(Vault {
+        let mut vault = Vault {
             ropes: ropes.into(),
             md_files: md_files.into(),
+            attachments,
+            symlinked,
             root_dir: root_dir.into(),
-        })
+            folders,
+            case_insensitive_headings: context.case_insensitive_headings,
+            case_insensitive_filenames: context.case_insensitive_filenames,
+            link_resolution_priority: context.link_resolution_priority.clone(),
+            name_ranks: HashMap::new(),
+            modified,
+        };
+        vault.index_name_ranks();
+
+        Ok(vault)
     }
 
+    /// Index the note's new text, as it is being edited, so that it is now the most recently
+    /// modified
     pub fn update_vault(context: &Settings, old: &mut Vault, new_file: (&PathBuf, &str)) {
         let new_md_file = MDFile::new(context, new_file.1, new_file.0.clone());
+        old.modified.insert(new_file.0.clone(), SystemTime::now());
         let new = old.md_files.get_mut(new_file.0);
 
         match new {
@@ -72,6 +170,7 @@ impl Vault {
             }
             None => {
                 old.md_files.insert(new_file.0.into(), new_md_file);
+                old.rank_name(new_file.0);
             }
         };
 
@@ -87,6 +186,149 @@ impl Vault {
             }
         }
     }
+
+    /// Index a note or attachment created or changed outside the editor, reading notes from disk;
+    /// files that constructing the vault would skip are left out
+    pub fn index_file(
+        context: &Settings,
+        vault: &mut Vault,
+        path: &Path,
+    ) -> Result<(), std::io::Error> {
+        let ignored = std::iter::once(vault.root_dir())
+            .chain(&vault.folders)
+            .find(|root| path.starts_with(root))
+            .is_none_or(|root| walk_skips(root, path));
+        if ignored || matches_ignore_globs(&context.ignore_globs, vault.root_dir(), path) {
+            return Ok(());
+        }
+
+        if is_attachment(path) {
+            if let Err(index) = vault
+                .attachments
+                .binary_search_by(|other| other.as_path().cmp(path))
+            {
+                vault.attachments.insert(index, path.to_path_buf());
+                vault.rank_name(path);
+            }
+        } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            let text = std::fs::read_to_string(path)?;
+            Vault::update_vault(context, vault, (&path.to_path_buf(), &text));
+        }
+
+        if let Ok(modified) = std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            vault.modified.insert(path.to_path_buf(), modified);
+        }
+
+        Ok(())
+    }
+
+    /// Drop a deleted note or attachment, or everything under a deleted folder, from the vault
+    pub fn remove_path(&mut self, path: &Path) {
+        self.md_files
+            .retain(|file_path, _| !file_path.starts_with(path));
+        self.ropes
+            .retain(|file_path, _| !file_path.starts_with(path));
+        self.attachments
+            .retain(|file_path| !file_path.starts_with(path));
+        self.modified
+            .retain(|file_path, _| !file_path.starts_with(path));
+        self.index_name_ranks();
+    }
+}
+
+/// Whether the path, relative to the root dir, matches one of the ignore globs
+fn matches_ignore_globs(ignore_globs: &[String], root_dir: &Path, path: &Path) -> bool {
+    diff_paths(path, root_dir).is_some_and(|relative| {
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        ignore_globs
+            .iter()
+            .any(|glob| glob_match(glob.trim_end_matches('/'), &relative))
+    })
+}
+
+/// Whether walking the root dir for `construct_vault` skips the path in it: hidden files and
+/// folders, `logseq` folders, and what the `.ignore` and `.gitignore` files between them ignore
+fn walk_skips(root_dir: &Path, path: &Path) -> bool {
+    let Some(relative) = diff_paths(path, root_dir) else {
+        return true;
+    };
+    let hidden = relative.iter().any(|segment| {
+        segment
+            .to_str()
+            .is_none_or(|segment| segment.starts_with('.') || segment == "logseq")
+    });
+
+    // the deepest ignore file with a rule for the path decides, and `.ignore` over `.gitignore`
+    hidden
+        || path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root_dir))
+            .find_map(|dir| {
+                [".ignore", ".gitignore"].into_iter().find_map(|file| {
+                    let (ignore_file, _) = Gitignore::new(dir.join(file));
+                    match ignore_file.matched_path_or_any_parents(path, false) {
+                        ignore::Match::None => None,
+                        matched => Some(matched.is_ignore()),
+                    }
+                })
+            })
+            .unwrap_or(false)
+}
+
+/// The number of files found and parsed so far while constructing a vault
+#[derive(Debug, Default)]
+pub struct IndexProgress {
+    pub total: AtomicUsize,
+    pub done: AtomicUsize,
+}
+
+impl IndexProgress {
+    /// The (done, total) file counts
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.done.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+impl Vault {
+    /// Construct a vault from in memory files, given as paths relative to the root dir and their
+    /// text; attachments are only indexed by path
+    pub fn from_texts<'a>(
+        context: &Settings,
+        root_dir: &Path,
+        files: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Vault {
+        let mut vault = Vault {
+            md_files: HashMap::new().into(),
+            ropes: HashMap::new().into(),
+            attachments: Vec::new(),
+            symlinked: HashMap::new(),
+            root_dir: root_dir.into(),
+            folders: Vec::new(),
+            case_insensitive_headings: context.case_insensitive_headings,
+            case_insensitive_filenames: context.case_insensitive_filenames,
+            link_resolution_priority: context.link_resolution_priority.clone(),
+            name_ranks: HashMap::new(),
+            modified: HashMap::new(),
+        };
+
+        for (path, text) in files {
+            let path = root_dir.join(path);
+            match is_attachment(&path) {
+                true => {
+                    vault.rank_name(&path);
+                    vault.attachments.push(path);
+                }
+                false => Vault::update_vault(context, &mut vault, (&path, text)),
+            }
+        }
+
+        vault
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -135,11 +377,217 @@ impl Hash for Vault {
 pub struct Vault {
     pub md_files: MyHashMap<MDFile>,
     pub ropes: MyHashMap<Rope>,
+    /// Images, PDFs, and canvases, which links and embeds can point to
+    pub attachments: Vec<PathBuf>,
+    /// The canonical paths of the files indexed through symbolic links, to their paths in the vault
+    symlinked: HashMap<PathBuf, PathBuf>,
     root_dir: PathBuf,
+    /// Folders outside the root dir indexed with it, as the other workspace folders are without
+    /// `separate_workspace_folders`
+    folders: Vec<PathBuf>,
+    /// Heading links match headings regardless of case, like Obsidian
+    case_insensitive_headings: bool,
+    /// Links match the paths of files regardless of case
+    case_insensitive_filenames: bool,
+    /// The folders that links naming a file without a path go to first
+    link_resolution_priority: Vec<String>,
+    /// The `link_resolution_priority` ranks of the files by their names, kept as files are added
+    /// and removed so that resolving a link doesn't compare it with every file of the vault
+    name_ranks: HashMap<(bool, String), NameRanks>,
+    /// When the files were last modified, read as they're indexed rather than on every request
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+/// The ranks of the files sharing a name: the best, the number of files with it, and the number
+/// of files with the name
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct NameRanks {
+    best: usize,
+    at_best: usize,
+    files: usize,
 }
 
 /// Methods using vaults data
 impl Vault {
+    /// Whether a link's heading refers to the heading, by its text, percent-encoded or not, or by its
+    /// slug or GitHub-style anchor; with `case_insensitive_headings`, case is ignored, using Unicode
+    /// lowercasing rather than just ASCII. Without it, only links in slug form, with words joined by
+    /// `-`, match by slug, so that `introduction` doesn't match `Introduction`.
+    pub fn headings_match(&self, link_heading: &str, heading: &str) -> bool {
+        if link_heading == heading {
+            return true;
+        }
+
+        let link_heading = percent_decode(link_heading);
+        let text_matches = match self.case_insensitive_headings {
+            true => link_heading.to_lowercase() == heading.to_lowercase(),
+            false => link_heading == heading,
+        };
+
+        text_matches
+            || (self.is_slug_form(&link_heading)
+                && (link_heading == slugify(heading) || link_heading == github_slug(heading)))
+    }
+
+    /// Whether a link's heading is compared with headings' slugs: always with
+    /// `case_insensitive_headings`, otherwise only when it joins words by `-` like a slug
+    fn is_slug_form(&self, link_heading: &str) -> bool {
+        self.case_insensitive_headings || link_heading.contains('-')
+    }
+
+    /// Whether the block is in the section of a heading matching the link's heading: after the
+    /// heading and before the next heading of the same or a higher level
+    pub fn block_in_section(
+        &self,
+        path: &Path,
+        block: &MDIndexedBlock,
+        link_heading: &str,
+    ) -> bool {
+        let Some(file) = self.md_files.get(path) else {
+            return false;
+        };
+        let line = block.range.start.line;
+
+        file.headings.iter().enumerate().any(|(i, heading)| {
+            self.headings_match(link_heading, &heading.heading_text)
+                && heading.range.start.line < line
+                && file.headings[i + 1..]
+                    .iter()
+                    .find(|next| next.level <= heading.level)
+                    .is_none_or(|next| line < next.range.start.line)
+        })
+    }
+
+    /// The refname, like `file#Heading`, as compared by `headings_match` and, with
+    /// `case_insensitive_filenames`, `matches_path_or_file`; block refs are unchanged
+    pub(crate) fn refname_match_key(&self, refname: String) -> String {
+        let (file_ref, infile_ref) = match refname.split_once('#') {
+            Some((file_ref, infile_ref)) => (file_ref, Some(infile_ref)),
+            None => (refname.as_str(), None),
+        };
+        let file_ref = self.filename_match_key(file_ref);
+
+        match infile_ref {
+            Some(heading) if self.case_insensitive_headings && !heading.starts_with('^') => {
+                format!("{}#{}", file_ref, heading.to_lowercase())
+            }
+            Some(infile_ref) => format!("{}#{}", file_ref, infile_ref),
+            None => file_ref,
+        }
+    }
+
+    /// The path or filename of a link, as compared with `case_insensitive_filenames`
+    pub(crate) fn filename_match_key(&self, file_ref: &str) -> String {
+        match self.case_insensitive_filenames {
+            true => file_ref.to_lowercase(),
+            false => file_ref.to_string(),
+        }
+    }
+
+    /// Whether a link naming the file without a path goes to another file of the same name instead,
+    /// one in a folder earlier in `link_resolution_priority`
+    pub fn is_outranked(&self, path: &Path) -> bool {
+        self.name_ranks(path)
+            .is_some_and(|ranks| self.resolution_rank(path) > ranks.best)
+    }
+
+    /// Whether links naming the file without a path go to it over the other files of the same
+    /// name, by `link_resolution_priority`
+    pub fn is_preferred(&self, path: &Path) -> bool {
+        self.name_ranks(path).is_some_and(|ranks| {
+            ranks.files > 1 && ranks.at_best == 1 && self.resolution_rank(path) == ranks.best
+        })
+    }
+
+    /// The position in `link_resolution_priority` of the first folder the file is in, or the
+    /// length of the list for a file in none of them
+    fn resolution_rank(&self, path: &Path) -> usize {
+        let relative = diff_paths(path, self.root_dir())
+            .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+
+        self.link_resolution_priority
+            .iter()
+            .map(|folder| folder.trim_start_matches("./").trim_matches('/'))
+            .position(|folder| match folder.is_empty() {
+                true => !relative.contains('/'),
+                false => relative.starts_with(&format!("{}/", folder)),
+            })
+            .unwrap_or(self.link_resolution_priority.len())
+    }
+
+    /// The name links use for the file without a path: a note's name without its extension, or an
+    /// attachment's filename, with whether the file is a note
+    fn name_key(&self, path: &Path) -> Option<(bool, String)> {
+        let is_note = path.extension().and_then(|e| e.to_str()) == Some("md");
+        let name = match is_note {
+            true => path.file_stem(),
+            false => path.file_name(),
+        }?;
+
+        Some((is_note, self.filename_match_key(&name.to_string_lossy())))
+    }
+
+    fn name_ranks(&self, path: &Path) -> Option<&NameRanks> {
+        if self.link_resolution_priority.is_empty() {
+            return None;
+        }
+
+        self.name_ranks.get(&self.name_key(path)?)
+    }
+
+    /// Count a file added to the vault in the ranks of its name
+    fn rank_name(&mut self, path: &Path) {
+        if self.link_resolution_priority.is_empty() {
+            return;
+        }
+        let Some(key) = self.name_key(path) else {
+            return;
+        };
+
+        let rank = self.resolution_rank(path);
+        let ranks = self.name_ranks.entry(key).or_insert(NameRanks {
+            best: rank,
+            at_best: 0,
+            files: 0,
+        });
+        ranks.files += 1;
+        match rank.cmp(&ranks.best) {
+            std::cmp::Ordering::Less => {
+                ranks.best = rank;
+                ranks.at_best = 1;
+            }
+            std::cmp::Ordering::Equal => ranks.at_best += 1,
+            std::cmp::Ordering::Greater => (),
+        }
+    }
+
+    /// Count the ranks of every file's name again, as after files are removed
+    fn index_name_ranks(&mut self) {
+        self.name_ranks.clear();
+        if self.link_resolution_priority.is_empty() {
+            return;
+        }
+
+        let paths = self
+            .md_files
+            .keys()
+            .chain(&self.attachments)
+            .cloned()
+            .collect_vec();
+        for path in paths {
+            self.rank_name(&path);
+        }
+    }
+
+    /// The files ordered by path, so that selections across the vault keep the same order
+    fn sorted_md_files(&self) -> Vec<(&PathBuf, &MDFile)> {
+        self.md_files
+            .iter()
+            .sorted_by_key(|(path, _)| *path)
+            .collect()
+    }
+
     /// Select all references ([[link]] or #tag) in a file if path is some, else select all references in the vault.
     pub fn select_references<'a>(
         &'a self,
@@ -152,8 +600,8 @@ impl Vault {
                 .map(|md| &md.references)
                 .map(|vec| vec.iter().map(|i| (path, i)).collect()),
             None => Some(
-                self.md_files
-                    .iter()
+                self.sorted_md_files()
+                    .into_iter()
                     .flat_map(|(path, md)| md.references.iter().map(|link| (path.as_path(), link)))
                     .collect(),
             ),
@@ -225,44 +673,124 @@ impl Vault {
                 // TODO: Add unresolved referenceables
             }
             None => {
-                let resolved_referenceables = self
-                    .md_files
-                    .values()
-                    .par_bridge()
+                let mut resolved_referenceables = self
+                    .sorted_md_files()
                     .into_par_iter()
-                    .flat_map(|file| file.get_referenceables())
+                    .flat_map(|(_, file)| file.get_referenceables())
                     .collect::<Vec<_>>();
+                resolved_referenceables
+                    .extend(self.attachments.iter().map(Referenceable::Attachment));
 
                 let resolved_referenceables_refnames: HashSet<String> = resolved_referenceables
                     .par_iter()
                     .flat_map(|resolved| {
-                        resolved.get_refname(self.root_dir()).and_then(|refname| {
-                            vec![
-                                refname.to_string(),
-                                format!(
-                                    "{}{}",
-                                    refname.link_file_key()?,
-                                    refname
-                                        .infile_ref
-                                        .map(|refe| format!("#{}", refe))
-                                        .unwrap_or("".to_string())
-                                ),
-                            ]
-                            .into()
-                        })
+                        let refnames = resolved
+                            .get_refname(self.root_dir())
+                            .and_then(|refname| {
+                                vec![
+                                    refname.to_string(),
+                                    format!(
+                                        "{}{}",
+                                        refname.link_file_key()?,
+                                        refname
+                                            .infile_ref
+                                            .map(|refe| format!("#{}", refe))
+                                            .unwrap_or("".to_string())
+                                    ),
+                                ]
+                                .into()
+                            })
+                            .unwrap_or_default();
+
+                        // notes, and the headings and blocks in them, are also linked to by the
+                        // note's id
+                        let id = match resolved {
+                            Referenceable::File(path, _)
+                            | Referenceable::Heading(path, _)
+                            | Referenceable::IndexedBlock(path, _) => self
+                                .md_files
+                                .get(*path)
+                                .and_then(|file| file.metadata.as_ref()?.id())
+                                .zip(resolved.get_refname(self.root_dir()))
+                                .map(|(id, refname)| match refname.infile_ref {
+                                    Some(infile_ref) => format!("{}#{}", id, infile_ref),
+                                    None => id.to_string(),
+                                }),
+                            _ => None,
+                        };
+                        // canvases are also linked to without their extension
+                        let canvas = match resolved {
+                            Referenceable::Attachment(path) if is_canvas(path) => refnames
+                                .iter()
+                                .filter_map(|refname| refname.strip_suffix(".canvas"))
+                                .map(str::to_string)
+                                .collect_vec(),
+                            _ => vec![],
+                        };
+                        let refnames = refnames.into_iter().chain(id).chain(canvas).collect_vec();
+
+                        // headings are also linked to by their slugs
+                        let slugs = match resolved {
+                            Referenceable::Heading(_, heading) => refnames
+                                .iter()
+                                .filter_map(|refname| refname.split_once('#'))
+                                .flat_map(|(file_ref, _)| {
+                                    [
+                                        slugify(&heading.heading_text),
+                                        github_slug(&heading.heading_text),
+                                    ]
+                                    .into_iter()
+                                    .filter(|slug| self.is_slug_form(slug))
+                                    .map(move |slug| format!("{}#{}", file_ref, slug))
+                                })
+                                .collect_vec(),
+                            _ => vec![],
+                        };
+
+                        refnames.into_iter().chain(slugs).collect_vec()
                     })
-                    .flatten()
+                    .map(|refname| self.refname_match_key(refname))
                     .collect();
 
+                // links relative to the linking file's folder are compared from the vault root
+                let root_text = |path: &Path, reference: &Reference| {
+                    let text = &reference.data().reference_text;
+                    let (file_ref, infile_ref) = match text.split_once('#') {
+                        Some((file_ref, infile_ref)) => (file_ref, Some(infile_ref)),
+                        None => (text.as_str(), None),
+                    };
+
+                    let text = relative_refname(self.root_dir(), path, file_ref)
+                        .filter(|refname| resolved_referenceables_refnames.contains(refname))
+                        .map(|refname| match infile_ref {
+                            Some(infile_ref) => format!("{refname}#{infile_ref}"),
+                            None => refname,
+                        })
+                        .unwrap_or_else(|| text.clone());
+                    // anchors can be percent-encoded, `note.md#My%20Heading`
+                    let text = match text.split_once('#') {
+                        Some((file_ref, infile_ref)) if infile_ref.contains('%') => {
+                            format!("{}#{}", file_ref, percent_decode(infile_ref))
+                        }
+                        _ => text,
+                    };
+
+                    self.refname_match_key(text)
+                };
+
                 let unresolved = self.select_references(None).map(|references| {
                     references
                         .iter()
-                        .unique_by(|(_, reference)| &reference.data().reference_text)
-                        .par_bridge()
+                        .unique_by(|(path, reference)| root_text(path, reference))
+                        .collect_vec()
                         .into_par_iter()
-                        .filter(|(_, reference)| {
-                            !resolved_referenceables_refnames
-                                .contains(&reference.data().reference_text)
+                        .filter(|(path, reference)| match reference.block_scope() {
+                            // resolved only by a block in the heading's section
+                            Some(_) => !resolved_referenceables
+                                .iter()
+                                .any(|resolved| reference.references(self, path, resolved)),
+                            None => !resolved_referenceables_refnames
+                                .contains(&root_text(path, reference)),
                         })
                         .flat_map(|(_, reference)| match reference {
                             Reference::WikiFileLink(data) | Reference::MDFileLink(data) => {
@@ -297,7 +825,8 @@ Some(Referenceable::UnresovledFile(path, &data.reference_text))
                             }
                             Reference::Tag(..)
                             | Reference::Footnote(..)
-                            | Reference::LinkRef(..) => None,
+                            | Reference::LinkRef(..)
+                            | Reference::AttachmentLink(..) => None,
                         })
                         .collect::<Vec<_>>()
                 });
@@ -329,6 +858,38 @@ Some(Referenceable::UnresovledFile(path, &data.reference_text))
         &self.root_dir
     }
 
+    /// The folders outside the root dir that are indexed with it
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    /// The path the file is indexed at, for a path to it that may resolve symbolic links
+    /// differently, like the real path of a note in a symlinked folder or of a vault that is itself
+    /// a link. Paths that are indexed or can't be canonicalized are kept as they are.
+    pub fn vault_path(&self, path: &Path) -> PathBuf {
+        if self.md_files.contains_key(path)
+            || self
+                .attachments
+                .binary_search_by(|other| other.as_path().cmp(path))
+                .is_ok()
+        {
+            return path.to_path_buf();
+        }
+        let Ok(canonical) = std::fs::canonicalize(path) else {
+            return path.to_path_buf();
+        };
+
+        match self.symlinked.get(&canonical) {
+            Some(indexed) => indexed.clone(),
+            None => std::fs::canonicalize(&self.root_dir)
+                .ok()
+                .and_then(|root_dir| {
+                    Some(self.root_dir.join(canonical.strip_prefix(root_dir).ok()?))
+                })
+                .unwrap_or_else(|| path.to_path_buf()),
+        }
+    }
+
     pub fn select_references_for_referenceable(
         &self,
         referenceable: &Referenceable,
@@ -339,14 +900,9 @@ Some(Referenceable::UnresovledFile(path, &data.reference_text))
             references
                 .into_par_iter()
                 .filter(|(ref_path, reference)| {
-                    referenceable.matches_reference(&self.root_dir, reference, ref_path)
-                })
-                .map(|(path, reference)| {
-                    match std::fs::metadata(path).and_then(|meta| meta.modified()) {
-                        Ok(modified) => (path, reference, modified),
-                        Err(_) => (path, reference, SystemTime::UNIX_EPOCH),
-                    }
+                    referenceable.matches_reference(self, reference, ref_path)
                 })
+                .map(|(path, reference)| (path, reference, self.modified(path)))
                 .collect::<Vec<_>>()
                 .into_iter()
                 .sorted_by_key(|(_, _, modified)| *modified)
@@ -365,9 +921,88 @@ Some(Referenceable::UnresovledFile(path, &data.reference_text))
 
         referenceables
             .into_iter()
-            .filter(|i| reference.references(self.root_dir(), reference_path, i))
+            .filter(|i| reference.references(self, reference_path, i))
+            .collect()
+    }
+
+    /// Select the lines of a heading's section: from the heading up to the next heading of the same or
+    /// higher level, or the end of the file
+    pub fn select_heading_section(
+        &self,
+        path: &Path,
+        heading: &MDHeading,
+    ) -> Option<std::ops::Range<usize>> {
+        let headings = self.select_headings(path)?;
+        let rope = self.ropes.get(path)?;
+
+        let start = heading.range.start.line as usize;
+        let end = headings
+            .iter()
+            .filter(|other| other.range.start.line as usize > start)
+            .find(|other| other.level <= heading.level)
+            .map(|other| other.range.start.line as usize)
+            .unwrap_or(rope.len_lines());
+
+        Some(start..end)
+    }
+
+    /// Select the file names, without extensions, that more than one file in the vault shares
+    pub fn select_duplicate_file_names(&self) -> HashSet<String> {
+        self.md_files
+            .keys()
+            .filter_map(|path| path.file_stem()?.to_str())
+            .counts()
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(name, _)| name.to_string())
             .collect()
     }
+
+    /// When the note or attachment was last modified, on disk or in the editor, as of indexing it
+    pub fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.modified.get(path).copied()
+    }
+
+    /// Select the file that a link's file part resolves to, even if its heading or block does not.
+    /// This scans every note; `ResolutionIndex::linked_file` resolves many links at once.
+    fn select_linked_file(
+        &self,
+        reference: &Reference,
+        reference_path: &Path,
+    ) -> Option<(&Path, &MDFile)> {
+        self.md_files
+            .iter()
+            .find(|(path, file)| {
+                Referenceable::File(path, file).matches_reference(self, reference, reference_path)
+            })
+            .map(|(path, file)| (path.as_path(), file))
+    }
+
+    /// Whether the file part of a link is the frontmatter `id` of the note at the path; a note
+    /// named the same wins over the id, so the link is then to that note
+    pub fn is_note_id(&self, file_ref: &str, path: &Path) -> bool {
+        self.md_files
+            .get(path)
+            .and_then(|file| file.metadata.as_ref()?.id())
+            .is_some_and(|id| id == file_ref)
+            && !self
+                .md_files
+                .keys()
+                .any(|path| path.file_stem().and_then(|stem| stem.to_str()) == Some(file_ref))
+    }
+
+    /// Whether the link is an embed, `![[note]]` or `![alt](image.png)`: a `!` comes right before it
+    pub fn is_embed(&self, path: &Path, reference: &Reference) -> bool {
+        let range = reference.data().range;
+
+        range.start.character > 0
+            && self
+                .ropes
+                .get(path)
+                .and_then(|rope| rope.get_line(range.start.line as usize))
+                .and_then(|line| line.get_char(range.start.character as usize - 1))
+                == Some('!')
+    }
 }
 
 pub enum Preview {
@@ -414,11 +1049,20 @@ impl Vault {
                         .into(),
                 )
             }
-            Referenceable::IndexedBlock(_, _) => {
-                let range = referenceable.get_range()?;
-                self.select_line(referenceable.get_path(), range.start.line as isize)
+            Referenceable::IndexedBlock(path, block) => {
+                let range = block.block_range;
+                let text = (range.start.line..=range.end.line)
+                    .filter_map(|ln| self.select_line(path, ln as isize))
                     .map(String::from_iter)
-                    .map(Into::into)
+                    .join("");
+
+                Some(
+                    text.trim_end()
+                        .trim_end_matches(&format!("^{}", block.index))
+                        .trim_end()
+                        .to_string()
+                        .into(),
+                )
             }
             Referenceable::File(_, _) => {
                 Some(
@@ -433,6 +1077,7 @@ impl Vault {
             Referenceable::UnresovledFile(_, _) => None,
             Referenceable::UnresolvedHeading(_, _, _) => None,
             Referenceable::UnresovledIndexedBlock(_, _, _) => None,
+            Referenceable::Attachment(_) => None,
         }
     }
 
@@ -550,7 +1195,13 @@ pub struct MDFile {
     pub path: PathBuf,
     pub link_reference_definitions: Vec<MDLinkReferenceDefinition>,
     pub metadata: Option<MDMetadata>,
+    /// Why the frontmatter couldn't be read, when it couldn't; the rest of the note is still indexed
+    pub metadata_error: Option<MDMetadataError>,
     pub codeblocks: Vec<MDCodeBlock>,
+    /// Task list items, outside of code blocks
+    pub tasks: Vec<MDTask>,
+    /// The title from the note's H1 or frontmatter, following `title_source`; see `MDFile::title`
+    pub title: Option<String>,
 }
 
 impl MDFile {
@@ -582,23 +1233,91 @@ impl MDFile {
                 .collect_vec(),
             _ => MDTag::new(text).collect_vec(),
         };
-        let metadata = MDMetadata::new(text);
+        let tasks = MDTask::new(text)
+            .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)));
+        let (metadata, metadata_error) = match MDMetadata::new(text) {
+            Some(Ok(metadata)) => (Some(metadata), None),
+            Some(Err(error)) => (None, Some(error)),
+            None => (None, None),
+        };
+        let headings = headings.collect_vec();
+
+        // inline tags and the frontmatter's `tags`, per `tag_sources`
+        let frontmatter_tags = match (context.tag_sources, &metadata) {
+            (TagSources::Frontmatter | TagSources::Both, Some(metadata)) => {
+                MDTag::from_frontmatter(text, metadata.tags())
+            }
+            _ => vec![],
+        };
+        let inline_tags = context.tag_sources != TagSources::Frontmatter;
+        let links = links
+            .into_iter()
+            .filter(|link| inline_tags || !matches!(link, Reference::Tag(..)))
+            .chain(frontmatter_tags.iter().map(|tag| {
+                Reference::Tag(ReferenceData {
+                    display_text: None,
+                    range: tag.range,
+                    reference_text: format!("#{}", tag.tag_ref),
+                })
+            }))
+            .collect_vec();
+        let tags = tags
+            .into_iter()
+            .filter(|_| inline_tags)
+            .chain(frontmatter_tags)
+            .collect_vec();
+
+        let h1 = || {
+            headings
+                .iter()
+                .find(|heading| heading.level.0 == 1)
+                .filter(|_| context.title_headings)
+                .map(|heading| heading.heading_text.clone())
+        };
+        let title = match context.title_source {
+            TitleSource::H1 => h1(),
+            TitleSource::Filename => None,
+            TitleSource::Frontmatter => metadata
+                .as_ref()
+                .and_then(|metadata| metadata.title())
+                .map(str::to_string)
+                .or_else(h1),
+        };
 
         MDFile {
             references: links,
-            headings: headings.collect(),
+            headings,
             indexed_blocks: indexed_blocks.collect(),
             tags,
             footnotes: footnotes.collect(),
             path,
             link_reference_definitions: link_refs.collect(),
             metadata,
+            metadata_error,
+            tasks: tasks.collect(),
             codeblocks: code_blocks,
+            title,
         }
     }
 
-    pub fn file_name(&self) -> Option<&str> {
-        self.path.file_stem()?.to_str()
+    /// The note's title: the one from its H1 or frontmatter, or else its filename without the
+    /// extension
+    pub fn title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Whether the line is in a fenced code block, fences included
+    pub fn in_fenced_code(&self, line: usize) -> bool {
+        self.codeblocks.iter().any(|codeblock| {
+            let range = codeblock.range();
+            range.start.line != range.end.line
+                && (range.start.line as usize..=range.end.line as usize).contains(&line)
+        })
     }
 }
 
@@ -613,7 +1332,10 @@ impl MDFile {
             path: _,
             link_reference_definitions,
             metadata: _,
+            metadata_error: _,
             codeblocks: _,
+            tasks: _,
+            title: _,
         } = self;
 
         iter::once(Referenceable::File(&self.path, self))
@@ -670,6 +1392,9 @@ pub enum Reference {
     MDIndexedBlockLink(ReferenceData, File, Specialref),
     Footnote(ReferenceData),
     LinkRef(ReferenceData),
+    /// A link or embed of an image, PDF, or canvas with its extension, `![[image.png|200]]` or
+    /// `![alt](image.png)`; the display text of a wiki embed is the image size
+    AttachmentLink(ReferenceData),
 }
 
 impl Deref for Reference {
@@ -687,11 +1412,33 @@ impl Default for Reference {
 
 use Reference::*;
 
-use crate::config::Settings;
+use crate::{
+    config::{LinkFormat, Settings, TagSources, TitleSource},
+    util::{github_slug, glob_match, percent_decode, slugify},
+};
+
+use self::{
+    metadata::{MDMetadata, MDMetadataError},
+    parsing::{MDCodeBlock, MDTask},
+};
 
-use self::{metadata::MDMetadata, parsing::MDCodeBlock};
+pub use self::resolution::ResolutionIndex;
 
 impl Reference {
+    /// The heading that a block link like `[[note#Heading#^block]]` looks for the block under. The
+    /// block index decides what the link resolves to, but only within the heading's section
+    pub fn block_scope(&self) -> Option<&str> {
+        match self {
+            WikiIndexedBlockLink(data, file_ref, _) | MDIndexedBlockLink(data, file_ref, _) => data
+                .reference_text
+                .strip_prefix(file_ref.as_str())?
+                .strip_prefix('#')?
+                .rsplit_once("#^")
+                .map(|(heading, _)| heading),
+            _ => None,
+        }
+    }
+
     pub fn data(&self) -> &ReferenceData {
         match &self {
             Tag(data, ..) => data,
@@ -703,6 +1450,7 @@ impl Reference {
             MDHeadingLink(data, ..) => data,
             MDIndexedBlockLink(data, ..) => data,
             LinkRef(data, ..) => data,
+            AttachmentLink(data) => data,
         }
     }
 
@@ -717,14 +1465,15 @@ impl Reference {
             MDHeadingLink(..) => matches!(self, MDHeadingLink(..)),
             MDIndexedBlockLink(..) => matches!(self, MDIndexedBlockLink(..)),
             LinkRef(..) => matches!(self, LinkRef(..)),
+            AttachmentLink(..) => matches!(self, AttachmentLink(..)),
         }
     }
 
     pub fn new(text: &str) -> impl Iterator<Item = Reference> + '_ {
         static WIKI_LINK_RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\[\[(?<filepath>[^\[\]\|\.\#]+)(\#(?<infileref>[^\[\]\.\|]+))?(\|(?<display>[^\[\]\.\|]+))?\]\]")
+            Regex::new(r"\[\[(?<filepath>(\.\.?\/)*[^\[\]\|\.\#]+)(\.md)?(\#(?<infileref>[^\[\]\.\|]+))?(\|(?<display>[^\[\]\.\|]+))?\]\]")
                 .unwrap()
-        }); // A [[link]] that does not have any [ or ] in it
+        }); // A [[link]] that does not have any [ or ] in it; a `.md` extension is left out
 
         let wiki_links = WIKI_LINK_RE
             .captures_iter(text)
@@ -734,10 +1483,53 @@ impl Reference {
             });
 
         static MD_LINK_RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\[(?<display>[^\[\]\.]*)\]\(<?(?<filepath>(\.?\/)?[^\[\]\|\.\#<>]+)(?<ending>\.[^\# <>]+)?(\#(?<infileref>[^\[\]\.\|<>]+))?>?\)")
+            Regex::new(r"\[(?<display>[^\[\]\.]*)\]\(<?(?<filepath>(\.{0,2}\/)*[^\[\]\|\.\#<>]+)(?<ending>\.[^\# <>]+)?(\#(?<infileref>[^\[\]\.\|<>]+))?>?\)")
                 .expect("MD Link Not Constructing")
         }); // [display](relativePath)
 
+        static WIKI_ATTACHMENT_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"\[\[(?<filepath>[^\[\]\|\#]+\.[a-zA-Z]+)(\|(?<display>[^\[\]\|]+))?\]\]")
+                .unwrap()
+        });
+
+        let rope = Rope::from_str(text);
+        let attachment_link = |full: Match, filepath: &str, display: Option<Match>| {
+            is_attachment(Path::new(filepath)).then(|| {
+                AttachmentLink(ReferenceData {
+                    reference_text: filepath.to_string(),
+                    display_text: display.map(|display| display.as_str().to_string()),
+                    range: MyRange::from_range(&rope, full.range()),
+                })
+            })
+        };
+
+        let wiki_attachments = WIKI_ATTACHMENT_RE
+            .captures_iter(text)
+            .flat_map(|captures| {
+                attachment_link(
+                    captures.get(0)?,
+                    captures.name("filepath")?.as_str(),
+                    captures.name("display"),
+                )
+            })
+            .collect_vec();
+
+        let md_attachments = MD_LINK_RE
+            .captures_iter(text)
+            .filter(|captures| captures.name("infileref").is_none())
+            .flat_map(|captures| {
+                let filepath = format!(
+                    "{}{}",
+                    captures.name("filepath")?.as_str(),
+                    captures.name("ending")?.as_str()
+                );
+                match filepath.contains("://") {
+                    true => None,
+                    false => attachment_link(captures.get(0)?, &filepath, captures.name("display")),
+                }
+            })
+            .collect_vec();
+
         let md_links = MD_LINK_RE
             .captures_iter(text)
             .filter(|captures| match captures.name("ending").map(|ending| ending.as_str()) {
@@ -781,7 +1573,8 @@ impl Reference {
             .not()
         {
             static LINK_REF_RE: Lazy<Regex> = Lazy::new(|| {
-                Regex::new(r"([^\[]|^)(?<full>\[(?<index>[^\^][^\[\] ]+)\])([^\]\(\:]|$)").unwrap()
+                Regex::new(r"([^\[\]]|^)(?<full>\[(?<index>[^\^][^\[\] ]+)\])([^\[\]\(\:]|$)")
+                    .unwrap()
             });
 
             let link_ref_references: Vec<Reference> = LINK_REF_RE
@@ -807,21 +1600,54 @@ impl Reference {
             vec![]
         };
 
+        // full and collapsed reference links, `[text][ref]` and `[text][]`, are links even without
+        // a definition, unlike the shortcut `[ref]`
+        static FULL_LINK_REF_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"\[(?<display>[^\[\]\n]+)\]\[(?<index>[^\[\]\^\n]*)\]").unwrap()
+        });
+
+        let full_link_ref_references = FULL_LINK_REF_RE
+            .captures_iter(text)
+            .flat_map(|capture| {
+                let display = capture.name("display")?.as_str();
+                let index = match capture.name("index")?.as_str() {
+                    "" => display,
+                    index => index,
+                };
+
+                Some(LinkRef(ReferenceData {
+                    reference_text: index.into(),
+                    range: MyRange::from_range(&rope, capture.get(0)?.range()),
+                    display_text: Some(display.into()),
+                }))
+            })
+            .collect_vec();
+
         wiki_links
             .into_iter()
             .chain(md_links)
             .chain(tags)
             .chain(footnote_references)
             .chain(link_ref_references)
+            .chain(full_link_ref_references)
+            .chain(wiki_attachments)
+            .chain(md_attachments)
     }
 
     pub fn references(
         &self,
-        root_dir: &Path,
+        vault: &Vault,
         file_path: &Path,
         referenceable: &Referenceable,
     ) -> bool {
+        let root_dir = vault.root_dir();
         let text = &self.data().reference_text;
+        // a name without a path goes to the file in the first folder of `link_resolution_priority`
+        let outranked = |file_ref_text: &str| {
+            !file_ref_text.contains('/')
+                && !referenceable.is_unresolved()
+                && vault.is_outranked(referenceable.get_path())
+        };
         match referenceable {
             &Referenceable::Tag(_, _) => {
                 match self {
@@ -839,6 +1665,7 @@ impl Reference {
                     MDHeadingLink(_, _, _) => false,
                     MDIndexedBlockLink(_, _, _) => false,
                     Footnote(_) => false,
+                    AttachmentLink(_) => false,
                     LinkRef(_) => false, // (no I don't write all of these by hand; I use rust-analyzers code action; I do this because when I add new item to the Reference enum, I want workspace errors everywhere relevant)
                 }
             }
@@ -855,6 +1682,7 @@ impl Reference {
                 MDHeadingLink(_, _, _) => false,
                 MDIndexedBlockLink(_, _, _) => false,
                 LinkRef(_) => false,
+                AttachmentLink(_) => false,
             },
             &Referenceable::File(..) | &Referenceable::UnresovledFile(..) => match self {
                 MDFileLink(ReferenceData {
@@ -864,7 +1692,15 @@ impl Reference {
                 | WikiFileLink(ReferenceData {
                     reference_text: file_ref_text,
                     ..
-                }) => matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir)),
+                }) => {
+                    (matches_path_or_file(
+                        vault.case_insensitive_filenames,
+                        file_ref_text,
+                        referenceable.get_refname(root_dir),
+                        relative_refname(root_dir, file_path, file_ref_text),
+                    ) && !outranked(file_ref_text))
+                        || vault.is_note_id(file_ref_text, referenceable.get_path())
+                }
                 Tag(_) => false,
                 WikiHeadingLink(_, _, _) => false,
                 WikiIndexedBlockLink(_, _, _) => false,
@@ -872,6 +1708,7 @@ impl Reference {
                 MDIndexedBlockLink(_, _, _) => false,
                 Footnote(_) => false,
                 LinkRef(_) => false,
+                AttachmentLink(_) => false,
             },
             &Referenceable::Heading(
                 ..,
@@ -892,14 +1729,34 @@ impl Reference {
                 | WikiIndexedBlockLink(.., file_ref_text, link_infile_ref)
                 | MDHeadingLink(.., file_ref_text, link_infile_ref)
                 | MDIndexedBlockLink(.., file_ref_text, link_infile_ref) => {
-                    matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir))
-                        && link_infile_ref == infile_ref
+                    let infile_refs_match = match referenceable {
+                        Referenceable::Heading(..) | Referenceable::UnresolvedHeading(..) => {
+                            vault.headings_match(link_infile_ref, infile_ref)
+                        }
+                        Referenceable::IndexedBlock(path, block) => {
+                            link_infile_ref == infile_ref
+                                && self.block_scope().is_none_or(|heading| {
+                                    vault.block_in_section(path, block, heading)
+                                })
+                        }
+                        _ => link_infile_ref == infile_ref,
+                    };
+
+                    ((matches_path_or_file(
+                        vault.case_insensitive_filenames,
+                        file_ref_text,
+                        referenceable.get_refname(root_dir),
+                        relative_refname(root_dir, file_path, file_ref_text),
+                    ) && !outranked(file_ref_text))
+                        || vault.is_note_id(file_ref_text, referenceable.get_path()))
+                        && infile_refs_match
                 }
                 Tag(_) => false,
                 WikiFileLink(_) => false,
                 MDFileLink(_) => false,
                 Footnote(_) => false,
                 LinkRef(_) => false,
+                AttachmentLink(_) => false,
             },
             Referenceable::LinkRefDef(path, _link_ref) => match self {
                 Tag(_) => false,
@@ -910,6 +1767,7 @@ impl Reference {
                 MDHeadingLink(_, _, _) => false,
                 MDIndexedBlockLink(_, _, _) => false,
                 Footnote(_) => false,
+                AttachmentLink(_) => false,
                 LinkRef(data) => {
                     Some(data.reference_text.to_lowercase())
                         == referenceable
@@ -919,6 +1777,46 @@ impl Reference {
                         && file_path == *path
                 }
             },
+            Referenceable::Attachment(path) => match self {
+                AttachmentLink(ReferenceData {
+                    reference_text: file_ref_text,
+                    ..
+                }) => {
+                    matches_path_or_file(
+                        vault.case_insensitive_filenames,
+                        file_ref_text,
+                        referenceable.get_refname(root_dir),
+                        relative_refname(root_dir, file_path, file_ref_text),
+                    ) && !outranked(file_ref_text)
+                }
+                // `[[board]]` links to `board.canvas`, unless a note is named the same
+                WikiFileLink(ReferenceData {
+                    reference_text: file_ref_text,
+                    ..
+                })
+                | MDFileLink(ReferenceData {
+                    reference_text: file_ref_text,
+                    ..
+                }) if is_canvas(path) => {
+                    let file_ref_text = format!("{}.canvas", file_ref_text);
+                    matches_path_or_file(
+                        vault.case_insensitive_filenames,
+                        &file_ref_text,
+                        referenceable.get_refname(root_dir),
+                        relative_refname(root_dir, file_path, &file_ref_text),
+                    ) && !outranked(&file_ref_text)
+                        && vault.select_linked_file(self, file_path).is_none()
+                }
+                Tag(_) => false,
+                WikiFileLink(_) => false,
+                WikiHeadingLink(_, _, _) => false,
+                WikiIndexedBlockLink(_, _, _) => false,
+                MDFileLink(_) => false,
+                MDHeadingLink(_, _, _) => false,
+                MDIndexedBlockLink(_, _, _) => false,
+                Footnote(_) => false,
+                LinkRef(_) => false,
+            },
         }
     }
 }
@@ -1015,6 +1913,19 @@ fn generic_link_constructor<T: ParseableReferenceConstructor>(
                 &infile.as_str()[1..], // drop the ^ for the index
             ))
         }
+        // a block under a heading, `#Heading#^index`; the heading stays in the reference text
+        (full, filepath, Some(infile), display) if infile.as_str().contains("#^") => {
+            let (_, index) = infile.as_str().rsplit_once("#^")?;
+            Some(T::new_indexed_block_link(
+                ReferenceData {
+                    reference_text: format!("{}#{}", filepath.as_str(), infile.as_str()),
+                    range: MyRange::from_range(&Rope::from_str(text), full.range()),
+                    display_text: display.map(|d| d.as_str().into()),
+                },
+                filepath.as_str(),
+                index,
+            ))
+        }
         (full, filepath, Some(infile), display) => Some(T::new_heading(
             ReferenceData {
                 reference_text: format!("{}#{}", filepath.as_str(), infile.as_str()),
@@ -1131,6 +2042,9 @@ pub struct MDIndexedBlock {
     /// THe index of the block; does not include '^'
     pub index: String,
     pub range: MyRange,
+    /// The block that the index marks: the list item or heading on the index's line, or the
+    /// paragraph that ends on it
+    pub block_range: MyRange,
 }
 
 impl Hash for MDIndexedBlock {
@@ -1144,19 +2058,53 @@ impl MDIndexedBlock {
         static INDEXED_BLOCK_RE: Lazy<Regex> =
             Lazy::new(|| Regex::new(r".+ (\^(?<index>\w+))").unwrap());
 
+        let rope = Rope::from_str(text);
+
         let indexed_blocks = INDEXED_BLOCK_RE
             .captures_iter(text)
             .flat_map(|c| match (c.get(1), c.name("index")) {
                 (Some(full), Some(index)) => Some((full, index)),
                 _ => None,
             })
-            .map(|(full, index)| MDIndexedBlock {
-                index: index.as_str().into(),
-                range: MyRange::from_range(&Rope::from_str(text), full.range()),
+            .map(move |(full, index)| {
+                let range = MyRange::from_range(&rope, full.range());
+                let start_line = Self::block_start_line(&rope, range.start.line as usize);
+
+                MDIndexedBlock {
+                    index: index.as_str().into(),
+                    range,
+                    block_range: tower_lsp::lsp_types::Range {
+                        start: Position {
+                            line: start_line as u32,
+                            character: 0,
+                        },
+                        end: range.end,
+                    }
+                    .into(),
+                }
             });
 
         indexed_blocks
     } // Make this better identify the full blocks
+
+    /// The first line of the block that ends on the line
+    fn block_start_line(rope: &Rope, line: usize) -> usize {
+        let is_boundary = |line: usize| {
+            rope.get_line(line)
+                .map(|slice| slice.to_string())
+                .is_some_and(|string| is_block_boundary(&string))
+        };
+
+        if is_boundary(line) {
+            return line;
+        }
+
+        (0..line)
+            .rev()
+            .take_while(|previous| !is_boundary(*previous))
+            .last()
+            .unwrap_or(line)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -1212,7 +2160,7 @@ impl Hash for MDTag {
 impl MDTag {
     fn new(text: &str) -> impl Iterator<Item = MDTag> + '_ {
         static TAG_RE: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"(\n|\A| )(?<full>#(?<tag>[.[^ \n\#]]+))(\n|\z| )").unwrap());
+            Lazy::new(|| Regex::new(r"(\n|\A| )(?<full>#(?<tag>[.[^ \n\#]]+))").unwrap());
 
         let tagged_blocks = TAG_RE
             .captures_iter(text)
@@ -1220,6 +2168,8 @@ impl MDTag {
                 (Some(full), Some(index)) => Some((full, index)),
                 _ => None,
             })
+            // the tag ends at a space or line end, without consuming it, so that `#a #b` is two tags
+            .filter(|(full, _)| !text[full.end()..].starts_with('#'))
             .filter(|(_, index)| index.as_str().chars().any(|c| c.is_alphabetic()))
             .map(|(full, index)| MDTag {
                 tag_ref: index.as_str().into(),
@@ -1228,6 +2178,50 @@ impl MDTag {
 
         tagged_blocks
     }
+
+    /// The frontmatter's tags where they're written, in its `tags` field
+    fn from_frontmatter(text: &str, tags: &[String]) -> Vec<MDTag> {
+        static TOKEN_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[^\s,\[\]"']+"#).unwrap());
+
+        let frontmatter_lines = frontmatter_len(&Rope::from_str(text)).saturating_sub(1);
+        let lines = text.lines().take(frontmatter_lines).collect_vec();
+        let Some(start) = lines.iter().position(|line| line.starts_with("tags:")) else {
+            return vec![];
+        };
+
+        // the key's line and the list items under it
+        lines[start..]
+            .iter()
+            .enumerate()
+            .take_while(|(i, line)| *i == 0 || line.starts_with([' ', '\t', '-']))
+            .flat_map(|(i, line)| {
+                let line_number = start + i;
+                let value = match i {
+                    0 => &line["tags:".len()..],
+                    _ => line,
+                };
+                let offset = line.len() - value.len();
+
+                TOKEN_RE
+                    .find_iter(value)
+                    .filter(|token| {
+                        tags.contains(&token.as_str().trim_start_matches('#').to_string())
+                    })
+                    .map(move |token| {
+                        let character = |byte: usize| line[..offset + byte].chars().count() as u32;
+                        MDTag {
+                            tag_ref: token.as_str().trim_start_matches('#').to_string(),
+                            range: tower_lsp::lsp_types::Range::new(
+                                Position::new(line_number as u32, character(token.start())),
+                                Position::new(line_number as u32, character(token.end())),
+                            )
+                            .into(),
+                        }
+                    })
+                    .collect_vec()
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
@@ -1283,6 +2277,55 @@ pub enum Referenceable<'a> {
     /// full path, link path, index (without ^)
     UnresovledIndexedBlock(PathBuf, &'a String, &'a String),
     LinkRefDef(&'a PathBuf, &'a MDLinkReferenceDefinition),
+    /// An image, PDF, or Obsidian canvas in the vault
+    Attachment(&'a PathBuf),
+}
+
+/// Extensions of the files that are indexed as attachments
+const ATTACHMENT_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "pdf", "canvas",
+];
+
+/// Whether the file is an image, PDF, or canvas, by its extension
+pub fn is_attachment(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ATTACHMENT_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Whether the attachment is an image, which embeds can size
+pub fn is_image(path: &Path) -> bool {
+    is_attachment(path)
+        && path.extension().is_some_and(|extension| {
+            !extension.eq_ignore_ascii_case("pdf") && !extension.eq_ignore_ascii_case("canvas")
+        })
+}
+
+/// Whether the attachment is an Obsidian canvas, which links name like a note, with or without
+/// its `.canvas`
+pub fn is_canvas(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("canvas"))
+}
+
+/// Whether the line starts a new block rather than continuing a paragraph: a blank line, list item,
+/// heading, quote, or code fence
+pub fn is_block_boundary(line: &str) -> bool {
+    static BLOCK_BOUNDARY_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*([-*+] |\d+[.)] |#+ |>|```|$)").unwrap());
+
+    BLOCK_BOUNDARY_RE.is_match(line.trim_end())
+}
+
+/// How many lines the frontmatter at the start of the file takes, fences included
+pub fn frontmatter_len(rope: &Rope) -> usize {
+    let mut lines = rope.lines().map(|line| line.to_string());
+    match lines.next() {
+        Some(first) if first.trim_end() == "---" => lines
+            .position(|line| line.trim_end() == "---")
+            .map_or(0, |end| end + 2),
+        _ => 0,
+    }
 }
 
 /// Utility function
@@ -1290,6 +2333,31 @@ pub fn get_obsidian_ref_path(root_dir: &Path, path: &Path) -> Option<String> {
     diff_paths(path, root_dir).and_then(|diff| diff.with_extension("").to_str().map(String::from))
 }
 
+/// The file part of a link from the file at `from` to the file at `path`, in the `link_format`.
+/// `duplicate_names` are file names shared by several files; shortest links to those files use the
+/// path from the vault root to tell them apart
+pub fn link_name(
+    root_dir: &Path,
+    link_format: LinkFormat,
+    from: &Path,
+    path: &Path,
+    duplicate_names: &HashSet<String>,
+) -> Option<String> {
+    let file_name = path.file_stem()?.to_str()?;
+
+    match link_format {
+        LinkFormat::Shortest if duplicate_names.contains(file_name) => {
+            get_obsidian_ref_path(root_dir, path)
+        }
+        LinkFormat::Shortest => Some(file_name.to_string()),
+        LinkFormat::Relative => {
+            let relative = diff_paths(path, from.parent()?)?;
+            Some(relative.with_extension("").to_str()?.replace('\\', "/"))
+        }
+        LinkFormat::Absolute => get_obsidian_ref_path(root_dir, path),
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Default)]
 pub struct Refname {
     pub full_refname: String,
@@ -1410,15 +2478,24 @@ impl Referenceable<'_> {
                 infile_ref: None,
                 path: None,
             }),
+            // attachments are linked with their extension
+            Referenceable::Attachment(path) => diff_paths(path, root_dir)
+                .and_then(|diff| diff.to_str().map(|path| path.replace('\\', "/")))
+                .map(|path| Refname {
+                    full_refname: path.clone(),
+                    path: Some(path),
+                    infile_ref: None,
+                }),
         }
     }
 
     pub fn matches_reference(
         &self,
-        root_dir: &Path,
+        vault: &Vault,
         reference: &Reference,
         reference_path: &Path,
     ) -> bool {
+        let root_dir = vault.root_dir();
         let text = &reference.data().reference_text;
         match &self {
             Referenceable::Tag(_, _) => {
@@ -1443,6 +2520,7 @@ impl Referenceable<'_> {
                 MDHeadingLink(_, _, _) => false,
                 MDIndexedBlockLink(_, _, _) => false,
                 LinkRef(_) => false,
+                AttachmentLink(_) => false,
             },
             Referenceable::File(..) | Referenceable::UnresovledFile(..) => match reference {
                 WikiFileLink(ReferenceData {
@@ -1457,14 +2535,22 @@ impl Referenceable<'_> {
                 })
                 | MDHeadingLink(.., file_ref_text, _)
                 | MDIndexedBlockLink(.., file_ref_text, _) => {
-                    matches_path_or_file(file_ref_text, self.get_refname(root_dir))
+                    matches_path_or_file(
+                        vault.case_insensitive_filenames,
+                        file_ref_text,
+                        self.get_refname(root_dir),
+                        relative_refname(root_dir, reference_path, file_ref_text),
+                    ) && (file_ref_text.contains('/')
+                        || self.is_unresolved()
+                        || !vault.is_outranked(self.get_path()))
                 }
                 Tag(_) => false,
                 Footnote(_) => false,
                 LinkRef(_) => false,
+                AttachmentLink(_) => false,
             },
 
-            _ => reference.references(root_dir, reference_path, self),
+            _ => reference.references(vault, reference_path, self),
         }
     }
 
@@ -1479,12 +2565,13 @@ impl Referenceable<'_> {
             Referenceable::UnresovledFile(path, ..) => path,
             Referenceable::UnresolvedHeading(path, ..) => path,
             Referenceable::LinkRefDef(path, ..) => path,
+            Referenceable::Attachment(path) => path,
         }
     }
 
     pub fn get_range(&self) -> Option<MyRange> {
         match self {
-            Referenceable::File(_, _) => None,
+            Referenceable::File(_, _) | Referenceable::Attachment(_) => None,
             Referenceable::Heading(_, heading) => Some(heading.range),
             Referenceable::IndexedBlock(_, indexed_block) => Some(indexed_block.range),
             Referenceable::Tag(_, tag) => Some(tag.range),
@@ -1506,26 +2593,67 @@ impl Referenceable<'_> {
     }
 }
 
-fn matches_path_or_file(file_ref_text: &str, refname: Option<Refname>) -> bool {
+/// The refname of a link path written relative to the folder of the file it is in, like `../note`
+fn relative_refname(root_dir: &Path, reference_path: &Path, file_ref_text: &str) -> Option<String> {
+    if !file_ref_text.contains('/') {
+        return None;
+    }
+
+    let folder = diff_paths(reference_path.parent()?, root_dir)?;
+    let file_ref_text = file_ref_text.replace(r"%20", " ").replace(r"\ ", " ");
+
+    let segments = folder
+        .iter()
+        .filter_map(|segment| segment.to_str())
+        .chain(file_ref_text.split('/'))
+        .try_fold(Vec::new(), |mut segments, segment| {
+            match segment {
+                "" | "." => (),
+                ".." => {
+                    segments.pop()?;
+                }
+                segment => segments.push(segment),
+            };
+            Some(segments)
+        })?;
+
+    Some(segments.join("/"))
+}
+
+/// `relative_refname` is the link path resolved against the linking file's folder, which is
+/// accepted alongside paths from the vault root; with `case_insensitive`, case is ignored
+fn matches_path_or_file(
+    case_insensitive: bool,
+    file_ref_text: &str,
+    refname: Option<Refname>,
+    relative_refname: Option<String>,
+) -> bool {
+    let key = |text: &str| match case_insensitive {
+        true => text.to_lowercase(),
+        false => text.to_string(),
+    };
+    let file_ref_text = &key(file_ref_text);
+    let relative_refname = relative_refname.map(|refname| key(&refname));
+
     (|| {
         let refname = refname?;
-        let refname_path = refname.path.clone()?; // this function should not be used for tags, ... only for heading, files, indexed blocks
+        let refname_path = key(&refname.path.clone()?); // this function should not be used for tags, ... only for heading, files, indexed blocks
 
         if file_ref_text.contains('/') {
             let file_ref_text = file_ref_text.replace(r"%20", " ");
             let file_ref_text = file_ref_text.replace(r"\ ", " ");
 
             let chars: Vec<char> = file_ref_text.chars().collect();
-            match chars.as_slice() {
-                &['.', '/', ref path @ ..] | &['/', ref path @ ..] => {
-                    Some(String::from_iter(path) == refname_path)
-                }
-                path => Some(String::from_iter(path) == refname_path),
-            }
+            let from_root = match chars.as_slice() {
+                &['.', '/', ref path @ ..] | &['/', ref path @ ..] => String::from_iter(path),
+                path => String::from_iter(path),
+            };
+
+            Some(from_root == refname_path || relative_refname == Some(refname_path))
         } else {
-            let last_segment = refname.link_file_key()?;
+            let last_segment = key(&refname.link_file_key()?);
 
-            Some(file_ref_text == last_segment)
+            Some(*file_ref_text == last_segment)
         }
     })()
     .is_some_and(|b| b)
@@ -1974,7 +3102,152 @@ mod vault_tests {
         let text = "This is a png [[link.png]] [[link|display.png]]";
         let parsed = Reference::new(text).collect_vec();
 
-        assert_eq!(parsed, vec![])
+        let expected = vec![AttachmentLink(ReferenceData {
+            reference_text: "link.png".into(),
+            range: tower_lsp::lsp_types::Range {
+                start: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 14,
+                },
+                end: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 26,
+                },
+            }
+            .into(),
+            ..ReferenceData::default()
+        })];
+
+        assert_eq!(parsed, expected)
+    }
+
+    #[test]
+    fn test_block_under_heading_links() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "guide.md",
+                    "# Guide\n\n## Setup\n\nA block ^blk\n\n## Other\n\nOther block ^out\n",
+                ),
+                (
+                    "note.md",
+                    "[[guide#Setup#^blk]] [[guide#Setup#^out]] [[guide#Missing#^blk]] [text](guide#Guide#^out)\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("note.md");
+        let references = vault.select_references(Some(&path)).unwrap();
+        assert!(matches!(
+            references[0],
+            (_, WikiIndexedBlockLink(_, file, index)) if file == "guide" && index == "blk"
+        ));
+        assert_eq!(references[0].1.block_scope(), Some("Setup"));
+
+        // the block must be in the heading's section, including its subsections
+        let unresolved = crate::diagnostics::unresolved_references(&vault, Some(&path))
+            .unwrap()
+            .into_iter()
+            .map(|(_, reference)| reference.data().reference_text.clone())
+            .sorted()
+            .collect_vec();
+        assert_eq!(unresolved, vec!["guide#Missing#^blk", "guide#Setup#^out"]);
+    }
+
+    #[test]
+    fn test_canvas_references() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "[[board]] [[board.canvas]] [md](boards/plan.canvas) [[boards/plan]] [[plan]]\n",
+                ),
+                ("board.canvas", "{}"),
+                ("boards/plan.canvas", "{}"),
+                ("plan.md", "# Plan\n"),
+            ],
+        );
+
+        let references = |attachment: &str| {
+            let path = root_dir.join(attachment);
+            vault
+                .select_references_for_referenceable(&Referenceable::Attachment(&path))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, reference)| reference.data().reference_text.clone())
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(references("board.canvas"), vec!["board", "board.canvas"]);
+        // `[[plan]]` is the note's
+        assert_eq!(
+            references("boards/plan.canvas"),
+            vec!["boards/plan", "boards/plan.canvas"]
+        );
+
+        assert!(crate::diagnostics::unresolved_references(&vault, None)
+            .unwrap()
+            .is_empty());
+
+        let index = vault.resolution_index();
+        let referenceables = vault.select_referenceable_nodes(None);
+        for (path, reference) in vault.select_references(None).unwrap() {
+            let scanned = referenceables
+                .iter()
+                .filter(|referenceable| reference.references(&vault, path, referenceable))
+                .collect_vec();
+            assert_eq!(index.resolve(reference, path).collect_vec(), scanned);
+        }
+    }
+
+    #[test]
+    fn test_attachment_references() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "![[image.png|200]] ![alt](assets/doc.pdf) ![[missing.png]]\n",
+                ),
+                ("image.png", ""),
+                ("assets/doc.pdf", ""),
+            ],
+        );
+
+        assert_eq!(
+            vault.attachments,
+            vec![root_dir.join("image.png"), root_dir.join("assets/doc.pdf")]
+        );
+
+        let references = |attachment: &str| {
+            let path = root_dir.join(attachment);
+            vault
+                .select_references_for_referenceable(&Referenceable::Attachment(&path))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, reference)| reference.data().reference_text.clone())
+                .collect_vec()
+        };
+        assert_eq!(references("image.png"), vec!["image.png"]);
+        assert_eq!(references("assets/doc.pdf"), vec!["assets/doc.pdf"]);
+
+        // attachments aren't flagged as unresolved, even when missing
+        assert!(
+            crate::diagnostics::unresolved_references(&vault, Some(&root_dir.join("note.md")))
+                .unwrap()
+                .is_empty()
+        );
     }
 
     #[test]
@@ -2050,6 +3323,19 @@ more text
         assert_eq!(parsed[0].index, "12345")
     }
 
+    #[test]
+    fn indexed_block_range_parsing() {
+        let text = "# Heading\n\nfirst line\nsecond line ^para\n\n- item\n- another item ^item\n";
+
+        let parsed = MDIndexedBlock::new(text).collect_vec();
+
+        assert_eq!(parsed[0].index, "para");
+        assert_eq!(parsed[0].block_range.start.line, 2);
+        assert_eq!(parsed[0].block_range.end.line, 3);
+        assert_eq!(parsed[1].index, "item");
+        assert_eq!(parsed[1].block_range.start.line, 6);
+    }
+
     #[test]
     fn test_linkable_reference() {
         let path = Path::new("/home/vault/test.md");
@@ -2101,6 +3387,7 @@ more text
         let md_indexed_block = MDIndexedBlock {
             index: "12345".into(),
             range: tower_lsp::lsp_types::Range::default().into(),
+            block_range: tower_lsp::lsp_types::Range::default().into(),
         };
         let linkable: Referenceable = Referenceable::IndexedBlock(&path_buf, &md_indexed_block);
 
@@ -2173,6 +3460,51 @@ more text
         assert_eq!(parsed, expected)
     }
 
+    #[test]
+    fn test_tag_sources() {
+        let text = "---\ntags: [project]\n---\n\nAn inline #idea\n";
+        let tags = |tag_sources| {
+            let settings = crate::config::Settings {
+                tag_sources,
+                ..Default::default()
+            };
+            let vault = Vault::from_texts(&settings, Path::new("/vault"), [("note.md", text)]);
+            let file = &vault.md_files[Path::new("/vault/note.md")];
+
+            // each tag is also a reference
+            let references = file
+                .references
+                .iter()
+                .filter(|reference| matches!(reference, Reference::Tag(..)))
+                .map(|reference| {
+                    (
+                        reference.data().reference_text.clone(),
+                        reference.data().range,
+                    )
+                })
+                .collect_vec();
+            let tags = file
+                .tags
+                .iter()
+                .map(|tag| (format!("#{}", tag.tag_ref), tag.range))
+                .collect_vec();
+            assert_eq!(tags, references);
+
+            tags.into_iter()
+                .map(|(tag, range)| (tag, range.start.line, range.start.character))
+                .collect_vec()
+        };
+
+        let idea = ("#idea".to_string(), 4, 10);
+        let project = ("#project".to_string(), 1, 7);
+        assert_eq!(
+            tags(crate::config::TagSources::Both),
+            vec![idea.clone(), project.clone()]
+        );
+        assert_eq!(tags(crate::config::TagSources::Frontmatter), vec![project]);
+        assert_eq!(tags(crate::config::TagSources::Inline), vec![idea]);
+    }
+
     #[test]
     fn test_obsidian_tag() {
         let text = r"# This is a heading
@@ -2370,4 +3702,461 @@ Continued
 
         assert_eq!(parsed, expected);
     }
+
+    #[test]
+    fn parse_full_link_ref() {
+        let text = "A [full link][ref], a [collapsed][] one, and [not one]\n\n[ref]: target";
+
+        let parsed = Reference::new(text)
+            .map(|reference| {
+                let data = reference.data().clone();
+                (
+                    data.reference_text,
+                    data.display_text,
+                    data.range.start.character,
+                    data.range.end.character,
+                )
+            })
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("ref".to_string(), Some("full link".to_string()), 2, 18),
+                (
+                    "collapsed".to_string(),
+                    Some("collapsed".to_string()),
+                    22,
+                    35
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_qualified_link_resolution() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("a/note.md", ""),
+                ("b/note.md", ""),
+                ("links.md", "[[b/note]]\n"),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let reference = &vault.select_references(Some(&path)).unwrap()[0].1;
+
+        let resolved = vault
+            .select_referenceables_for_reference(reference, &path)
+            .into_iter()
+            .map(|referenceable| referenceable.get_path().to_path_buf())
+            .collect_vec();
+
+        assert_eq!(resolved, vec![root_dir.join("b/note.md")]);
+    }
+
+    #[test]
+    fn relative_link_resolution() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("archive/note.md", ""),
+                ("notes/sub/note.md", ""),
+                ("notes/links.md", "[[../archive/note]]\n\n[sub](sub/note)\n"),
+            ],
+        );
+
+        let path = root_dir.join("notes/links.md");
+        let resolved = vault
+            .select_references(Some(&path))
+            .unwrap()
+            .into_iter()
+            .map(|(_, reference)| {
+                vault
+                    .select_referenceables_for_reference(reference, &path)
+                    .into_iter()
+                    .map(|referenceable| referenceable.get_path().to_path_buf())
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        assert_eq!(
+            resolved,
+            vec![
+                vec![root_dir.join("archive/note.md")],
+                vec![root_dir.join("notes/sub/note.md")]
+            ]
+        );
+    }
+
+    #[test]
+    fn case_insensitive_heading_resolution() {
+        let root_dir = Path::new("/vault");
+        let texts = [
+            ("note.md", "# Äpfel\n\n## Introduction\n"),
+            ("links.md", "[[note#introduction]]\n\n[[note#äPFEL]]\n"),
+        ];
+        let path = root_dir.join("links.md");
+
+        let resolved_headings = |vault: &Vault| {
+            vault
+                .select_references(Some(&path))
+                .unwrap()
+                .into_iter()
+                .map(|(_, reference)| {
+                    vault
+                        .select_referenceables_for_reference(reference, &path)
+                        .into_iter()
+                        .filter_map(|referenceable| match referenceable {
+                            Referenceable::Heading(_, heading) => {
+                                Some(heading.heading_text.clone())
+                            }
+                            _ => None,
+                        })
+                        .collect_vec()
+                })
+                .collect_vec()
+        };
+        let unresolved_count = |vault: &Vault| {
+            vault
+                .select_referenceable_nodes(None)
+                .into_iter()
+                .filter(|referenceable| referenceable.is_unresolved())
+                .count()
+        };
+
+        let settings = crate::config::Settings::default();
+        let vault = Vault::from_texts(&settings, root_dir, texts);
+        assert_eq!(
+            resolved_headings(&vault),
+            vec![vec!["Introduction".to_string()], vec!["Äpfel".to_string()]]
+        );
+        assert_eq!(unresolved_count(&vault), 0);
+
+        let settings = crate::config::Settings {
+            case_insensitive_headings: false,
+            case_insensitive_filenames: false,
+            ..settings
+        };
+        let vault = Vault::from_texts(&settings, root_dir, texts);
+        assert_eq!(resolved_headings(&vault), vec![Vec::<String>::new(); 2]);
+        assert_eq!(unresolved_count(&vault), 2);
+    }
+
+    #[test]
+    fn md_extension_resolution() {
+        let settings = crate::config::Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Part\n"),
+                (
+                    "links.md",
+                    "[[note.md]] [[note.md#Part]] [[note.md|alias]] [md](note.md) [md](note.md#Part)\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let resolved = vault
+            .select_references(Some(&path))
+            .unwrap()
+            .into_iter()
+            .map(|(_, reference)| {
+                vault
+                    .select_referenceables_for_reference(reference, &path)
+                    .into_iter()
+                    .filter(|referenceable| !referenceable.is_unresolved())
+                    .map(|referenceable| referenceable.get_path().to_path_buf())
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        assert_eq!(resolved, vec![vec![root_dir.join("note.md")]; 5]);
+    }
+
+    #[test]
+    fn slug_heading_resolution() {
+        let root_dir = Path::new("/vault");
+        let settings = crate::config::Settings {
+            case_insensitive_headings: false,
+            case_insensitive_filenames: false,
+            ..crate::config::Settings::default()
+        };
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Goals & Plans\n\ntext ^block\n"),
+                (
+                    "links.md",
+                    "[[note#Goals & Plans]] [[note#goals-plans]] [[note#goals-plans#^block]] [[note#goals]]\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let resolved = vault
+            .select_references(Some(&path))
+            .unwrap()
+            .into_iter()
+            .map(|(_, reference)| {
+                vault
+                    .select_referenceables_for_reference(reference, &path)
+                    .into_iter()
+                    .any(|referenceable| !referenceable.is_unresolved())
+            })
+            .collect_vec();
+
+        assert_eq!(resolved, vec![true, true, true, false]);
+        assert_eq!(
+            crate::diagnostics::unresolved_references(&vault, Some(&path))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn github_anchor_heading_resolution() {
+        let root_dir = Path::new("/vault");
+        let settings = crate::config::Settings {
+            case_insensitive_headings: false,
+            case_insensitive_filenames: false,
+            ..crate::config::Settings::default()
+        };
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("note.md", "# Note\n\n## Q & A: snake_case\n\n## My Heading\n"),
+                (
+                    "links.md",
+                    "[a](note.md#q--a-snake_case) [b](note.md#My%20Heading) [c](note.md#my-heading) \
+                     [d](note.md#my%20heading)\n",
+                ),
+            ],
+        );
+
+        let path = root_dir.join("links.md");
+        let resolved = vault
+            .select_references(Some(&path))
+            .unwrap()
+            .into_iter()
+            .map(|(_, reference)| {
+                vault
+                    .select_referenceables_for_reference(reference, &path)
+                    .into_iter()
+                    .any(|referenceable| !referenceable.is_unresolved())
+            })
+            .collect_vec();
+
+        assert_eq!(resolved, vec![true, true, true, false]);
+        assert_eq!(
+            crate::diagnostics::unresolved_references(&vault, Some(&path))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn construct_vault_skips_ignored_files() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-ignore-{}", std::process::id()));
+        for (file, text) in [
+            ("note.md", "# Note\n"),
+            ("build/generated.md", ""),
+            ("drafts/draft.md", ""),
+            ("drafts/keep/kept.md", ""),
+            (".obsidian/plugin.md", ""),
+            (".gitignore", "build/\n"),
+        ] {
+            let path = root_dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+
+        let settings = crate::config::Settings {
+            ignore_globs: vec!["drafts/*.md".to_string()],
+            ..Default::default()
+        };
+        let vault = Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        let indexed = vault
+            .md_files
+            .keys()
+            .map(|path| path.strip_prefix(&root_dir).unwrap().to_path_buf())
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(
+            indexed,
+            vec![
+                PathBuf::from("drafts/keep/kept.md"),
+                PathBuf::from("note.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn construct_vault_indexes_other_folders() {
+        let dir = std::env::temp_dir().join(format!("moxide-folders-{}", std::process::id()));
+        let (root_dir, other) = (dir.join("notes"), dir.join("work"));
+        for path in [root_dir.join("note.md"), other.join("task.md")] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "[[task]] [[note]]\n").unwrap();
+        }
+
+        let settings = crate::config::Settings::default();
+        let vault =
+            Vault::construct_vault(&settings, &root_dir, std::slice::from_ref(&other)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(vault.folders(), std::slice::from_ref(&other));
+        let index = vault.resolution_index();
+        for path in [root_dir.join("note.md"), other.join("task.md")] {
+            assert!(vault.md_files.contains_key(&path));
+            // links cross between the folders
+            let references = vault.select_references(Some(&path)).unwrap();
+            assert_eq!(references.len(), 2);
+            assert!(references
+                .into_iter()
+                .all(|(path, reference)| !index.is_unresolved(reference, path)));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn construct_vault_follows_symlinks() {
+        let dir = std::env::temp_dir().join(format!("moxide-symlinks-{}", std::process::id()));
+        let root_dir = dir.join("vault");
+        let shared = dir.join("shared");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(
+            root_dir.join("note.md"),
+            "[[shared note]] [[shared/shared note#Heading]]\n",
+        )
+        .unwrap();
+        std::fs::write(shared.join("shared note.md"), "# Heading\n\n[[../note]]\n").unwrap();
+        std::os::unix::fs::symlink(&shared, root_dir.join("shared")).unwrap();
+        // links that point nowhere are skipped rather than failing the vault
+        std::os::unix::fs::symlink(dir.join("missing.md"), root_dir.join("broken.md")).unwrap();
+
+        let settings = crate::config::Settings::default();
+        let vault = Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+
+        let indexed = root_dir.join("shared/shared note.md");
+        let real = std::fs::canonicalize(shared.join("shared note.md")).unwrap();
+        let unresolved = crate::diagnostics::unresolved_references(&vault, None).unwrap();
+        let vault_path = vault.vault_path(&real);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            vault.md_files.keys().sorted().collect_vec(),
+            vec![&root_dir.join("note.md"), &indexed]
+        );
+        assert_eq!(unresolved, vec![]);
+        // the editor may open the note at its real path
+        assert_eq!(vault_path, indexed);
+        assert_eq!(
+            vault.vault_path(&root_dir.join("note.md")),
+            root_dir.join("note.md")
+        );
+    }
+
+    #[test]
+    fn test_watched_file_changes() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-watched-{}", std::process::id()));
+        std::fs::create_dir_all(root_dir.join(".trash")).unwrap();
+        std::fs::write(root_dir.join("note.md"), "[[other]] ![[image.png]]\n").unwrap();
+        std::fs::write(root_dir.join("other.md"), "# Other\n").unwrap();
+
+        let settings = crate::config::Settings::default();
+        let mut vault = Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+        let unresolved = |vault: &Vault| {
+            crate::diagnostics::unresolved_references(vault, Some(&root_dir.join("note.md")))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(unresolved(&vault), 0);
+        assert!(vault.modified(&root_dir.join("note.md")).is_some());
+
+        // deleting the linked note unresolves the link, and creating it again resolves it
+        std::fs::remove_file(root_dir.join("other.md")).unwrap();
+        vault.remove_path(&root_dir.join("other.md"));
+        assert_eq!(unresolved(&vault), 1);
+        assert_eq!(vault.modified(&root_dir.join("other.md")), None);
+
+        std::fs::write(root_dir.join("other.md"), "# Other\n").unwrap();
+        Vault::index_file(&settings, &mut vault, &root_dir.join("other.md")).unwrap();
+        assert_eq!(unresolved(&vault), 0);
+
+        std::fs::write(root_dir.join("image.png"), "").unwrap();
+        Vault::index_file(&settings, &mut vault, &root_dir.join("image.png")).unwrap();
+        assert_eq!(vault.attachments, vec![root_dir.join("image.png")]);
+        assert!(vault.modified(&root_dir.join("image.png")).is_some());
+
+        // hidden folders and gitignored files aren't indexed, as constructing the vault skips them
+        std::fs::write(root_dir.join(".trash/old.md"), "").unwrap();
+        Vault::index_file(&settings, &mut vault, &root_dir.join(".trash/old.md")).unwrap();
+        std::fs::write(root_dir.join(".gitignore"), "build/\n").unwrap();
+        std::fs::create_dir_all(root_dir.join("build")).unwrap();
+        std::fs::write(root_dir.join("build/generated.md"), "").unwrap();
+        Vault::index_file(&settings, &mut vault, &root_dir.join("build/generated.md")).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(vault.md_files.len(), 2);
+    }
+
+    #[test]
+    #[ignore = "indexes 1000 notes on disk; run with `cargo test -- --ignored`"]
+    fn construct_large_vault() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-bench-{}", std::process::id()));
+        for i in 0..1000 {
+            let path = root_dir.join(format!("folder {}/note {}.md", i % 10, i));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(
+                path,
+                format!(
+                    "# Note {i}\n\nLinks to [[note {}]] and [[note {}#Note {}]] #tag{}\n\n## Details\n\nText ^block{i}\n",
+                    (i + 1) % 1000,
+                    (i + 7) % 1000,
+                    (i + 7) % 1000,
+                    i % 20
+                ),
+            )
+            .unwrap();
+        }
+
+        let settings = crate::config::Settings::default();
+        let vault = Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+        let again = Vault::construct_vault(&settings, &root_dir, &[]).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(vault.md_files.len(), 1000);
+        assert_eq!(vault, again);
+
+        // selections across the vault come out in the same order every time
+        let refnames = |vault: &Vault| {
+            vault
+                .select_referenceable_nodes(None)
+                .into_iter()
+                .flat_map(|referenceable| referenceable.get_refname(vault.root_dir()))
+                .map(|refname| refname.to_string())
+                .collect_vec()
+        };
+        assert_eq!(refnames(&vault), refnames(&again));
+        assert_eq!(refnames(&vault)[0], "folder 0/note 0");
+    }
 }
@@ -1,6 +1,7 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range};
 
 use super::{MyRange, Rangeable};
 
@@ -42,12 +43,70 @@ impl Rangeable for MDCodeBlock {
     }
 }
 
+/// A task list item, `- [ ] text`; checked with `x`, and any other character in the brackets is
+/// a custom state, like `/` for in progress
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MDTask {
+    /// The whole line of the item
+    range: MyRange,
+    /// The character between the brackets
+    state_character: u32,
+    pub state: char,
+    pub text: String,
+    pub done: bool,
+}
+
+impl MDTask {
+    pub fn new(text: &str) -> impl Iterator<Item = MDTask> + '_ {
+        static RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^[ \t]*(?:[-*+]|\d+[.)]) \[(?<state>[^\]\t])\](?: (?<text>.*))?$")
+                .expect("Task Regex Not Constructing")
+        });
+
+        text.lines().enumerate().flat_map(|(line, text)| {
+            let captures = RE.captures(text)?;
+            let state = captures.name("state")?;
+
+            Some(MDTask {
+                range: Range {
+                    start: Position::new(line as u32, 0),
+                    end: Position::new(line as u32, text.chars().count() as u32),
+                }
+                .into(),
+                state_character: text[..state.start()].chars().count() as u32,
+                state: state.as_str().chars().next()?,
+                text: captures
+                    .name("text")
+                    .map_or("", |text| text.as_str())
+                    .trim()
+                    .to_string(),
+                done: matches!(state.as_str(), "x" | "X"),
+            })
+        })
+    }
+
+    /// The range of the state character between the brackets
+    pub fn state_range(&self) -> Range {
+        let line = self.range.start.line;
+        Range::new(
+            Position::new(line, self.state_character),
+            Position::new(line, self.state_character + 1),
+        )
+    }
+}
+
+impl Rangeable for MDTask {
+    fn range(&self) -> &MyRange {
+        &self.range
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
     use tower_lsp::lsp_types::{Position, Range};
 
-    use super::MDCodeBlock;
+    use super::{MDCodeBlock, MDTask};
 
     #[test]
     fn test_code_block_parsing() {
@@ -290,4 +349,32 @@ fj aklfjd
 
         assert_eq!(parsed, expected)
     }
+
+    #[test]
+    fn test_task_parsing() {
+        let text = "- [ ] write\n  * [x] nested done\n1. [X] numbered\n- [] not a task\n- [ ]\ntext - [ ] mid line\n- [/] started\n";
+
+        let parsed = MDTask::new(text)
+            .map(|task| (task.range.start.line, task.text, task.done))
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                (0, "write".to_string(), false),
+                (1, "nested done".to_string(), true),
+                (2, "numbered".to_string(), true),
+                (4, "".to_string(), false),
+                (6, "started".to_string(), false),
+            ]
+        );
+
+        let states = MDTask::new(text)
+            .map(|task| (task.state, task.state_range().start.character))
+            .collect_vec();
+        assert_eq!(
+            states,
+            vec![(' ', 3), ('x', 5), ('X', 4), (' ', 3), ('/', 3)]
+        );
+    }
 }
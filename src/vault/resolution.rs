@@ -0,0 +1,432 @@
+use std::{collections::HashMap, path::Path};
+
+use itertools::Itertools;
+
+use super::{is_canvas, relative_refname, MDFile, Reference, Referenceable, Vault};
+
+/// The vault's referenceables, resolved and unresolved, keyed by the names links use for them so
+/// that resolving a link only checks the few referenceables with its name rather than all of them.
+/// Built from `select_referenceable_nodes`, so it holds until the vault changes.
+pub struct ResolutionIndex<'a> {
+    vault: &'a Vault,
+    referenceables: Vec<Referenceable<'a>>,
+    /// Positions in `referenceables`, in order, by each of the referenceable's keys
+    by_key: HashMap<String, Vec<usize>>,
+}
+
+impl Vault {
+    pub fn resolution_index(&self) -> ResolutionIndex<'_> {
+        let referenceables = self.select_referenceable_nodes(None);
+
+        let mut by_key = HashMap::<String, Vec<usize>>::new();
+        for (i, referenceable) in referenceables.iter().enumerate() {
+            for key in referenceable_keys(self, referenceable) {
+                by_key.entry(key).or_default().push(i);
+            }
+        }
+
+        ResolutionIndex {
+            vault: self,
+            referenceables,
+            by_key,
+        }
+    }
+}
+
+impl<'a> ResolutionIndex<'a> {
+    /// The referenceables the reference links to, in the order of `select_referenceable_nodes`:
+    /// resolved ones first. The same as filtering them all with `Reference::references`.
+    pub fn resolve<'b>(
+        &'b self,
+        reference: &'b Reference,
+        reference_path: &'b Path,
+    ) -> impl Iterator<Item = &'b Referenceable<'a>> + 'b {
+        reference_keys(self.vault, reference, reference_path)
+            .into_iter()
+            .filter_map(|key| self.by_key.get(&key))
+            .kmerge()
+            .dedup()
+            .map(|i| &self.referenceables[*i])
+            .filter(move |referenceable| {
+                reference.references(self.vault, reference_path, referenceable)
+            })
+    }
+
+    /// The note the file part of the link names, for links to notes and to the headings and blocks
+    /// in them, whether or not the heading or block is there
+    pub fn linked_file(
+        &self,
+        reference: &Reference,
+        reference_path: &Path,
+    ) -> Option<(&'a Path, &'a MDFile)> {
+        reference_keys(self.vault, reference, reference_path)
+            .into_iter()
+            .filter_map(|key| self.by_key.get(&key))
+            .kmerge()
+            .dedup()
+            .map(|i| &self.referenceables[*i])
+            .find_map(|referenceable| match *referenceable {
+                Referenceable::File(path, file)
+                    if referenceable.matches_reference(self.vault, reference, reference_path) =>
+                {
+                    Some((path.as_path(), file))
+                }
+                _ => None,
+            })
+    }
+
+    /// Whether the first referenceable the reference links to is an unresolved one; links to
+    /// nothing, like tags, aren't unresolved
+    pub fn is_unresolved(&self, reference: &Reference, reference_path: &Path) -> bool {
+        self.resolve(reference, reference_path)
+            .next()
+            .is_some_and(|referenceable| referenceable.is_unresolved())
+    }
+}
+
+/// The names that links to the referenceable are looked up by: for notes and the headings and
+/// blocks in them, and for attachments, the path from the vault root and the filename, and for
+/// notes with a frontmatter `id`, the id; lowercased with `case_insensitive_filenames`
+fn referenceable_keys(vault: &Vault, referenceable: &Referenceable) -> Vec<String> {
+    let Some(refname) = referenceable.get_refname(vault.root_dir()) else {
+        return vec![];
+    };
+    let id = match referenceable {
+        Referenceable::File(path, _)
+        | Referenceable::Heading(path, _)
+        | Referenceable::IndexedBlock(path, _) => vault
+            .md_files
+            .get(*path)
+            .and_then(|file| Some(file.metadata.as_ref()?.id()?.to_string())),
+        _ => None,
+    };
+
+    match referenceable {
+        Referenceable::Tag(..) | Referenceable::Footnote(..) => vec![refname.full_refname],
+        Referenceable::LinkRefDef(..) => vec![refname.full_refname.to_lowercase()],
+        _ => {
+            let keys = refname
+                .link_file_key()
+                .into_iter()
+                .chain(refname.path)
+                .chain(id)
+                .collect_vec();
+            // canvases are also linked to without their extension
+            let canvas = match referenceable {
+                Referenceable::Attachment(path) if is_canvas(path) => keys
+                    .iter()
+                    .filter_map(|key| key.strip_suffix(".canvas"))
+                    .map(str::to_string)
+                    .collect_vec(),
+                _ => vec![],
+            };
+
+            keys.into_iter()
+                .chain(canvas)
+                .map(|key| vault.filename_match_key(&key))
+                .unique()
+                .collect()
+        }
+    }
+}
+
+/// The names of the referenceables the reference can link to, as `matches_path_or_file` compares
+/// them: the filename, or for a path, the path from the vault root or from the linking file's
+/// folder
+fn reference_keys(vault: &Vault, reference: &Reference, reference_path: &Path) -> Vec<String> {
+    let root_dir = vault.root_dir();
+    let text = &reference.data().reference_text;
+    let file_ref_text = match reference {
+        Reference::Tag(..) | Reference::Footnote(..) => return vec![text.clone()],
+        Reference::LinkRef(..) => return vec![text.to_lowercase()],
+        Reference::WikiFileLink(..) | Reference::MDFileLink(..) | Reference::AttachmentLink(..) => {
+            text
+        }
+        Reference::WikiHeadingLink(_, file_ref_text, _)
+        | Reference::WikiIndexedBlockLink(_, file_ref_text, _)
+        | Reference::MDHeadingLink(_, file_ref_text, _)
+        | Reference::MDIndexedBlockLink(_, file_ref_text, _) => file_ref_text,
+    };
+
+    if !file_ref_text.contains('/') {
+        return vec![vault.filename_match_key(file_ref_text)];
+    }
+
+    let unescaped = file_ref_text.replace(r"%20", " ").replace(r"\ ", " ");
+    let from_root = unescaped
+        .strip_prefix("./")
+        .or_else(|| unescaped.strip_prefix('/'))
+        .unwrap_or(&unescaped)
+        .to_string();
+
+    std::iter::once(from_root)
+        .chain(relative_refname(root_dir, reference_path, file_ref_text))
+        .map(|key| vault.filename_match_key(&key))
+        .unique()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use itertools::Itertools;
+
+    use crate::config::Settings;
+    use crate::vault::Vault;
+
+    #[test]
+    fn test_resolution_index_matches_scan() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                (
+                    "note.md",
+                    "# Note\n\n## Part\n\ntext ^block\n\n#tag #tag/nested [^1]\n\n[^1]: footnote\n[ref]: https://example.com\n",
+                ),
+                ("folder/note.md", "# Other Note\n"),
+                ("folder/sub/deep.md", "# Deep\n"),
+                ("image.png", ""),
+                (
+                    "links.md",
+                    "[[note]] [[note#Part]] [[note#part]] [[note#Missing]] [[note#^block]] \
+                     [[note#^missing]] [[note#Part#^block]] [[note#Note#^block]] [[missing]] \
+                     [[missing#Heading]] [[folder/note]] [[./folder/note]] [[/folder/note#Other Note]] \
+                     [[folder/missing]] [[deep]] [[sub/deep]] [md](note) [md](<folder/note#Other Note>) \
+                     [md](missing) ![[image.png]] ![[missing.png]] #tag #tag/nested #other [^1] [text][ref] \
+                     [text][undefined]\n",
+                ),
+                (
+                    "folder/sub/relative.md",
+                    "[[../note]] [[../../note#Part]] [[../missing]] [[./deep]] [[../../../outside]]\n",
+                ),
+            ],
+        );
+
+        let index = vault.resolution_index();
+        let referenceables = vault.select_referenceable_nodes(None);
+        let references = vault.select_references(None).unwrap();
+        assert!(references.len() > 30);
+
+        for (path, reference) in references {
+            let scanned = referenceables
+                .iter()
+                .filter(|referenceable| reference.references(&vault, path, referenceable))
+                .collect_vec();
+            let resolved = index.resolve(reference, path).collect_vec();
+
+            assert_eq!(resolved, scanned, "{:?} in {:?}", reference, path);
+        }
+
+        for path in vault.md_files.keys() {
+            // the scan `unresolved_references` did before the index
+            let scanned = vault
+                .select_references(Some(path))
+                .unwrap()
+                .into_iter()
+                .filter(|(path, reference)| {
+                    referenceables
+                        .iter()
+                        .find(|referenceable| reference.references(&vault, path, referenceable))
+                        .is_some_and(|matched| matched.is_unresolved())
+                })
+                .collect_vec();
+
+            assert_eq!(
+                crate::diagnostics::unresolved_references(&vault, Some(path)).unwrap(),
+                scanned
+            );
+        }
+
+        let unresolved = crate::diagnostics::unresolved_references(&vault, None).unwrap();
+        assert_eq!(unresolved.len(), 9);
+    }
+
+    #[test]
+    fn test_frontmatter_id_links() {
+        let settings = Settings::default();
+        let root_dir = Path::new("/vault");
+        let vault = Vault::from_texts(
+            &settings,
+            root_dir,
+            [
+                ("plan.md", "---\nid: 20240115\n---\n# Plan\n\n## Part\n"),
+                ("other.md", "---\nid: taken\n---\n# Other\n"),
+                ("taken.md", "# Taken\n"),
+                (
+                    "links.md",
+                    "[[20240115]] [[20240115#Part]] [[taken]] [md](20240115) [[20240115#Missing]]\n",
+                ),
+            ],
+        );
+
+        let index = vault.resolution_index();
+        let path = root_dir.join("links.md");
+        let references = vault.select_references(Some(&path)).unwrap();
+        let resolved = |text: &str| {
+            let (_, reference) = references
+                .iter()
+                .find(|(_, reference)| reference.data().reference_text == text)
+                .unwrap();
+            index
+                .resolve(reference, &path)
+                .map(|referenceable| referenceable.get_refname(root_dir).unwrap().to_string())
+                .collect_vec()
+        };
+
+        assert_eq!(resolved("20240115"), vec!["plan"]);
+        assert_eq!(resolved("20240115#Part"), vec!["plan#Part"]);
+        // a note named like another note's id is linked over it
+        assert_eq!(resolved("taken"), vec!["taken"]);
+
+        let unresolved = crate::diagnostics::unresolved_references(&vault, None).unwrap();
+        assert_eq!(
+            unresolved
+                .iter()
+                .map(|(_, reference)| reference.data().reference_text.as_str())
+                .collect_vec(),
+            vec!["20240115#Missing"]
+        );
+
+        let referenceables = vault.select_referenceable_nodes(None);
+        for (path, reference) in vault.select_references(None).unwrap() {
+            let scanned = referenceables
+                .iter()
+                .filter(|referenceable| reference.references(&vault, path, referenceable))
+                .collect_vec();
+            assert_eq!(index.resolve(reference, path).collect_vec(), scanned);
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_filenames() {
+        let root_dir = Path::new("/vault");
+        let texts = [
+            ("Note.md", "# Note\n\n## Part\n"),
+            ("Folder/Deep.md", "# Deep\n"),
+            ("Image.png", ""),
+            (
+                "links.md",
+                "[[note]] [[NOTE#part]] [[folder/deep]] [md](note) ![[image.png]] [[missing]]\n",
+            ),
+        ];
+        let unresolved = |settings: &Settings| {
+            let vault = Vault::from_texts(settings, root_dir, texts);
+
+            let index = vault.resolution_index();
+            let referenceables = vault.select_referenceable_nodes(None);
+            for (path, reference) in vault.select_references(None).unwrap() {
+                let scanned = referenceables
+                    .iter()
+                    .filter(|referenceable| reference.references(&vault, path, referenceable))
+                    .collect_vec();
+                assert_eq!(index.resolve(reference, path).collect_vec(), scanned);
+            }
+
+            crate::diagnostics::unresolved_references(&vault, None)
+                .unwrap()
+                .into_iter()
+                .map(|(_, reference)| reference.data().reference_text.clone())
+                .collect_vec()
+        };
+
+        let insensitive = Settings {
+            case_insensitive_filenames: true,
+            ..Settings::default()
+        };
+        assert_eq!(unresolved(&insensitive), vec!["missing"]);
+
+        let sensitive = Settings {
+            case_insensitive_filenames: false,
+            ..Settings::default()
+        };
+        assert_eq!(
+            unresolved(&sensitive),
+            vec!["note", "NOTE#part", "folder/deep", "missing", "note"]
+        );
+    }
+
+    #[test]
+    fn test_link_resolution_priority() {
+        let root_dir = Path::new("/vault");
+        let texts = [
+            ("Projects/Note.md", "# Note\n\n## Part\n"),
+            ("Archive/Note.md", "# Note\n"),
+            ("Archive/Other/Note.md", "# Note\n"),
+            ("Projects/image.png", ""),
+            ("Archive/image.png", ""),
+            (
+                "Archive/links.md",
+                "[[Note]] [[Note#Part]] [[Archive/Note]] ![[image.png]]\n",
+            ),
+        ];
+        let settings = Settings {
+            link_resolution_priority: vec!["Projects/".to_string(), "Archive".to_string()],
+            ..Settings::default()
+        };
+        let vault = Vault::from_texts(&settings, root_dir, texts);
+
+        let index = vault.resolution_index();
+        let path = root_dir.join("Archive/links.md");
+        let references = vault.select_references(Some(&path)).unwrap();
+        let referenceables = vault.select_referenceable_nodes(None);
+        let resolved = references
+            .iter()
+            .map(|(path, reference)| {
+                let resolved = index.resolve(reference, path).collect_vec();
+                let scanned = referenceables
+                    .iter()
+                    .filter(|referenceable| reference.references(&vault, path, referenceable))
+                    .collect_vec();
+                assert_eq!(resolved, scanned);
+
+                resolved
+                    .iter()
+                    .map(|referenceable| referenceable.get_refname(root_dir).unwrap().to_string())
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        assert_eq!(
+            resolved,
+            vec![
+                vec!["Projects/Note"],
+                vec!["Projects/Note#Part"],
+                // a path still goes where it says
+                vec!["Archive/Note"],
+                vec!["Projects/image.png"],
+            ]
+        );
+        assert!(crate::diagnostics::ambiguous_links(&vault, &index, &path)
+            .unwrap()
+            .is_empty());
+
+        assert!(vault.is_preferred(&root_dir.join("Projects/Note.md")));
+        assert!(!vault.is_preferred(&root_dir.join("Archive/Note.md")));
+        assert!(vault.is_outranked(&root_dir.join("Archive/Other/Note.md")));
+
+        // the ranks follow files removed from the vault
+        let mut removed = vault.clone();
+        removed.remove_path(&root_dir.join("Projects"));
+        assert!(!removed.is_outranked(&root_dir.join("Archive/Other/Note.md")));
+        assert!(!removed.is_preferred(&root_dir.join("Archive/Note.md")));
+
+        // without priorities, the names are ambiguous; only one note has the heading
+        let vault = Vault::from_texts(&Settings::default(), root_dir, texts);
+        assert_eq!(
+            crate::diagnostics::ambiguous_links(&vault, &vault.resolution_index(), &path)
+                .unwrap()
+                .into_iter()
+                .map(|(reference, candidates)| (
+                    reference.data().reference_text.as_str(),
+                    candidates.len()
+                ))
+                .collect_vec(),
+            vec![("Note", 3), ("image.png", 2)]
+        );
+        assert!(!vault.is_preferred(&root_dir.join("Projects/Note.md")));
+    }
+}